@@ -1,7 +1,7 @@
-/// This benchmark tests different id access strategies.
-/// First it creates partially pre-filled structure of ids.
-/// Then it starts an updater thread which periodically adds more values to simulate writer load.
-/// Then in measures read access time.
+//! This benchmark tests different id access strategies.
+//! First it creates partially pre-filled structure of ids.
+//! Then it starts an updater thread which periodically adds more values to simulate writer load.
+//! Then in measures read access time.
 
 #[macro_use]
 extern crate bencher;
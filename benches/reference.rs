@@ -86,5 +86,41 @@ fn reference(bencher: &mut Bencher) {
     })
 }
 
-benchmark_group!(benches, reference);
+fn filled_reference() -> Reference<Foo> {
+    let reference = Reference::new(REFERENCE_SIZE);
+
+    for id in 1..(REFERENCE_SIZE as i32) {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    reference
+}
+
+fn scan_iter(bencher: &mut Bencher) {
+    let reference = filled_reference();
+
+    bencher.iter(|| {
+        let mut len = 0;
+
+        for item in reference.iter().filter_map(|entry| entry.load()) {
+            len += item.name.len();
+        }
+
+        len
+    })
+}
+
+fn scan_for_each(bencher: &mut Bencher) {
+    let reference = filled_reference();
+
+    bencher.iter(|| {
+        let mut len = 0;
+        reference.for_each(|_id, item| len += item.name.len());
+        len
+    })
+}
+
+benchmark_group!(benches, reference, scan_iter, scan_for_each);
 benchmark_main!(benches);
@@ -18,7 +18,7 @@ struct Product {
     subject: Entry<Subject>,
 }
 
-impl<'a> Identifiable for Product {
+impl Identifiable for Product {
     fn id(&self) -> Id<Self> {
         self.id
     }
@@ -44,20 +44,21 @@ fn main() {
         subjects: Reference::new(SUBJECTS_COUNT),
     };
 
-    for id in 1..(PRODUCTS_COUNT as i32) {
-        let subject = ctx
-            .subjects
-            .get_or_reserve(Id::new(id % 2 + 1))
-            .expect("Failed to get or reserve subject");
-
-        ctx.products
-            .insert(Product {
+    let unresolved = ctx
+        .products
+        .link(
+            &ctx.subjects,
+            1..(PRODUCTS_COUNT as i32),
+            |id| Id::new(id % 2 + 1),
+            |id, subject| Product {
                 id: Id::new(id),
                 name: format!("Product {id}"),
                 subject,
-            })
-            .expect("Failed to insert product");
-    }
+            },
+        )
+        .expect("Failed to link products");
+
+    println!("Subjects to load: {unresolved:?}");
 
     for id in 1..(SUBJECTS_COUNT as i32) {
         ctx.subjects
@@ -0,0 +1,37 @@
+/* C API of the `reference` crate built with the `ffi` feature. See `src/ffi.rs`. */
+
+#ifndef REFERENCE_H
+#define REFERENCE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define REFERENCE_OK 0
+#define REFERENCE_NOT_FOUND 1
+#define REFERENCE_ERROR -1
+
+typedef struct FfiReference FfiReference;
+typedef struct FfiEntry FfiEntry;
+
+/* Receives an item id and its encoded bytes valid only during the call.
+   Iteration stops when it returns false. */
+typedef bool (*ItemCallback)(int32_t id, const uint8_t *data, size_t len, void *user_data);
+
+void reference_free(FfiReference *reference);
+FfiEntry *reference_get(const FfiReference *reference, int32_t id);
+int32_t reference_iter(const FfiReference *reference, ItemCallback callback, void *user_data);
+int32_t reference_insert(const FfiReference *reference, const uint8_t *data, size_t len);
+
+void reference_entry_free(FfiEntry *entry);
+int32_t reference_entry_load(const FfiEntry *entry, ItemCallback callback, void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* REFERENCE_H */
@@ -0,0 +1,82 @@
+//! Approximate tracking of lookups for eviction.
+//!
+//! Precise LRU bookkeeping would make each read write a shared timestamp. Instead each slot
+//! has a small saturating counter bumped by lookups with plain relaxed stores, optionally
+//! only for a sample of them, and the CLOCK algorithm picks an item to evict: a hand goes
+//! around the slots decrementing counters until it meets a slot whose counter is zero.
+
+use std::cell::Cell;
+
+use super::backend::ACCESS_COUNT_MAX;
+use super::sync::Ordering as AtomicOrdering;
+use super::{Backend, CapacityPolicy, Identifiable, Reference};
+
+thread_local! {
+    /// Lookups made by the current thread for sampling.
+    static LOOKUPS: Cell<u32> = const { Cell::new(0) };
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Makes every `sample_every`-th lookup by id in each thread count for eviction.
+    /// Zero turns tracking off so eviction degrades to going around the slots in order.
+    ///
+    /// By default lookups are not tracked unless the capacity policy is
+    /// `CapacityPolicy::EvictLeastRecentlyUsed` in which case every lookup is.
+    pub fn with_access_tracking(mut self, sample_every: u32) -> Self {
+        self.access_sampling = Some(sample_every);
+        self
+    }
+
+    /// Counts a lookup of `slot` if it's sampled.
+    pub(crate) fn track_access(&self, slot: &B::Slot) {
+        let sample_every = match (self.access_sampling, self.capacity_policy) {
+            (Some(sample_every), _) => sample_every,
+            (None, CapacityPolicy::EvictLeastRecentlyUsed) => 1,
+            (None, CapacityPolicy::Reject) => 0,
+        };
+
+        // A read-only backend may be mapped to read-only memory.
+        if sample_every == 0 || self.items.is_read_only() {
+            return;
+        }
+
+        let is_sampled = sample_every == 1
+            || LOOKUPS.with(|lookups| {
+                let count = lookups.get().wrapping_add(1);
+                lookups.set(count);
+                count % sample_every == 0
+            });
+
+        if is_sampled {
+            B::meta(slot).touch_access();
+        }
+    }
+
+    /// Moves the clock hand until it meets an unpinned item which hasn't been looked up since
+    /// the previous pass. Returns `None` if there are no such items.
+    pub(crate) fn clock_victim(&self) -> Option<&'static B::Slot> {
+        let len = self.items.len();
+
+        if len < 2 {
+            return None;
+        }
+
+        let pins = self.pinned_ids();
+        let is_unpinned = |item: Option<&T>| item.is_some_and(|item| !pins.contains(&item.id()));
+
+        // Enough steps to bring every counter down to zero and visit the slot once more.
+        let max_steps = (ACCESS_COUNT_MAX as usize + 1) * (len - 1) + 1;
+
+        for _ in 0..max_steps {
+            // The zero element at vid 0 is never evicted.
+            let vid = self.clock_hand.fetch_add(1, AtomicOrdering::Relaxed) % (len - 1) + 1;
+            let slot = self.items.slot(vid)?;
+
+            if B::peek(slot, is_unpinned) && B::meta(slot).age_access() {
+                return Some(slot);
+            }
+        }
+
+        None
+    }
+}
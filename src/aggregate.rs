@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::Sum;
+
+use super::{Backend, Id, Identifiable, Reference};
+
+/// Aggregations over all items.
+///
+/// Each of them makes a single pass over slots like `iter` so items changed meanwhile may be
+/// seen either in the old or in the new state. Items are borrowed from slots without cloning
+/// `Arc`s. No lock of the reference is held while closures run so they may call into it.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Returns the number of items matching the predicate.
+    pub fn count_where<F>(&self, mut predicate: F) -> usize
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.peek_items(|item| predicate(item))
+            .filter(|matches| *matches)
+            .count()
+    }
+
+    /// Sums values returned by `f` for all items.
+    pub fn sum_by<V, F>(&self, f: F) -> V
+    where
+        V: Sum<V>,
+        F: FnMut(&T) -> V,
+    {
+        self.peek_items(f).sum()
+    }
+
+    /// Calls `f` with each item borrowed from its slot. Faster than loading items from
+    /// `iter` for passes which don't keep them.
+    ///
+    /// Backends keeping values behind a lock, e.g. `RwLockBackend`, hold the slot locked
+    /// while `f` runs so changing the item being passed from `f` deadlocks with them.
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(Id<T>, &T),
+    {
+        self.peek_items(|item| f(item.id(), item)).for_each(drop);
+    }
+
+    /// Groups ids of items by the key returned by `f`.
+    pub fn group_by<K, F>(&self, mut f: F) -> HashMap<K, Vec<Id<T>>>
+    where
+        K: Eq + Hash,
+        F: FnMut(&T) -> K,
+    {
+        let mut groups: HashMap<K, Vec<Id<T>>> = HashMap::new();
+
+        for (key, id) in self.peek_items(|item| (f(item), item.id())) {
+            groups.entry(key).or_default().push(id);
+        }
+
+        groups
+    }
+
+    /// Maps items skipping free and empty slots.
+    fn peek_items<'a, R, F>(&'a self, mut f: F) -> impl Iterator<Item = R> + 'a
+    where
+        F: FnMut(&T) -> R + 'a,
+    {
+        self.items
+            .iter()
+            .filter(|slot| !B::meta(slot).is_free())
+            .filter_map(move |slot| B::peek(slot, |maybe_item| maybe_item.map(&mut f)))
+    }
+}
@@ -3,7 +3,8 @@ use std::error::Error as StdError;
 use std::fmt::{self, Debug};
 use std::ops::{Deref, DerefMut};
 use std::ptr::NonNull;
-use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::sync::{AtomicUsize, LEN_OBSERVE, LEN_PUBLISH};
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -22,24 +23,86 @@ pub struct Array<T> {
 
 impl<T: 'static> Array<T> {
     /// Create an array of `T` with the given capacity. The capacity is being preallocated.
+    /// Panics if the allocation fails. See `try_new`.
     pub fn new(capacity: usize) -> Self {
-        let layout = Layout::array::<T>(capacity).unwrap();
-        let ptr = unsafe { std::alloc::alloc(layout) };
+        match Self::try_new(capacity) {
+            Ok(array) => array,
+            Err(err) => panic!("Failed to create array: {err}"),
+        }
+    }
 
-        let ptr = match NonNull::new(ptr as *mut T) {
-            Some(ptr) => ptr,
-            None => std::alloc::handle_alloc_error(layout),
+    /// Like `new` but returns an error if the capacity is too large to allocate.
+    pub fn try_new(capacity: usize) -> Result<Self, Error> {
+        let layout =
+            Layout::array::<T>(capacity).map_err(|_| Error::AllocationFailed { capacity })?;
+
+        // Allocating zero bytes is undefined behavior so an empty capacity or zero-sized `T`
+        // gets a dangling pointer which is never dereferenced.
+        let ptr = match layout.size() {
+            0 => NonNull::dangling(),
+            _ => {
+                let ptr = unsafe { std::alloc::alloc(layout) };
+                NonNull::new(ptr as *mut T).ok_or(Error::AllocationFailed { capacity })?
+            }
         };
 
-        Self {
+        Ok(Self {
+            ptr,
+            capacity,
+            len: AtomicUsize::new(0),
+        })
+    }
+
+    /// Like `try_new` but the memory is an anonymous mapping which is never unmapped.
+    /// Pages get committed by the OS as items are pushed.
+    #[cfg(feature = "mmap")]
+    pub fn try_new_mapped(capacity: usize) -> Result<Self, Error> {
+        let layout =
+            Layout::array::<T>(capacity).map_err(|_| Error::AllocationFailed { capacity })?;
+
+        let ptr = match layout.size() {
+            0 => NonNull::dangling(),
+            size => {
+                let mut map = memmap2::MmapOptions::new()
+                    .len(size)
+                    .map_anon()
+                    .map_err(|_| Error::AllocationFailed { capacity })?;
+
+                let ptr = map.as_mut_ptr();
+                std::mem::forget(map);
+
+                // Mappings are page aligned which is enough for all but exotic types.
+                match ptr as usize % layout.align() {
+                    0 => NonNull::new(ptr as *mut T).ok_or(Error::AllocationFailed { capacity })?,
+                    _ => return Err(Error::AllocationFailed { capacity }),
+                }
+            }
+        };
+
+        Ok(Self {
             ptr,
             capacity,
             len: AtomicUsize::new(0),
+        })
+    }
+
+    /// Creates an array of `items` with room for one more.
+    pub fn try_from_vec(items: Vec<T>) -> Result<Self, Error> {
+        let array = Self::try_new(items.len() + 1)?;
+
+        for item in items {
+            array.push(item)?;
         }
+
+        Ok(array)
     }
 
     /// Add an element to the end of the array.
     /// Returns error in case of exceeded capacity.
+    ///
+    /// Pushes must not run concurrently: the caller is responsible for serializing them.
+    /// Reading concurrently with a push is fine.
+    #[allow(clippy::mut_from_ref)]
     pub fn push(&self, item: T) -> Result<&mut T, Error> {
         let len = self.len();
 
@@ -55,7 +118,8 @@ impl<T: 'static> Array<T> {
             &mut *ptr
         };
 
-        self.len.fetch_add(1, Ordering::Relaxed);
+        let prev_len = self.len.fetch_add(1, LEN_PUBLISH);
+        debug_assert_eq!(prev_len, len, "Concurrent pushes to an array");
         Ok(ptr)
     }
 
@@ -70,8 +134,10 @@ impl<T: 'static> Array<T> {
     }
 
     /// Returns a reference to an item without bounds checking.
-    #[allow(clippy::mut_from_ref)]
     pub unsafe fn get_unchecked(&self, idx: usize) -> &'static T {
+        #[cfg(feature = "strict-ordering")]
+        debug_assert!(idx < self.len(), "Reading uninitialized element {idx}");
+
         &*self.ptr.as_ptr().add(idx)
     }
 
@@ -82,12 +148,19 @@ impl<T: 'static> Array<T> {
 
     /// Returns the number of elements.
     pub fn len(&self) -> usize {
-        self.len.load(Ordering::Relaxed)
+        self.len.load(LEN_OBSERVE)
+    }
+
+    /// Returns the maximum number of elements.
+    pub fn capacity(&self) -> usize {
+        self.capacity
     }
 }
 
 unsafe impl<T: Send> Send for Array<T> {}
-unsafe impl<T: Sync> Sync for Array<T> {}
+// `push` moves items in through a shared reference so sharing requires `T: Send` too.
+#[cfg(not(feature = "single-thread"))]
+unsafe impl<T: Send + Sync> Sync for Array<T> {}
 
 impl<T: 'static> Deref for Array<T> {
     type Target = [T];
@@ -110,43 +183,44 @@ impl<T: fmt::Debug + 'static> fmt::Debug for Array<T> {
 }
 
 impl<T: 'static> From<Vec<T>> for Array<T> {
+    /// Panics if the allocation fails. See `Array::try_from_vec`.
     fn from(items: Vec<T>) -> Self {
-        let array = Self::new(items.len() + 1);
-
-        for item in items {
-            if let Err(err) = array.push(item) {
-                panic!("Failed to add an item to array: {err:#}");
-            }
+        match Self::try_from_vec(items) {
+            Ok(array) => array,
+            Err(err) => panic!("Failed to add an item to array: {err:#}"),
         }
-
-        array
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 /// Iterates over items of `Array<T>`.
+/// Since the items are never deallocated the iterator may outlive the array itself.
 pub struct Iter<T: 'static> {
-    array: &'static Array<T>,
+    ptr: NonNull<T>,
     len: usize,
     idx: usize,
 }
 
 impl<T: 'static> Iter<T> {
     fn new(array: &Array<T>) -> Self {
-        let len = array.len();
-        let ptr = array as *const Array<T>;
-        let array = unsafe { ptr.as_ref::<'static>() }.unwrap();
-        Self { array, len, idx: 0 }
+        Self {
+            ptr: array.ptr,
+            len: array.len(),
+            idx: 0,
+        }
     }
 }
 
-impl<T> Iterator for Iter<T> {
+unsafe impl<T: Sync> Send for Iter<T> {}
+unsafe impl<T: Sync> Sync for Iter<T> {}
+
+impl<T: 'static> Iterator for Iter<T> {
     type Item = &'static T;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.idx < self.len {
-            let item = unsafe { self.array.get_unchecked(self.idx) };
+            let item = unsafe { &*self.ptr.as_ptr().add(self.idx) };
             self.idx += 1;
             Some(item)
         } else {
@@ -161,12 +235,17 @@ impl<T> Iterator for Iter<T> {
 pub enum Error {
     /// Attempted to add an item to an `Array<T>` capacity of which is already filled.
     CapacityExceeded { capacity: usize },
+    /// Failed to allocate memory for the capacity.
+    AllocationFailed { capacity: usize },
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::CapacityExceeded { capacity } => write!(f, "Capacity exceeded ({})", capacity),
+            Self::AllocationFailed { capacity } => {
+                write!(f, "Failed to allocate capacity of {capacity}")
+            }
         }
     }
 }
@@ -175,6 +254,7 @@ impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
             Self::CapacityExceeded { .. } => None,
+            Self::AllocationFailed { .. } => None,
         }
     }
 }
@@ -0,0 +1,742 @@
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use arc_swap::ArcSwapOption;
+
+use super::array::{Array, Iter as ArrayIter};
+use super::seq_cell::SeqCell;
+use super::sync::{AtomicU8, AtomicUsize, Ordering, PoisonError, RwLock};
+use super::{Error, Id};
+
+/// Slot storage of `Reference<T>`.
+///
+/// A backend holds a fixed-capacity sequence of slots each holding an optional item.
+/// Slots are addressed by vids – virtual ids assigned sequentially on push.
+/// Slots must never move nor be deallocated since entries keep `'static` references to them.
+///
+/// Bundled backends are `ArcSwapBackend`, `RwLockBackend`, `InPlaceBackend` for items stored
+/// by value, `InlineBackend` for plain data and `MmapBackend` with `mmap` feature.
+pub trait Backend<T>: 'static {
+    /// A single slot holding an optional item.
+    type Slot: 'static;
+
+    /// Iterator over all slots in vid order. `Unpin` so entry streams are.
+    type Iter: Iterator<Item = &'static Self::Slot> + Unpin;
+
+    /// Returns a slot by its vid or `None` if it's out of bounds.
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot>;
+
+    /// Adds a new slot to the end returning its vid.
+    /// Pushes are serialized by `Reference` so implementations don't need to handle
+    /// concurrent pushes. Reads concurrent with a push must be safe though.
+    fn push_slot(&self, item: Option<Arc<T>>) -> Result<usize, Error<T>>;
+
+    /// Returns the number of slots.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no slots.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of slots.
+    fn capacity(&self) -> usize;
+
+    /// Returns `true` if slots must not be changed. `Reference` refuses changes then.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Creates an iterator over slots existing at the moment of the call.
+    fn iter(&self) -> Self::Iter;
+
+    /// Returns the current value of the slot.
+    fn load(slot: &Self::Slot) -> Option<Arc<T>>;
+
+    /// Calls `f` with the current value of the slot without cloning the `Arc`.
+    /// Implementations should override this when they can borrow the value cheaply.
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        f(Self::load(slot).as_deref())
+    }
+
+    /// Returns the metadata of the slot.
+    fn meta(slot: &Self::Slot) -> &SlotMeta;
+
+    /// Called when the slot gets taken by `id` for an item or a reservation. Backends may
+    /// record it, e.g. for other processes to index reservations. Does nothing by default.
+    fn assign_id(slot: &Self::Slot, id: Id<T>) {
+        let _ = (slot, id);
+    }
+
+    /// Sets a new value to the slot and returns the previous one.
+    fn store(slot: &Self::Slot, item: Option<Arc<T>>) -> Option<Arc<T>>;
+
+    /// Read-copy-update: sets the value returned by `f` called with the current one
+    /// and returns the previous value. `f` may be called several times on contention.
+    fn rcu<F>(slot: &Self::Slot, f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>;
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Saturation value of the per-slot lookup counter. The clock hand has to pass a slot this
+/// many times without lookups in between for the slot to get evicted.
+pub(crate) const ACCESS_COUNT_MAX: u8 = 3;
+
+/// Backend-independent state of a slot maintained by `Reference`.
+pub struct SlotMeta {
+    /// Incremented on each removal and reuse of the slot so it's odd while the slot is free.
+    generation: AtomicUsize,
+    /// Milliseconds since `clock_base` of the last value change plus one. Zero means never.
+    updated_at: AtomicUsize,
+    /// Like `updated_at` but of the last lookup extending the expiration.
+    /// See `Reference::with_refresh_on_read`.
+    refreshed_at: AtomicUsize,
+    /// CLOCK counter of recent lookups for eviction. See `Reference::with_access_tracking`.
+    access: AtomicU8,
+    /// The batch which has set the value or zero if unknown. See `Reference::begin_batch`.
+    batch: AtomicUsize,
+}
+
+impl SlotMeta {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            generation: AtomicUsize::new(0),
+            updated_at: AtomicUsize::new(0),
+            refreshed_at: AtomicUsize::new(0),
+            access: AtomicU8::new(0),
+            batch: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn generation(&self) -> usize {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn bump_generation(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_free(&self) -> bool {
+        self.generation() % 2 == 1
+    }
+
+    /// Returns when the value was last set or `None` if it never was.
+    pub(crate) fn updated_at(&self) -> Option<Instant> {
+        match self.updated_at.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some(clock_base() + Duration::from_millis(millis as u64 - 1)),
+        }
+    }
+
+    /// Returns when the value was last set or looked up extending the expiration.
+    pub(crate) fn refreshed_at(&self) -> Option<Instant> {
+        let millis = self
+            .updated_at
+            .load(Ordering::Relaxed)
+            .max(self.refreshed_at.load(Ordering::Relaxed));
+
+        match millis {
+            0 => None,
+            millis => Some(clock_base() + Duration::from_millis(millis as u64 - 1)),
+        }
+    }
+
+    /// Records a lookup extending the expiration at `now`.
+    pub(crate) fn refresh_at(&self, now: Instant) {
+        self.refreshed_at
+            .store(millis_since_base(now), Ordering::Relaxed);
+    }
+
+    /// Records that the value has been set by `batch` at `now`.
+    pub(crate) fn touch_at(&self, now: Instant, batch: usize) {
+        self.updated_at
+            .store(millis_since_base(now), Ordering::Relaxed);
+        self.batch.store(batch, Ordering::Relaxed);
+    }
+
+    /// Returns the batch which has set the value or `None` if it's unknown.
+    pub(crate) fn batch(&self) -> Option<usize> {
+        match self.batch.load(Ordering::Relaxed) {
+            0 => None,
+            batch => Some(batch),
+        }
+    }
+
+    /// Records a lookup of the slot saturating at `ACCESS_COUNT_MAX`. The counter is loaded
+    /// and stored separately since missing a concurrent lookup is fine while a locked
+    /// instruction on each read is not.
+    pub(crate) fn touch_access(&self) {
+        let count = self.access.load(Ordering::Relaxed);
+
+        if count < ACCESS_COUNT_MAX {
+            self.access.store(count + 1, Ordering::Relaxed);
+        }
+    }
+
+    /// Decrements the lookup counter as the clock hand passes the slot.
+    /// Returns `true` if it has already been zero so the slot hasn't been looked up
+    /// since the previous pass.
+    pub(crate) fn age_access(&self) -> bool {
+        match self.access.load(Ordering::Relaxed) {
+            0 => true,
+            count => {
+                self.access.store(count - 1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    /// Forgets the update and refresh times, batch and lookups when the slot gets reused for a reservation.
+    pub(crate) fn reset_updated_at(&self) {
+        self.updated_at.store(0, Ordering::Relaxed);
+        self.refreshed_at.store(0, Ordering::Relaxed);
+        self.access.store(0, Ordering::Relaxed);
+        self.batch.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Update times are kept as offsets from this instant to fit an atomic.
+pub(crate) fn clock_base() -> Instant {
+    static BASE: OnceLock<Instant> = OnceLock::new();
+    *BASE.get_or_init(Instant::now)
+}
+
+/// Converts `instant` to the representation of times in slots.
+fn millis_since_base(instant: Instant) -> usize {
+    instant.saturating_duration_since(clock_base()).as_millis() as usize + 1
+}
+
+impl fmt::Debug for SlotMeta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlotMeta")
+            .field("generation", &self.generation())
+            .field("updated_at", &self.updated_at())
+            .field("batch", &self.batch())
+            .finish()
+    }
+}
+
+/// A slot of the bundled backends: a value cell along with the metadata.
+pub struct Slot<C> {
+    meta: SlotMeta,
+    value: C,
+}
+
+impl<C> Slot<C> {
+    fn new(value: C) -> Self {
+        Self {
+            meta: SlotMeta::new(),
+            value,
+        }
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for Slot<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.value, f)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The default backend. Slots are `ArcSwapOption`s so both reads and writes are lock-free.
+pub struct ArcSwapBackend<T: 'static> {
+    slots: Array<Slot<ArcSwapOption<T>>>,
+}
+
+impl<T: 'static> ArcSwapBackend<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: Array::new(capacity),
+        }
+    }
+
+    /// Like `new` but returns an error if the capacity is too large to allocate.
+    pub fn try_new(capacity: usize) -> Result<Self, Error<T>> {
+        let slots = Array::try_new(capacity).map_err(|err| Error::Other(Box::new(err)))?;
+        Ok(Self { slots })
+    }
+}
+
+impl<T: 'static> Backend<T> for ArcSwapBackend<T> {
+    type Slot = Slot<ArcSwapOption<T>>;
+    type Iter = ArrayIter<Slot<ArcSwapOption<T>>>;
+
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot> {
+        self.slots.get(vid)
+    }
+
+    fn push_slot(&self, item: Option<Arc<T>>) -> Result<usize, Error<T>> {
+        let vid = self.slots.len();
+
+        self.slots
+            .push(Slot::new(ArcSwapOption::new(item)))
+            .map_err(|err| Error::Other(Box::new(err)))?;
+
+        Ok(vid)
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    fn iter(&self) -> Self::Iter {
+        self.slots.iter()
+    }
+
+    fn load(slot: &Self::Slot) -> Option<Arc<T>> {
+        slot.value.load_full()
+    }
+
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        f(slot.value.load().as_deref())
+    }
+
+    fn meta(slot: &Self::Slot) -> &SlotMeta {
+        &slot.meta
+    }
+
+    fn store(slot: &Self::Slot, item: Option<Arc<T>>) -> Option<Arc<T>> {
+        slot.value.swap(item)
+    }
+
+    fn rcu<F>(slot: &Self::Slot, f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>,
+    {
+        slot.value.rcu(f)
+    }
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for ArcSwapBackend<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ArcSwapBackend").field(&self.slots).finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A backend with slots guarded by `RwLock`s.
+/// This is the `entry_parking_lot_rwlock_arc` strategy from the `sync` bench.
+///
+/// Poisoned slot locks are recovered since a slot value is always replaced as a whole.
+pub struct RwLockBackend<T: 'static> {
+    slots: Array<Slot<RwLock<Option<Arc<T>>>>>,
+}
+
+impl<T: 'static> RwLockBackend<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: Array::new(capacity),
+        }
+    }
+
+    /// Like `new` but returns an error if the capacity is too large to allocate.
+    pub fn try_new(capacity: usize) -> Result<Self, Error<T>> {
+        let slots = Array::try_new(capacity).map_err(|err| Error::Other(Box::new(err)))?;
+        Ok(Self { slots })
+    }
+}
+
+impl<T: 'static> Backend<T> for RwLockBackend<T> {
+    type Slot = Slot<RwLock<Option<Arc<T>>>>;
+    type Iter = ArrayIter<Slot<RwLock<Option<Arc<T>>>>>;
+
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot> {
+        self.slots.get(vid)
+    }
+
+    fn push_slot(&self, item: Option<Arc<T>>) -> Result<usize, Error<T>> {
+        let vid = self.slots.len();
+
+        self.slots
+            .push(Slot::new(RwLock::new(item)))
+            .map_err(|err| Error::Other(Box::new(err)))?;
+
+        Ok(vid)
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    fn iter(&self) -> Self::Iter {
+        self.slots.iter()
+    }
+
+    fn load(slot: &Self::Slot) -> Option<Arc<T>> {
+        slot.value
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        f(slot
+            .value
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_deref())
+    }
+
+    fn meta(slot: &Self::Slot) -> &SlotMeta {
+        &slot.meta
+    }
+
+    fn store(slot: &Self::Slot, item: Option<Arc<T>>) -> Option<Arc<T>> {
+        let mut value = slot.value.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *value, item)
+    }
+
+    fn rcu<F>(slot: &Self::Slot, mut f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>,
+    {
+        // `f` is called outside of the lock like `ArcSwap::rcu` does so a slow or
+        // reentrant `f` doesn't block readers. The value is replaced only if it's the same.
+        loop {
+            let current = Self::load(slot);
+            let new_value = f(&current);
+            let mut value = slot.value.write().unwrap_or_else(PoisonError::into_inner);
+
+            let is_same = match (&*value, &current) {
+                (Some(value), Some(current)) => Arc::ptr_eq(value, current),
+                (None, None) => true,
+                _ => false,
+            };
+
+            if is_same {
+                return std::mem::replace(&mut *value, new_value);
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for RwLockBackend<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RwLockBackend").field(&self.slots).finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A value stored in place along with the number of times it has been replaced.
+#[derive(Debug)]
+pub struct Versioned<T> {
+    item: Option<T>,
+    version: usize,
+}
+
+/// A backend storing items by value in slots guarded by `RwLock`s as the first version
+/// of the crate did.
+///
+/// Stored `Arc`s are unwrapped cloning items which are shared elsewhere and loads clone
+/// items into new `Arc`s. So items should be cheap to clone. Scans like aggregations peek
+/// at items in place without cloning though.
+pub struct InPlaceBackend<T: 'static> {
+    slots: Array<Slot<RwLock<Versioned<T>>>>,
+}
+
+impl<T: 'static> InPlaceBackend<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: Array::new(capacity),
+        }
+    }
+
+    /// Like `new` but returns an error if the capacity is too large to allocate.
+    pub fn try_new(capacity: usize) -> Result<Self, Error<T>> {
+        let slots = Array::try_new(capacity).map_err(|err| Error::Other(Box::new(err)))?;
+        Ok(Self { slots })
+    }
+}
+
+impl<T: Clone + 'static> Backend<T> for InPlaceBackend<T> {
+    type Slot = Slot<RwLock<Versioned<T>>>;
+    type Iter = ArrayIter<Slot<RwLock<Versioned<T>>>>;
+
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot> {
+        self.slots.get(vid)
+    }
+
+    fn push_slot(&self, item: Option<Arc<T>>) -> Result<usize, Error<T>> {
+        let vid = self.slots.len();
+
+        let value = Versioned {
+            item: item.map(Arc::unwrap_or_clone),
+            version: 0,
+        };
+
+        self.slots
+            .push(Slot::new(RwLock::new(value)))
+            .map_err(|err| Error::Other(Box::new(err)))?;
+
+        Ok(vid)
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    fn iter(&self) -> Self::Iter {
+        self.slots.iter()
+    }
+
+    fn load(slot: &Self::Slot) -> Option<Arc<T>> {
+        Self::peek(slot, |maybe_item| maybe_item.cloned().map(Arc::new))
+    }
+
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        f(slot
+            .value
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .item
+            .as_ref())
+    }
+
+    fn meta(slot: &Self::Slot) -> &SlotMeta {
+        &slot.meta
+    }
+
+    fn store(slot: &Self::Slot, item: Option<Arc<T>>) -> Option<Arc<T>> {
+        let item = item.map(Arc::unwrap_or_clone);
+        let mut value = slot.value.write().unwrap_or_else(PoisonError::into_inner);
+        value.version = value.version.wrapping_add(1);
+        std::mem::replace(&mut value.item, item).map(Arc::new)
+    }
+
+    fn rcu<F>(slot: &Self::Slot, mut f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>,
+    {
+        // Loads make new `Arc`s so the version tells whether the value is the same.
+        loop {
+            let (current, version) = {
+                let value = slot.value.read().unwrap_or_else(PoisonError::into_inner);
+                (value.item.clone().map(Arc::new), value.version)
+            };
+
+            let new_item = f(&current).map(Arc::unwrap_or_clone);
+            let mut value = slot.value.write().unwrap_or_else(PoisonError::into_inner);
+
+            if value.version == version {
+                value.version = value.version.wrapping_add(1);
+                value.item = new_item;
+                return current;
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug + 'static> fmt::Debug for InPlaceBackend<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("InPlaceBackend").field(&self.slots).finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A backend storing plain data items inline in seqlock slots. See `SeqCell`.
+///
+/// Neither reads nor writes take locks and storing doesn't allocate. Loads copy items
+/// into new `Arc`s while scans like aggregations peek at copies on the stack.
+pub struct InlineBackend<T: 'static> {
+    slots: Array<Slot<SeqCell<T>>>,
+}
+
+impl<T: 'static> InlineBackend<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: Array::new(capacity),
+        }
+    }
+
+    /// Like `new` but returns an error if the capacity is too large to allocate.
+    pub fn try_new(capacity: usize) -> Result<Self, Error<T>> {
+        let slots = Array::try_new(capacity).map_err(|err| Error::Other(Box::new(err)))?;
+        Ok(Self { slots })
+    }
+}
+
+impl<T: Copy + 'static> Backend<T> for InlineBackend<T> {
+    type Slot = Slot<SeqCell<T>>;
+    type Iter = ArrayIter<Slot<SeqCell<T>>>;
+
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot> {
+        self.slots.get(vid)
+    }
+
+    fn push_slot(&self, item: Option<Arc<T>>) -> Result<usize, Error<T>> {
+        let vid = self.slots.len();
+
+        self.slots
+            .push(Slot::new(SeqCell::new(item.as_deref().copied())))
+            .map_err(|err| Error::Other(Box::new(err)))?;
+
+        Ok(vid)
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    fn iter(&self) -> Self::Iter {
+        self.slots.iter()
+    }
+
+    fn load(slot: &Self::Slot) -> Option<Arc<T>> {
+        slot.value.load().map(Arc::new)
+    }
+
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        f(slot.value.load().as_ref())
+    }
+
+    fn meta(slot: &Self::Slot) -> &SlotMeta {
+        &slot.meta
+    }
+
+    fn store(slot: &Self::Slot, item: Option<Arc<T>>) -> Option<Arc<T>> {
+        slot.value.replace(item.as_deref().copied()).map(Arc::new)
+    }
+
+    fn rcu<F>(slot: &Self::Slot, mut f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>,
+    {
+        slot.value
+            .rcu(|current| f(&current.map(Arc::new)).as_deref().copied())
+            .map(Arc::new)
+    }
+}
+
+impl<T: 'static> fmt::Debug for InlineBackend<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InlineBackend")
+            .field("len", &self.slots.len())
+            .field("capacity", &self.slots.capacity())
+            .finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The default backend with slots placed in an anonymous memory mapping instead of
+/// the heap. The whole capacity is reserved as address space upfront while the OS commits
+/// pages as slots get pushed regardless of the allocator, so a generous capacity costs
+/// memory only when used. Like heap allocations of other backends the mapping is never
+/// released.
+#[cfg(feature = "mmap")]
+pub struct MmapBackend<T: 'static>(ArcSwapBackend<T>);
+
+#[cfg(feature = "mmap")]
+impl<T: 'static> MmapBackend<T> {
+    /// Panics if mapping fails. See `try_new`.
+    pub fn new(capacity: usize) -> Self {
+        match Self::try_new(capacity) {
+            Ok(backend) => backend,
+            Err(err) => panic!("Failed to create mmap backend: {err}"),
+        }
+    }
+
+    /// Like `new` but returns an error if the capacity can't be mapped.
+    pub fn try_new(capacity: usize) -> Result<Self, Error<T>> {
+        let slots = Array::try_new_mapped(capacity).map_err(|err| Error::Other(Box::new(err)))?;
+        Ok(Self(ArcSwapBackend { slots }))
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T: 'static> Backend<T> for MmapBackend<T> {
+    type Slot = Slot<ArcSwapOption<T>>;
+    type Iter = ArrayIter<Slot<ArcSwapOption<T>>>;
+
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot> {
+        self.0.slot(vid)
+    }
+
+    fn push_slot(&self, item: Option<Arc<T>>) -> Result<usize, Error<T>> {
+        self.0.push_slot(item)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    fn iter(&self) -> Self::Iter {
+        self.0.iter()
+    }
+
+    fn load(slot: &Self::Slot) -> Option<Arc<T>> {
+        ArcSwapBackend::load(slot)
+    }
+
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        ArcSwapBackend::peek(slot, f)
+    }
+
+    fn meta(slot: &Self::Slot) -> &SlotMeta {
+        &slot.meta
+    }
+
+    fn store(slot: &Self::Slot, item: Option<Arc<T>>) -> Option<Arc<T>> {
+        ArcSwapBackend::store(slot, item)
+    }
+
+    fn rcu<F>(slot: &Self::Slot, f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>,
+    {
+        ArcSwapBackend::rcu(slot, f)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<T: fmt::Debug + 'static> fmt::Debug for MmapBackend<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MmapBackend").field(&self.0.slots).finish()
+    }
+}
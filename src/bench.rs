@@ -0,0 +1,269 @@
+//! A harness for benchmarking references with custom entity types.
+//!
+//! The bundled benches measure primitives only while the actual costs depend on the entity
+//! type, the id index and the backend. A `Harness` populates a reference with synthetic
+//! entities, runs updater threads replacing random items at the given rates and measures
+//! latencies of reads made meanwhile.
+//!
+//! ```
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use reference::bench::Harness;
+//! # use reference::{Id, Identifiable, Reference};
+//! #
+//! struct Foo {
+//!     id: Id<Self>,
+//! }
+//! #
+//! # impl Identifiable for Foo {
+//! #     fn id(&self) -> Id<Self> {
+//! #         self.id
+//! #     }
+//! # }
+//!
+//! let reference = Arc::new(Reference::new(1001));
+//!
+//! let report = Harness::new(reference, 1000, |id| Foo { id })
+//!     .with_updaters(2, Duration::from_millis(1))
+//!     .measure_reads(10_000)
+//!     .unwrap();
+//!
+//! assert_eq!(report.samples, 10_000);
+//! assert!(report.p50 <= report.p99);
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Backend, Error, Id, Identifiable, Reference};
+
+type Factory<T> = Arc<dyn Fn(Id<T>) -> T + Send + Sync>;
+
+/// Read latency percentiles measured by a `Harness`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LatencyReport {
+    /// Number of measured reads.
+    pub samples: usize,
+    /// Number of reads which have found a resolved item.
+    pub hits: usize,
+    /// Number of items replaced by updaters during the measurement.
+    pub updates: usize,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} reads ({} hits, {} updates): mean {:?}, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+            self.samples,
+            self.hits,
+            self.updates,
+            self.mean,
+            self.p50,
+            self.p90,
+            self.p99,
+            self.max
+        )
+    }
+}
+
+/// Runs read benchmarks against a reference under concurrent updates.
+pub struct Harness<T: Identifiable + 'static, B: Backend<T>> {
+    reference: Arc<Reference<T, B>>,
+    factory: Factory<T>,
+    size: i32,
+    updaters: usize,
+    update_period: Duration,
+}
+
+impl<T, B> Harness<T, B>
+where
+    T: Identifiable + Send + Sync + 'static,
+    B: Backend<T>,
+    Reference<T, B>: Send + Sync,
+{
+    /// Creates a harness filling `reference` with items of ids from 1 to `size` built by
+    /// `factory`. The reference must have capacity for `size` items and the zero element.
+    /// There are no updaters by default.
+    pub fn new<F>(reference: Arc<Reference<T, B>>, size: usize, factory: F) -> Self
+    where
+        F: Fn(Id<T>) -> T + Send + Sync + 'static,
+    {
+        Self {
+            reference,
+            factory: Arc::new(factory),
+            size: i32::try_from(size).unwrap_or(i32::MAX),
+            updaters: 0,
+            update_period: Duration::ZERO,
+        }
+    }
+
+    /// Sets the number of threads each replacing a random item every `period`.
+    /// A zero `period` makes them replace items continuously.
+    pub fn with_updaters(mut self, count: usize, period: Duration) -> Self {
+        self.updaters = count;
+        self.update_period = period;
+        self
+    }
+
+    /// Returns the benchmarked reference.
+    pub fn reference(&self) -> &Arc<Reference<T, B>> {
+        &self.reference
+    }
+
+    /// Upserts all synthetic items into the reference.
+    pub fn populate(&self) -> Result<(), Error<T>> {
+        for id in 1..=self.size {
+            self.reference.upsert((self.factory)(id.into()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Populates the reference, starts updaters and makes `reads` reads of random ids
+    /// timing each of them. Updaters are stopped before returning.
+    pub fn measure_reads(&self, reads: usize) -> Result<LatencyReport, Error<T>> {
+        self.populate()?;
+
+        let is_halt = Arc::new(AtomicBool::new(false));
+        let updates = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..self.updaters)
+            .map(|_| {
+                let updater = Updater {
+                    reference: self.reference.clone(),
+                    factory: self.factory.clone(),
+                    size: self.size,
+                    period: self.update_period,
+                    is_halt: is_halt.clone(),
+                    updates: updates.clone(),
+                };
+
+                thread::spawn(move || updater.run())
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = Rng::new();
+        let mut latencies = Vec::with_capacity(reads);
+        let mut hits = 0;
+
+        for _ in 0..reads {
+            let id = rng.id(self.size);
+            let start = Instant::now();
+            let item = self.reference.get(id).and_then(|entry| entry.load());
+            latencies.push(start.elapsed());
+
+            if black_box(item).is_some() {
+                hits += 1;
+            }
+        }
+
+        is_halt.store(true, Ordering::SeqCst);
+
+        for handle in handles {
+            let result = handle.join().expect("Updater panicked");
+            result.map_err(|message| Error::Other(message.into()))?;
+        }
+
+        Ok(LatencyReport {
+            hits,
+            updates: updates.load(Ordering::SeqCst),
+            ..LatencyReport::from_latencies(latencies)
+        })
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> fmt::Debug for Harness<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Harness")
+            .field("size", &self.size)
+            .field("updaters", &self.updaters)
+            .field("update_period", &self.update_period)
+            .finish()
+    }
+}
+
+impl LatencyReport {
+    fn from_latencies(mut latencies: Vec<Duration>) -> Self {
+        if latencies.is_empty() {
+            return Self::default();
+        }
+
+        latencies.sort_unstable();
+
+        let samples = latencies.len();
+        let percentile = |p: usize| latencies[(samples - 1) * p / 100];
+
+        Self {
+            samples,
+            mean: latencies.iter().sum::<Duration>() / samples as u32,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: latencies[samples - 1],
+            ..Self::default()
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+struct Updater<T: Identifiable + 'static, B: Backend<T>> {
+    reference: Arc<Reference<T, B>>,
+    factory: Factory<T>,
+    size: i32,
+    period: Duration,
+    is_halt: Arc<AtomicBool>,
+    updates: Arc<AtomicUsize>,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Updater<T, B> {
+    /// Error isn't `Send` so a failed update is reported as a message.
+    fn run(self) -> Result<(), String> {
+        let mut rng = Rng::new();
+
+        while !self.is_halt.load(Ordering::Relaxed) {
+            let item = (self.factory)(rng.id(self.size));
+
+            self.reference
+                .upsert(item)
+                .map_err(|err| format!("Failed to update: {err}"))?;
+
+            self.updates.fetch_add(1, Ordering::Relaxed);
+
+            if !self.period.is_zero() {
+                thread::sleep(self.period);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A xorshift generator of ids which is cheap enough not to affect measurements.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Self(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    fn id<T>(&mut self, size: i32) -> Id<T> {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        Id::from((self.0 % size.max(1) as u64) as i32 + 1)
+    }
+}
@@ -0,0 +1,104 @@
+use std::f64::consts::LN_2;
+use std::fmt;
+
+use super::poison::INDEX_LOCK;
+use super::sync::{AtomicUsize, Ordering};
+use super::{Backend, Id, Identifiable, Reference};
+
+/// A Bloom filter of ids known to a reference. It's lock-free so misses don't touch the index lock.
+///
+/// Ids are never removed from the filter since bits are shared between ids.
+/// Removed ids just become false positives.
+pub(crate) struct BloomFilter {
+    words: Box<[AtomicUsize]>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// Creates a filter sized for `expected` ids with the given false positive rate.
+    pub(crate) fn new(expected: usize, false_positive_rate: f64) -> Self {
+        let expected = expected.max(1) as f64;
+        let bits = (-expected * false_positive_rate.ln() / (LN_2 * LN_2)).ceil() as usize;
+        let words = bits.div_ceil(usize::BITS as usize).max(1);
+        let bits = words * usize::BITS as usize;
+        let hashes = ((bits as f64 / expected) * LN_2).round().max(1.0) as u32;
+
+        Self {
+            words: (0..words).map(|_| AtomicUsize::new(0)).collect(),
+            hashes,
+        }
+    }
+
+    pub(crate) fn insert<T>(&self, id: Id<T>) {
+        for (word, mask) in self.positions(id) {
+            self.words[word].fetch_or(mask, Ordering::SeqCst);
+        }
+    }
+
+    /// Returns `false` if `id` has never been inserted.
+    pub(crate) fn may_contain<T>(&self, id: Id<T>) -> bool {
+        self.positions(id)
+            .all(|(word, mask)| self.words[word].load(Ordering::SeqCst) & mask != 0)
+    }
+
+    /// Returns word indexes and bit masks of `id` using double hashing.
+    fn positions<T>(&self, id: Id<T>) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let hash = mix(id.as_i32() as u32 as u64);
+        let (h1, h2) = (hash & 0xFFFF_FFFF, (hash >> 32) | 1);
+        let bits = (self.words.len() * usize::BITS as usize) as u64;
+
+        (0..self.hashes as u64).map(move |i| {
+            let bit = (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize;
+            let word_bits = usize::BITS as usize;
+            (bit / word_bits, 1 << (bit % word_bits))
+        })
+    }
+}
+
+/// SplitMix64 finalizer.
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+impl fmt::Debug for BloomFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BloomFilter")
+            .field("bits", &(self.words.len() * usize::BITS as usize))
+            .field("hashes", &self.hashes)
+            .finish()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Enables a Bloom filter of ids so lookups of absent ids are answered
+    /// without taking the index lock. It's sized for the capacity of the reference
+    /// with the given false positive rate which must be in `(0, 1)`.
+    ///
+    /// It's worth it when most lookups are misses. Removed ids stay in the filter
+    /// so they add up to false positives.
+    pub fn with_bloom_filter(mut self, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "False positive rate must be in (0, 1)"
+        );
+
+        let bloom = BloomFilter::new(self.items.capacity(), false_positive_rate);
+
+        for (id, _) in self.recovered_lock(self.vids.read(), INDEX_LOCK).iter() {
+            bloom.insert(id);
+        }
+
+        self.bloom = Some(bloom);
+        self
+    }
+
+    /// Returns `false` if `id` is surely unknown to the reference.
+    pub(crate) fn may_contain(&self, id: Id<T>) -> bool {
+        match &self.bloom {
+            Some(bloom) => bloom.may_contain(id),
+            None => true,
+        }
+    }
+}
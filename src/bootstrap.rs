@@ -0,0 +1,336 @@
+//! Loading interdependent references in the right order.
+//!
+//! Items of one reference usually refer to items of others through entries so it's
+//! convenient to load referents first and take resolved entries with `get`. A `Bootstrap`
+//! collects per-type loaders with their dependencies, orders them topologically and checks
+//! that no reservation has been left unresolved once all of them are done.
+//!
+//! Dependencies forming a cycle can't be ordered. Then the earliest registered loader of the
+//! cycle runs first as if its dependencies were loaded so it must take entries with
+//! `get_or_reserve` and the following loaders resolve those reservations.
+
+use std::any::{type_name, TypeId};
+use std::error::Error as StdError;
+use std::fmt;
+
+use super::graph::Node;
+use super::poison::INDEX_LOCK;
+use super::sync::MaybeSync;
+use super::{Backend, Id, Identifiable, Reference};
+
+/// An error of loading a set of references.
+#[derive(Debug)]
+pub enum BootstrapError {
+    /// A loader has failed. Loaders of the following steps are not run.
+    LoadFailed {
+        type_name: &'static str,
+        source: Box<dyn StdError + 'static>,
+    },
+    /// Some entities have been reserved but none of the loaders have provided them.
+    Unresolved(Vec<Node>),
+}
+
+impl fmt::Display for BootstrapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LoadFailed { type_name, source } => {
+                write!(f, "Failed to load reference of {type_name}: {source}")
+            }
+            Self::Unresolved(nodes) => write!(f, "Unresolved reservations: {nodes:?}"),
+        }
+    }
+}
+
+impl StdError for BootstrapError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::LoadFailed { source, .. } => Some(source.as_ref()),
+            Self::Unresolved(_nodes) => None,
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A loader of a reference type-erased for registration.
+trait Step: MaybeSync {
+    fn type_id(&self) -> TypeId;
+    fn type_name(&self) -> &'static str;
+    fn load(&mut self) -> Result<(), Box<dyn StdError>>;
+    fn unresolved(&self) -> Vec<Node>;
+}
+
+struct ReferenceStep<'a, T: Identifiable + 'static, B: Backend<T>, L> {
+    reference: &'a Reference<T, B>,
+    loader: Option<L>,
+}
+
+impl<T, B, L, E> Step for ReferenceStep<'_, T, B, L>
+where
+    T: Identifiable + 'static,
+    B: Backend<T>,
+    L: FnOnce(&Reference<T, B>) -> Result<(), E> + MaybeSync,
+    E: Into<Box<dyn StdError>>,
+    Reference<T, B>: MaybeSync,
+{
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn load(&mut self) -> Result<(), Box<dyn StdError>> {
+        match self.loader.take() {
+            Some(loader) => loader(self.reference).map_err(Into::into),
+            None => Ok(()),
+        }
+    }
+
+    fn unresolved(&self) -> Vec<Node> {
+        self.reference
+            .unresolved_ids()
+            .into_iter()
+            .map(Node::new)
+            .collect()
+    }
+}
+
+/// A plan of loading references of different types.
+///
+/// ```
+/// # use reference::bootstrap::Bootstrap;
+/// # use reference::{Entry, Id, Identifiable, Reference};
+/// #
+/// struct Subject {
+///     id: Id<Self>,
+/// }
+/// #
+/// # impl Identifiable for Subject {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// struct Product {
+///     id: Id<Self>,
+///     subject: Entry<Subject>,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// let subjects = Reference::new(2);
+/// let products = Reference::new(2);
+/// let mut bootstrap = Bootstrap::new();
+///
+/// bootstrap
+///     .add(&products, |products| {
+///         let subject = subjects.get_or_reserve(1.into())?;
+///         products.insert(Product { id: 1.into(), subject })?;
+///         Ok::<_, Box<dyn std::error::Error>>(())
+///     })
+///     .add(&subjects, |subjects| {
+///         subjects.insert(Subject { id: 1.into() }).map(|_| ())
+///     })
+///     .dependency::<Product, Subject>();
+///
+/// bootstrap.run().unwrap();
+/// ```
+#[derive(Default)]
+pub struct Bootstrap<'a> {
+    steps: Vec<Box<dyn Step + 'a>>,
+    dependencies: Vec<(TypeId, TypeId)>,
+}
+
+impl<'a> Bootstrap<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `loader` filling `reference`.
+    pub fn add<T, B, L, E>(&mut self, reference: &'a Reference<T, B>, loader: L) -> &mut Self
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+        L: FnOnce(&Reference<T, B>) -> Result<(), E> + MaybeSync + 'a,
+        E: Into<Box<dyn StdError>>,
+        Reference<T, B>: MaybeSync,
+    {
+        let step = ReferenceStep {
+            reference,
+            loader: Some(loader),
+        };
+
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Declares that items of `T` refer to items of `U` so `U` has to be loaded first.
+    /// Dependencies on types without a registered loader are ignored.
+    pub fn dependency<T: 'static, U: 'static>(&mut self) -> &mut Self {
+        self.dependencies
+            .push((TypeId::of::<T>(), TypeId::of::<U>()));
+        self
+    }
+
+    /// Returns type names of registered references in the order they are going to be loaded.
+    pub fn order(&self) -> Vec<&'static str> {
+        self.levels()
+            .into_iter()
+            .flatten()
+            .map(|idx| self.steps[idx].type_name())
+            .collect()
+    }
+
+    /// Runs loaders one by one and validates that no reservations are left unresolved
+    /// in the registered references.
+    pub fn run(mut self) -> Result<(), BootstrapError> {
+        for level in self.levels() {
+            for idx in level {
+                let step = &mut self.steps[idx];
+
+                step.load().map_err(|source| BootstrapError::LoadFailed {
+                    type_name: step.type_name(),
+                    source,
+                })?;
+            }
+        }
+
+        self.validate()
+    }
+
+    /// Like `run` but runs loaders of independent references in parallel threads.
+    /// Loader errors are passed between threads as messages.
+    #[cfg(not(feature = "single-thread"))]
+    pub fn run_parallel(mut self) -> Result<(), BootstrapError> {
+        for level in self.levels() {
+            let steps = self
+                .steps
+                .iter_mut()
+                .enumerate()
+                .filter(|(idx, _)| level.contains(idx))
+                .map(|(_, step)| step);
+
+            std::thread::scope(|scope| {
+                let handles = steps
+                    .map(|step| {
+                        let type_name = step.type_name();
+                        let handle =
+                            scope.spawn(move || step.load().map_err(|err| err.to_string()));
+                        (type_name, handle)
+                    })
+                    .collect::<Vec<_>>();
+
+                for (type_name, handle) in handles {
+                    let result = handle.join().expect("Loader panicked");
+
+                    result.map_err(|message| BootstrapError::LoadFailed {
+                        type_name,
+                        source: message.into(),
+                    })?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        self.validate()
+    }
+
+    fn validate(&self) -> Result<(), BootstrapError> {
+        let unresolved = self
+            .steps
+            .iter()
+            .flat_map(|step| step.unresolved())
+            .collect::<Vec<_>>();
+
+        match unresolved.is_empty() {
+            true => Ok(()),
+            false => Err(BootstrapError::Unresolved(unresolved)),
+        }
+    }
+
+    /// Groups step indexes into levels where each level depends only on previous ones.
+    /// A cycle is broken by taking its earliest registered step as if it had no dependencies.
+    fn levels(&self) -> Vec<Vec<usize>> {
+        let len = self.steps.len();
+
+        let mut dependencies = (0..len)
+            .map(|idx| {
+                let type_id = self.steps[idx].type_id();
+
+                self.dependencies
+                    .iter()
+                    .filter(|(dependent, _)| *dependent == type_id)
+                    .flat_map(|(_, dependency)| {
+                        (0..len).filter(|other| {
+                            *other != idx && self.steps[*other].type_id() == *dependency
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut done = vec![false; len];
+        let mut levels = Vec::new();
+
+        while done.iter().any(|done| !done) {
+            let mut level = (0..len)
+                .filter(|idx| !done[*idx])
+                .filter(|idx| dependencies[*idx].iter().all(|dep| done[*dep]))
+                .collect::<Vec<_>>();
+
+            if level.is_empty() {
+                let idx = (0..len).find(|idx| !done[*idx]).expect("No steps left");
+
+                log::debug!(
+                    "Loading {} before its dependencies since they form a cycle",
+                    self.steps[idx].type_name()
+                );
+
+                dependencies[idx].clear();
+                level.push(idx);
+            }
+
+            for idx in &level {
+                done[*idx] = true;
+            }
+
+            levels.push(level);
+        }
+
+        levels
+    }
+}
+
+impl fmt::Debug for Bootstrap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bootstrap")
+            .field("order", &self.order())
+            .finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Returns ids which have been reserved but don't have an item yet.
+    pub fn unresolved_ids(&self) -> Vec<Id<T>> {
+        let vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+
+        // The zero element is never resolved and isn't a reservation.
+        vids.iter()
+            .filter(|(_, vid)| *vid != 0)
+            .filter(|(_, vid)| match self.items.slot(*vid as usize) {
+                Some(slot) => B::load(slot).is_none(),
+                None => true,
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
@@ -0,0 +1,126 @@
+//! Telling apart references of the same entity type loaded from different contexts.
+//!
+//! References of `T` for, e.g. staging and production datasets, accept each other's ids and
+//! their entries are interchangeable. Wrapping items into `Branded<T, C>` with a distinct
+//! brand type `C` per context makes mixing them up a compile-time error:
+//!
+//! ```compile_fail
+//! # use reference::{Branded, Id, Identifiable, Reference};
+//! # struct Product {
+//! #     id: Id<Self>,
+//! # }
+//! #
+//! # impl Identifiable for Product {
+//! #     fn id(&self) -> Id<Self> {
+//! #         self.id
+//! #     }
+//! # }
+//! enum Staging {}
+//! enum Production {}
+//!
+//! let staging = Reference::<Branded<Product, Staging>>::new(2);
+//! let production = Reference::<Branded<Product, Production>>::new(2);
+//!
+//! let id = Branded::<Product, Staging>::brand_id(1.into());
+//! production.get(id);
+//! ```
+
+use std::any::type_name;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use super::{Id, Identifiable};
+
+/// An item of `T` branded with the context `C` it belongs to.
+/// Dereferences to the item so its fields and methods are accessible as is.
+///
+/// ```
+/// # use reference::{Branded, Id, Identifiable, Reference};
+/// struct Product {
+///     id: Id<Self>,
+///     name: &'static str,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// enum Staging {}
+///
+/// let staging = Reference::new(2);
+///
+/// staging
+///     .insert(Branded::<_, Staging>::new(Product { id: 1.into(), name: "one" }))
+///     .unwrap();
+///
+/// let id = Branded::<Product, Staging>::brand_id(1.into());
+/// let item = staging.get(id).and_then(|entry| entry.load()).unwrap();
+/// assert_eq!(item.name, "one");
+/// ```
+pub struct Branded<T, C> {
+    value: T,
+    brand: PhantomData<fn() -> C>,
+}
+
+impl<T, C> Branded<T, C> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            brand: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Brands an id of `T` with the context.
+    pub fn brand_id(id: Id<T>) -> Id<Self> {
+        Id::new(id.as_i32())
+    }
+}
+
+impl<T: Identifiable, C> Identifiable for Branded<T, C> {
+    fn id(&self) -> Id<Self> {
+        Self::brand_id(self.value.id())
+    }
+}
+
+impl<T, C> Deref for Branded<T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, C> AsRef<T> for Branded<T, C> {
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Clone, C> Clone for Branded<T, C> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<T: PartialEq, C> PartialEq for Branded<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq, C> Eq for Branded<T, C> {}
+
+impl<T: fmt::Debug, C> fmt::Debug for Branded<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple(&format!("Branded<{}>", type_name::<C>()))
+            .field(&self.value)
+            .finish()
+    }
+}
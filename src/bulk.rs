@@ -0,0 +1,150 @@
+//! Loading batches of items which may be partially bad.
+
+use std::fmt;
+use std::sync::Arc;
+
+use super::poison::INDEX_LOCK;
+use super::progress::Progress;
+use super::{Backend, DuplicateMode, Error, Id, Identifiable, Reference};
+
+/// Outcome of loading a batch of items which doesn't stop at the first failure.
+#[non_exhaustive]
+pub struct BulkReport<T> {
+    /// Number of items added to new slots or resolving reservations.
+    pub inserted: usize,
+    /// Number of items which have replaced resolved ones.
+    pub replaced: usize,
+    /// Ids of items skipped since an item with the same id was already there.
+    pub duplicates: Vec<Id<T>>,
+    /// Other failures along with positions of the items in the batch.
+    pub errors: Vec<(usize, Error<T>)>,
+}
+
+impl<T> BulkReport<T> {
+    /// Tells whether all the items have been loaded.
+    pub fn is_clean(&self) -> bool {
+        self.duplicates.is_empty() && self.errors.is_empty()
+    }
+}
+
+impl<T> Default for BulkReport<T> {
+    fn default() -> Self {
+        Self {
+            inserted: 0,
+            replaced: 0,
+            duplicates: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for BulkReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BulkReport")
+            .field("inserted", &self.inserted)
+            .field("replaced", &self.replaced)
+            .field("duplicates", &self.duplicates)
+            .field("errors", &self.errors)
+            .finish()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Inserts `items` one by one according to the duplicate mode going on after failures.
+    /// With `DuplicateMode::Reject` items with resolved ids are listed in `duplicates`.
+    pub fn insert_many<I>(&self, items: I) -> BulkReport<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.insert_many_inner(items, || ())
+    }
+
+    /// Like `insert_many` but calls `callback` with the progress every `every` items,
+    /// e.g. `DEFAULT_PROGRESS_EVERY`, and once done. The total is known if `items` tell
+    /// their exact number. The reference is loading meanwhile. See `readiness`.
+    pub fn insert_many_with_progress<I, F>(
+        &self,
+        items: I,
+        every: usize,
+        callback: F,
+    ) -> BulkReport<T>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(&Progress),
+    {
+        let items = items.into_iter();
+
+        let total = match items.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        };
+
+        let (hydration, mut meter) = self.start_hydration(total, every, callback);
+        let report = self.insert_many_inner(items, || meter.advance(1, self.now()));
+        self.finish_hydration(hydration, meter);
+        report
+    }
+
+    /// Does the job of `insert_many` calling `processed` after each item.
+    fn insert_many_inner<I>(&self, items: I, mut processed: impl FnMut()) -> BulkReport<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let mut report = BulkReport::default();
+
+        for (idx, item) in items.into_iter().enumerate() {
+            match self.insert_inner(item) {
+                Ok((_, true)) => report.replaced += 1,
+                Ok((_, false)) => report.inserted += 1,
+                Err(Error::DuplicateId(id)) => report.duplicates.push(id),
+                Err(err) => report.errors.push((idx, err)),
+            }
+
+            processed();
+        }
+
+        report
+    }
+}
+
+impl<T: Identifiable + 'static> Reference<T> {
+    /// Like `from_iter_with_headroom` but reports duplicates and failures instead of
+    /// returning an error. The first item of each id is kept.
+    pub fn from_iter_with_report<I>(iter: I, headroom: f64) -> (Self, BulkReport<T>)
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        let reference = Self::with_headroom(iter.len(), headroom);
+        let mut report = BulkReport::default();
+
+        {
+            // The reference is not shared yet so the lock can't be poisoned.
+            let mut vids = reference.recovered_lock(reference.vids.write(), INDEX_LOCK);
+
+            for (idx, item) in iter.enumerate() {
+                let id = item.id();
+
+                if vids.get(id).is_some() {
+                    report.duplicates.push(id);
+                    continue;
+                }
+
+                let item = Arc::new(item);
+                let mode = DuplicateMode::Replace;
+
+                match reference.add_locked(&mut vids, id, Some(item.clone()), mode) {
+                    Ok((entry, _)) => {
+                        // It's fine to call the hook under the lock for the same reason.
+                        item.attached(&entry);
+                        report.inserted += 1;
+                    }
+                    Err(err) => report.errors.push((idx, err)),
+                }
+            }
+        }
+
+        (reference, report)
+    }
+}
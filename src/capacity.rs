@@ -0,0 +1,198 @@
+use std::fmt;
+use std::mem::size_of;
+
+use super::poison;
+use super::sync::MaybeSync;
+use super::{ArcSwapBackend, Backend, Error, Identifiable, Reference};
+
+/// Default utilization fraction above which a warning gets logged.
+pub const DEFAULT_UTILIZATION_WARNING_THRESHOLD: f64 = 0.9;
+
+/// Utilization fractions worth notifying operators about: nearly full, almost full and full.
+pub const DEFAULT_CAPACITY_EVENT_THRESHOLDS: [f64; 3] = [0.8, 0.95, 1.0];
+
+/// Returns capacity for `expected` items plus `headroom` fraction of it and the zero element.
+pub(crate) fn capacity_with_headroom(expected: usize, headroom: f64) -> usize {
+    expected + (expected as f64 * headroom.max(0.0)).ceil() as usize + 1
+}
+
+impl<T: Identifiable + 'static> Reference<T> {
+    /// Creates a `Reference<T>` for `expected` number of items leaving room for
+    /// `fraction` of it more. For example `with_headroom(1000, 0.2)` fits 1200 items.
+    pub fn with_headroom(expected: usize, fraction: f64) -> Self {
+        Self::with_backend(ArcSwapBackend::new(capacity_with_headroom(
+            expected, fraction,
+        )))
+    }
+
+    /// Creates a `Reference<T>` filled with items from `iter` with capacity just enough for them.
+    /// See `from_iter_with_headroom`.
+    pub fn from_iter_exact<I>(iter: I) -> Result<Self, Error<T>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        Self::from_iter_with_headroom(iter, 0.0)
+    }
+
+    /// Creates a `Reference<T>` filled with items from `iter` leaving room for
+    /// `headroom` fraction of their number to insert more items later.
+    ///
+    /// Items are inserted in a single batch. `Identifiable::attached` hook is called for each.
+    /// If some ids occur more than once only the first item is kept and `Error::DuplicateIds`
+    /// listing the ids is returned.
+    pub fn from_iter_with_headroom<I>(iter: I, headroom: f64) -> Result<Self, Error<T>>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let (reference, report) = Self::from_iter_with_report(iter, headroom);
+
+        if let Some((_, err)) = report.errors.into_iter().next() {
+            return Err(err);
+        }
+
+        match report.duplicates.is_empty() {
+            true => Ok(reference),
+            false => Err(Error::DuplicateIds(report.duplicates)),
+        }
+    }
+}
+
+impl<T: Identifiable + 'static> TryFrom<Vec<T>> for Reference<T> {
+    type Error = Error<T>;
+
+    fn try_from(items: Vec<T>) -> Result<Self, Self::Error> {
+        Self::from_iter_exact(items)
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets utilization fraction above which a warning gets logged on adding an item.
+    /// The default is `DEFAULT_UTILIZATION_WARNING_THRESHOLD`.
+    pub fn with_utilization_warning_threshold(self, threshold: f64) -> Self {
+        self.tune(|tunables| tunables.utilization_warning_threshold = threshold);
+        self
+    }
+
+    /// Returns the fraction of capacity taken by items, reservations and the zero element.
+    /// Slots of removed items are considered free.
+    pub fn utilization(&self) -> f64 {
+        self.used_slots() as f64 / self.items.capacity() as f64
+    }
+
+    /// Releases memory reserved for growth after the load phase: shrinks the id index and
+    /// the free slot list and drops allocations pooled by `insert_pooled`.
+    /// Returns the approximate number of bytes reclaimed.
+    ///
+    /// Slots are preallocated for the whole capacity and never freed.
+    pub fn trim(&self) -> usize {
+        let free_list = {
+            let mut free_vids = self.recovered_lock(self.free_vids.lock(), poison::FREE_LIST_LOCK);
+            let before = free_vids.capacity();
+            free_vids.shrink_to_fit();
+            (before - free_vids.capacity()) * size_of::<u32>()
+        };
+
+        self.shrink_index_to_fit() + free_list + self.pool.clear()
+    }
+
+    /// Returns the number of slots except free ones.
+    pub(crate) fn used_slots(&self) -> usize {
+        let free_vids = self.recovered_lock(self.free_vids.lock(), poison::FREE_LIST_LOCK);
+        self.items.len() - free_vids.len()
+    }
+
+    /// Calls `listener` each time utilization grows past one of `thresholds` on adding
+    /// an item, e.g. `DEFAULT_CAPACITY_EVENT_THRESHOLDS`, to warn before inserts start failing.
+    /// The listener is called outside of the reference locks.
+    pub fn with_capacity_events<L>(mut self, thresholds: &[f64], listener: L) -> Self
+    where
+        L: Fn(&CapacityEvent) + MaybeSync + 'static,
+    {
+        self.capacity_events = CapacityEvents {
+            thresholds: thresholds.to_vec(),
+            listener: Some(Box::new(listener)),
+        };
+
+        self
+    }
+
+    /// Logs a warning and notifies the capacity event listener if utilization has just
+    /// crossed the thresholds.
+    pub(crate) fn warn_on_utilization(&self, utilization_before: f64) {
+        let threshold = self.tunables().utilization_warning_threshold;
+        let utilization = self.utilization();
+
+        if utilization_before < threshold && utilization >= threshold {
+            log::warn!(
+                "Reference of {} is {:.0}% full: {} of {} slots are used",
+                std::any::type_name::<T>(),
+                utilization * 100.0,
+                self.used_slots(),
+                self.items.capacity(),
+            );
+        }
+
+        let Some(listener) = &self.capacity_events.listener else {
+            return;
+        };
+
+        for threshold in &self.capacity_events.thresholds {
+            if utilization_before < *threshold && utilization >= *threshold {
+                listener.notify(&CapacityEvent {
+                    type_name: std::any::type_name::<T>(),
+                    threshold: *threshold,
+                    utilization,
+                    used: self.used_slots(),
+                    capacity: self.items.capacity(),
+                });
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Notification of utilization growing past a threshold.
+/// See `Reference::with_capacity_events`.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct CapacityEvent {
+    /// Name of the item type of the reference.
+    pub type_name: &'static str,
+    /// The threshold crossed.
+    pub threshold: f64,
+    /// Utilization after crossing the threshold.
+    pub utilization: f64,
+    /// Number of used slots including reservations and the zero element.
+    pub used: usize,
+    /// Maximum number of slots.
+    pub capacity: usize,
+}
+
+trait CapacityListener: MaybeSync {
+    fn notify(&self, event: &CapacityEvent);
+}
+
+impl<F: Fn(&CapacityEvent) + MaybeSync> CapacityListener for F {
+    fn notify(&self, event: &CapacityEvent) {
+        self(event)
+    }
+}
+
+/// Capacity event thresholds with their listener.
+#[derive(Default)]
+pub(crate) struct CapacityEvents {
+    thresholds: Vec<f64>,
+    listener: Option<Box<dyn CapacityListener>>,
+}
+
+impl fmt::Debug for CapacityEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapacityEvents")
+            .field("thresholds", &self.thresholds)
+            .field("has_listener", &self.listener.is_some())
+            .finish()
+    }
+}
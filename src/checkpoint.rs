@@ -0,0 +1,304 @@
+//! Checkpoints of all references registered in a `Registry` at once.
+//!
+//! A checkpoint holds a snapshot of each reference registered with a codec along with
+//! a table of links between items of different references. Restoring reserves all link
+//! targets first and then decodes the parts so decoders may take entries of other types with
+//! `get` regardless of the order of registration. Items are inserted only once every part
+//! is decoded and fits.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use rustc_hash::FxHashMap;
+
+use super::graph::Registry;
+
+const MAGIC: &[u8; 8] = b"REFCKPT\0";
+const FORMAT_VERSION: u8 = 1;
+
+/// An error of saving or restoring a checkpoint.
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    /// Saving or loading a snapshot of a registered reference has failed.
+    Reference {
+        type_name: String,
+        source: Box<dyn StdError + 'static>,
+    },
+    /// The checkpoint doesn't match the registered references.
+    Mismatch(String),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "Checkpoint I/O error: {err}"),
+            Self::Reference { type_name, source } => {
+                write!(f, "Failed to checkpoint reference of {type_name}: {source}")
+            }
+            Self::Mismatch(msg) => write!(f, "Checkpoint mismatch: {msg}"),
+        }
+    }
+}
+
+impl StdError for CheckpointError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Reference { source, .. } => Some(source.as_ref()),
+            Self::Mismatch(_msg) => None,
+        }
+    }
+}
+
+impl From<io::Error> for CheckpointError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A link from an item of one part to an item of another.
+struct Link {
+    source_part: u32,
+    source_id: i32,
+    target_part: u32,
+    target_id: i32,
+}
+
+impl Registry<'_> {
+    /// Writes snapshots of all registered references and links between their items
+    /// to `writer`. All references must be registered with `register_with_codec`.
+    ///
+    /// Links to items of unregistered types aren't saved. Snapshots are buffered in memory
+    /// one at a time while saving unlike restoring which holds the whole checkpoint.
+    pub fn save_to<W: Write>(&self, mut writer: W) -> Result<(), CheckpointError> {
+        let parts = self
+            .references
+            .iter()
+            .enumerate()
+            .map(|(idx, reference)| (reference.type_name(), idx as u32))
+            .collect::<FxHashMap<_, _>>();
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[FORMAT_VERSION])?;
+        writer.write_all(&(self.references.len() as u32).to_le_bytes())?;
+
+        let mut links = Vec::new();
+        let mut buf = Vec::new();
+
+        for (idx, reference) in self.references.iter().enumerate() {
+            let name = reference.type_name().as_bytes();
+            writer.write_all(&(name.len() as u16).to_le_bytes())?;
+            writer.write_all(name)?;
+
+            buf.clear();
+
+            reference
+                .save(&mut buf)
+                .map_err(|source| CheckpointError::Reference {
+                    type_name: reference.type_name().to_owned(),
+                    source,
+                })?;
+
+            writer.write_all(&(buf.len() as u64).to_le_bytes())?;
+            writer.write_all(&buf)?;
+
+            reference.visit(&mut |node, targets| {
+                for target in targets {
+                    if let Some(&target_part) = parts.get(target.type_name()) {
+                        links.push(Link {
+                            source_part: idx as u32,
+                            source_id: node.raw_id(),
+                            target_part,
+                            target_id: target.raw_id(),
+                        });
+                    }
+                }
+            });
+        }
+
+        writer.write_all(&(links.len() as u64).to_le_bytes())?;
+
+        for link in &links {
+            writer.write_all(&link.source_part.to_le_bytes())?;
+            writer.write_all(&link.source_id.to_le_bytes())?;
+            writer.write_all(&link.target_part.to_le_bytes())?;
+            writer.write_all(&link.target_id.to_le_bytes())?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Restores references from a checkpoint written by `save_to` with the same types
+    /// registered in the same order. References must have room for the restored items.
+    ///
+    /// Link targets are reserved before any part is decoded so decoders may take entries
+    /// of other registered types with `get`. All parts are decoded and checked to fit
+    /// before anything is inserted so a corrupt checkpoint or a lack of room fails
+    /// without leaving anything behind. The whole checkpoint is held in memory meanwhile.
+    pub fn restore_from<R: Read>(&self, mut reader: R) -> Result<(), CheckpointError> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(CheckpointError::Mismatch("Not a checkpoint".to_owned()));
+        }
+
+        let version = read_array::<1>(&mut reader)?[0];
+
+        if version != FORMAT_VERSION {
+            let msg = format!("Unsupported checkpoint format version {version}");
+            return Err(CheckpointError::Mismatch(msg));
+        }
+
+        let count = u32::from_le_bytes(read_array(&mut reader)?) as usize;
+
+        if count != self.references.len() {
+            let msg = format!(
+                "{count} references saved but {} registered",
+                self.references.len()
+            );
+
+            return Err(CheckpointError::Mismatch(msg));
+        }
+
+        let mut parts = Vec::with_capacity(count);
+
+        for reference in &self.references {
+            let name_len = u16::from_le_bytes(read_array(&mut reader)?) as u64;
+            let name = read_vec(&mut reader, name_len)?;
+
+            if name != reference.type_name().as_bytes() {
+                let msg = format!(
+                    "Expected {} but found {}",
+                    reference.type_name(),
+                    String::from_utf8_lossy(&name)
+                );
+
+                return Err(CheckpointError::Mismatch(msg));
+            }
+
+            let len = u64::from_le_bytes(read_array(&mut reader)?);
+            parts.push(read_vec(&mut reader, len)?);
+        }
+
+        let links_count = u64::from_le_bytes(read_array(&mut reader)?);
+        let mut links = Vec::new();
+
+        for _ in 0..links_count {
+            let link = Link {
+                source_part: u32::from_le_bytes(read_array(&mut reader)?),
+                source_id: i32::from_le_bytes(read_array(&mut reader)?),
+                target_part: u32::from_le_bytes(read_array(&mut reader)?),
+                target_id: i32::from_le_bytes(read_array(&mut reader)?),
+            };
+
+            for part in [link.source_part, link.target_part] {
+                if part as usize >= count {
+                    let msg = format!("Link to unknown reference {part}");
+                    return Err(CheckpointError::Mismatch(msg));
+                }
+            }
+
+            links.push(link);
+        }
+
+        let mut reserved = Vec::new();
+        let result = self.restore_parts(&parts, &links, &mut reserved);
+
+        if result.is_err() {
+            for link in reserved {
+                self.references[link.target_part as usize].unreserve(link.target_id);
+            }
+        }
+
+        result
+    }
+
+    /// Reserves link targets pushing links with new reservations to `reserved`,
+    /// decodes and checks all parts and then inserts their items.
+    fn restore_parts<'l>(
+        &self,
+        parts: &[Vec<u8>],
+        links: &'l [Link],
+        reserved: &mut Vec<&'l Link>,
+    ) -> Result<(), CheckpointError> {
+        for link in links {
+            let reference = &self.references[link.target_part as usize];
+
+            if reference
+                .reserve(link.target_id)
+                .map_err(|source| reference_error(reference.type_name(), source))?
+            {
+                reserved.push(link);
+            }
+        }
+
+        let mut decoded = Vec::with_capacity(parts.len());
+
+        for (reference, bytes) in self.references.iter().zip(parts) {
+            let items = reference
+                .decode(bytes)
+                .map_err(|source| reference_error(reference.type_name(), source))?;
+
+            let lacking = items
+                .lacking_slots()
+                .map_err(|source| reference_error(reference.type_name(), source))?;
+
+            if lacking > 0 {
+                let msg = format!("{lacking} more free slots are needed");
+                return Err(reference_error(reference.type_name(), msg.into()));
+            }
+
+            decoded.push(items);
+        }
+
+        for link in links {
+            if !decoded[link.source_part as usize].contains(link.source_id) {
+                let msg = format!(
+                    "Link from {}({}) which isn't saved",
+                    self.references[link.source_part as usize].type_name(),
+                    link.source_id
+                );
+
+                return Err(CheckpointError::Mismatch(msg));
+            }
+        }
+
+        for (reference, items) in self.references.iter().zip(decoded) {
+            items
+                .insert()
+                .map_err(|source| reference_error(reference.type_name(), source))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn reference_error(type_name: &str, source: Box<dyn StdError>) -> CheckpointError {
+    CheckpointError::Reference {
+        type_name: type_name.to_owned(),
+        source,
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads exactly `len` bytes allocating only as much as the reader actually has.
+fn read_vec(reader: &mut impl Read, len: u64) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+
+    if reader.take(len).read_to_end(&mut buf)? as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(buf)
+}
@@ -0,0 +1,116 @@
+//! Sources of the current time for time-based features.
+//!
+//! Update times of slots and the staleness checks built on them take the time from the
+//! reference's `Clock`. A `ManualClock` only moves when advanced so expiration can be tested
+//! without sleeping. Entries don't know their reference so changes made through them are
+//! timed by the reference's clock only with `Reference::modify` and
+//! `Reference::lock_for_update`.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::backend::clock_base;
+use super::sync::MaybeSync;
+use super::{Backend, Entry, Identifiable, Reference, UpdateGuard};
+
+/// A source of the current time.
+pub trait Clock: MaybeSync + fmt::Debug + 'static {
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic system clock. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock standing still until advanced. Clones share the time.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use reference::{Id, Identifiable, ManualClock, Reference};
+/// # struct Foo {
+/// #     id: Id<Self>,
+/// # }
+/// #
+/// # impl Identifiable for Foo {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+/// let clock = ManualClock::new();
+/// let reference = Reference::new(2).with_clock(clock.clone());
+/// reference.insert(Foo { id: 1.into() }).unwrap();
+///
+/// assert!(reference.stale_ids(Duration::from_secs(60)).is_empty());
+/// clock.advance(Duration::from_secs(61));
+/// assert_eq!(reference.stale_ids(Duration::from_secs(60)), vec![1.into()]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a clock showing the current time.
+    pub fn new() -> Self {
+        // Update times are stored in whole milliseconds since the base so starting
+        // at one of them makes durations between them exact.
+        let base = clock_base();
+        let now = base + Duration::from_millis(base.elapsed().as_millis() as u64);
+
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Moves the time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner()) += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets the clock for update times of slots. The default is `SystemClock`.
+    pub fn with_clock<C: Clock>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Like `Entry::modify` but the update time is taken from the reference's clock.
+    /// `entry` must belong to this reference.
+    pub fn modify<F>(&self, entry: &Entry<T, B>, f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&T) -> T,
+    {
+        entry.modify_at(&*self.clock, f)
+    }
+
+    /// Like `Entry::lock_for_update` but stores through the guard are timed by
+    /// the reference's clock. `entry` must belong to this reference.
+    pub fn lock_for_update(&self, entry: &Entry<T, B>) -> UpdateGuard<T, B> {
+        entry.lock_for_update_at(Some(self.clock.clone()))
+    }
+
+    /// Returns the current time of the reference's clock.
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+}
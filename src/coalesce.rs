@@ -0,0 +1,142 @@
+//! Coalescing rapid updates of the same items.
+//!
+//! When an upstream replaces some items many times a second, applying every value makes
+//! readers observe short-living ones and each of them allocates an `Arc` and notifies
+//! indexes. A `Coalescer` buffers updates and applies only the latest value of each id
+//! periodically.
+
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use rustc_hash::FxHashMap;
+
+use super::scheduler::Stop;
+use super::{Backend, Error, Id, Identifiable, Reference};
+
+/// Buffers upserts to a reference and applies the latest value of each id every interval
+/// in a background thread. Pending updates are flushed when dropped.
+///
+/// Until then readers of the reference observe the previous values.
+pub struct Coalescer<T: Identifiable + 'static, B: Backend<T>> {
+    shared: Arc<Shared<T, B>>,
+    stop: Arc<Stop>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared<T: Identifiable + 'static, B: Backend<T>> {
+    reference: Arc<Reference<T, B>>,
+    pending: Mutex<FxHashMap<Id<T>, T>>,
+    coalesced: AtomicUsize,
+}
+
+impl<T, B> Coalescer<T, B>
+where
+    T: Identifiable + Send + Sync + 'static,
+    B: Backend<T>,
+    Reference<T, B>: Send + Sync,
+{
+    /// Starts flushing updates of `reference` every `interval`.
+    pub fn start(reference: Arc<Reference<T, B>>, interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            reference,
+            pending: Mutex::new(FxHashMap::default()),
+            coalesced: AtomicUsize::new(0),
+        });
+
+        let stop = Arc::new(Stop::default());
+        let (thread_shared, thread_stop) = (shared.clone(), stop.clone());
+
+        let thread = std::thread::Builder::new()
+            .name("reference-coalesce".to_string())
+            .spawn(move || {
+                while thread_stop.wait(interval) {
+                    if let Err(err) = thread_shared.flush() {
+                        log::error!("Failed to flush coalesced updates: {err}");
+                    }
+                }
+            })
+            .expect("Failed to spawn coalescing thread");
+
+        Self {
+            shared,
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Buffers `item` to be upserted replacing a pending update of its id if any.
+    pub fn upsert(&self, item: T) {
+        if self.shared.pending().insert(item.id(), item).is_some() {
+            self.shared.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of buffered updates.
+    pub fn pending(&self) -> usize {
+        self.shared.pending().len()
+    }
+
+    /// Returns the number of updates dropped since a later one of the same id has replaced
+    /// them before being applied.
+    pub fn coalesced(&self) -> usize {
+        self.shared.coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Applies the buffered updates right away and returns their number.
+    ///
+    /// All of them are tried even if some fail. The first error is returned then.
+    pub fn flush(&self) -> Result<usize, Error<T>> {
+        self.shared.flush()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Shared<T, B> {
+    fn pending(&self) -> MutexGuard<'_, FxHashMap<Id<T>, T>> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn flush(&self) -> Result<usize, Error<T>> {
+        let pending = mem::take(&mut *self.pending());
+        let count = pending.len();
+        let mut result = Ok(count);
+
+        for item in pending.into_values() {
+            if let Err(err) = self.reference.upsert(item) {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Drop for Coalescer<T, B> {
+    fn drop(&mut self) {
+        self.stop.set();
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Coalescing thread panicked");
+            }
+        }
+
+        if let Err(err) = self.shared.flush() {
+            log::error!("Failed to flush coalesced updates: {err}");
+        }
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> fmt::Debug for Coalescer<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Coalescer")
+            .field("pending", &self.shared.pending().len())
+            .field("coalesced", &self.shared.coalesced.load(Ordering::Relaxed))
+            .finish()
+    }
+}
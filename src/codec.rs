@@ -0,0 +1,25 @@
+use std::error::Error as StdError;
+
+use super::sync::MaybeSync;
+
+/// Converts items to bytes and back when they leave the process.
+pub trait Codec<T>: MaybeSync + 'static {
+    /// Appends the encoded `item` to `buf`.
+    fn encode(&self, item: &T, buf: &mut Vec<u8>) -> Result<(), Box<dyn StdError + Send + Sync>>;
+
+    /// Decodes an item from `bytes` produced by `encode`.
+    fn decode(&self, bytes: &[u8]) -> Result<T, Box<dyn StdError + Send + Sync>>;
+
+    /// Version of the encoding saved to snapshots. Bump it when `T` or the encoding changes
+    /// and implement `migrate` to read older snapshots.
+    fn schema_version(&self) -> u32 {
+        0
+    }
+
+    /// Decodes an item from `bytes` encoded with an older or newer `version` of the schema.
+    /// Fails by default so snapshots of other versions are never decoded as the current one.
+    fn migrate(&self, version: u32, bytes: &[u8]) -> Result<T, Box<dyn StdError + Send + Sync>> {
+        let _ = bytes;
+        Err(format!("No migration from schema version {version}").into())
+    }
+}
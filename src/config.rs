@@ -0,0 +1,152 @@
+//! Tunables of references kept out of the code, e.g. in a TOML or JSON manifest.
+//!
+//! With the `serde` feature configs deserialize from any `serde` format. Missing fields
+//! take default values so a manifest only lists what differs.
+//!
+//! ```toml
+//! [references.Product]
+//! capacity = 100000
+//! headroom = 0.2
+//! ttl_secs = 3600
+//! capacity_policy = "evict_least_recently_used"
+//! id_index = "flat"
+//! ```
+
+use std::any::type_name;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::capacity::capacity_with_headroom;
+use super::{
+    Backend, CapacityPolicy, Error, FlatIdIndex, Identifiable, Reference, SortedVecIndex,
+    DEFAULT_UTILIZATION_WARNING_THRESHOLD,
+};
+
+/// Expected number of items of a reference which isn't configured otherwise.
+pub const DEFAULT_CONFIG_CAPACITY: usize = 1024;
+
+/// Which `IdIndex` a configured reference uses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum IdIndexKind {
+    /// `FxHashMap`, the default.
+    #[default]
+    Hash,
+    /// `FlatIdIndex` for dense ids.
+    Flat,
+    /// `SortedVecIndex` for ids rarely added after loading.
+    SortedVec,
+}
+
+/// Tunables of a reference of one entity type.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct ReferenceConfig {
+    /// Expected number of items.
+    pub capacity: usize,
+    /// Fraction of `capacity` to leave room for. See `Reference::with_headroom`.
+    pub headroom: f64,
+    /// Default TTL of items in seconds. See `Reference::with_ttl`.
+    pub ttl_secs: Option<u64>,
+    /// See `Reference::with_refresh_on_read`.
+    pub refresh_on_read: bool,
+    pub capacity_policy: CapacityPolicy,
+    pub id_index: IdIndexKind,
+    /// See `Reference::with_utilization_warning_threshold`.
+    pub utilization_warning_threshold: f64,
+}
+
+impl Default for ReferenceConfig {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CONFIG_CAPACITY,
+            headroom: 0.0,
+            ttl_secs: None,
+            refresh_on_read: false,
+            capacity_policy: CapacityPolicy::default(),
+            id_index: IdIndexKind::default(),
+            utilization_warning_threshold: DEFAULT_UTILIZATION_WARNING_THRESHOLD,
+        }
+    }
+}
+
+impl ReferenceConfig {
+    /// Creates an empty reference configured accordingly.
+    pub fn build<T: Identifiable + 'static>(&self) -> Result<Reference<T>, Error<T>> {
+        let capacity = capacity_with_headroom(self.capacity, self.headroom);
+        Ok(self.apply(Reference::try_new(capacity)?))
+    }
+
+    /// Applies the tunables except for the capacity to `reference`.
+    pub fn apply<T, B>(&self, reference: Reference<T, B>) -> Reference<T, B>
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+    {
+        let mut reference = reference
+            .with_capacity_policy(self.capacity_policy)
+            .with_utilization_warning_threshold(self.utilization_warning_threshold);
+
+        if let Some(secs) = self.ttl_secs {
+            reference = reference.with_ttl(Duration::from_secs(secs));
+        }
+
+        if self.refresh_on_read {
+            reference = reference.with_refresh_on_read();
+        }
+
+        let capacity = capacity_with_headroom(self.capacity, self.headroom);
+
+        match self.id_index {
+            IdIndexKind::Hash => reference,
+            IdIndexKind::Flat => reference.with_id_index(FlatIdIndex::with_capacity(capacity)),
+            IdIndexKind::SortedVec => {
+                reference.with_id_index(SortedVecIndex::with_capacity(capacity))
+            }
+        }
+    }
+}
+
+/// Configs of references by entity type.
+///
+/// Types are keyed by their names without the module path, e.g. `Product`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct Manifest {
+    /// The config of types not listed in `references`.
+    pub defaults: ReferenceConfig,
+    pub references: BTreeMap<String, ReferenceConfig>,
+}
+
+impl Manifest {
+    /// Returns the config of `T`.
+    pub fn config<T: 'static>(&self) -> &ReferenceConfig {
+        self.references
+            .get(short_type_name::<T>())
+            .unwrap_or(&self.defaults)
+    }
+
+    /// Creates an empty reference of `T` configured accordingly.
+    pub fn build<T: Identifiable + 'static>(&self) -> Result<Reference<T>, Error<T>> {
+        self.config::<T>().build()
+    }
+}
+
+/// Strips the module path and generic arguments off the name of `T`.
+fn short_type_name<T: 'static>() -> &'static str {
+    let name = type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
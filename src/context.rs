@@ -0,0 +1,120 @@
+//! Access to references through a context holding them.
+//!
+//! Business logic generic over `C: HasReference<Product>` works with any context which has
+//! products, be it an application-wide struct, a test fixture or a bare reference.
+//!
+//! `ContextExt` is implemented for every type so it's not re-exported from the crate root
+//! to avoid shadowing `get` methods reachable through deref.
+
+use std::sync::Arc;
+
+use super::{Entry, Error, Id, Identifiable, Reference};
+
+/// A context holding a reference of `T`.
+///
+/// ```
+/// # use reference::context::{ContextExt, HasReference};
+/// # use reference::{Id, Identifiable, Reference};
+/// #
+/// struct Product {
+///     id: Id<Self>,
+///     price: u64,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// struct Ctx {
+///     products: Reference<Product>,
+/// }
+///
+/// impl HasReference<Product> for Ctx {
+///     fn reference(&self) -> &Reference<Product> {
+///         &self.products
+///     }
+/// }
+///
+/// fn price<C: HasReference<Product>>(ctx: &C, id: Id<Product>) -> Option<u64> {
+///     Some(ctx.get::<Product>(id)?.load()?.price)
+/// }
+///
+/// let ctx = Ctx { products: Reference::new(2) };
+/// ctx.products.insert(Product { id: 1.into(), price: 100 }).unwrap();
+/// assert_eq!(price(&ctx, 1.into()), Some(100));
+/// ```
+pub trait HasReference<T: Identifiable + 'static> {
+    fn reference(&self) -> &Reference<T>;
+}
+
+impl<T: Identifiable + 'static> HasReference<T> for Reference<T> {
+    fn reference(&self) -> &Reference<T> {
+        self
+    }
+}
+
+impl<T: Identifiable + 'static, C: HasReference<T> + ?Sized> HasReference<T> for &C {
+    fn reference(&self) -> &Reference<T> {
+        (**self).reference()
+    }
+}
+
+impl<T: Identifiable + 'static, C: HasReference<T> + ?Sized> HasReference<T> for Arc<C> {
+    fn reference(&self) -> &Reference<T> {
+        (**self).reference()
+    }
+}
+
+/// Shortcuts for contexts selecting the reference by the item type, e.g. `ctx.get::<Product>(id)`.
+pub trait ContextExt {
+    /// Returns the entry of `T` with `id` or `None` if there's no such item.
+    fn get<T>(&self, id: Id<T>) -> Option<Entry<T>>
+    where
+        T: Identifiable + 'static,
+        Self: HasReference<T>,
+    {
+        self.reference().get(id)
+    }
+
+    /// Returns the entry of `T` with `id` reserving it if there's no such item yet.
+    fn get_or_reserve<T>(&self, id: Id<T>) -> Result<Entry<T>, Error<T>>
+    where
+        T: Identifiable + 'static,
+        Self: HasReference<T>,
+    {
+        self.reference().get_or_reserve(id)
+    }
+
+    /// Loads items of `D` referring to items of `T` in two phases with `Reference::link`.
+    fn link<D, T, R>(
+        &self,
+        rows: impl IntoIterator<Item = R>,
+        key: impl Fn(&R) -> Id<T>,
+        build: impl FnMut(R, Entry<T>) -> D,
+    ) -> Result<Vec<Id<T>>, Error<D>>
+    where
+        D: Identifiable + 'static,
+        T: Identifiable + 'static,
+        Self: HasReference<D> + HasReference<T>,
+    {
+        HasReference::<D>::reference(self).link(
+            HasReference::<T>::reference(self),
+            rows,
+            key,
+            build,
+        )
+    }
+
+    /// Tells whether there's an item of `T` with `id` having a value.
+    fn contains_resolved<T>(&self, id: Id<T>) -> bool
+    where
+        T: Identifiable + 'static,
+        Self: HasReference<T>,
+    {
+        self.reference().contains_resolved(id)
+    }
+}
+
+impl<C: ?Sized> ContextExt for C {}
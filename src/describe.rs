@@ -0,0 +1,46 @@
+//! Lightweight descriptions of entries for logging.
+
+use std::any::type_name;
+use std::fmt;
+
+use super::{Backend, Entry, Identifiable};
+
+/// A snapshot of what an entry refers to which doesn't hold the item.
+///
+/// Displays as `Type#id v{version}` or `Type#? v{version}` if the entry is empty or stale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct EntryDescription {
+    /// Full name of the item type.
+    pub type_name: &'static str,
+    /// Id of the referent or `None` if the entry is empty or stale.
+    pub id: Option<i32>,
+    /// Whether the entry had an item at the moment of description.
+    pub resolved: bool,
+    /// Generation of the slot the entry was created with.
+    pub version: usize,
+}
+
+impl fmt::Display for EntryDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.id {
+            Some(id) => write!(f, "{}#{id} v{}", self.type_name, self.version),
+            None => write!(f, "{}#? v{}", self.type_name, self.version),
+        }
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Entry<T, B> {
+    /// Describes the entry for logs without handing out the item.
+    pub fn describe(&self) -> EntryDescription {
+        let id = self.load().map(|item| item.id().as_i32());
+
+        EntryDescription {
+            type_name: type_name::<T>(),
+            id,
+            resolved: id.is_some(),
+            version: self.generation,
+        }
+    }
+}
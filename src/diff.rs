@@ -0,0 +1,173 @@
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use rustc_hash::FxHashSet;
+
+use super::{Backend, DuplicateMode, Error, Id, Identifiable, Reference, Timestamp};
+
+/// Changes turning one state of a reference into another.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ChangeSet<T> {
+    /// Items absent in the old state.
+    pub added: Vec<Arc<T>>,
+    /// Items which are different in the new state.
+    pub updated: Vec<Arc<T>>,
+    /// Ids of items absent in the new state.
+    pub removed: Vec<Id<T>>,
+    /// When the changes have been made if they come from a replica. See `with_timestamp`.
+    pub timestamp: Option<Timestamp>,
+}
+
+impl<T> ChangeSet<T> {
+    pub fn new(added: Vec<Arc<T>>, updated: Vec<Arc<T>>, removed: Vec<Id<T>>) -> Self {
+        Self {
+            added,
+            updated,
+            removed,
+            timestamp: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl<T> Default for ChangeSet<T> {
+    fn default() -> Self {
+        Self::new(Vec::new(), Vec::new(), Vec::new())
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Returns changes turning this reference into `newer` ordered by id.
+    /// Items are compared by value. Reservations are ignored.
+    pub fn diff<NB: Backend<T>>(&self, newer: &Reference<T, NB>) -> ChangeSet<T>
+    where
+        T: PartialEq,
+    {
+        let mut changes = ChangeSet::default();
+
+        for item in newer.iter().filter_map(|entry| entry.load()) {
+            match self.get(item.id()).and_then(|entry| entry.load()) {
+                None => changes.added.push(item),
+                Some(old) if *old != *item => changes.updated.push(item),
+                Some(_) => (),
+            }
+        }
+
+        for item in self.iter().filter_map(|entry| entry.load()) {
+            if !newer.contains_resolved(item.id()) {
+                changes.removed.push(item.id());
+            }
+        }
+
+        changes.added.sort_by_key(|item| item.id().as_i32());
+        changes.updated.sort_by_key(|item| item.id().as_i32());
+        changes.removed.sort_by_key(|id| id.as_i32());
+        changes
+    }
+
+    /// Upserts added and updated items and removes removed ones.
+    ///
+    /// Timestamped changes are merged: changes of ids having a later timestamp are skipped
+    /// and items having an earlier one are resolved by the merge strategy. See `with_merge`.
+    pub fn apply(&self, changes: &ChangeSet<T>) -> Result<(), Error<T>> {
+        if let Some(timestamp) = changes.timestamp {
+            return self.merge_changes(changes, timestamp);
+        }
+
+        for item in changes.added.iter().chain(&changes.updated) {
+            self.insert_arc(item.clone(), DuplicateMode::Replace)?;
+        }
+
+        for id in &changes.removed {
+            self.remove(*id);
+        }
+
+        Ok(())
+    }
+
+    /// Makes the reference hold exactly `items`, e.g. a full dump from upstream, changing
+    /// only what differs. Unchanged items keep their `Arc`s so caches keyed by them and
+    /// entries of other items stay valid. Reservations are kept.
+    ///
+    /// Removals are applied first to make room for additions. Nothing is applied if
+    /// the additions don't fit even then. Returns the applied changes.
+    pub fn sync_with(&self, items: impl IntoIterator<Item = T>) -> Result<ChangeSet<T>, Error<T>>
+    where
+        T: PartialEq,
+    {
+        self.sync_in(items, |_| true)
+    }
+
+    /// Like `sync_with` but only items with ids in `range` are removed if absent in `items`,
+    /// e.g. to repair a range found by `reconcile`.
+    pub fn sync_range(
+        &self,
+        range: RangeInclusive<i32>,
+        items: impl IntoIterator<Item = T>,
+    ) -> Result<ChangeSet<T>, Error<T>>
+    where
+        T: PartialEq,
+    {
+        self.sync_in(items, |id| range.contains(&id.as_i32()))
+    }
+
+    fn sync_in<F>(
+        &self,
+        items: impl IntoIterator<Item = T>,
+        in_scope: F,
+    ) -> Result<ChangeSet<T>, Error<T>>
+    where
+        T: PartialEq,
+        F: Fn(Id<T>) -> bool,
+    {
+        self.check_writable()?;
+        let mut changes = ChangeSet::default();
+        let mut seen = FxHashSet::default();
+
+        for item in items {
+            seen.insert(item.id());
+
+            match self.get(item.id()).and_then(|entry| entry.load()) {
+                None => changes.added.push(Arc::new(item)),
+                Some(old) if *old != item => changes.updated.push(Arc::new(item)),
+                Some(_) => (),
+            }
+        }
+
+        for item in self.iter().filter_map(|entry| entry.load()) {
+            if in_scope(item.id()) && !seen.contains(&item.id()) {
+                changes.removed.push(item.id());
+            }
+        }
+
+        let lacking = self.lacking_sync_slots(&changes)?;
+
+        if lacking > 0 {
+            return Err(Error::InsertError(format!(
+                "Failed to sync {} items: {lacking} more free slots are needed",
+                changes.added.len() + changes.updated.len()
+            )));
+        }
+
+        for id in &changes.removed {
+            self.remove(*id);
+        }
+
+        for item in changes.added.iter().chain(&changes.updated) {
+            self.insert_arc(item.clone(), DuplicateMode::Replace)?;
+        }
+
+        Ok(changes)
+    }
+
+    /// Returns how many more free slots additions of `changes` need after its removals.
+    fn lacking_sync_slots(&self, changes: &ChangeSet<T>) -> Result<usize, Error<T>> {
+        // Added items may fill reservations which already have slots.
+        let ids = changes.added.iter().map(|item| item.id());
+        self.lacking_free_slots(ids, changes.removed.len())
+    }
+}
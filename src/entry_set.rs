@@ -0,0 +1,110 @@
+//! Entries of a small fixed set of well-known items.
+
+use std::fmt;
+use std::ops::Index;
+
+use super::{ArcSwapBackend, Backend, Entry, Error, Id, Identifiable, Reference};
+
+/// A key of an `EntrySet`, typically a fieldless enum naming well-known items of `T`.
+///
+/// ```
+/// # use reference::{EntryKey, Id, Identifiable, Reference};
+/// #
+/// struct Currency {
+///     id: Id<Self>,
+///     code: &'static str,
+/// }
+/// #
+/// # impl Identifiable for Currency {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// #[derive(Clone, Copy, PartialEq, Eq)]
+/// enum Code {
+///     Usd = 1,
+///     Eur = 2,
+/// }
+///
+/// impl EntryKey<Currency> for Code {
+///     const ALL: &'static [Self] = &[Self::Usd, Self::Eur];
+///
+///     fn id(self) -> Id<Currency> {
+///         Id::new(self as i32)
+///     }
+/// }
+///
+/// let currencies = Reference::new(3);
+/// let set = currencies.entry_set::<Code>().unwrap();
+/// currencies.insert(Currency { id: 2.into(), code: "EUR" }).unwrap();
+/// assert_eq!(set[Code::Eur].load().unwrap().code, "EUR");
+/// assert!(set[Code::Usd].load().is_none());
+/// ```
+pub trait EntryKey<T>: Copy + PartialEq + 'static {
+    /// All the keys. Each of them must map to a distinct id.
+    const ALL: &'static [Self];
+
+    /// Returns the id of the item the key stands for.
+    fn id(self) -> Id<T>;
+}
+
+/// An entry per key of `K`.
+pub struct EntrySet<K, T: 'static, B: Backend<T> = ArcSwapBackend<T>> {
+    entries: Vec<(K, Entry<T, B>)>,
+}
+
+impl<K: EntryKey<T>, T: 'static, B: Backend<T>> EntrySet<K, T, B> {
+    pub fn get(&self, key: K) -> &Entry<T, B> {
+        // Sets are small so a linear scan beats hashing.
+        let (_, entry) = self
+            .entries
+            .iter()
+            .find(|(k, _)| *k == key)
+            .expect("Key is not in `EntryKey::ALL`");
+
+        entry
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, &Entry<T, B>)> {
+        self.entries.iter().map(|(key, entry)| (*key, entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K: EntryKey<T>, T: 'static, B: Backend<T>> Index<K> for EntrySet<K, T, B> {
+    type Output = Entry<T, B>;
+
+    fn index(&self, key: K) -> &Self::Output {
+        self.get(key)
+    }
+}
+
+impl<K: fmt::Debug, T: 'static, B: Backend<T>> fmt::Debug for EntrySet<K, T, B>
+where
+    B::Slot: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(key, entry)| (key, entry)))
+            .finish()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Returns entries of all the keys of `K` reserving the absent items.
+    pub fn entry_set<K: EntryKey<T>>(&self) -> Result<EntrySet<K, T, B>, Error<T>> {
+        let entries = self.reserve_many(K::ALL.iter().map(|key| key.id()))?;
+
+        Ok(EntrySet {
+            entries: K::ALL.iter().copied().zip(entries).collect(),
+        })
+    }
+}
@@ -3,8 +3,18 @@ use std::error::Error as StdError;
 use std::fmt::{self, Debug};
 use std::marker::PhantomData;
 
+use super::Id;
+
 pub enum Error<T> {
     InsertError(String),
+    DuplicateId(Id<T>),
+    DuplicateIds(Vec<Id<T>>),
+    ReserveFailed { id: Id<T>, capacity: usize },
+    RemoveRestricted { id: Id<T>, dependents: usize },
+    LockPoisoned(&'static str),
+    CodecError(Box<dyn StdError + Send + Sync + 'static>),
+    CorruptSnapshot { offset: u64 },
+    Unavailable { id: Id<T> },
     UpdateError(Box<dyn StdError + 'static>),
     Other(Box<dyn StdError + 'static>),
     _Phantom(PhantomData<T>),
@@ -23,6 +33,37 @@ impl<T> fmt::Display for Error<T> {
         match self {
             Self::InsertError(msg) => write!(f, "Insert error: {msg}"),
             Self::UpdateError(source) => write!(f, "Update error: {source}"),
+            Self::DuplicateId(id) => {
+                write!(f, "Failed to insert id {id} because it already exists")
+            }
+            Self::DuplicateIds(ids) => {
+                write!(f, "Duplicate ids:")?;
+
+                for id in ids {
+                    write!(f, " {id}")?;
+                }
+
+                Ok(())
+            }
+            Self::ReserveFailed { id, capacity } => write!(
+                f,
+                "Failed to reserve id {id}: all {capacity} slots are taken"
+            ),
+            Self::RemoveRestricted { id, dependents } => write!(
+                f,
+                "Failed to remove id {id} because {dependents} items refer to it"
+            ),
+            Self::LockPoisoned(lock) => write!(f, "The {lock} lock is poisoned"),
+            Self::CodecError(source) => write!(f, "Codec error: {source}"),
+            Self::CorruptSnapshot { offset } => {
+                write!(f, "Snapshot is corrupt at byte {offset}")
+            }
+            Self::Unavailable { id } => {
+                write!(
+                    f,
+                    "Failed to read id {id} because the reference is degraded"
+                )
+            }
             Self::Other(source) => write!(f, "{source}"),
             Self::_Phantom(_) => unreachable!(),
         }
@@ -34,6 +75,14 @@ impl<T> StdError for Error<T> {
         match self {
             Self::InsertError(_msg) => None,
             Self::UpdateError(source) => source.source(),
+            Self::DuplicateId(_id) => None,
+            Self::DuplicateIds(_ids) => None,
+            Self::ReserveFailed { .. } => None,
+            Self::LockPoisoned(_lock) => None,
+            Self::CodecError(source) => source.source(),
+            Self::CorruptSnapshot { .. } => None,
+            Self::Unavailable { .. } => None,
+            Self::RemoveRestricted { .. } => None,
             Self::Other(source) => source.source(),
             Self::_Phantom(_) => unreachable!(),
         }
@@ -0,0 +1,72 @@
+//! Making room for new items in full references.
+
+use std::sync::Arc;
+
+use super::poison::INDEX_LOCK;
+use super::sync::{Ordering as AtomicOrdering, RwLockWriteGuard};
+use super::{Backend, Entry, Error, Id, IdIndex, Identifiable, Reference};
+
+/// How `Reference::insert` behaves when all slots are taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum CapacityPolicy {
+    /// Fail like any other insert error.
+    #[default]
+    Reject,
+    /// Remove the least recently used item to make room as in a cache. See `Reference::evict`.
+    /// Lookups by id get tracked for that unless configured by `Reference::with_access_tracking`.
+    EvictLeastRecentlyUsed,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets what inserting a new id into a full reference does.
+    /// The default is `CapacityPolicy::Reject`.
+    pub fn with_capacity_policy(mut self, policy: CapacityPolicy) -> Self {
+        self.capacity_policy = policy;
+        self
+    }
+
+    /// Removes an item which hasn't been looked up recently and returns it.
+    /// Returns `None` if there are no items. Reservations and pinned items are never evicted.
+    ///
+    /// The item is chosen approximately. See `with_access_tracking`.
+    pub fn evict(&self) -> Option<Arc<T>> {
+        loop {
+            let entry = Entry::<T, B>::new(self.clock_victim()?);
+
+            // The victim might have been removed concurrently, e.g. by another eviction.
+            if let Some(item) = entry.load().and_then(|item| self.remove(item.id())) {
+                self.evictions.fetch_add(1, AtomicOrdering::Relaxed);
+                return Some(item);
+            }
+        }
+    }
+
+    /// Locks the index for adding `id` evicting items first if the policy says so and there's
+    /// no room. The room is checked under the lock so a concurrent insert can't take it.
+    pub(crate) fn lock_with_room(&self, id: Id<T>) -> Result<IndexWriteGuard<'_, T>, Error<T>> {
+        loop {
+            let vids = self.checked_lock(self.vids.write(), INDEX_LOCK)?;
+
+            if self.capacity_policy != CapacityPolicy::EvictLeastRecentlyUsed
+                || vids.get(id).is_some()
+                || self.has_free_slot()?
+            {
+                return Ok(vids);
+            }
+
+            // Eviction takes an update lock which goes before the index lock.
+            drop(vids);
+
+            if self.evict().is_none() {
+                return self.checked_lock(self.vids.write(), INDEX_LOCK);
+            }
+        }
+    }
+}
+
+type IndexWriteGuard<'a, T> = RwLockWriteGuard<'a, Box<dyn IdIndex<T>>>;
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use super::{Backend, Entry, Error, Id, Identifiable, Reference};
+
+/// What `Reference::read` does while the reference isn't ready. See `Reference::readiness`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DegradedReads {
+    /// Serve items as they are.
+    #[default]
+    ServeStale,
+    /// Serve the fallback item in place of absent items and, with `with_degradation`,
+    /// items which haven't been updated for longer than the age given there.
+    ServeFallback,
+    /// Fail with `Error::Unavailable`.
+    Fail,
+}
+
+/// Null objects standing in for absent referents, e.g. an "unknown subject".
+impl<T: 'static, B: Backend<T>> Entry<T, B> {
+    /// Returns the current item or `default` if the entry is empty or stale.
+    pub fn load_or(&self, default: Arc<T>) -> Arc<T> {
+        self.load().unwrap_or(default)
+    }
+
+    /// Returns the current item or a new default one if the entry is empty or stale.
+    pub fn load_or_default(&self) -> Arc<T>
+    where
+        T: Default,
+    {
+        self.load().unwrap_or_default()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets an item returned by `load_or_fallback` in place of absent ones.
+    /// It's not stored in any slot so it's not found by its id nor iterated over.
+    pub fn with_fallback(mut self, item: T) -> Self {
+        self.fallback = Some(Arc::new(item));
+        self
+    }
+
+    pub fn fallback(&self) -> Option<&Arc<T>> {
+        self.fallback.as_ref()
+    }
+
+    /// Returns the item of `entry` or the fallback item if the entry is empty or stale.
+    /// Returns `None` only if there's no fallback either.
+    pub fn load_or_fallback(&self, entry: &Entry<T, B>) -> Option<Arc<T>> {
+        entry.load().or_else(|| self.fallback.clone())
+    }
+}
+
+/// Reads under degradation.
+///
+/// The policy is consulted only while the reference is loading or degraded as of the last call
+/// of `readiness`, so the readiness probe or a scheduled task has to call it to detect
+/// degradation. Loading is detected immediately.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets what `read` does while the reference isn't ready.
+    /// The default is `DegradedReads::ServeStale`.
+    pub fn with_degraded_reads(mut self, policy: DegradedReads) -> Self {
+        self.degraded_reads = policy;
+        self
+    }
+
+    /// Returns the item with `id` or `None` if there's none like loading the entry got by
+    /// `get` unless the reference isn't ready and the degraded reads policy says otherwise.
+    pub fn read(&self, id: Id<T>) -> Result<Option<Arc<T>>, Error<T>> {
+        let maybe_entry = self.get(id);
+
+        if self.degraded_reads == DegradedReads::ServeStale || self.was_ready() {
+            return Ok(maybe_entry.and_then(|entry| entry.load()));
+        }
+
+        if self.degraded_reads == DegradedReads::Fail {
+            return Err(Error::Unavailable { id });
+        }
+
+        let is_fresh = |entry: &Entry<T, B>| match self.degradation {
+            Some((older_than, _)) => B::meta(entry.slot).updated_at().is_some_and(|updated_at| {
+                self.now().saturating_duration_since(updated_at) <= older_than
+            }),
+            None => true,
+        };
+
+        Ok(maybe_entry
+            .filter(is_fresh)
+            .and_then(|entry| entry.load())
+            .or_else(|| self.fallback.clone()))
+    }
+}
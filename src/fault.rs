@@ -0,0 +1,170 @@
+//! Injecting failures and delays into references to test how their users cope.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{Backend, Error, Identifiable, Reference};
+
+/// A call of a reference faults may be injected into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Call {
+    /// Inserting a single item by `insert`, `upsert`, `replace_all`, `refresh` and so on.
+    /// A faulty insert fails with `Error::InsertError`.
+    Insert,
+    /// `Reference::get`. A faulty get misses.
+    Get,
+    /// Calling a loader by `refresh` or `refresh_async`. A faulty load fails with `Error::Other`.
+    Load,
+}
+
+const CALLS: usize = 3;
+
+/// A seeded schedule of faults. Clones share the schedule.
+///
+/// Each call of a configured kind draws from a pseudo-random sequence determined by the seed
+/// so the same seed and the same order of calls produce the same faults.
+///
+/// ```
+/// # use reference::testing::{Call, FaultInjector};
+/// # use reference::{Id, Identifiable, Reference};
+/// # struct Foo {
+/// #     id: Id<Self>,
+/// # }
+/// #
+/// # impl Identifiable for Foo {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+/// let faults = FaultInjector::new(42).with_failures(Call::Insert, 1.0);
+/// let reference = Reference::new(2).with_fault_injector(faults.clone());
+///
+/// assert!(reference.insert(Foo { id: 1.into() }).is_err());
+/// assert_eq!(faults.injected(Call::Insert), 1);
+/// ```
+#[derive(Clone)]
+pub struct FaultInjector {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    seed: u64,
+    state: AtomicU64,
+    failures: [f64; CALLS],
+    delays: [Option<(Duration, f64)>; CALLS],
+    injected: [AtomicUsize; CALLS],
+}
+
+impl FaultInjector {
+    /// Creates an injector with the given seed and no faults.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                seed,
+                state: AtomicU64::new(seed | 1),
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Makes `probability` fraction of `call`s fail. Panics if the injector has been cloned.
+    pub fn with_failures(mut self, call: Call, probability: f64) -> Self {
+        self.inner_mut().failures[call as usize] = probability;
+        self
+    }
+
+    /// Makes `probability` fraction of `call`s sleep for `delay` first.
+    /// Panics if the injector has been cloned.
+    pub fn with_delays(mut self, call: Call, delay: Duration, probability: f64) -> Self {
+        self.inner_mut().delays[call as usize] = Some((delay, probability));
+        self
+    }
+
+    /// Returns the number of failures injected into `call`s so far.
+    pub fn injected(&self, call: Call) -> usize {
+        self.inner.injected[call as usize].load(Ordering::Relaxed)
+    }
+
+    /// Delays the call if scheduled and returns `true` if it has to fail.
+    pub(crate) fn inject(&self, call: Call) -> bool {
+        if let Some((delay, probability)) = self.inner.delays[call as usize] {
+            if self.draw() < probability {
+                std::thread::sleep(delay);
+            }
+        }
+
+        let probability = self.inner.failures[call as usize];
+
+        if probability <= 0.0 || self.draw() >= probability {
+            return false;
+        }
+
+        self.inner.injected[call as usize].fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    /// Configuration is done before sharing so the inner state is still unique.
+    fn inner_mut(&mut self) -> &mut Inner {
+        Arc::get_mut(&mut self.inner).expect("Failed to configure a shared fault injector")
+    }
+
+    /// Returns the next number of the sequence in `[0, 1)`.
+    fn draw(&self) -> f64 {
+        let prev = self
+            .inner
+            .state
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |state| {
+                Some(xorshift(state))
+            })
+            .unwrap_or_else(|state| state);
+
+        (xorshift(prev) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl fmt::Debug for FaultInjector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultInjector")
+            .field("seed", &self.inner.seed)
+            .field("failures", &self.inner.failures)
+            .field("delays", &self.inner.delays)
+            .finish()
+    }
+}
+
+pub(crate) fn xorshift(mut state: u64) -> u64 {
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Makes calls of the reference fail or slow down as scheduled by `injector`.
+    pub fn with_fault_injector(mut self, injector: FaultInjector) -> Self {
+        self.faults = Some(injector);
+        self
+    }
+
+    /// Returns `true` if `call` has to fail.
+    pub(crate) fn inject_fault(&self, call: Call) -> bool {
+        match &self.faults {
+            Some(faults) => faults.inject(call),
+            None => false,
+        }
+    }
+
+    /// Fails with an error for `call` if it has to fail.
+    pub(crate) fn check_fault(&self, call: Call) -> Result<(), Error<T>> {
+        match (self.inject_fault(call), call) {
+            (false, _) => Ok(()),
+            (true, Call::Load) => Err(Error::Other("Injected load fault".into())),
+            (true, _) => Err(Error::InsertError("Injected insert fault".into())),
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! C API for reading and filling references from other languages.
+//!
+//! The Rust side wraps a shared reference along with a `Codec` into a `FfiReference`
+//! and passes the raw handle to the foreign side. Items cross the boundary encoded
+//! with the codec. Declarations for C are in `include/reference.h`.
+//!
+//! All functions returning a status return `REFERENCE_OK` on success, `REFERENCE_NOT_FOUND`
+//! for absent items and `REFERENCE_ERROR` on failures which are logged.
+
+use std::ffi::c_void;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+use super::sync::MaybeSync;
+use super::{Backend, Codec, Entry, Error, Identifiable, Reference};
+
+pub const REFERENCE_OK: i32 = 0;
+pub const REFERENCE_NOT_FOUND: i32 = 1;
+pub const REFERENCE_ERROR: i32 = -1;
+
+/// Receives an item id and its encoded bytes which are valid only during the call.
+/// Iteration stops when it returns `false`.
+pub type ItemCallback =
+    extern "C" fn(id: i32, data: *const u8, len: usize, user_data: *mut c_void) -> bool;
+
+/// An opaque handle of a reference for the foreign side.
+pub struct FfiReference {
+    inner: Box<dyn ErasedReference>,
+}
+
+impl FfiReference {
+    pub fn new<T, B, C>(reference: Arc<Reference<T, B>>, codec: C) -> Self
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+        C: Codec<T>,
+        Reference<T, B>: MaybeSync,
+        Entry<T, B>: MaybeSync,
+    {
+        let codec = Arc::new(codec);
+
+        Self {
+            inner: Box::new(ErasedReferenceImpl { reference, codec }),
+        }
+    }
+
+    /// Converts the handle into a pointer to pass to the foreign side which must release it
+    /// with `reference_free`.
+    pub fn into_raw(self) -> *mut Self {
+        Box::into_raw(Box::new(self))
+    }
+}
+
+impl fmt::Debug for FfiReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FfiReference").finish()
+    }
+}
+
+/// An opaque handle of an entry for the foreign side.
+pub struct FfiEntry {
+    inner: Box<dyn ErasedEntry>,
+}
+
+impl fmt::Debug for FfiEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FfiEntry").finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+trait ErasedReference: MaybeSync {
+    fn get(&self, id: i32) -> Option<FfiEntry>;
+    fn for_each(&self, f: &mut dyn FnMut(i32, &[u8]) -> bool) -> i32;
+    fn insert(&self, bytes: &[u8]) -> i32;
+}
+
+trait ErasedEntry: MaybeSync {
+    fn load(&self, f: &mut dyn FnMut(i32, &[u8])) -> i32;
+}
+
+struct ErasedReferenceImpl<T: Identifiable + 'static, B: Backend<T>, C> {
+    reference: Arc<Reference<T, B>>,
+    codec: Arc<C>,
+}
+
+impl<T, B, C> ErasedReference for ErasedReferenceImpl<T, B, C>
+where
+    T: Identifiable + 'static,
+    B: Backend<T>,
+    C: Codec<T>,
+    Reference<T, B>: MaybeSync,
+    Entry<T, B>: MaybeSync,
+{
+    fn get(&self, id: i32) -> Option<FfiEntry> {
+        let entry = self.reference.get(id.into())?;
+
+        let erased = ErasedEntryImpl {
+            entry,
+            codec: self.codec.clone(),
+        };
+
+        Some(FfiEntry {
+            inner: Box::new(erased),
+        })
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(i32, &[u8]) -> bool) -> i32 {
+        let mut buf = Vec::new();
+
+        for item in self.reference.iter().filter_map(|entry| entry.load()) {
+            buf.clear();
+
+            if let Err(err) = self.codec.encode(&item, &mut buf) {
+                log::error!("Failed to encode item {}: {err}", item.id());
+                return REFERENCE_ERROR;
+            }
+
+            if !f(item.id().as_i32(), &buf) {
+                break;
+            }
+        }
+
+        REFERENCE_OK
+    }
+
+    fn insert(&self, bytes: &[u8]) -> i32 {
+        let result = self
+            .codec
+            .decode(bytes)
+            .map_err(Error::CodecError)
+            .and_then(|item| self.reference.insert(item));
+
+        match result {
+            Ok(_) => REFERENCE_OK,
+            Err(err) => {
+                log::error!("Failed to insert item: {err}");
+                REFERENCE_ERROR
+            }
+        }
+    }
+}
+
+struct ErasedEntryImpl<T: 'static, B: Backend<T>, C> {
+    entry: Entry<T, B>,
+    codec: Arc<C>,
+}
+
+impl<T, B, C> ErasedEntry for ErasedEntryImpl<T, B, C>
+where
+    T: Identifiable + 'static,
+    B: Backend<T>,
+    C: Codec<T>,
+    Entry<T, B>: MaybeSync,
+{
+    fn load(&self, f: &mut dyn FnMut(i32, &[u8])) -> i32 {
+        let Some(item) = self.entry.load() else {
+            return REFERENCE_NOT_FOUND;
+        };
+
+        let mut buf = Vec::new();
+
+        match self.codec.encode(&item, &mut buf) {
+            Ok(()) => {
+                f(item.id().as_i32(), &buf);
+                REFERENCE_OK
+            }
+            Err(err) => {
+                log::error!("Failed to encode item {}: {err}", item.id());
+                REFERENCE_ERROR
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Runs `f` turning panics into `REFERENCE_ERROR` since they can't unwind into foreign code.
+fn guard(f: impl FnOnce() -> i32) -> i32 {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(REFERENCE_ERROR)
+}
+
+/// Releases a reference handle.
+///
+/// # Safety
+///
+/// `reference` must be obtained from `FfiReference::into_raw` and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn reference_free(reference: *mut FfiReference) {
+    if !reference.is_null() {
+        drop(Box::from_raw(reference));
+    }
+}
+
+/// Returns an entry of the item with the given `id` or null if there's no such item.
+/// The entry must be released with `reference_entry_free`.
+///
+/// # Safety
+///
+/// `reference` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn reference_get(reference: *const FfiReference, id: i32) -> *mut FfiEntry {
+    let reference = &*reference;
+
+    match catch_unwind(AssertUnwindSafe(|| reference.inner.get(id))) {
+        Ok(Some(entry)) => Box::into_raw(Box::new(entry)),
+        Ok(None) | Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Calls `callback` with each item of the reference.
+///
+/// # Safety
+///
+/// `reference` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn reference_iter(
+    reference: *const FfiReference,
+    callback: ItemCallback,
+    user_data: *mut c_void,
+) -> i32 {
+    let reference = &*reference;
+
+    guard(|| {
+        reference
+            .inner
+            .for_each(&mut |id, bytes| callback(id, bytes.as_ptr(), bytes.len(), user_data))
+    })
+}
+
+/// Decodes an item from `len` bytes at `data` and inserts it to the reference.
+///
+/// # Safety
+///
+/// `reference` must be a valid handle and `data` must point to `len` readable bytes.
+/// `data` may be null if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn reference_insert(
+    reference: *const FfiReference,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let reference = &*reference;
+
+    // `from_raw_parts` needs a non-null pointer even for no bytes.
+    let bytes = match len {
+        0 => &[],
+        _ if data.is_null() => {
+            log::error!("Failed to insert item: null data of {len} bytes");
+            return REFERENCE_ERROR;
+        }
+        _ => std::slice::from_raw_parts(data, len),
+    };
+
+    guard(|| reference.inner.insert(bytes))
+}
+
+/// Releases an entry handle.
+///
+/// # Safety
+///
+/// `entry` must be obtained from `reference_get` and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn reference_entry_free(entry: *mut FfiEntry) {
+    if !entry.is_null() {
+        drop(Box::from_raw(entry));
+    }
+}
+
+/// Calls `callback` with the current item of the entry.
+/// Returns `REFERENCE_NOT_FOUND` without calling it if the entry is empty.
+///
+/// # Safety
+///
+/// `entry` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn reference_entry_load(
+    entry: *const FfiEntry,
+    callback: ItemCallback,
+    user_data: *mut c_void,
+) -> i32 {
+    let entry = &*entry;
+
+    guard(|| {
+        entry.inner.load(&mut |id, bytes| {
+            callback(id, bytes.as_ptr(), bytes.len(), user_data);
+        })
+    })
+}
@@ -0,0 +1,81 @@
+//! Content hashes of items and fingerprints of whole references.
+//!
+//! A fingerprint combines hashes of all resolved items with their ids regardless of the order
+//! so two references hold the same items if their fingerprints match, barring collisions.
+//! Hashes must be deterministic for fingerprints of different processes to be comparable.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use rustc_hash::FxHasher;
+
+use super::bloom::mix;
+use super::sync::MaybeSync;
+use super::unchanged::ByHash;
+use super::{Backend, Id, Identifiable, Reference};
+
+/// Computes a hash of the content of an item. See `Reference::with_content_hash`.
+pub trait ContentHash<T>: MaybeSync + fmt::Debug + 'static {
+    fn content_hash(&self, item: &T) -> u64;
+}
+
+/// Hashes items with `Hash` using `FxHasher` which is deterministic across processes
+/// of the same build.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HashContent;
+
+impl<T: Hash> ContentHash<T> for HashContent {
+    fn content_hash(&self, item: &T) -> u64 {
+        let mut hasher = FxHasher::default();
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl<T, F> ContentHash<T> for ByHash<F>
+where
+    F: Fn(&T) -> u64 + MaybeSync + 'static,
+{
+    fn content_hash(&self, item: &T) -> u64 {
+        (self.0)(item)
+    }
+}
+
+/// Combines `id` with the content `hash` of its item into a term of a fingerprint.
+/// Terms are summed so the order of items doesn't matter.
+pub(crate) fn fingerprint_term<T>(id: Id<T>, hash: u64) -> u64 {
+    mix(mix(id.as_i32() as u32 as u64) ^ hash)
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets how content hashes of items are computed enabling `content_hash`
+    /// and `fingerprint`.
+    pub fn with_content_hash<H: ContentHash<T>>(mut self, hasher: H) -> Self {
+        self.content_hasher = Some(Box::new(hasher));
+        self
+    }
+
+    /// Returns the content hash of the item with `id` or `None` if it's absent, reserved
+    /// or no content hash has been set with `with_content_hash`.
+    pub fn content_hash(&self, id: Id<T>) -> Option<u64> {
+        let hasher = self.content_hasher.as_ref()?;
+        let item = self.get(id)?.load()?;
+        Some(hasher.content_hash(&item))
+    }
+
+    /// Returns a fingerprint of all resolved items or `None` if no content hash has been set
+    /// with `with_content_hash`. Takes a pass over all items hashing each of them.
+    ///
+    /// Items changed meanwhile may be hashed either in the old or in the new state.
+    pub fn fingerprint(&self) -> Option<u64> {
+        let hasher = self.content_hasher.as_ref()?;
+
+        let fingerprint = self
+            .iter()
+            .filter_map(|entry| entry.load())
+            .map(|item| fingerprint_term(item.id(), hasher.content_hash(&item)))
+            .fold(0u64, u64::wrapping_add);
+
+        Some(fingerprint)
+    }
+}
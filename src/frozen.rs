@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::slice;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use super::poison::INDEX_LOCK;
+use super::{Backend, Id, Identifiable, Reference};
+
+/// An immutable snapshot of a `Reference`. See `Reference::freeze`.
+///
+/// Items are kept in a plain vector and the id index is a plain hash map
+/// so reads take no locks and touch no atomics.
+#[derive(Debug)]
+pub struct FrozenReference<T> {
+    items: Vec<Arc<T>>,
+    positions: FxHashMap<Id<T>, u32>,
+}
+
+impl<T: Identifiable> FrozenReference<T> {
+    /// Returns the item with the given `id`.
+    pub fn get(&self, id: Id<T>) -> Option<&Arc<T>> {
+        self.positions
+            .get(&id)
+            .map(|position| &self.items[*position as usize])
+    }
+
+    /// Tells whether there's an item with the given `id`.
+    pub fn contains(&self, id: Id<T>) -> bool {
+        self.positions.contains_key(&id)
+    }
+
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if there are no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Iterates over items in the order of their slots in the original reference.
+    pub fn iter(&self) -> slice::Iter<'_, Arc<T>> {
+        self.items.iter()
+    }
+}
+
+impl<'a, T: Identifiable> IntoIterator for &'a FrozenReference<T> {
+    type Item = &'a Arc<T>;
+    type IntoIter = slice::Iter<'a, Arc<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Converts the reference into an immutable `FrozenReference` for the fastest reads
+    /// when no more changes are expected, e.g. after loading.
+    ///
+    /// Reservations and the zero element are dropped. Existing entries stay valid
+    /// and keep pointing to the original slots which won't change anymore.
+    pub fn freeze(self) -> FrozenReference<T> {
+        let len = self.used_slots();
+        let mut items = Vec::with_capacity(len);
+        let mut positions = FxHashMap::default();
+        positions.reserve(len);
+
+        for item in self.iter().filter_map(|entry| entry.load()) {
+            positions.insert(item.id(), items.len() as u32);
+            items.push(item);
+        }
+
+        FrozenReference { items, positions }
+    }
+
+    /// Copies the current items to a vector in the order of their slots.
+    ///
+    /// No items are added or removed meanwhile but items replaced during the copy may be
+    /// taken either in the old or in the new state.
+    pub fn to_vec(&self) -> Vec<Arc<T>> {
+        let _vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+        self.iter().filter_map(|entry| entry.load()).collect()
+    }
+
+    /// Like `to_vec` but copies the items to a map by id.
+    pub fn to_map(&self) -> HashMap<Id<T>, Arc<T>> {
+        let _vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+
+        self.iter()
+            .filter_map(|entry| entry.load())
+            .map(|item| (item.id(), item))
+            .collect()
+    }
+}
@@ -0,0 +1,650 @@
+//! Reference graph analysis.
+//!
+//! Entities refer to each other through entries. An `Entry` never owns its referent so
+//! cycles of entries don't keep anything alive and need no weak counterpart. Yet cycles may be
+//! undesired in the data itself, e.g. a category being its own ancestor. `find_cycles`
+//! finds them across all references registered in a `Registry`.
+//! `Registry::collect_garbage` removes entities no longer reachable from given roots.
+//!
+//! Items implementing `Visit` report the entries they hold so `walk` can traverse everything
+//! reachable from an entry regardless of types, e.g. to export or check a subgraph.
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::VecDeque;
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::Arc;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+pub use super::checkpoint::CheckpointError;
+use super::{Backend, Codec, Entry, Id, Identifiable, Readiness, Reference};
+
+/// An entity in the graph: its type and id.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Node {
+    type_id: TypeId,
+    type_name: &'static str,
+    id: i32,
+}
+
+impl Node {
+    pub fn new<T: 'static>(id: Id<T>) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            id: id.as_i32(),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the id regardless of the type.
+    pub(crate) fn raw_id(&self) -> i32 {
+        self.id
+    }
+
+    /// Returns the id if the node is of type `T`.
+    pub fn id<T: 'static>(&self) -> Option<Id<T>> {
+        match self.type_id == TypeId::of::<T>() {
+            true => Some(Id::new(self.id)),
+            false => None,
+        }
+    }
+}
+
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.type_name, self.id)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Outgoing links of an entity collected by a links function passed to `Registry::register`.
+#[derive(Debug, Default)]
+pub struct Links {
+    targets: Vec<Node>,
+}
+
+impl Links {
+    /// Adds a link to the item referred by `entry`. Empty entries are skipped.
+    pub fn add<T: Identifiable + 'static, B: Backend<T>>(&mut self, entry: &Entry<T, B>) {
+        if let Some(item) = entry.load() {
+            self.targets.push(Node::new(item.id()));
+        }
+    }
+}
+
+/// A reference type-erased for registration.
+pub(crate) trait Registered {
+    /// Calls `f` with each item of the reference and its links.
+    fn visit(&self, f: &mut dyn FnMut(Node, &[Node]));
+
+    /// Returns the name of the item type.
+    fn type_name(&self) -> &'static str;
+
+    /// Returns nodes of pinned items.
+    fn pinned(&self) -> Vec<Node>;
+
+    /// Removes the item of `node` if it's of the item type and not pinned.
+    /// Returns whether it was removed.
+    fn remove(&self, node: Node) -> bool;
+
+    fn readiness(&self) -> Readiness;
+
+    /// Appends a snapshot of the reference. Fails if it's registered without a codec.
+    fn save(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn StdError>>;
+
+    /// Decodes items of a snapshot made by `save` without inserting them.
+    fn decode(&self, bytes: &[u8]) -> Result<Box<dyn Decoded + '_>, Box<dyn StdError>>;
+
+    /// Reserves the item with `id` unless it's already there.
+    /// Returns whether a new reservation has been made.
+    fn reserve(&self, id: i32) -> Result<bool, Box<dyn StdError>>;
+
+    /// Removes the reservation of `id` made by `reserve` unless it's resolved meanwhile.
+    fn unreserve(&self, id: i32);
+}
+
+/// Items decoded by `Registered::decode` to be inserted later.
+pub(crate) trait Decoded {
+    /// Returns whether there's an item with `id`.
+    fn contains(&self, id: i32) -> bool;
+
+    /// Returns how many more free slots the reference needs for the items.
+    fn lacking_slots(&self) -> Result<usize, Box<dyn StdError>>;
+
+    /// Inserts the items into the reference they're decoded for.
+    fn insert(self: Box<Self>) -> Result<(), Box<dyn StdError>>;
+}
+
+struct DecodedItems<'a, T: Identifiable + 'static, B: Backend<T>> {
+    reference: &'a Reference<T, B>,
+    items: Vec<T>,
+    ids: FxHashSet<Id<T>>,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Decoded for DecodedItems<'_, T, B> {
+    fn contains(&self, id: i32) -> bool {
+        self.ids.contains(&Id::new(id))
+    }
+
+    fn lacking_slots(&self) -> Result<usize, Box<dyn StdError>> {
+        Ok(self
+            .reference
+            .lacking_free_slots(self.ids.iter().copied(), 0)?)
+    }
+
+    fn insert(self: Box<Self>) -> Result<(), Box<dyn StdError>> {
+        for item in self.items {
+            self.reference.insert(item)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct RegisteredReference<'a, T: Identifiable + 'static, B: Backend<T>, F> {
+    reference: &'a Reference<T, B>,
+    links: F,
+    codec: Option<Box<dyn Codec<T>>>,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>, F> RegisteredReference<'_, T, B, F> {
+    fn codec(&self) -> Result<&dyn Codec<T>, Box<dyn StdError>> {
+        match &self.codec {
+            Some(codec) => Ok(codec.as_ref()),
+            None => Err(format!("Reference of {} has no codec", type_name::<T>()).into()),
+        }
+    }
+}
+
+impl<T, B, F> Registered for RegisteredReference<'_, T, B, F>
+where
+    T: Identifiable + 'static,
+    B: Backend<T>,
+    F: Fn(&T, &mut Links),
+{
+    fn visit(&self, f: &mut dyn FnMut(Node, &[Node])) {
+        let mut links = Links::default();
+
+        for entry in self.reference.iter() {
+            if let Some(item) = entry.load() {
+                links.targets.clear();
+                (self.links)(&item, &mut links);
+                f(Node::new(item.id()), &links.targets);
+            }
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn pinned(&self) -> Vec<Node> {
+        self.reference
+            .pinned_ids()
+            .into_iter()
+            .map(Node::new)
+            .collect()
+    }
+
+    fn remove(&self, node: Node) -> bool {
+        node.id::<T>()
+            .filter(|id| !self.reference.is_pinned(*id))
+            .and_then(|id| self.reference.remove(id))
+            .is_some()
+    }
+
+    fn readiness(&self) -> Readiness {
+        self.reference.readiness()
+    }
+
+    fn save(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn StdError>> {
+        Ok(self.reference.save_to(buf, self.codec()?)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Box<dyn Decoded + '_>, Box<dyn StdError>> {
+        let items = Reference::<T, B>::decode_snapshot(bytes, self.codec()?)?;
+
+        Ok(Box::new(DecodedItems {
+            reference: self.reference,
+            ids: items.iter().map(|item| item.id()).collect(),
+            items,
+        }))
+    }
+
+    fn reserve(&self, id: i32) -> Result<bool, Box<dyn StdError>> {
+        let id = Id::new(id);
+        let is_new = !self.reference.contains(id);
+        self.reference.get_or_reserve(id)?;
+        Ok(is_new)
+    }
+
+    fn unreserve(&self, id: i32) {
+        let id = Id::new(id);
+
+        if self.reference.contains(id) && !self.reference.contains_resolved(id) {
+            self.reference.remove(id);
+        }
+    }
+}
+
+/// A set of references of different types analyzed together.
+#[derive(Default)]
+pub struct Registry<'a> {
+    pub(crate) references: Vec<Box<dyn Registered + 'a>>,
+}
+
+impl<'a> Registry<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `reference` where `links` adds entries an item refers to.
+    pub fn register<T, B, F>(&mut self, reference: &'a Reference<T, B>, links: F) -> &mut Self
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+        F: Fn(&T, &mut Links) + 'a,
+    {
+        self.references.push(Box::new(RegisteredReference {
+            reference,
+            links,
+            codec: None,
+        }));
+
+        self
+    }
+
+    /// Like `register` but with a `codec` for saving the reference with `save_to`.
+    pub fn register_with_codec<T, B, F, C>(
+        &mut self,
+        reference: &'a Reference<T, B>,
+        links: F,
+        codec: C,
+    ) -> &mut Self
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+        F: Fn(&T, &mut Links) + 'a,
+        C: Codec<T>,
+    {
+        self.references.push(Box::new(RegisteredReference {
+            reference,
+            links,
+            codec: Some(Box::new(codec)),
+        }));
+
+        self
+    }
+
+    /// Removes items of registered references which aren't reachable from `roots`
+    /// through links. Reservations are kept. Pinned items are kept too along with
+    /// everything reachable from them.
+    ///
+    /// The graph is a snapshot taken while iterating so items added concurrently are kept
+    /// even if unreachable and links changed concurrently may be missed.
+    pub fn collect_garbage(&self, roots: impl IntoIterator<Item = Node>) -> GarbageSummary {
+        let mut adjacency = FxHashMap::default();
+        let mut nodes = Vec::with_capacity(self.references.len());
+
+        for reference in &self.references {
+            let mut reference_nodes = Vec::new();
+
+            reference.visit(&mut |node, targets| {
+                reference_nodes.push(node);
+                adjacency.insert(node, targets.to_vec());
+            });
+
+            nodes.push(reference_nodes);
+        }
+
+        let pinned = self
+            .references
+            .iter()
+            .flat_map(|reference| reference.pinned());
+
+        let reachable = traverse(
+            roots.into_iter().chain(pinned),
+            Order::BreadthFirst,
+            |node, targets| targets.extend(adjacency.get(node).into_iter().flatten()),
+        )
+        .collect::<FxHashSet<_>>();
+
+        let mut summary = GarbageSummary::default();
+
+        for (reference, nodes) in self.references.iter().zip(nodes) {
+            let reclaimed = nodes
+                .into_iter()
+                .filter(|node| !reachable.contains(node) && reference.remove(*node))
+                .count();
+
+            summary.reclaimed.push((reference.type_name(), reclaimed));
+        }
+
+        summary.reachable = reachable
+            .iter()
+            .filter(|node| adjacency.contains_key(node))
+            .count();
+
+        summary
+    }
+
+    /// Returns readiness of all registered references combined by `Readiness::combine`
+    /// so the registry is ready only when every reference is.
+    pub fn readiness(&self) -> Readiness {
+        self.references
+            .iter()
+            .map(|reference| reference.readiness())
+            .fold(Readiness::Ready, Readiness::combine)
+    }
+
+    /// Returns readiness of registered references by type name in the order of registration.
+    pub fn readiness_by_type(&self) -> Vec<(&'static str, Readiness)> {
+        self.references
+            .iter()
+            .map(|reference| (reference.type_name(), reference.readiness()))
+            .collect()
+    }
+}
+
+impl fmt::Debug for Registry<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("references", &self.references.len())
+            .finish()
+    }
+}
+
+/// Result of `Registry::collect_garbage`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GarbageSummary {
+    /// Number of items kept since they are reachable from the roots.
+    pub reachable: usize,
+    /// Numbers of removed items by type name in the order of registration.
+    pub reclaimed: Vec<(&'static str, usize)>,
+}
+
+impl GarbageSummary {
+    /// Returns the number of removed items of all types.
+    pub fn total_reclaimed(&self) -> usize {
+        self.reclaimed.iter().map(|(_, count)| count).sum()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Returns strongly connected components of the graph of registered references
+/// which form cycles, i.e. have more than one node or a node linked to itself.
+///
+/// The graph is a snapshot taken while iterating so concurrent changes may be missed.
+pub fn find_cycles(registry: &Registry<'_>) -> Vec<Vec<Node>> {
+    let mut nodes = Vec::new();
+    let mut indexes = FxHashMap::default();
+    let mut adjacency: Vec<Vec<usize>> = Vec::new();
+
+    let mut node_index = |node: Node, adjacency: &mut Vec<Vec<usize>>| {
+        *indexes.entry(node).or_insert_with(|| {
+            nodes.push(node);
+            adjacency.push(Vec::new());
+            nodes.len() - 1
+        })
+    };
+
+    for reference in &registry.references {
+        reference.visit(&mut |node, targets| {
+            let from = node_index(node, &mut adjacency);
+
+            for target in targets {
+                let to = node_index(*target, &mut adjacency);
+                adjacency[from].push(to);
+            }
+        });
+    }
+
+    strongly_connected_components(&adjacency)
+        .into_iter()
+        .filter(|component| match component.as_slice() {
+            [single] => adjacency[*single].contains(single),
+            _ => true,
+        })
+        .map(|component| component.into_iter().map(|idx| nodes[idx]).collect())
+        .collect()
+}
+
+/// Tarjan's algorithm without recursion since the graph may be deep.
+fn strongly_connected_components(adjacency: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let len = adjacency.len();
+    let mut indexes: Vec<Option<usize>> = vec![None; len];
+    let mut lowlinks = vec![0; len];
+    let mut on_stack = vec![false; len];
+    let mut stack = Vec::new();
+    let mut next_index = 0;
+    let mut components = Vec::new();
+
+    for root in 0..len {
+        if indexes[root].is_some() {
+            continue;
+        }
+
+        // Pairs of a node and the number of its edges visited so far.
+        let mut call_stack = vec![(root, 0)];
+        indexes[root] = Some(next_index);
+        lowlinks[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(node, edge)) = call_stack.last() {
+            if let Some(&target) = adjacency[node].get(edge) {
+                call_stack.last_mut().expect("Empty call stack").1 += 1;
+
+                match indexes[target] {
+                    None => {
+                        indexes[target] = Some(next_index);
+                        lowlinks[target] = next_index;
+                        next_index += 1;
+                        stack.push(target);
+                        on_stack[target] = true;
+                        call_stack.push((target, 0));
+                    }
+                    Some(target_index) if on_stack[target] => {
+                        lowlinks[node] = lowlinks[node].min(target_index);
+                    }
+                    Some(_) => (),
+                }
+
+                continue;
+            }
+
+            call_stack.pop();
+
+            if let Some(&(parent, _)) = call_stack.last() {
+                lowlinks[parent] = lowlinks[parent].min(lowlinks[node]);
+            }
+
+            if Some(lowlinks[node]) == indexes[node] {
+                let mut component = Vec::new();
+
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component.push(member);
+
+                    if member == node {
+                        break;
+                    }
+                }
+
+                components.push(component);
+            }
+        }
+    }
+
+    components
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// An item reporting the entities it refers to.
+///
+/// ```
+/// # use reference::graph::{Visit, Visitor};
+/// # use reference::{Entry, Id, Identifiable};
+/// struct Category {
+///     id: Id<Self>,
+///     parent: Option<Entry<Category>>,
+/// }
+/// #
+/// # impl Identifiable for Category {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// impl Visit for Category {
+///     fn visit_references(&self, visitor: &mut dyn Visitor) {
+///         if let Some(ref parent) = self.parent {
+///             visitor.entry(parent);
+///         }
+///     }
+/// }
+/// ```
+pub trait Visit: Any {
+    /// Calls `visitor` with each entity the item refers to.
+    fn visit_references(&self, visitor: &mut dyn Visitor);
+}
+
+impl dyn Visit {
+    /// Returns the item if it's of type `T`.
+    pub fn downcast_ref<T: Visit>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+}
+
+/// A receiver of entities reported by `Visit` items and reached by `walk`.
+pub trait Visitor {
+    fn visit(&mut self, node: Node, item: &Arc<dyn Visit>);
+}
+
+impl<F: FnMut(Node, &Arc<dyn Visit>)> Visitor for F {
+    fn visit(&mut self, node: Node, item: &Arc<dyn Visit>) {
+        self(node, item)
+    }
+}
+
+impl dyn Visitor + '_ {
+    /// Reports the item referred by `entry`. Empty entries are skipped.
+    pub fn entry<T, B>(&mut self, entry: &Entry<T, B>)
+    where
+        T: Visit + Identifiable,
+        B: Backend<T>,
+    {
+        if let Some(item) = entry.load() {
+            let node = Node::new(item.id());
+            self.visit(node, &(item as Arc<dyn Visit>));
+        }
+    }
+}
+
+/// Order of visiting entities by a traversal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Entities closer to the start first.
+    #[default]
+    BreadthFirst,
+    /// Each link followed as deep as possible before the next one.
+    DepthFirst,
+}
+
+/// Calls `visitor` once with each entity reachable from `start` including itself
+/// in breadth-first order. Does nothing if `start` is empty.
+pub fn walk<T, B>(start: &Entry<T, B>, visitor: &mut dyn Visitor)
+where
+    T: Visit + Identifiable,
+    B: Backend<T>,
+{
+    walk_in(start, Order::BreadthFirst, visitor)
+}
+
+/// Same as `walk` but in the given order.
+pub fn walk_in<T, B>(start: &Entry<T, B>, order: Order, visitor: &mut dyn Visitor)
+where
+    T: Visit + Identifiable,
+    B: Backend<T>,
+{
+    let starts = start
+        .load()
+        .map(|item| (Node::new(item.id()), item as Arc<dyn Visit>));
+
+    traverse(starts, order, |(_, item), targets| {
+        item.visit_references(&mut |node, item: &Arc<dyn Visit>| targets.push((node, item.clone())))
+    })
+    .for_each(|(node, item)| visitor.visit(node, &item));
+}
+
+/// Yields each node reachable from `starts` once in `order` where `expand` adds targets
+/// of a node. Iterative since the graph may be deep.
+fn traverse<K, F>(
+    starts: impl IntoIterator<Item = K>,
+    order: Order,
+    mut expand: F,
+) -> impl Iterator<Item = K>
+where
+    K: GraphKey,
+    F: FnMut(&K, &mut Vec<K>),
+{
+    let mut seen = FxHashSet::default();
+    let mut pending = starts.into_iter().collect::<VecDeque<_>>();
+    let mut targets = Vec::new();
+
+    if order == Order::DepthFirst {
+        pending.make_contiguous().reverse();
+    }
+
+    std::iter::from_fn(move || loop {
+        let current = match order {
+            Order::BreadthFirst => pending.pop_front()?,
+            Order::DepthFirst => pending.pop_back()?,
+        };
+
+        // A node may be pending several times if reached by different paths before visited.
+        if !seen.insert(current.node()) {
+            continue;
+        }
+
+        expand(&current, &mut targets);
+        let unseen = targets
+            .drain(..)
+            .filter(|target| !seen.contains(&target.node()));
+
+        match order {
+            Order::BreadthFirst => pending.extend(unseen),
+            // Reversed so the first target is visited first.
+            Order::DepthFirst => pending.extend(unseen.collect::<Vec<_>>().into_iter().rev()),
+        }
+
+        return Some(current);
+    })
+}
+
+/// Something standing for a node in a traversal.
+trait GraphKey {
+    fn node(&self) -> Node;
+}
+
+impl GraphKey for Node {
+    fn node(&self) -> Node {
+        *self
+    }
+}
+
+impl GraphKey for (Node, Arc<dyn Visit>) {
+    fn node(&self) -> Node {
+        self.0
+    }
+}
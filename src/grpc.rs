@@ -0,0 +1,273 @@
+//! gRPC replication of references between services.
+//!
+//! The canonical service serves its reference with `ReplicationServer` and replicas fill
+//! their own references with `ReplicationClient::load_snapshot`. Items cross the wire encoded
+//! with a `Codec`. The service is equivalent to this protobuf definition:
+//!
+//! ```protobuf
+//! syntax = "proto3";
+//! package reference;
+//!
+//! service Replication {
+//!   rpc Snapshot(SnapshotRequest) returns (stream Item);
+//! }
+//!
+//! message SnapshotRequest {}
+//!
+//! message Item {
+//!   int32 id = 1;
+//!   bytes data = 2;
+//! }
+//! ```
+
+use std::convert::Infallible;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tonic::body::Body;
+use tonic::codegen::http::{self, uri::PathAndQuery};
+use tonic::codegen::tokio_stream::Stream;
+use tonic::codegen::{Body as HttpBody, BoxFuture, Bytes, Service, StdError};
+use tonic::server::{Grpc, NamedService};
+use tonic::{Request, Response, Status, Streaming};
+use tonic_prost::ProstCodec;
+
+use super::{Backend, Codec, Error, Identifiable, Reference};
+
+pub const SERVICE_NAME: &str = "reference.Replication";
+const SNAPSHOT_PATH: &str = "/reference.Replication/Snapshot";
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SnapshotRequest {}
+
+/// An item encoded with a codec.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Item {
+    #[prost(int32, tag = "1")]
+    pub id: i32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: Vec<u8>,
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Serves a reference to replicas.
+#[derive(Clone)]
+pub struct ReplicationServer {
+    inner: Arc<dyn ErasedReference>,
+}
+
+impl ReplicationServer {
+    pub fn new<T, B, C>(reference: Arc<Reference<T, B>>, codec: C) -> Self
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+        C: Codec<T>,
+        Reference<T, B>: Send + Sync,
+    {
+        Self {
+            inner: Arc::new(ErasedReferenceImpl { reference, codec }),
+        }
+    }
+}
+
+impl NamedService for ReplicationServer {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<ReqBody> Service<http::Request<ReqBody>> for ReplicationServer
+where
+    ReqBody: HttpBody + Send + 'static,
+    ReqBody::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        match request.uri().path() {
+            SNAPSHOT_PATH => {
+                let service = SnapshotService(self.inner.clone());
+
+                Box::pin(async move {
+                    let mut grpc = Grpc::new(ProstCodec::default());
+                    Ok(grpc.server_streaming(service, request).await)
+                })
+            }
+            _ => Box::pin(async { Ok(Status::unimplemented("").into_http()) }),
+        }
+    }
+}
+
+impl fmt::Debug for ReplicationServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplicationServer").finish()
+    }
+}
+
+struct SnapshotService(Arc<dyn ErasedReference>);
+
+impl Service<Request<SnapshotRequest>> for SnapshotService {
+    type Response = Response<SnapshotStream>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _request: Request<SnapshotRequest>) -> Self::Future {
+        let stream = SnapshotStream {
+            reference: self.0.clone(),
+            vid: 0,
+        };
+
+        Box::pin(async { Ok(Response::new(stream)) })
+    }
+}
+
+/// Encodes items lazily slot by slot so the snapshot is never held in memory as a whole.
+/// Items changed during streaming may be sent either in the old or in the new state.
+struct SnapshotStream {
+    reference: Arc<dyn ErasedReference>,
+    vid: usize,
+}
+
+impl Stream for SnapshotStream {
+    type Item = Result<Item, Status>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let vid = self.vid;
+            self.vid += 1;
+
+            match self.reference.encode(vid) {
+                None => return Poll::Ready(None),
+                Some(Ok(None)) => continue,
+                Some(Ok(Some(item))) => return Poll::Ready(Some(Ok(item))),
+                Some(Err(err)) => return Poll::Ready(Some(Err(Status::internal(err)))),
+            }
+        }
+    }
+}
+
+trait ErasedReference: Send + Sync {
+    /// Encodes the item in the slot with `vid`. Returns `None` past the last slot.
+    fn encode(&self, vid: usize) -> Option<Result<Option<Item>, String>>;
+}
+
+struct ErasedReferenceImpl<T: Identifiable + 'static, B: Backend<T>, C> {
+    reference: Arc<Reference<T, B>>,
+    codec: C,
+}
+
+impl<T, B, C> ErasedReference for ErasedReferenceImpl<T, B, C>
+where
+    T: Identifiable + 'static,
+    B: Backend<T>,
+    C: Codec<T>,
+    Reference<T, B>: Send + Sync,
+{
+    fn encode(&self, vid: usize) -> Option<Result<Option<Item>, String>> {
+        let slot = self.reference.items.slot(vid)?;
+
+        let Some(item) = B::load(slot) else {
+            return Some(Ok(None));
+        };
+
+        let mut data = Vec::new();
+
+        let result = match self.codec.encode(&item, &mut data) {
+            Ok(()) => Ok(Some(Item {
+                id: item.id().as_i32(),
+                data,
+            })),
+            Err(err) => {
+                log::error!("Failed to encode item {}: {err}", item.id());
+                Err(format!("Failed to encode item {}", item.id()))
+            }
+        };
+
+        Some(result)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Fetches references from a `ReplicationServer`.
+///
+/// `S` is a gRPC transport, e.g. `tonic::transport::Channel`.
+#[derive(Clone, Debug)]
+pub struct ReplicationClient<S> {
+    inner: tonic::client::Grpc<S>,
+}
+
+impl<S> ReplicationClient<S>
+where
+    S: tonic::client::GrpcService<Body>,
+    S::Error: Into<StdError>,
+    S::ResponseBody: HttpBody<Data = Bytes> + Send + 'static,
+    <S::ResponseBody as HttpBody>::Error: Into<StdError> + Send,
+{
+    pub fn new(service: S) -> Self {
+        Self {
+            inner: tonic::client::Grpc::new(service),
+        }
+    }
+
+    /// Requests a stream of all items of the remote reference.
+    pub async fn snapshot(&mut self) -> Result<Streaming<Item>, Status> {
+        self.inner
+            .ready()
+            .await
+            .map_err(|err| Status::unavailable(format!("Service was not ready: {}", err.into())))?;
+
+        let path = PathAndQuery::from_static(SNAPSHOT_PATH);
+        let request = Request::new(SnapshotRequest {});
+        let response = self
+            .inner
+            .server_streaming(request, path, ProstCodec::default())
+            .await?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Upserts all items of the remote reference into `reference` returning their number.
+    ///
+    /// Items are upserted as they arrive so on failure `reference` is left partially updated.
+    pub async fn load_snapshot<T, B, C>(
+        &mut self,
+        reference: &Reference<T, B>,
+        codec: &C,
+    ) -> Result<usize, Error<T>>
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+        C: Codec<T>,
+    {
+        let mut stream = self
+            .snapshot()
+            .await
+            .map_err(|err| Error::Other(Box::new(err)))?;
+
+        let mut count = 0;
+
+        while let Some(item) = stream
+            .message()
+            .await
+            .map_err(|err| Error::Other(Box::new(err)))?
+        {
+            let item = codec.decode(&item.data).map_err(Error::CodecError)?;
+            reference.upsert(item)?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+use std::sync::Arc;
+
+use super::{Backend, Entry, HotField, Id, Identifiable, Reference};
+
+/// Approximate number of bytes an entity owns on the heap beyond its own size.
+///
+/// Implement it for entity types with `heap_size!` listing fields owning heap memory.
+/// Fields not listed are considered to own nothing.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! impl_no_heap {
+    ($($ty:ty),*) => {
+        $(
+            impl HeapSize for $ty {
+                fn heap_size(&self) -> usize {
+                    0
+                }
+            }
+        )*
+    };
+}
+
+impl_no_heap!(
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    ()
+);
+
+impl<T> HeapSize for Id<T> {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+/// An entry doesn't own its referent which is accounted in its own reference.
+impl<T: 'static, B: Backend<T>> HeapSize for Entry<T, B> {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<T: HeapSize + ?Sized> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+/// Shared values are accounted in full by each owner so the result may overestimate.
+impl<T: HeapSize + ?Sized> HeapSize for Arc<T> {
+    fn heap_size(&self) -> usize {
+        ARC_HEADER + size_of_val(&**self) + (**self).heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for [T] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(HeapSize::heap_size).sum()
+    }
+}
+
+impl HeapSize for str {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        let entries = self
+            .iter()
+            .map(|(key, value)| key.heap_size() + value.heap_size())
+            .sum::<usize>();
+
+        self.capacity() * size_of::<(K, V)>() + entries
+    }
+}
+
+impl<K: HeapSize, V: HeapSize> HeapSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.iter()
+            .map(|(key, value)| size_of::<(K, V)>() + key.heap_size() + value.heap_size())
+            .sum()
+    }
+}
+
+impl<F: HeapSize> HeapSize for HotField<F> {
+    fn heap_size(&self) -> usize {
+        self.load().heap_size()
+    }
+}
+
+/// Implements `HeapSize` for a struct summing up heap sizes of the listed fields.
+///
+/// ```
+/// # use reference::{heap_size, HeapSize};
+/// #
+/// struct Product {
+///     price: u64,
+///     name: String,
+///     tags: Vec<String>,
+/// }
+///
+/// heap_size!(Product { name, tags });
+///
+/// let product = Product {
+///     price: 100,
+///     name: String::with_capacity(10),
+///     tags: Vec::new(),
+/// };
+///
+/// assert_eq!(product.heap_size(), 10);
+/// ```
+#[macro_export]
+macro_rules! heap_size {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::HeapSize for $ty {
+            fn heap_size(&self) -> usize {
+                0 $(+ $crate::HeapSize::heap_size(&self.$field))*
+            }
+        }
+    };
+}
+
+/// Reference counters preceding the value in an `Arc` allocation.
+const ARC_HEADER: usize = 2 * size_of::<usize>();
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Approximate memory taken by a reference in bytes. See `Reference::approx_bytes`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MemoryUsage {
+    /// Preallocated slots.
+    pub slots: usize,
+    /// The id index.
+    pub index: usize,
+    /// Items along with their `Arc` allocations and heap memory they own.
+    pub items: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.slots + self.index + self.items
+    }
+}
+
+impl<T: Identifiable + HeapSize + 'static, B: Backend<T>> Reference<T, B> {
+    /// Estimates memory taken by the reference. Takes a pass over all items.
+    /// Secondary indexes, bloom filter and pooled allocations are not accounted.
+    pub fn approx_bytes(&self) -> MemoryUsage {
+        let items = self
+            .iter()
+            .filter_map(|entry| entry.load())
+            .map(|item| item.heap_size())
+            .sum();
+
+        MemoryUsage {
+            slots: self.items.capacity() * size_of::<B::Slot>(),
+            index: self.id_index_memory_usage(),
+            items,
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+/// A frequently changing part of an entity stored in its own cell.
+///
+/// Replacing a whole entity means cloning it. For large entities where only one field changes
+/// often it's cheaper to keep that field in a `HotField` and update it in place:
+///
+/// ```
+/// # use reference::{HotField, Id, Identifiable, Reference};
+/// #
+/// struct Product {
+///     id: Id<Self>,
+///     description: String,
+///     price: HotField<u64>,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// let products = Reference::new(2);
+///
+/// let entry = products
+///     .insert(Product {
+///         id: 1.into(),
+///         description: "Very long description".to_string(),
+///         price: HotField::new(100),
+///     })
+///     .unwrap();
+///
+/// entry.load().unwrap().price.set(200);
+/// assert_eq!(*entry.load().unwrap().price.load(), 200);
+/// ```
+///
+/// Clones share the same cell so an entity replaced with its modified clone keeps
+/// observing updates of the field.
+pub struct HotField<F> {
+    value: Arc<ArcSwap<F>>,
+}
+
+impl<F> HotField<F> {
+    pub fn new(value: F) -> Self {
+        Self {
+            value: Arc::new(ArcSwap::from_pointee(value)),
+        }
+    }
+
+    /// Returns the current value.
+    pub fn load(&self) -> Arc<F> {
+        self.value.load_full()
+    }
+
+    /// Sets a new value.
+    pub fn set(&self, value: F) {
+        self.value.store(Arc::new(value));
+    }
+
+    /// Sets a value computed by `f` from the current one and returns the previous value.
+    /// `f` may be called several times on contention.
+    pub fn modify<M>(&self, mut f: M) -> Arc<F>
+    where
+        M: FnMut(&F) -> F,
+    {
+        self.value.rcu(|current| f(current))
+    }
+
+    /// Creates a new independent cell with the current value.
+    pub fn detach(&self) -> Self {
+        Self {
+            value: Arc::new(ArcSwap::new(self.load())),
+        }
+    }
+}
+
+impl<F> Clone for HotField<F> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<F: Default> Default for HotField<F> {
+    fn default() -> Self {
+        Self::new(F::default())
+    }
+}
+
+impl<F: fmt::Debug> fmt::Debug for HotField<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("HotField").field(&self.load()).finish()
+    }
+}
@@ -0,0 +1,151 @@
+//! Read-only JSON endpoints for live inspection of references.
+//!
+//! References are registered in an `Inspector` under URL-friendly names and mounted
+//! to an application's `axum` router:
+//!
+//! - `GET /reference/{name}` – all items of the reference as a JSON array ordered by id;
+//! - `GET /reference/{name}/{id}` – a single item or 404;
+//! - `GET /stats` – stats of all registered references keyed by name.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{Backend, Identifiable, Reference, Stats};
+
+/// A set of references exposed over HTTP.
+#[derive(Default)]
+pub struct Inspector {
+    references: BTreeMap<String, Box<dyn Inspected>>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `reference` to be served under `/reference/{name}`.
+    /// Registering another reference under the same name replaces the previous one.
+    pub fn register<T, B>(
+        &mut self,
+        name: impl Into<String>,
+        reference: Arc<Reference<T, B>>,
+    ) -> &mut Self
+    where
+        T: Identifiable + Serialize + 'static,
+        B: Backend<T>,
+        Reference<T, B>: Send + Sync,
+    {
+        self.references.insert(name.into(), Box::new(reference));
+        self
+    }
+
+    /// Adds the endpoints to `router`.
+    pub fn routes<S>(self, router: Router<S>) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let inspector = Arc::new(self);
+        let (list, item, stats) = (inspector.clone(), inspector.clone(), inspector);
+
+        router
+            .route(
+                "/reference/{name}",
+                get(|Path(name): Path<String>| async move { list.list(&name) }),
+            )
+            .route(
+                "/reference/{name}/{id}",
+                get(|Path((name, id)): Path<(String, i32)>| async move { item.item(&name, id) }),
+            )
+            .route("/stats", get(|| async move { stats.stats() }))
+    }
+
+    fn list(&self, name: &str) -> Response {
+        match self.references.get(name) {
+            Some(reference) => json_response(reference.items()),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    fn item(&self, name: &str, id: i32) -> Response {
+        match self
+            .references
+            .get(name)
+            .and_then(|reference| reference.item(id))
+        {
+            Some(result) => json_response(result),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    fn stats(&self) -> Response {
+        let stats = self
+            .references
+            .iter()
+            .map(|(name, reference)| (name.as_str(), reference.stats()))
+            .collect::<BTreeMap<_, _>>();
+
+        Json(stats).into_response()
+    }
+}
+
+impl fmt::Debug for Inspector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Inspector")
+            .field("references", &self.references.keys())
+            .finish()
+    }
+}
+
+fn json_response(result: serde_json::Result<Value>) -> Response {
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(err) => {
+            log::error!("Failed to serialize an item: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A reference type-erased for serving.
+trait Inspected: Send + Sync {
+    fn items(&self) -> serde_json::Result<Value>;
+    fn item(&self, id: i32) -> Option<serde_json::Result<Value>>;
+    fn stats(&self) -> Stats;
+}
+
+impl<T, B> Inspected for Arc<Reference<T, B>>
+where
+    T: Identifiable + Serialize + 'static,
+    B: Backend<T>,
+    Reference<T, B>: Send + Sync,
+{
+    fn items(&self) -> serde_json::Result<Value> {
+        let items = self
+            .iter_ordered()
+            .filter_map(|entry| entry.load())
+            .map(|item| serde_json::to_value(&*item))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Value::Array(items))
+    }
+
+    fn item(&self, id: i32) -> Option<serde_json::Result<Value>> {
+        let item = self.get(id.into())?.load()?;
+        Some(serde_json::to_value(&*item))
+    }
+
+    fn stats(&self) -> Stats {
+        Reference::stats(self)
+    }
+}
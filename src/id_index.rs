@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::mem::size_of;
+
+use rustc_hash::FxHashMap;
+
+use super::poison::INDEX_LOCK;
+use super::sync::MaybeSync;
+use super::{Backend, Id, Identifiable, Reference};
+
+/// Mapping of ids to vids of a `Reference`. See `Reference::with_id_index`.
+///
+/// The default is `FxHashMap`. Other structures may win depending on the workload
+/// as the `id_index` bench shows. Adapters for `dashmap` and `papaya` are available
+/// under features of the same names.
+///
+/// `Reference` guards the index with its own lock so implementations don't need to handle
+/// concurrent writes. Reads are concurrent with each other though.
+///
+/// Vids are `u32` to keep index entries small.
+pub trait IdIndex<T>: MaybeSync + fmt::Debug + 'static {
+    /// Returns the vid of `id`.
+    fn get(&self, id: Id<T>) -> Option<u32>;
+
+    /// Sets the vid of `id` and returns the previous one.
+    fn insert(&mut self, id: Id<T>, vid: u32) -> Option<u32>;
+
+    /// Removes `id` and returns its vid.
+    fn remove(&mut self, id: Id<T>) -> Option<u32>;
+
+    /// Returns the number of ids including the zero element.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no ids.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over ids and their vids in arbitrary order.
+    fn iter(&self) -> Box<dyn Iterator<Item = (Id<T>, u32)> + '_>;
+
+    /// Returns the approximate number of bytes allocated by the index.
+    fn memory_usage(&self) -> usize;
+
+    /// Releases memory reserved for ids which are not there. Does nothing by default.
+    fn shrink_to_fit(&mut self) {}
+
+    /// Returns a copy of the index taking as little memory as possible so it can be built
+    /// without blocking lookups and swapped in. Returns `None` by default which makes
+    /// `Reference::shrink_index_to_fit` call `shrink_to_fit` in place instead.
+    fn shrunk(&self) -> Option<Box<dyn IdIndex<T>>> {
+        None
+    }
+}
+
+/// Size of a single id to vid pair.
+const PAIR_SIZE: usize = size_of::<(i32, u32)>();
+
+impl<T, S> IdIndex<T> for HashMap<Id<T>, u32, S>
+where
+    T: 'static,
+    S: BuildHasher + Clone + MaybeSync + 'static,
+{
+    fn get(&self, id: Id<T>) -> Option<u32> {
+        HashMap::get(self, &id).copied()
+    }
+
+    fn insert(&mut self, id: Id<T>, vid: u32) -> Option<u32> {
+        HashMap::insert(self, id, vid)
+    }
+
+    fn remove(&mut self, id: Id<T>) -> Option<u32> {
+        HashMap::remove(self, &id)
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (Id<T>, u32)> + '_> {
+        Box::new(HashMap::iter(self).map(|(id, vid)| (*id, *vid)))
+    }
+
+    fn memory_usage(&self) -> usize {
+        // Each bucket also has a control byte.
+        self.capacity() * (PAIR_SIZE + 1)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        HashMap::shrink_to_fit(self)
+    }
+
+    fn shrunk(&self) -> Option<Box<dyn IdIndex<T>>> {
+        let mut shrunk = HashMap::with_capacity_and_hasher(self.len(), self.hasher().clone());
+        shrunk.extend(HashMap::iter(self).map(|(id, vid)| (*id, *vid)));
+        Some(Box::new(shrunk))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// An open-addressing hash table of packed id and vid pairs tuned for integer ids.
+/// It uses linear probing and takes 8 bytes per bucket with no further overhead
+/// so it's about twice as compact as `FxHashMap` with `usize` values.
+pub struct FlatIdIndex<T> {
+    buckets: Vec<(Id<T>, u32)>,
+    len: usize,
+    shift: u32,
+}
+
+/// Marks an empty bucket. Vids never reach it since the capacity of a reference fits `u32`.
+const EMPTY_VID: u32 = u32::MAX;
+
+const MIN_BUCKETS: usize = 8;
+
+impl<T> FlatIdIndex<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an index which doesn't grow until `capacity` ids are inserted.
+    pub fn with_capacity(capacity: usize) -> Self {
+        // Keeping the load factor under 7/8.
+        let buckets = (capacity * 8 / 7 + 1).next_power_of_two().max(MIN_BUCKETS);
+
+        Self {
+            buckets: vec![(Id::new(0), EMPTY_VID); buckets],
+            len: 0,
+            shift: u64::BITS - buckets.trailing_zeros(),
+        }
+    }
+
+    /// Fibonacci hashing: the high bits of the product are well mixed even for sequential ids.
+    fn home(&self, id: Id<T>) -> usize {
+        let hash = (id.as_i32() as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        (hash >> self.shift) as usize
+    }
+
+    fn mask(&self) -> usize {
+        self.buckets.len() - 1
+    }
+
+    /// Returns the bucket of `id` if it's present or the empty bucket to put it to otherwise.
+    fn find(&self, id: Id<T>) -> Result<usize, usize> {
+        let mut pos = self.home(id);
+
+        loop {
+            let (bucket_id, vid) = self.buckets[pos];
+
+            if vid == EMPTY_VID {
+                return Err(pos);
+            }
+
+            if bucket_id == id {
+                return Ok(pos);
+            }
+
+            pos = (pos + 1) & self.mask();
+        }
+    }
+
+    fn grow(&mut self) {
+        self.rebuild(self.buckets.len());
+    }
+
+    /// Moves pairs to a new table fitting `capacity` ids.
+    fn rebuild(&mut self, capacity: usize) {
+        *self = self.rebuilt(capacity);
+    }
+
+    /// Returns a copy of the table fitting `capacity` ids.
+    fn rebuilt(&self, capacity: usize) -> Self {
+        let mut rebuilt = Self::with_capacity(capacity);
+
+        for (id, vid) in self.pairs() {
+            if let Err(pos) = rebuilt.find(id) {
+                rebuilt.buckets[pos] = (id, vid);
+            }
+        }
+
+        rebuilt.len = self.len;
+        rebuilt
+    }
+
+    fn pairs(&self) -> impl Iterator<Item = (Id<T>, u32)> + '_ {
+        self.buckets
+            .iter()
+            .copied()
+            .filter(|(_, vid)| *vid != EMPTY_VID)
+    }
+}
+
+impl<T> Default for FlatIdIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for FlatIdIndex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.pairs()).finish()
+    }
+}
+
+impl<T: 'static> IdIndex<T> for FlatIdIndex<T> {
+    fn get(&self, id: Id<T>) -> Option<u32> {
+        self.find(id).ok().map(|pos| self.buckets[pos].1)
+    }
+
+    fn insert(&mut self, id: Id<T>, vid: u32) -> Option<u32> {
+        debug_assert_ne!(vid, EMPTY_VID, "Vid is out of range");
+
+        if (self.len + 1) * 8 > self.buckets.len() * 7 {
+            self.grow();
+        }
+
+        match self.find(id) {
+            Ok(pos) => Some(std::mem::replace(&mut self.buckets[pos].1, vid)),
+            Err(pos) => {
+                self.buckets[pos] = (id, vid);
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, id: Id<T>) -> Option<u32> {
+        let mut hole = self.find(id).ok()?;
+        let vid = self.buckets[hole].1;
+        let mask = self.mask();
+        let mut pos = (hole + 1) & mask;
+
+        // Shifting back the following pairs which would become unreachable
+        // so there's no need in tombstones.
+        while self.buckets[pos].1 != EMPTY_VID {
+            let home = self.home(self.buckets[pos].0);
+
+            if (pos.wrapping_sub(home) & mask) >= (pos.wrapping_sub(hole) & mask) {
+                self.buckets[hole] = self.buckets[pos];
+                hole = pos;
+            }
+
+            pos = (pos + 1) & mask;
+        }
+
+        self.buckets[hole] = (Id::new(0), EMPTY_VID);
+        self.len -= 1;
+        Some(vid)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (Id<T>, u32)> + '_> {
+        Box::new(self.pairs())
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.buckets.capacity() * PAIR_SIZE
+    }
+
+    fn shrink_to_fit(&mut self) {
+        if Self::with_capacity(self.len).buckets.len() < self.buckets.len() {
+            self.rebuild(self.len);
+        }
+    }
+
+    fn shrunk(&self) -> Option<Box<dyn IdIndex<T>>> {
+        Some(Box::new(self.rebuilt(self.len)))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// An index of ids sorted in a vector. Lookups are binary searches and changes are linear
+/// so it's meant for references which are filled once and rarely changed afterwards.
+/// It takes less memory than hash maps and has better cache locality.
+pub struct SortedVecIndex<T> {
+    pairs: Vec<(Id<T>, u32)>,
+}
+
+impl<T> SortedVecIndex<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            pairs: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn position(&self, id: Id<T>) -> Result<usize, usize> {
+        self.pairs
+            .binary_search_by_key(&id.as_i32(), |(id, _)| id.as_i32())
+    }
+}
+
+impl<T> Default for SortedVecIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for SortedVecIndex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.pairs.iter().copied()).finish()
+    }
+}
+
+impl<T: 'static> IdIndex<T> for SortedVecIndex<T> {
+    fn get(&self, id: Id<T>) -> Option<u32> {
+        self.position(id).ok().map(|pos| self.pairs[pos].1)
+    }
+
+    fn insert(&mut self, id: Id<T>, vid: u32) -> Option<u32> {
+        match self.position(id) {
+            Ok(pos) => Some(std::mem::replace(&mut self.pairs[pos].1, vid)),
+            Err(pos) => {
+                self.pairs.insert(pos, (id, vid));
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, id: Id<T>) -> Option<u32> {
+        self.position(id).ok().map(|pos| self.pairs.remove(pos).1)
+    }
+
+    fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (Id<T>, u32)> + '_> {
+        Box::new(self.pairs.iter().copied())
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.pairs.capacity() * PAIR_SIZE
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.pairs.shrink_to_fit()
+    }
+
+    fn shrunk(&self) -> Option<Box<dyn IdIndex<T>>> {
+        // A clone of a vector has no spare capacity.
+        Some(Box::new(Self {
+            pairs: self.pairs.clone(),
+        }))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(feature = "dashmap")]
+impl<T, S> IdIndex<T> for dashmap::DashMap<Id<T>, u32, S>
+where
+    T: 'static,
+    S: BuildHasher + Clone + MaybeSync + 'static,
+{
+    fn get(&self, id: Id<T>) -> Option<u32> {
+        dashmap::DashMap::get(self, &id).map(|vid| *vid)
+    }
+
+    fn insert(&mut self, id: Id<T>, vid: u32) -> Option<u32> {
+        dashmap::DashMap::insert(self, id, vid)
+    }
+
+    fn remove(&mut self, id: Id<T>) -> Option<u32> {
+        dashmap::DashMap::remove(self, &id).map(|(_, vid)| vid)
+    }
+
+    fn len(&self) -> usize {
+        dashmap::DashMap::len(self)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (Id<T>, u32)> + '_> {
+        Box::new(dashmap::DashMap::iter(self).map(|pair| (*pair.key(), *pair.value())))
+    }
+
+    fn memory_usage(&self) -> usize {
+        // Shards are hash maps with a control byte per bucket.
+        self.capacity() * (PAIR_SIZE + 1)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        dashmap::DashMap::shrink_to_fit(self)
+    }
+}
+
+#[cfg(feature = "papaya")]
+impl<T, S> IdIndex<T> for papaya::HashMap<Id<T>, u32, S>
+where
+    T: 'static,
+    S: BuildHasher + MaybeSync + 'static,
+{
+    fn get(&self, id: Id<T>) -> Option<u32> {
+        self.pin().get(&id).copied()
+    }
+
+    fn insert(&mut self, id: Id<T>, vid: u32) -> Option<u32> {
+        self.pin().insert(id, vid).copied()
+    }
+
+    fn remove(&mut self, id: Id<T>) -> Option<u32> {
+        self.pin().remove(&id).copied()
+    }
+
+    fn len(&self) -> usize {
+        papaya::HashMap::len(self)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (Id<T>, u32)> + '_> {
+        // Pairs are collected since the iterator borrows a guard which can't be returned.
+        let pairs = self
+            .pin()
+            .iter()
+            .map(|(id, vid)| (*id, *vid))
+            .collect::<Vec<_>>();
+        Box::new(pairs.into_iter())
+    }
+
+    fn memory_usage(&self) -> usize {
+        // A rough estimate: pairs are allocated separately and pointed to from the table
+        // which also has a metadata byte per bucket.
+        papaya::HashMap::len(self) * (PAIR_SIZE + size_of::<usize>() + 1)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The default id index.
+pub(crate) fn default_id_index<T: 'static>(capacity: usize) -> Box<dyn IdIndex<T>> {
+    let mut vids = FxHashMap::default();
+    vids.reserve(capacity);
+    Box::new(vids)
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Replaces the id index which is `FxHashMap` by default. Existing ids are moved
+    /// to the new index.
+    pub fn with_id_index<I: IdIndex<T>>(self, mut index: I) -> Self {
+        {
+            let mut vids = self.recovered_lock(self.vids.write(), INDEX_LOCK);
+
+            for (id, vid) in vids.iter() {
+                index.insert(id, vid);
+            }
+
+            *vids = Box::new(index);
+        }
+
+        self
+    }
+
+    /// Returns the approximate number of bytes allocated by the id index.
+    pub(crate) fn id_index_memory_usage(&self) -> usize {
+        self.recovered_lock(self.vids.read(), INDEX_LOCK)
+            .memory_usage()
+    }
+}
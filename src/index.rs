@@ -0,0 +1,218 @@
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::sync::{MaybeSync, Mutex, MutexGuard, PoisonError};
+use super::{ArcSwapBackend, Backend, Entry, Identifiable, Reference};
+
+pub(crate) const INDEXES_LOCK: &str = "secondary indexes";
+
+/// A secondary index maintained by `Reference` on its writes.
+pub(crate) trait SecondaryIndex<T, B: Backend<T>>: MaybeSync + fmt::Debug {
+    /// Moves the slot from the key of the previous item to the key of the current one.
+    fn update(&self, slot: &'static B::Slot, maybe_prev: Option<&T>);
+
+    /// Drops everything indexed.
+    fn clear(&self);
+}
+
+/// A slot reference hashed by address.
+pub(crate) struct SlotRef<S: 'static>(pub(crate) &'static S);
+
+impl<S> Clone for SlotRef<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for SlotRef<S> {}
+
+impl<S> PartialEq for SlotRef<S> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl<S> Eq for SlotRef<S> {}
+
+impl<S> Hash for SlotRef<S> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.0, state)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(not(feature = "single-thread"))]
+type KeyFn<T, K> = Box<dyn Fn(&T) -> K + Send + Sync>;
+
+#[cfg(feature = "single-thread")]
+type KeyFn<T, K> = Box<dyn Fn(&T) -> K>;
+
+struct KeyIndexInner<T, K, B: Backend<T>> {
+    key: KeyFn<T, K>,
+    slots: Mutex<Buckets<K, B::Slot>>,
+}
+
+type Buckets<K, S> = FxHashMap<K, FxHashSet<SlotRef<S>>>;
+
+impl<T, K, B: Backend<T>> KeyIndexInner<T, K, B> {
+    /// The lock gets recovered if poisoned since the index is verified on lookups anyway.
+    fn lock(&self) -> MutexGuard<'_, Buckets<K, B::Slot>> {
+        self.slots.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T, K, B: Backend<T>> fmt::Debug for KeyIndexInner<T, K, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyIndex")
+            .field("keys", &self.lock().len())
+            .finish()
+    }
+}
+
+impl<T, K, B> SecondaryIndex<T, B> for KeyIndexInner<T, K, B>
+where
+    K: Eq + Hash,
+    B: Backend<T>,
+    Self: MaybeSync,
+{
+    fn update(&self, slot: &'static B::Slot, maybe_prev: Option<&T>) {
+        let mut slots = self.lock();
+
+        if let Some(prev) = maybe_prev {
+            let key = (self.key)(prev);
+
+            if let Some(bucket) = slots.get_mut(&key) {
+                bucket.remove(&SlotRef(slot));
+
+                if bucket.is_empty() {
+                    slots.remove(&key);
+                }
+            }
+        }
+
+        // Loading the current value under the lock rather than using the stored one so
+        // the last update of concurrent writers always reflects the latest value.
+        if let Some(current) = B::load(slot) {
+            slots
+                .entry((self.key)(&current))
+                .or_default()
+                .insert(SlotRef(slot));
+        }
+    }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+/// A secondary index of items by a key. See `Reference::add_index`.
+///
+/// The index is updated on writes through the `Reference`. Writes through an entry
+/// (`Entry::modify`, `Entry::lock_for_update`) which change the key are not reflected
+/// until `Reference::reindex`. Lookup results are always verified against the current values.
+pub struct KeyIndex<T: 'static, K, B: Backend<T> = ArcSwapBackend<T>> {
+    inner: Arc<KeyIndexInner<T, K, B>>,
+}
+
+impl<T: 'static, K: Eq + Hash, B: Backend<T>> KeyIndex<T, K, B> {
+    /// Returns entries of items with the given `key`.
+    pub fn get(&self, key: &K) -> Vec<Entry<T, B>> {
+        let candidates = self
+            .inner
+            .lock()
+            .get(key)
+            .map(|bucket| bucket.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        candidates
+            .into_iter()
+            .map(|slot| Entry::new(slot.0))
+            .filter(|entry| self.matches(entry, key))
+            .collect()
+    }
+
+    /// Tells whether the item of `entry` has the given `key`.
+    pub(crate) fn matches(&self, entry: &Entry<T, B>, key: &K) -> bool {
+        match entry.load() {
+            Some(item) => (self.inner.key)(&item) == *key,
+            None => false,
+        }
+    }
+
+    pub(crate) fn key_of(&self, item: &T) -> K {
+        (self.inner.key)(item)
+    }
+}
+
+impl<T: 'static, K, B: Backend<T>> Clone for KeyIndex<T, K, B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: 'static, K, B: Backend<T>> fmt::Debug for KeyIndex<T, K, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.inner, f)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Adds a secondary index of items by `key` and fills it with the existing items.
+    /// The returned handle is used for lookups and in `Query::filter_by`.
+    pub fn add_index<K, F>(&self, key: F) -> KeyIndex<T, K, B>
+    where
+        K: Eq + Hash + MaybeSync + 'static,
+        F: Fn(&T) -> K + MaybeSync + 'static,
+        B::Slot: MaybeSync,
+    {
+        let inner = Arc::new(KeyIndexInner {
+            key: Box::new(key),
+            slots: Mutex::new(FxHashMap::default()),
+        });
+
+        self.register_index(inner.clone());
+        KeyIndex { inner }
+    }
+
+    /// Registers the index and fills it. The index is registered first so items written
+    /// concurrently with filling don't get missed.
+    pub(crate) fn register_index(&self, index: Arc<dyn SecondaryIndex<T, B>>) {
+        self.recovered_lock(self.indexes.write(), INDEXES_LOCK)
+            .push(index.clone());
+
+        for entry in self.iter() {
+            index.update(entry.slot, None);
+        }
+    }
+
+    /// Rebuilds all secondary indexes from scratch. Needed after changing indexed keys
+    /// through entries.
+    pub fn reindex(&self) {
+        let indexes = self.recovered_lock(self.indexes.read(), INDEXES_LOCK);
+
+        for index in indexes.iter() {
+            index.clear();
+
+            for entry in self.iter() {
+                index.update(entry.slot, None);
+            }
+        }
+    }
+
+    /// Updates secondary indexes after replacing `maybe_prev` in the slot of `entry`.
+    pub(crate) fn update_indexes(&self, entry: &Entry<T, B>, maybe_prev: Option<&Arc<T>>) {
+        let indexes = self.recovered_lock(self.indexes.read(), INDEXES_LOCK);
+
+        for index in indexes.iter() {
+            index.update(entry.slot, maybe_prev.map(|prev| &**prev));
+        }
+    }
+}
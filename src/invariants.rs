@@ -0,0 +1,171 @@
+//! Self-checks of the internal structures of a reference.
+
+use std::any::type_name;
+use std::fmt;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::poison::{FREE_LIST_LOCK, INDEX_LOCK};
+use super::{Backend, Identifiable, Reference};
+
+/// A broken invariant of a reference. Ids are given as numbers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// The zero element is missing from the id index or has another vid.
+    ZeroElement { vid: Option<u32> },
+    /// An id is mapped to a vid past the last slot.
+    VidOutOfBounds { id: i32, vid: u32 },
+    /// Several ids are mapped to the same vid.
+    SharedVid { vid: u32, ids: Vec<i32> },
+    /// The item in a slot has another id than the one mapped to it.
+    IdMismatch { id: i32, vid: u32, item_id: i32 },
+    /// A slot in the free list is mapped to an id.
+    FreeSlotIndexed { id: i32, vid: u32 },
+    /// A slot in the free list isn't marked as free or the other way round.
+    FreeMarkMismatch { vid: u32, listed: bool },
+    /// A vid occurs in the free list more than once or is out of bounds.
+    BadFreeListEntry { vid: u32 },
+    /// A slot in use isn't mapped to any id.
+    OrphanedSlot { vid: u32 },
+    /// Slots in use don't add up with the number of ids.
+    LenMismatch { ids: usize, used_slots: usize },
+}
+
+/// Result of `Reference::verify_invariants`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct InvariantReport {
+    /// Full name of the item type.
+    pub type_name: &'static str,
+    /// Number of ids checked including the zero element.
+    pub ids: usize,
+    /// Number of slots checked.
+    pub slots: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl InvariantReport {
+    /// Tells whether no violations have been found.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for InvariantReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Reference of {}: {} ids, {} slots, {} violations",
+            self.type_name,
+            self.ids,
+            self.slots,
+            self.violations.len()
+        )?;
+
+        for violation in &self.violations {
+            write!(f, "\n  {violation:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Cross-checks the id index against the slots and the free slot list to catch corruption
+    /// early rather than as wrong lookup results. Meant for debugging and tests.
+    ///
+    /// Writes are blocked while checking and it takes a pass over all ids and slots.
+    /// Changes made through entries don't break invariants and are not blocked.
+    pub fn verify_invariants(&self) -> InvariantReport {
+        let vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+        let free_vids = self.recovered_lock(self.free_vids.lock(), FREE_LIST_LOCK);
+        let slots = self.items.len();
+        let mut violations = Vec::new();
+
+        let zero_vid = vids.get(0.into());
+
+        if zero_vid != Some(0) {
+            violations.push(Violation::ZeroElement { vid: zero_vid });
+        }
+
+        let mut ids_by_vid = FxHashMap::<u32, Vec<i32>>::default();
+
+        for (id, vid) in vids.iter() {
+            ids_by_vid.entry(vid).or_default().push(id.as_i32());
+
+            let Some(slot) = self.items.slot(vid as usize) else {
+                violations.push(Violation::VidOutOfBounds {
+                    id: id.as_i32(),
+                    vid,
+                });
+
+                continue;
+            };
+
+            let item_id = B::peek(slot, |item| item.map(|item| item.id()));
+
+            match item_id {
+                Some(item_id) if item_id != id => violations.push(Violation::IdMismatch {
+                    id: id.as_i32(),
+                    vid,
+                    item_id: item_id.as_i32(),
+                }),
+                _ => (),
+            }
+        }
+
+        for (vid, ids) in ids_by_vid.iter().filter(|(_, ids)| ids.len() > 1) {
+            let mut ids = ids.clone();
+            ids.sort();
+            violations.push(Violation::SharedVid { vid: *vid, ids });
+        }
+
+        let mut listed = FxHashSet::default();
+
+        for &vid in free_vids.iter() {
+            if !listed.insert(vid) || vid as usize >= slots || vid == 0 {
+                violations.push(Violation::BadFreeListEntry { vid });
+                continue;
+            }
+
+            if let Some(ids) = ids_by_vid.get(&vid) {
+                for id in ids {
+                    violations.push(Violation::FreeSlotIndexed { id: *id, vid });
+                }
+            }
+        }
+
+        for (vid, slot) in self.items.iter().enumerate() {
+            let vid = vid as u32;
+            let is_listed = listed.contains(&vid);
+
+            if B::meta(slot).is_free() != is_listed {
+                violations.push(Violation::FreeMarkMismatch {
+                    vid,
+                    listed: is_listed,
+                });
+            }
+
+            if !is_listed && !ids_by_vid.contains_key(&vid) {
+                violations.push(Violation::OrphanedSlot { vid });
+            }
+        }
+
+        let used_slots = slots.saturating_sub(listed.len());
+
+        if vids.len() != used_slots {
+            violations.push(Violation::LenMismatch {
+                ids: vids.len(),
+                used_slots,
+            });
+        }
+
+        InvariantReport {
+            type_name: type_name::<T>(),
+            ids: vids.len(),
+            slots,
+            violations,
+        }
+    }
+}
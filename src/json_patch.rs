@@ -0,0 +1,96 @@
+//! RFC 6902 JSON Patch representation of changes.
+//!
+//! Patches address items as members of the id-keyed map a `Reference` serializes to:
+//! `{"op": "add", "path": "/42", "value": {...}}` adds or replaces the item with id 42
+//! and `{"op": "remove", "path": "/42"}` removes it. Operations on nested paths and
+//! other kinds of operations are not supported.
+
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::{ChangeSet, Error, Id, Identifiable};
+
+impl<T: Identifiable + Serialize> ChangeSet<T> {
+    /// Returns the changes as an array of JSON Patch operations:
+    /// `add` for added items, `replace` for updated ones and `remove` for removed ones.
+    pub fn to_json_patch(&self) -> serde_json::Result<Value> {
+        let mut operations = Vec::new();
+
+        for (op, items) in [("add", &self.added), ("replace", &self.updated)] {
+            for item in items {
+                operations.push(json!({
+                    "op": op,
+                    "path": path(item.id()),
+                    "value": serde_json::to_value(&**item)?,
+                }));
+            }
+        }
+
+        for id in &self.removed {
+            operations.push(json!({"op": "remove", "path": path(*id)}));
+        }
+
+        Ok(Value::Array(operations))
+    }
+}
+
+impl<T: Identifiable + DeserializeOwned> ChangeSet<T> {
+    /// Parses an array of JSON Patch operations.
+    /// Returns `Error::CodecError` if there's a malformed or unsupported operation.
+    pub fn from_json_patch(patch: &Value) -> Result<Self, Error<T>> {
+        let operations = patch
+            .as_array()
+            .ok_or_else(|| invalid("Patch is not an array"))?;
+        let mut changes = Self::default();
+
+        for operation in operations {
+            let op = operation.get("op").and_then(Value::as_str);
+
+            let id = operation
+                .get("path")
+                .and_then(Value::as_str)
+                .and_then(|path| path.strip_prefix('/'))
+                .and_then(|id| id.parse::<i32>().ok())
+                .map(Id::<T>::new)
+                .ok_or_else(|| invalid(format!("Unsupported path in {operation}")))?;
+
+            match op {
+                Some("add") | Some("replace") => {
+                    let value = operation
+                        .get("value")
+                        .cloned()
+                        .ok_or_else(|| invalid(format!("Missing value in {operation}")))?;
+
+                    let item = serde_json::from_value::<T>(value)
+                        .map_err(|err| Error::CodecError(Box::new(err)))?;
+
+                    if item.id() != id {
+                        return Err(invalid(format!(
+                            "Item id doesn't match path in {operation}"
+                        )));
+                    }
+
+                    match op {
+                        Some("add") => changes.added.push(Arc::new(item)),
+                        _ => changes.updated.push(Arc::new(item)),
+                    }
+                }
+                Some("remove") => changes.removed.push(id),
+                _ => return Err(invalid(format!("Unsupported operation {operation}"))),
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+fn path<T>(id: Id<T>) -> String {
+    format!("/{id}")
+}
+
+fn invalid<T>(message: impl Into<String>) -> Error<T> {
+    Error::CodecError(message.into().into())
+}
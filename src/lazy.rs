@@ -0,0 +1,77 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::sync::OnceLock;
+
+use super::{Id, Identifiable};
+
+/// An entity kept in its raw bytes form, e.g. `Vec<u8>` or `bytes::Bytes`,
+/// and parsed on first access.
+///
+/// Loading raw payloads into a `Reference<LazyEntity<T, R>>` doesn't pay for parsing
+/// entities which are never read. The parsed value or the parsing error is memoized.
+///
+/// ```
+/// # use reference::{LazyEntity, Reference};
+/// #
+/// fn parse(raw: &[u8]) -> Result<u64, std::num::ParseIntError> {
+///     std::str::from_utf8(raw).unwrap().parse()
+/// }
+///
+/// let prices = Reference::new(2);
+/// let entry = prices.insert(LazyEntity::new(1.into(), b"100".to_vec(), parse)).unwrap();
+///
+/// let price = entry.load().unwrap();
+/// assert!(!price.is_parsed());
+/// assert_eq!(price.get(), Ok(&100));
+/// assert!(price.is_parsed());
+/// ```
+pub struct LazyEntity<T, R, E = Box<dyn StdError + Send + Sync>> {
+    id: Id<Self>,
+    raw: R,
+    parse: fn(&[u8]) -> Result<T, E>,
+    parsed: OnceLock<Result<T, E>>,
+}
+
+impl<T, R: AsRef<[u8]>, E> LazyEntity<T, R, E> {
+    pub fn new(id: Id<Self>, raw: R, parse: fn(&[u8]) -> Result<T, E>) -> Self {
+        Self {
+            id,
+            raw,
+            parse,
+            parsed: OnceLock::new(),
+        }
+    }
+
+    /// Returns the raw payload without parsing.
+    pub fn raw(&self) -> &R {
+        &self.raw
+    }
+
+    /// Returns the parsed value parsing the payload on the first call.
+    /// Concurrent first calls parse it once.
+    pub fn get(&self) -> Result<&T, &E> {
+        self.parsed
+            .get_or_init(|| (self.parse)(self.raw.as_ref()))
+            .as_ref()
+    }
+
+    /// Tells whether the payload has been parsed already.
+    pub fn is_parsed(&self) -> bool {
+        self.parsed.get().is_some()
+    }
+}
+
+impl<T, R, E> Identifiable for LazyEntity<T, R, E> {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+impl<T: fmt::Debug, R, E: fmt::Debug> fmt::Debug for LazyEntity<T, R, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyEntity")
+            .field("id", &self.id)
+            .field("parsed", &self.parsed.get())
+            .finish()
+    }
+}
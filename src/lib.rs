@@ -1,20 +1,179 @@
+mod access;
+mod aggregate;
 mod array;
+mod backend;
+#[cfg(all(feature = "bench-harness", not(feature = "single-thread"), not(loom)))]
+pub mod bench;
+mod bloom;
+pub mod bootstrap;
+mod branded;
+mod bulk;
+mod capacity;
+mod checkpoint;
+mod clock;
+#[cfg(all(not(feature = "single-thread"), not(loom)))]
+mod coalesce;
+mod codec;
+mod config;
+pub mod context;
+mod describe;
+mod diff;
+mod entry_set;
 mod error;
+mod eviction;
+mod fallback;
+#[cfg(feature = "testing")]
+mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fingerprint;
+mod frozen;
+pub mod graph;
+#[cfg(all(feature = "grpc", not(feature = "single-thread")))]
+pub mod grpc;
+mod heap_size;
+mod hot_field;
+#[cfg(all(feature = "http", not(feature = "single-thread")))]
+pub mod http;
+mod id_index;
+mod index;
+mod invariants;
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+mod json_patch;
+mod lazy;
+mod link;
+mod lookup;
+mod maintenance;
+mod merge;
+mod migrate;
+mod missing;
+#[cfg(feature = "testing")]
+mod mock;
+mod overlay;
+#[cfg(feature = "rayon")]
+mod par_load;
+mod pin;
+mod poison;
+mod pool;
+mod progress;
+mod projection;
+mod provenance;
+#[cfg(all(feature = "pyo3", not(feature = "single-thread")))]
+pub mod python;
+mod query;
+mod readiness;
+mod reconcile;
+mod reconfigure;
+mod record;
+mod referential;
+mod refresh;
+mod relation;
+mod remap;
+#[cfg(all(not(feature = "single-thread"), not(loom)))]
+mod scheduler;
+mod seq_cell;
+#[cfg(feature = "serde")]
+pub mod serialize;
+#[cfg(all(feature = "shm", not(feature = "single-thread"), not(loom)))]
+mod shm;
+mod snapshot;
+mod staleness;
+mod stats;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(all(feature = "testing", not(feature = "single-thread")))]
+mod stress;
+mod sync;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod text_index;
+mod ttl;
+mod unchanged;
+mod update_lock;
+#[cfg(feature = "v1")]
+pub mod v1;
+#[cfg(feature = "v2")]
+pub mod v2;
+mod with_id;
+#[cfg(all(not(feature = "single-thread"), not(loom)))]
+mod writer;
 
 use std::any::type_name;
-use std::collections::HashMap;
 use std::fmt;
-use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
-use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use arc_swap::ArcSwapOption;
-use parking_lot::RwLock;
-use rustc_hash::{FxHashMap, FxHasher};
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use self::array::{Array, Iter as ArrayIter};
+#[cfg(feature = "mmap")]
+pub use self::backend::MmapBackend;
+pub use self::backend::{
+    ArcSwapBackend, Backend, InPlaceBackend, InlineBackend, RwLockBackend, Slot, SlotMeta,
+};
+use self::bloom::BloomFilter;
+pub use self::branded::Branded;
+pub use self::bulk::BulkReport;
+use self::capacity::CapacityEvents;
+pub use self::capacity::{
+    CapacityEvent, DEFAULT_CAPACITY_EVENT_THRESHOLDS, DEFAULT_UTILIZATION_WARNING_THRESHOLD,
+};
+pub use self::clock::{Clock, ManualClock, SystemClock};
+#[cfg(all(not(feature = "single-thread"), not(loom)))]
+pub use self::coalesce::Coalescer;
+pub use self::codec::Codec;
+pub use self::config::{IdIndexKind, Manifest, ReferenceConfig, DEFAULT_CONFIG_CAPACITY};
+pub use self::describe::EntryDescription;
+pub use self::diff::ChangeSet;
+pub use self::entry_set::{EntryKey, EntrySet};
 pub use self::error::Error;
+pub use self::eviction::CapacityPolicy;
+pub use self::fallback::DegradedReads;
+pub use self::fingerprint::{ContentHash, HashContent};
+pub use self::frozen::FrozenReference;
+pub use self::heap_size::{HeapSize, MemoryUsage};
+pub use self::hot_field::HotField;
+pub use self::id_index::{FlatIdIndex, IdIndex, SortedVecIndex};
+pub use self::index::KeyIndex;
+use self::index::SecondaryIndex;
+pub use self::invariants::{InvariantReport, Violation};
+pub use self::lazy::LazyEntity;
+pub use self::lookup::Lookup;
+pub use self::merge::{LastWriterWins, Merge, Timestamp};
+pub use self::overlay::Overlay;
+pub use self::poison::PoisonPolicy;
+use self::poison::{FREE_LIST_LOCK, INDEX_LOCK};
+use self::pool::Pool;
+pub use self::progress::{Progress, DEFAULT_PROGRESS_EVERY};
+pub use self::projection::Projection;
+pub use self::provenance::Provenance;
+pub use self::query::Query;
+use self::readiness::Hydrations;
+pub use self::readiness::Readiness;
+pub use self::reconcile::{FetchPlan, Summary, DEFAULT_BUCKET_WIDTH};
+pub use self::reconfigure::{ConfigChange, ConfigPatch};
+use self::reconfigure::{ConfigListener, Tunables};
+use self::record::{Op, Recorder};
+pub use self::referential::{Enterable, Referential};
+pub use self::refresh::RefreshSummary;
+pub use self::relation::{Cascade, Relation};
+pub use self::remap::IdRemap;
+#[cfg(all(not(feature = "single-thread"), not(loom)))]
+pub use self::scheduler::{RefreshHandle, RefreshScheduler, DEFAULT_REFRESH_JITTER};
+#[cfg(all(feature = "shm", not(feature = "single-thread"), not(loom)))]
+pub use self::shm::{ShmBackend, ShmIter, ShmReadSlot, ShmReader, ShmSlot};
+pub use self::snapshot::Compression;
+pub use self::stats::Stats;
+#[cfg(feature = "stream")]
+pub use self::stream::{EntryStream, LoadSummary, DEFAULT_LOAD_BATCH_SIZE, DEFAULT_YIELD_EVERY};
+use self::sync::{AtomicU8, AtomicUsize, Mutex, RwLock, LEN_PUBLISH};
+pub use self::text_index::TextIndex;
+pub use self::unchanged::{ByHash, ByValue, SameContent};
+pub use self::update_lock::{UpdateGuard, UPDATE_LOCK_STRIPES};
+pub use self::with_id::WithId;
+#[cfg(all(not(feature = "single-thread"), not(loom)))]
+pub use self::writer::{Overflow, Pending, Throttle, WriterHandle};
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -22,7 +181,7 @@ pub use self::error::Error;
 #[derive(Default)]
 pub struct Id<T> {
     id: i32,
-    _phantom: PhantomData<T>,
+    _phantom: PhantomData<fn() -> T>,
 }
 
 impl<T> Id<T> {
@@ -40,7 +199,7 @@ impl<T> Id<T> {
 
 impl<T> Clone for Id<T> {
     fn clone(&self) -> Self {
-        Id::new(self.id)
+        *self
     }
 }
 
@@ -89,6 +248,25 @@ pub trait Identifiable {
     fn id(&self) -> Id<Self>
     where
         Self: Sized;
+
+    /// Called after the entity has been stored into the slot of `entry`.
+    /// This allows the entity to register itself in secondary structures.
+    ///
+    /// The hook is called outside of the reference locks though an update lock may be held
+    /// when storing through `UpdateGuard`.
+    fn attached<B: Backend<Self>>(&self, _entry: &Entry<Self, B>)
+    where
+        Self: Sized + 'static,
+    {
+    }
+
+    /// Called after the entity has been replaced in or removed from the slot of `entry`.
+    /// On removal the entry is already stale.
+    fn detached<B: Backend<Self>>(&self, _entry: &Entry<Self, B>)
+    where
+        Self: Sized + 'static,
+    {
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -169,99 +347,511 @@ pub trait Identifiable {
 /// let subject = product.subject.load().unwrap();
 /// assert_eq!(subject.id, 1.into());
 /// ```
-pub struct Entry<T: 'static>(&'static ArcSwapOption<T>);
+///
+/// Slots of removed items are reused for other ids. To protect from observing such a newcomer
+/// an entry remembers the slot generation it was created with and becomes empty
+/// once the generation changes.
+pub struct Entry<T: 'static, B: Backend<T> = ArcSwapBackend<T>> {
+    slot: &'static B::Slot,
+    generation: usize,
+}
+
+impl<T: 'static, B: Backend<T>> Entry<T, B> {
+    fn new(slot: &'static B::Slot) -> Self {
+        Self {
+            slot,
+            generation: B::meta(slot).generation(),
+        }
+    }
 
-impl<T: 'static> Entry<T> {
     pub fn load(&self) -> Option<Arc<T>> {
-        (*self.0.load()).as_ref().cloned()
+        let maybe_item = B::load(self.slot);
+
+        // Checking after loading since the slot could have been reused in between.
+        match self.is_stale() {
+            false => maybe_item,
+            true => None,
+        }
+    }
+
+    /// Returns the generation of the slot the entry was created with.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Tells whether the item has been removed and the entry is not usable anymore.
+    pub fn is_stale(&self) -> bool {
+        B::meta(self.slot).generation() != self.generation
+    }
+
+    /// Replaces the referred entity with a modified copy made by `f` and returns the previous
+    /// value. If another writer replaces the entity concurrently `f` is called again
+    /// with the fresh value. Does nothing if the entry is empty or stale.
+    ///
+    /// `f` must not change the id of the entity. The update time is taken from the system
+    /// clock since the entry doesn't know its reference. See `Reference::modify`.
+    pub fn modify<F>(&self, f: F) -> Option<Arc<T>>
+    where
+        T: Identifiable,
+        F: FnMut(&T) -> T,
+    {
+        self.modify_at(&SystemClock, f)
+    }
+
+    /// Like `modify` but timed by `clock`.
+    pub(crate) fn modify_at<F>(&self, clock: &dyn Clock, mut f: F) -> Option<Arc<T>>
+    where
+        T: Identifiable,
+        F: FnMut(&T) -> T,
+    {
+        let mut maybe_new = None;
+
+        let maybe_prev = B::rcu(self.slot, |current| {
+            maybe_new = match (self.is_stale(), current) {
+                (false, Some(item)) => Some(Arc::new(f(item))),
+                _ => None,
+            };
+
+            match maybe_new {
+                Some(ref new) => Some(new.clone()),
+                None => current.clone(),
+            }
+        });
+
+        let new = maybe_new?;
+        B::meta(self.slot).touch_at(clock.now(), 0);
+        self.swapped(Some(&new), maybe_prev.as_ref());
+        maybe_prev
+    }
+
+    /// Calls lifecycle hooks after replacing `maybe_prev` with `maybe_new` in the slot.
+    fn swapped(&self, maybe_new: Option<&Arc<T>>, maybe_prev: Option<&Arc<T>>)
+    where
+        T: Identifiable,
+    {
+        let is_same = match (maybe_new, maybe_prev) {
+            (Some(new), Some(prev)) => Arc::ptr_eq(new, prev),
+            _ => false,
+        };
+
+        if is_same {
+            return;
+        }
+
+        if let Some(prev) = maybe_prev {
+            prev.detached(self);
+        }
+
+        if let Some(new) = maybe_new {
+            new.attached(self);
+        }
+    }
+}
+
+/// Entries are equal if they refer to the same slot of the same generation, i.e. to the same
+/// item regardless of its current value. A stale entry never equals a fresh one.
+/// Hashing is consistent with that so entries may be used as keys despite clippy's
+/// `mutable_key_type` lint which can't see that the slot contents are not hashed.
+impl<T: 'static, B: Backend<T>> PartialEq for Entry<T, B> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.slot, other.slot) && self.generation == other.generation
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for Entry<T> {
+impl<T: 'static, B: Backend<T>> Eq for Entry<T, B> {}
+
+impl<T: 'static, B: Backend<T>> Hash for Entry<T, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.slot, state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T: 'static, B: Backend<T>> fmt::Debug for Entry<T, B>
+where
+    B::Slot: fmt::Debug,
+{
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Entry({:?})", self.0)
+        write!(f, "Entry({:?})", self.slot)
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// How `Reference::insert` treats an item with an id which is already resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateMode {
+    /// Replace the existing item.
+    #[default]
+    Replace,
+    /// Keep the existing item and return `Error::DuplicateId`.
+    Reject,
+}
+
+/// An entry of the inserted item along with the replaced item if any.
+type InsertResult<T, B> = Result<(Entry<T, B>, Option<Arc<T>>), Error<T>>;
+
 /// Entity storage of `T`.
+/// Slots are kept by a `Backend` which is `ArcSwapBackend` by default.
+///
+/// A reference is `Send` and `Sync` only if `T` is both. Items which are not, e.g. holding
+/// `Rc` internals, may still be kept in a reference used by a single thread.
 #[derive(Debug)]
-pub struct Reference<T: Identifiable + 'static> {
-    items: Array<Arc<ArcSwapOption<T>>>,
-    vids: RwLock<FxHashMap<Id<T>, usize>>,
+pub struct Reference<T: Identifiable + 'static, B: Backend<T> = ArcSwapBackend<T>> {
+    items: B,
+    vids: RwLock<Box<dyn IdIndex<T>>>,
+    bloom: Option<BloomFilter>,
+    free_vids: Mutex<Vec<u32>>,
+    duplicate_mode: DuplicateMode,
+    poison_policy: PoisonPolicy,
     effective_len: AtomicUsize,
+    pool: Pool<T>,
+    indexes: RwLock<Vec<Arc<dyn SecondaryIndex<T, B>>>>,
+    tunables: RwLock<Tunables>,
+    config_listener: Option<Box<dyn ConfigListener>>,
+    capacity_events: CapacityEvents,
+    capacity_policy: CapacityPolicy,
+    evictions: AtomicUsize,
+    access_sampling: Option<u32>,
+    clock_hand: AtomicUsize,
+    pins: RwLock<FxHashSet<Id<T>>>,
+    batch: AtomicUsize,
+    recorder: Option<Recorder<T>>,
+    timestamps: Mutex<FxHashMap<Id<T>, Timestamp>>,
+    merge: Box<dyn Merge<T>>,
+    skip_unchanged: Option<Box<dyn SameContent<T>>>,
+    content_hasher: Option<Box<dyn ContentHash<T>>>,
+    clock: Arc<dyn Clock>,
+    ttls: Mutex<FxHashMap<Id<T>, Duration>>,
+    refresh_on_read: bool,
+    missing: Mutex<FxHashMap<Id<T>, Instant>>,
+    hydration: Mutex<Hydrations>,
+    degradation: Option<(Duration, f64)>,
+    last_readiness: AtomicU8,
+    degraded_reads: DegradedReads,
+    #[cfg(feature = "testing")]
+    faults: Option<fault::FaultInjector>,
+    #[cfg(debug_assertions)]
+    blocking_maintenance: AtomicUsize,
+    fallback: Option<Arc<T>>,
 }
 
 impl<T: Identifiable + 'static> Reference<T> {
     /// Creates a `Referential<T>` with the given capacity and zero element as `None`.
+    /// Panics if the reference can't be created. See `try_new`.
     pub fn new(capacity: usize) -> Self {
-        let items = Array::new(capacity);
-        let hasher = BuildHasherDefault::<FxHasher>::default();
-        let mut vids = HashMap::with_capacity_and_hasher(capacity, hasher);
+        Self::with_backend(ArcSwapBackend::new(capacity))
+    }
 
-        items
-            .push(Arc::new(ArcSwapOption::const_empty()))
-            .expect("Failed to insert zero element");
+    /// Like `new` but returns an error instead of panicking, e.g. for a zero capacity
+    /// which leaves no room for the zero element.
+    pub fn try_new(capacity: usize) -> Result<Self, Error<T>> {
+        Self::try_with_backend(ArcSwapBackend::try_new(capacity)?)
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Creates a `Reference<T>` on top of an empty `backend` and adds zero element as `None`.
+    /// The capacity is taken from the backend and must fit `u32` since vids are stored as such.
+    pub fn with_backend(backend: B) -> Self {
+        match Self::try_with_backend(backend) {
+            Ok(reference) => reference,
+            Err(err) => panic!("Failed to create reference: {err}"),
+        }
+    }
+
+    /// Like `with_backend` but returns an error instead of panicking.
+    pub fn try_with_backend(backend: B) -> Result<Self, Error<T>> {
+        let capacity = backend.capacity();
 
+        if capacity > u32::MAX as usize {
+            return Err(Error::InsertError(format!(
+                "Capacity {capacity} exceeds u32"
+            )));
+        }
+
+        backend
+            .push_slot(None)
+            .map_err(|err| Error::InsertError(format!("Failed to insert zero element: {err}")))?;
+
+        let mut vids = id_index::default_id_index(capacity);
         vids.insert(Id::from(0), 0);
+        Ok(Self::from_parts(backend, vids))
+    }
+
+    /// Creates a `Reference<T>` on top of a `backend` having slots of the ids in `vids`.
+    pub(crate) fn from_parts(backend: B, vids: Box<dyn IdIndex<T>>) -> Self {
+        assert!(
+            backend.capacity() <= u32::MAX as usize,
+            "Failed to create reference: capacity exceeds u32"
+        );
 
         Self {
-            items,
+            items: backend,
             vids: RwLock::new(vids),
+            bloom: None,
+            free_vids: Mutex::new(Vec::new()),
+            duplicate_mode: DuplicateMode::default(),
+            poison_policy: PoisonPolicy::default(),
             effective_len: AtomicUsize::new(0),
+            pool: Pool::new(),
+            indexes: RwLock::new(Vec::new()),
+            tunables: RwLock::new(Tunables::default()),
+            config_listener: None,
+            capacity_events: CapacityEvents::default(),
+            capacity_policy: CapacityPolicy::default(),
+            evictions: AtomicUsize::new(0),
+            access_sampling: None,
+            clock_hand: AtomicUsize::new(0),
+            pins: RwLock::new(FxHashSet::default()),
+            batch: AtomicUsize::new(0),
+            recorder: None,
+            timestamps: Mutex::new(FxHashMap::default()),
+            merge: Box::new(LastWriterWins),
+            skip_unchanged: None,
+            content_hasher: None,
+            clock: Arc::new(SystemClock),
+            ttls: Mutex::new(FxHashMap::default()),
+            refresh_on_read: false,
+            missing: Mutex::new(FxHashMap::default()),
+            hydration: Mutex::new(Hydrations::default()),
+            degradation: None,
+            last_readiness: AtomicU8::new(readiness::READY),
+            degraded_reads: DegradedReads::default(),
+            #[cfg(feature = "testing")]
+            faults: None,
+            #[cfg(debug_assertions)]
+            blocking_maintenance: AtomicUsize::new(0),
+            fallback: None,
         }
     }
 
-    /// Adds a new element to the storage or replaces existing one.
-    pub fn insert(&self, item: T) -> Result<Entry<T>, Error<T>> {
+    /// Adds a new element to the storage. If there's already an item with the same id
+    /// it gets replaced unless the duplicate mode is `DuplicateMode::Reject`.
+    /// In that case `Error::DuplicateId` is returned. Reservations are filled in both modes.
+    pub fn insert(&self, item: T) -> Result<Entry<T, B>, Error<T>> {
+        self.insert_inner(item).map(|(entry, _replaced)| entry)
+    }
+
+    /// Adds a new element to the storage or replaces existing one regardless of the duplicate mode.
+    pub fn upsert(&self, item: T) -> Result<Entry<T, B>, Error<T>> {
+        self.insert_arc(Arc::new(item), DuplicateMode::Replace)
+            .map(|(entry, _replaced)| entry)
+    }
+
+    /// Sets how `insert` treats items with ids which are already resolved.
+    /// The default is `DuplicateMode::Replace`.
+    pub fn with_duplicate_mode(mut self, mode: DuplicateMode) -> Self {
+        self.duplicate_mode = mode;
+        self
+    }
+
+    /// Like `insert` but reuses allocations of previously replaced items when possible
+    /// instead of allocating a new `Arc` each time. The replaced item goes to the pool.
+    /// This reduces allocator pressure when the same entities get replaced frequently.
+    pub fn insert_pooled(&self, item: T) -> Result<Entry<T, B>, Error<T>> {
+        let (entry, maybe_prev) = self.insert_arc(self.pool.wrap(item), self.duplicate_mode)?;
+
+        if let Some(prev) = maybe_prev {
+            self.pool.put(prev);
+        }
+
+        Ok(entry)
+    }
+
+    /// Like `insert` but also tells whether a resolved item has been replaced.
+    fn insert_inner(&self, item: T) -> Result<(Entry<T, B>, bool), Error<T>> {
+        self.insert_arc(Arc::new(item), self.duplicate_mode)
+            .map(|(entry, maybe_prev)| (entry, maybe_prev.is_some()))
+    }
+
+    /// Inserts an item returning the replaced one if any.
+    fn insert_arc(&self, item: Arc<T>, mode: DuplicateMode) -> InsertResult<T, B> {
+        #[cfg(feature = "testing")]
+        self.check_fault(fault::Call::Insert)?;
+
+        if let Some(entry) = self.unchanged_entry(&item, mode) {
+            return Ok((entry, None));
+        }
+
+        let (entry, maybe_prev) = self.store_arc(item.clone(), mode)?;
+        self.update_indexes(&entry, maybe_prev.as_ref());
+        entry.swapped(Some(&item), maybe_prev.as_ref());
+        Ok((entry, maybe_prev))
+    }
+
+    /// Does the job of `insert_arc` without updating indexes and calling lifecycle hooks.
+    fn store_arc(&self, item: Arc<T>, mode: DuplicateMode) -> InsertResult<T, B> {
+        self.check_writable()?;
         let id = item.id();
 
-        let maybe_existing_vid = {
-            let vids = self.vids.read();
-            let maybe_vid = vids.get(&id).copied();
+        {
+            let vids = self.checked_lock(self.vids.read(), INDEX_LOCK)?;
 
-            if maybe_vid.is_none() && vids.contains_key(&id) {
-                return Err(Error::InsertError(format!(
-                    "Failed to add id {} because it already exists",
-                    id,
-                )));
+            // Replacing under the lock so the slot can't get removed and reused meanwhile.
+            if let Some(vid) = vids.get(id) {
+                return self.replace(vid, item, mode);
             }
+        }
 
-            maybe_vid
-        };
+        let mut vids = self.lock_with_room(id)?;
+        self.add_locked(&mut vids, id, Some(item), mode)
+    }
 
-        match maybe_existing_vid {
-            None => self.add(id, Some(item)),
+    /// Adds a new slot for `id` with the index write lock taken so concurrent writers
+    /// never race for the same slot. If another writer has added `id` in the meantime
+    /// then its slot is reused. Slots of removed items are reused first.
+    fn add_locked(
+        &self,
+        vids: &mut Box<dyn IdIndex<T>>,
+        id: Id<T>,
+        maybe_item: Option<Arc<T>>,
+        mode: DuplicateMode,
+    ) -> InsertResult<T, B> {
+        self.check_writable()?;
+
+        if let Some(vid) = vids.get(id) {
+            return match maybe_item {
+                Some(item) => self.replace(vid, item, mode),
+                None => Ok((self.entry(vid)?, None)),
+            };
+        }
+
+        let utilization_before = self.utilization();
+        let has_item = maybe_item.is_some();
+        let recorded = self.recorder.as_ref().and(maybe_item.clone());
+        let maybe_free_vid = self
+            .checked_lock(self.free_vids.lock(), FREE_LIST_LOCK)?
+            .pop();
+
+        let vid = match maybe_free_vid {
             Some(vid) => {
-                let existing_item = self.items.get(vid).ok_or_else(|| {
-                    Error::InsertError(format!("Index {} is out of bounds", vid,))
-                })?;
+                let slot = self.entry(vid)?.slot;
+                B::meta(slot).bump_generation();
+                B::meta(slot).reset_updated_at();
+                B::store(slot, maybe_item);
+                vid
+            }
+            // The capacity is checked to fit `u32` on creation.
+            None => self.items.push_slot(maybe_item)? as u32,
+        };
 
-                existing_item.store(Some(Arc::new(item)));
-                self.effective_len.fetch_add(1, AtomicOrdering::Relaxed);
-                Ok(Entry(existing_item))
+        if has_item {
+            B::meta(self.entry(vid)?.slot).touch_at(self.now(), self.batch_id());
+        }
+
+        self.effective_len.fetch_add(1, LEN_PUBLISH);
+
+        B::assign_id(self.entry(vid)?.slot, id);
+
+        // Before the index so `get` never misses an indexed id because of the filter.
+        if let Some(bloom) = &self.bloom {
+            bloom.insert(id);
+        }
+
+        vids.insert(id, vid);
+        self.forget_missing(id);
+
+        match &recorded {
+            Some(item) => self.record(Op::Store(item)),
+            None => self.record(Op::Reserve(id)),
+        }
+
+        self.warn_on_utilization(utilization_before);
+        Ok((self.entry(vid)?, None))
+    }
+
+    fn replace(&self, vid: u32, item: Arc<T>, mode: DuplicateMode) -> InsertResult<T, B> {
+        let existing_item = self.entry(vid)?;
+        // Concurrent replaces of the same item are ordered by the recorder lock.
+        let mut recording = self.recorder.as_ref().map(|recorder| recorder.lock());
+
+        let maybe_prev = match mode {
+            DuplicateMode::Replace => B::store(existing_item.slot, Some(item.clone())),
+            DuplicateMode::Reject => {
+                // Checking and setting atomically so concurrent inserts can't both succeed.
+                let maybe_prev = B::rcu(existing_item.slot, |current| match current {
+                    Some(_) => current.clone(),
+                    None => Some(item.clone()),
+                });
+
+                if maybe_prev.is_some() {
+                    return Err(Error::DuplicateId(item.id()));
+                }
+
+                None
             }
+        };
+
+        B::meta(existing_item.slot).touch_at(self.now(), self.batch_id());
+
+        if let Some(recording) = &mut recording {
+            recording.record(Op::Store(&item));
         }
+
+        self.effective_len.fetch_add(1, LEN_PUBLISH);
+        Ok((existing_item, maybe_prev))
     }
 
-    fn add(&self, id: Id<T>, maybe_item: Option<T>) -> Result<Entry<T>, Error<T>> {
-        let vid = self.items.len();
+    fn check_writable(&self) -> Result<(), Error<T>> {
+        match self.items.is_read_only() {
+            true => Err(Error::InsertError(
+                "Failed to change a read-only reference".into(),
+            )),
+            false => Ok(()),
+        }
+    }
 
+    fn entry(&self, vid: u32) -> Result<Entry<T, B>, Error<T>> {
         self.items
-            .push(Arc::new(ArcSwapOption::from_pointee(maybe_item)))
-            .map_err(|err| Error::Other(Box::new(err)))?;
-
-        self.effective_len.fetch_add(1, AtomicOrdering::Relaxed);
-        self.vids.write().insert(id, vid);
-        Ok(Entry(self.items.get(vid).unwrap()))
+            .slot(vid as usize)
+            .map(Entry::new)
+            .ok_or_else(|| Error::InsertError(format!("Index {} is out of bounds", vid,)))
     }
 
     /// Gets an entry with the given `id`. Returns `None` if there's no item with this `id`.
-    pub fn get(&self, id: Id<T>) -> Option<Entry<T>> {
-        match self.vids.read().get(&id).copied() {
-            None => None,
-            Some(vid) => self.items.get(vid).map(|e| Entry(e)),
+    pub fn get(&self, id: Id<T>) -> Option<Entry<T, B>> {
+        #[cfg(feature = "testing")]
+        if self.inject_fault(fault::Call::Get) {
+            return None;
+        }
+
+        if !self.may_contain(id) {
+            return None;
+        }
+
+        let vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+
+        let slot = self.items.slot(vids.get(id)? as usize)?;
+
+        self.track_access(slot);
+        self.refresh_on_read(slot);
+        Some(Entry::new(slot))
+    }
+
+    /// Tells whether `id` is known to the reference either as an item or a reservation.
+    pub fn contains(&self, id: Id<T>) -> bool {
+        if !self.may_contain(id) {
+            return false;
+        }
+
+        self.recovered_lock(self.vids.read(), INDEX_LOCK)
+            .get(id)
+            .is_some()
+    }
+
+    /// Tells whether there's an item with the given `id` having a value.
+    /// Unlike `contains` this returns `false` for reservations.
+    pub fn contains_resolved(&self, id: Id<T>) -> bool {
+        if !self.may_contain(id) {
+            return false;
+        }
+
+        let vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+
+        match vids.get(id).and_then(|vid| self.items.slot(vid as usize)) {
+            Some(slot) => B::load(slot).is_some(),
+            None => false,
         }
     }
 
@@ -269,41 +859,199 @@ impl<T: Identifiable + 'static> Reference<T> {
     /// for the given `id`. The `Entry` may be set later using `replace` method.
     /// This method is useful when you want to fill the reference of dependent items first
     /// and add referred entities into another reference later.
-    pub fn get_or_reserve(&self, id: Id<T>) -> Result<Entry<T>, Error<T>> {
+    ///
+    /// Fails with `Error::ReserveFailed` if there's no free slot for the reservation.
+    pub fn get_or_reserve(&self, id: Id<T>) -> Result<Entry<T, B>, Error<T>> {
         match self.get(id) {
             Some(entry) => Ok(entry),
-            None => self.add(id, None),
+            None => {
+                let mut vids = self.checked_lock(self.vids.write(), INDEX_LOCK)?;
+                self.reserve_locked(&mut vids, id)
+            }
+        }
+    }
+
+    /// Like `get_or_reserve` but doesn't reserve anything.
+    /// Returns `Ok(None)` if the item is absent but could be reserved
+    /// and `Error::ReserveFailed` if it's absent and there's no free slot to reserve it.
+    pub fn try_get_or_reserve(&self, id: Id<T>) -> Result<Option<Entry<T, B>>, Error<T>> {
+        if let Some(entry) = self.get(id) {
+            return Ok(Some(entry));
+        }
+
+        match self.has_free_slot()? {
+            true => Ok(None),
+            false => Err(self.reserve_failed(id)),
+        }
+    }
+
+    fn has_free_slot(&self) -> Result<bool, Error<T>> {
+        Ok(self.items.len() < self.items.capacity()
+            || !self
+                .checked_lock(self.free_vids.lock(), FREE_LIST_LOCK)?
+                .is_empty())
+    }
+
+    /// Returns how many more free slots adding `ids` needs after freeing `freed` slots.
+    /// Ids already there take no slots. Always zero if items get evicted to make room.
+    pub(crate) fn lacking_free_slots(
+        &self,
+        ids: impl IntoIterator<Item = Id<T>>,
+        freed: usize,
+    ) -> Result<usize, Error<T>> {
+        if self.capacity_policy == CapacityPolicy::EvictLeastRecentlyUsed {
+            return Ok(0);
+        }
+
+        let new = ids
+            .into_iter()
+            .filter(|id| !self.contains(*id))
+            .collect::<FxHashSet<_>>()
+            .len();
+
+        let free = self.items.capacity() - self.items.len()
+            + freed
+            + self
+                .checked_lock(self.free_vids.lock(), FREE_LIST_LOCK)?
+                .len();
+
+        Ok(new.saturating_sub(free))
+    }
+
+    /// Other errors than the lack of a free slot, e.g. of a read-only backend,
+    /// are returned as they are.
+    fn reserve_locked(
+        &self,
+        vids: &mut Box<dyn IdIndex<T>>,
+        id: Id<T>,
+    ) -> Result<Entry<T, B>, Error<T>> {
+        self.check_writable()?;
+
+        if vids.get(id).is_none() && !self.has_free_slot()? {
+            return Err(self.reserve_failed(id));
+        }
+
+        self.add_locked(vids, id, None, DuplicateMode::Replace)
+            .map(|(entry, _)| entry)
+    }
+
+    fn reserve_failed(&self, id: Id<T>) -> Error<T> {
+        Error::ReserveFailed {
+            id,
+            capacity: self.items.capacity(),
         }
     }
 
-    /// Creates a reader iterator over items.
-    pub fn iter(&self) -> impl Iterator<Item = Entry<T>> {
+    /// Like `get_or_reserve` for many ids at once. The index is locked for writing only once
+    /// so this is much cheaper than reserving ids one by one when loading in two phases.
+    /// Entries are returned in the order of `ids`.
+    pub fn reserve_many(
+        &self,
+        ids: impl IntoIterator<Item = Id<T>>,
+    ) -> Result<Vec<Entry<T, B>>, Error<T>> {
+        let ids = ids.into_iter();
+        let mut entries = Vec::with_capacity(ids.size_hint().0);
+        let mut vids = self.checked_lock(self.vids.write(), INDEX_LOCK)?;
+
+        for id in ids {
+            entries.push(self.reserve_locked(&mut vids, id)?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Removes the item with the given `id` (or its reservation) and returns it.
+    ///
+    /// The slot gets freed for reuse by another id. Existing entries of the item become stale:
+    /// they stay empty even if `id` gets added again.
+    ///
+    /// Nothing is removed from a reference with a read-only backend.
+    pub fn remove(&self, id: Id<T>) -> Option<Arc<T>> {
+        if self.items.is_read_only() {
+            return None;
+        }
+
+        // The update lock goes before the index lock since guard holders may take the latter.
+        // See `Entry::lock_for_update`.
+        let (vid, slot, guard, mut vids) = loop {
+            let vid = self.recovered_lock(self.vids.read(), INDEX_LOCK).get(id)?;
+            let slot = self.items.slot(vid as usize)?;
+            let guard = update_lock::lock_slot(slot);
+            let vids = self.recovered_lock(self.vids.write(), INDEX_LOCK);
+
+            // The id might have been removed and added to another slot meanwhile.
+            if vids.get(id) == Some(vid) {
+                break (vid, slot, guard, vids);
+            }
+        };
+
+        vids.remove(id);
+        let entry = Entry::<T, B>::new(slot);
+        B::meta(slot).bump_generation();
+        let maybe_prev = B::store(slot, None);
+
+        self.recovered_lock(self.free_vids.lock(), FREE_LIST_LOCK)
+            .push(vid);
+
+        self.unpin(id);
+        self.forget_ttl(id);
+        self.record(Op::Remove(id));
+        drop(vids);
+        drop(guard);
+        self.update_indexes(&entry, maybe_prev.as_ref());
+        entry.swapped(None, maybe_prev.as_ref());
+        maybe_prev
+    }
+
+    /// Creates a reader iterator over items. Free slots of removed items are skipped.
+    ///
+    /// Items come in the order of their slots which depends on the order of insertions,
+    /// reservations and removals. Use `iter_ordered` for an order independent of that.
+    pub fn iter(&self) -> impl Iterator<Item = Entry<T, B>> {
         Iter::new(self.items.iter())
     }
+
+    /// Like `iter` but yields items ordered by id, e.g. for reproducible output.
+    /// Ids are collected and sorted upfront so items added meanwhile are not yielded.
+    pub fn iter_ordered(&self) -> impl Iterator<Item = Entry<T, B>> + '_ {
+        let mut vids = self
+            .recovered_lock(self.vids.read(), INDEX_LOCK)
+            .iter()
+            .collect::<Vec<_>>();
+
+        vids.sort_unstable_by_key(|(id, _)| id.as_i32());
+
+        vids.into_iter()
+            .filter_map(|(_, vid)| self.items.slot(vid as usize))
+            .map(Entry::new)
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
-struct Iter<T: Identifiable + 'static> {
-    inner: ArrayIter<Arc<ArcSwapOption<T>>>,
+struct Iter<T: Identifiable + 'static, B: Backend<T>> {
+    inner: B::Iter,
 }
 
-impl<T: Identifiable + 'static> fmt::Debug for Iter<T> {
+impl<T: Identifiable + 'static, B: Backend<T>> fmt::Debug for Iter<T, B> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Iter").finish()
     }
 }
 
-impl<T: Identifiable + 'static> Iter<T> {
-    fn new(inner: ArrayIter<Arc<ArcSwapOption<T>>>) -> Self {
+impl<T: Identifiable + 'static, B: Backend<T>> Iter<T, B> {
+    fn new(inner: B::Iter) -> Self {
         Self { inner }
     }
 }
 
-impl<T: Identifiable + 'static> Iterator for Iter<T> {
-    type Item = Entry<T>;
+impl<T: Identifiable + 'static, B: Backend<T>> Iterator for Iter<T, B> {
+    type Item = Entry<T, B>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().map(|e| Entry(e))
+        self.inner
+            .by_ref()
+            .find(|slot| !B::meta(slot).is_free())
+            .map(Entry::new)
     }
 }
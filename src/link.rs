@@ -0,0 +1,48 @@
+use rustc_hash::FxHashSet;
+
+use super::{Backend, Entry, Error, Id, Identifiable, Reference};
+
+impl<D: Identifiable + 'static, B: Backend<D>> Reference<D, B> {
+    /// Loads dependent items referring to items of `target` in two phases.
+    ///
+    /// First entries of ids returned by `key` for `rows` are taken from `target` reserving
+    /// absent ones under a single index lock. Then `build` makes an item of each row
+    /// and its entry and the item gets inserted.
+    ///
+    /// Returns sorted ids which are still unresolved in `target` after that.
+    /// A failure to reserve is returned as `Error::Other`.
+    pub fn link<T, TB, R, K, F>(
+        &self,
+        target: &Reference<T, TB>,
+        rows: impl IntoIterator<Item = R>,
+        key: K,
+        mut build: F,
+    ) -> Result<Vec<Id<T>>, Error<D>>
+    where
+        T: Identifiable + 'static,
+        TB: Backend<T>,
+        K: Fn(&R) -> Id<T>,
+        F: FnMut(R, Entry<T, TB>) -> D,
+    {
+        let rows = rows.into_iter().collect::<Vec<_>>();
+        let ids = rows.iter().map(&key).collect::<Vec<_>>();
+
+        let entries = target
+            .reserve_many(ids.iter().copied())
+            .map_err(|err| Error::Other(Box::new(err)))?;
+
+        for (row, entry) in rows.into_iter().zip(entries) {
+            self.insert(build(row, entry))?;
+        }
+
+        let mut unresolved = ids
+            .into_iter()
+            .collect::<FxHashSet<_>>()
+            .into_iter()
+            .filter(|id| !target.contains_resolved(*id))
+            .collect::<Vec<_>>();
+
+        unresolved.sort_unstable_by_key(|id| id.as_i32());
+        Ok(unresolved)
+    }
+}
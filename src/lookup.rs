@@ -0,0 +1,72 @@
+//! Looking items up telling apart whether and why they're absent.
+
+use std::fmt;
+
+use super::{ArcSwapBackend, Backend, Entry, Id, Identifiable, Reference};
+
+/// A result of `Reference::lookup`.
+pub enum Lookup<T: 'static, B: Backend<T> = ArcSwapBackend<T>> {
+    /// There's an item with the id.
+    Resolved(Entry<T, B>),
+    /// There's a reservation with the id which hasn't been filled yet.
+    Reserved(Entry<T, B>),
+    /// There's nothing with the id and the source is known not to have it.
+    /// See `Reference::mark_missing`.
+    KnownMissing,
+    /// There's nothing with the id and it's unknown whether the source has it.
+    Unknown,
+}
+
+impl<T: 'static, B: Backend<T>> Lookup<T, B> {
+    /// Returns the entry of an item or a reservation.
+    pub fn entry(self) -> Option<Entry<T, B>> {
+        match self {
+            Self::Resolved(entry) | Self::Reserved(entry) => Some(entry),
+            Self::KnownMissing | Self::Unknown => None,
+        }
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        matches!(self, Self::Resolved(_))
+    }
+
+    pub fn is_reserved(&self) -> bool {
+        matches!(self, Self::Reserved(_))
+    }
+
+    pub fn is_known_missing(&self) -> bool {
+        matches!(self, Self::KnownMissing)
+    }
+}
+
+impl<T: 'static, B: Backend<T>> fmt::Debug for Lookup<T, B>
+where
+    B::Slot: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resolved(entry) => f.debug_tuple("Resolved").field(entry).finish(),
+            Self::Reserved(entry) => f.debug_tuple("Reserved").field(entry).finish(),
+            Self::KnownMissing => f.write_str("KnownMissing"),
+            Self::Unknown => f.write_str("Unknown"),
+        }
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Like `get` but tells items, reservations and misses of ids marked missing apart
+    /// without loading the item.
+    ///
+    /// A reservation may get filled and an item removed right after the lookup so the result
+    /// only tells the state at the moment.
+    pub fn lookup(&self, id: Id<T>) -> Lookup<T, B> {
+        match self.get(id) {
+            Some(entry) if B::peek(entry.slot, |maybe_item| maybe_item.is_some()) => {
+                Lookup::Resolved(entry)
+            }
+            Some(entry) => Lookup::Reserved(entry),
+            None if self.is_known_missing(id) => Lookup::KnownMissing,
+            None => Lookup::Unknown,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Maintenance which doesn't get in the way of lookups.
+//!
+//! Lookups by id only take the id index lock for reading. Maintenance which has to go over
+//! the whole index, e.g. shrinking it, builds a shadow copy holding the lock for reading too
+//! so lookups proceed while writers wait. The copy is then swapped in with the lock held for
+//! writing only as long as the swap takes. If a writer sneaks in between the two, the copy
+//! is stale and gets rebuilt.
+//!
+//! An index which can't make a shadow copy is shrunk in place blocking lookups meanwhile.
+//! In debug builds `Reference::assert_read_lock_free` catches that.
+
+use std::mem;
+
+use super::poison::INDEX_LOCK;
+#[cfg(debug_assertions)]
+use super::sync::Ordering as AtomicOrdering;
+use super::{Backend, Identifiable, Reference};
+
+/// Number of times a shadow copy is rebuilt after being outdated by concurrent writes
+/// before giving up.
+const SHADOW_ATTEMPTS: usize = 3;
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Shrinks the id index to fit the ids it has. Returns the approximate number of bytes
+    /// reclaimed. The index grows back on demand when more ids are added.
+    ///
+    /// Lookups are not blocked unless the index doesn't support shadow copies. See
+    /// `IdIndex::shrunk`. Returns zero if concurrent writes keep outdating the copy.
+    pub fn shrink_index_to_fit(&self) -> usize {
+        for _ in 0..SHADOW_ATTEMPTS {
+            let (shrunk, before, writes) = {
+                let vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+
+                match vids.shrunk() {
+                    Some(shrunk) => (shrunk, vids.memory_usage(), self.vids.writes()),
+                    None => {
+                        drop(vids);
+                        return self.shrink_index_in_place();
+                    }
+                }
+            };
+
+            let after = shrunk.memory_usage();
+
+            if after >= before {
+                return 0;
+            }
+
+            let prev = {
+                let mut vids = self.recovered_lock(self.vids.write(), INDEX_LOCK);
+
+                // Taking the guard has counted one write itself.
+                if self.vids.writes() != writes + 1 {
+                    continue;
+                }
+
+                mem::replace(&mut *vids, shrunk)
+            };
+
+            // The old index is deallocated with the lock released.
+            drop(prev);
+            return before - after;
+        }
+
+        0
+    }
+
+    fn shrink_index_in_place(&self) -> usize {
+        #[cfg(debug_assertions)]
+        let _blocking = BlockingMaintenance::enter(self);
+
+        let mut vids = self.recovered_lock(self.vids.write(), INDEX_LOCK);
+        let before = vids.memory_usage();
+        vids.shrink_to_fit();
+        before.saturating_sub(vids.memory_usage())
+    }
+
+    /// Panics if maintenance holding a lock needed by `get` for longer than a swap
+    /// is in progress. Available in debug builds to check the guarantee in tests.
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    pub fn assert_read_lock_free(&self) {
+        let blocking = self.blocking_maintenance.load(AtomicOrdering::SeqCst);
+
+        assert!(
+            blocking == 0,
+            "Lookups are blocked by {blocking} maintenance operations"
+        );
+    }
+}
+
+/// Marks maintenance blocking lookups while alive.
+#[cfg(debug_assertions)]
+struct BlockingMaintenance<'a, T: Identifiable + 'static, B: Backend<T>> {
+    reference: &'a Reference<T, B>,
+}
+
+#[cfg(debug_assertions)]
+impl<'a, T: Identifiable + 'static, B: Backend<T>> BlockingMaintenance<'a, T, B> {
+    fn enter(reference: &'a Reference<T, B>) -> Self {
+        reference
+            .blocking_maintenance
+            .fetch_add(1, AtomicOrdering::SeqCst);
+
+        Self { reference }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T: Identifiable + 'static, B: Backend<T>> Drop for BlockingMaintenance<'_, T, B> {
+    fn drop(&mut self) {
+        self.reference
+            .blocking_maintenance
+            .fetch_sub(1, AtomicOrdering::SeqCst);
+    }
+}
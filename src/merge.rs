@@ -0,0 +1,143 @@
+//! Merging changes of replicas written concurrently.
+//!
+//! Each replica stamps its change sets with a `Timestamp` which orders all changes across
+//! replicas. A reference remembers the timestamp of the latest change of each id including
+//! removals, so applying the same change sets in any order converges to the same state:
+//! the change with the greater timestamp wins. Entities needing field-level resolution
+//! instead may get a custom `Merge` strategy.
+
+use std::fmt;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use super::sync::MaybeSync;
+use super::{Backend, ChangeSet, DuplicateMode, Error, Id, Identifiable, Reference};
+
+pub(crate) const TIMESTAMPS_LOCK: &str = "timestamps";
+
+/// A logical time of a change, e.g. a Lamport clock, along with the replica making it
+/// to break ties. Timestamps are ordered by the counter first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    pub counter: u64,
+    pub replica: u32,
+}
+
+impl Timestamp {
+    pub fn new(counter: u64, replica: u32) -> Self {
+        Self { counter, replica }
+    }
+}
+
+/// A strategy of resolving an incoming item against the current one.
+pub trait Merge<T>: MaybeSync + fmt::Debug + 'static {
+    /// Returns the item to keep given the current and the incoming one with timestamps
+    /// of their latest changes. Must be commutative for replicas to converge.
+    fn merge(
+        &self,
+        current: &Arc<T>,
+        current_at: Timestamp,
+        incoming: &Arc<T>,
+        incoming_at: Timestamp,
+    ) -> Arc<T>;
+}
+
+/// Keeps the whole item with the greater timestamp. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LastWriterWins;
+
+impl<T> Merge<T> for LastWriterWins {
+    fn merge(
+        &self,
+        current: &Arc<T>,
+        current_at: Timestamp,
+        incoming: &Arc<T>,
+        incoming_at: Timestamp,
+    ) -> Arc<T> {
+        match incoming_at > current_at {
+            true => incoming.clone(),
+            false => current.clone(),
+        }
+    }
+}
+
+impl<T> ChangeSet<T> {
+    /// Stamps the changes for merging by `Reference::apply`.
+    pub fn with_timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets how `apply` resolves timestamped changes of items which already have
+    /// a timestamp. The default is `LastWriterWins`.
+    pub fn with_merge<M: Merge<T>>(mut self, merge: M) -> Self {
+        self.merge = Box::new(merge);
+        self
+    }
+
+    /// Returns the timestamp of the latest applied change of `id` or `None` if no
+    /// timestamped change of it has been applied.
+    pub fn timestamp(&self, id: Id<T>) -> Option<Timestamp> {
+        self.recovered_lock(self.timestamps.lock(), TIMESTAMPS_LOCK)
+            .get(&id)
+            .copied()
+    }
+
+    /// Applies changes stamped with `timestamp` skipping the ones older than what's there.
+    pub(crate) fn merge_changes(
+        &self,
+        changes: &ChangeSet<T>,
+        timestamp: Timestamp,
+    ) -> Result<(), Error<T>> {
+        // Held throughout so concurrent merges don't interleave between checks and writes.
+        let mut timestamps = self.recovered_lock(self.timestamps.lock(), TIMESTAMPS_LOCK);
+
+        for item in changes.added.iter().chain(&changes.updated) {
+            let id = item.id();
+
+            let item = match timestamps.get(&id) {
+                None => item.clone(),
+                Some(&current_at) => match self.get(id).and_then(|entry| entry.load()) {
+                    Some(current) => {
+                        let merged = self.merge.merge(&current, current_at, item, timestamp);
+
+                        if Arc::ptr_eq(&merged, &current) {
+                            advance(&mut timestamps, id, timestamp);
+                            continue;
+                        }
+
+                        merged
+                    }
+                    // Removed by a later change.
+                    None if current_at > timestamp => continue,
+                    None => item.clone(),
+                },
+            };
+
+            self.insert_arc(item, DuplicateMode::Replace)?;
+            advance(&mut timestamps, id, timestamp);
+        }
+
+        for id in &changes.removed {
+            if timestamps
+                .get(id)
+                .is_some_and(|current_at| *current_at > timestamp)
+            {
+                continue;
+            }
+
+            self.remove(*id);
+            advance(&mut timestamps, *id, timestamp);
+        }
+
+        Ok(())
+    }
+}
+
+fn advance<T>(timestamps: &mut FxHashMap<Id<T>, Timestamp>, id: Id<T>, timestamp: Timestamp) {
+    let current = timestamps.entry(id).or_insert(timestamp);
+    *current = (*current).max(timestamp);
+}
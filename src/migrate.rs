@@ -0,0 +1,61 @@
+//! Converting references to new versions of entity types.
+
+use super::poison::INDEX_LOCK;
+use super::{Backend, Error, Id, Identifiable, Reference};
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Builds a reference of `U` with the same capacity converting each item with `f`.
+    /// Reservations stay reservations and slots keep their order.
+    ///
+    /// Entries of the original reference keep referring to it so dependents holding them
+    /// have to be migrated too. Writes made during the migration may be missed.
+    ///
+    /// `f` must keep ids: an item converted to another id fails with `Error::InsertError`.
+    pub fn migrate<U, F>(&self, mut f: F) -> Result<Reference<U>, Error<U>>
+    where
+        U: Identifiable + 'static,
+        F: FnMut(&T) -> U,
+    {
+        let mut vids = self
+            .recovered_lock(self.vids.read(), INDEX_LOCK)
+            .iter()
+            .filter(|(_, vid)| *vid != 0)
+            .collect::<Vec<_>>();
+
+        vids.sort_unstable_by_key(|(_, vid)| *vid);
+
+        let migrated = Reference::new(self.items.capacity())
+            .with_duplicate_mode(self.duplicate_mode)
+            .with_poison_policy(self.poison_policy)
+            .with_utilization_warning_threshold(self.tunables().utilization_warning_threshold);
+
+        for (id, vid) in vids {
+            let new_id = Id::<U>::new(id.as_i32());
+
+            // The slot could have been reused for another id after collecting vids.
+            let maybe_item = self
+                .items
+                .slot(vid as usize)
+                .and_then(B::load)
+                .filter(|item| item.id() == id);
+
+            let Some(item) = maybe_item else {
+                migrated.get_or_reserve(new_id)?;
+                continue;
+            };
+
+            let new_item = f(&item);
+
+            if new_item.id() != new_id {
+                return Err(Error::InsertError(format!(
+                    "Migration has changed id {id} to {}",
+                    new_item.id()
+                )));
+            }
+
+            migrated.upsert(new_item)?;
+        }
+
+        Ok(migrated)
+    }
+}
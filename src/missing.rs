@@ -0,0 +1,58 @@
+//! Negative caching of ids known to be missing from the source.
+
+use std::time::Duration;
+
+use super::{Backend, Id, Identifiable, Reference};
+
+pub(crate) const MISSING_LOCK: &str = "missing ids";
+
+/// Negative caching.
+///
+/// Ids the source has confirmed not to have may be marked missing for a while so `lookup`
+/// reports misses of them as `Lookup::KnownMissing` and the caller doesn't query the source
+/// again.
+/// A mark is dropped once it expires or the id gets added. Expiration times are taken from
+/// the reference's clock. See `with_clock`.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Marks `id` as missing from the source for `ttl`.
+    pub fn mark_missing(&self, id: Id<T>, ttl: Duration) {
+        let now = self.now();
+        let mut missing = self.recovered_lock(self.missing.lock(), MISSING_LOCK);
+
+        // Dropping expired marks each time the number doubles keeps it bounded by twice
+        // the number of live marks at amortized constant cost.
+        if missing.len().is_power_of_two() {
+            missing.retain(|_, expires_at| *expires_at > now);
+        }
+
+        missing.insert(id, now + ttl);
+    }
+
+    /// Drops the mark of `id`. Returns `false` if it wasn't marked missing.
+    pub fn unmark_missing(&self, id: Id<T>) -> bool {
+        self.recovered_lock(self.missing.lock(), MISSING_LOCK)
+            .remove(&id)
+            .is_some()
+    }
+
+    /// Tells whether `id` is marked missing and the mark hasn't expired yet.
+    pub fn is_known_missing(&self, id: Id<T>) -> bool {
+        let now = self.now();
+        let mut missing = self.recovered_lock(self.missing.lock(), MISSING_LOCK);
+
+        match missing.get(&id) {
+            Some(expires_at) if *expires_at > now => true,
+            Some(_) => {
+                missing.remove(&id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Drops the mark of an added id.
+    pub(crate) fn forget_missing(&self, id: Id<T>) {
+        self.recovered_lock(self.missing.lock(), MISSING_LOCK)
+            .remove(&id);
+    }
+}
@@ -0,0 +1,217 @@
+//! A stub of a reference for unit tests of code depending on `Referential`.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rustc_hash::FxHashMap;
+
+use super::sync::MaybeSync;
+use super::{Error, Id, Identifiable, Referential};
+
+/// A call made to a `MockReference`.
+pub enum MockCall<T> {
+    Load(Id<T>),
+    Contains(Id<T>),
+    Upsert(Id<T>),
+    Remove(Id<T>),
+}
+
+impl<T> MockCall<T> {
+    fn parts(&self) -> (&'static str, Id<T>) {
+        match self {
+            Self::Load(id) => ("Load", *id),
+            Self::Contains(id) => ("Contains", *id),
+            Self::Upsert(id) => ("Upsert", *id),
+            Self::Remove(id) => ("Remove", *id),
+        }
+    }
+}
+
+impl<T> Clone for MockCall<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for MockCall<T> {}
+
+impl<T> PartialEq for MockCall<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.parts() == other.parts()
+    }
+}
+
+impl<T> Eq for MockCall<T> {}
+
+impl<T> fmt::Debug for MockCall<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, id) = self.parts();
+        f.debug_tuple(name).field(&id).finish()
+    }
+}
+
+/// A map-backed `Referential` recording calls made to it.
+///
+/// ```
+/// # use reference::testing::{MockCall, MockReference};
+/// # use reference::{Id, Identifiable, Referential};
+/// # #[derive(Debug)]
+/// # struct Foo {
+/// #     id: Id<Self>,
+/// # }
+/// #
+/// # impl Identifiable for Foo {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+/// fn exists(reference: &impl Referential<Foo>, id: Id<Foo>) -> bool {
+///     reference.load(id).is_some()
+/// }
+///
+/// let mock = MockReference::new().with_items([Foo { id: 1.into() }]);
+///
+/// assert!(exists(&mock, 1.into()));
+/// assert!(!exists(&mock, 2.into()));
+/// assert_eq!(mock.calls(), [MockCall::Load(1.into()), MockCall::Load(2.into())]);
+/// ```
+pub struct MockReference<T> {
+    state: Mutex<State<T>>,
+    responder: Option<Box<dyn Responder<T>>>,
+    upsert_error: Option<String>,
+}
+
+struct State<T> {
+    items: FxHashMap<Id<T>, Arc<T>>,
+    calls: Vec<MockCall<T>>,
+}
+
+impl<T: Identifiable + 'static> MockReference<T> {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                items: FxHashMap::default(),
+                calls: Vec::new(),
+            }),
+            responder: None,
+            upsert_error: None,
+        }
+    }
+
+    /// Adds `items` without recording calls.
+    pub fn with_items(self, items: impl IntoIterator<Item = T>) -> Self {
+        self.state()
+            .items
+            .extend(items.into_iter().map(|item| (item.id(), Arc::new(item))));
+
+        self
+    }
+
+    /// Makes loads of ids missing from the stored items return what `responder` returns.
+    pub fn with_responder<F>(mut self, responder: F) -> Self
+    where
+        F: Fn(Id<T>) -> Option<T> + MaybeSync + 'static,
+    {
+        self.responder = Some(Box::new(responder));
+        self
+    }
+
+    /// Makes all upserts fail with `Error::InsertError` of `message`.
+    pub fn with_failing_upserts(mut self, message: impl Into<String>) -> Self {
+        self.upsert_error = Some(message.into());
+        self
+    }
+
+    /// Returns the calls made so far in order.
+    pub fn calls(&self) -> Vec<MockCall<T>> {
+        self.state().calls.clone()
+    }
+
+    /// Returns how many times `call` has been made.
+    pub fn count(&self, call: MockCall<T>) -> usize {
+        self.state().calls.iter().filter(|c| **c == call).count()
+    }
+
+    /// Returns ids passed to `load` in order.
+    pub fn loaded_ids(&self) -> Vec<Id<T>> {
+        self.state()
+            .calls
+            .iter()
+            .filter_map(|call| match call {
+                MockCall::Load(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Forgets the calls made so far.
+    pub fn clear_calls(&self) {
+        self.state().calls.clear();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn record(&self, call: MockCall<T>) -> MutexGuard<'_, State<T>> {
+        let mut state = self.state();
+        state.calls.push(call);
+        state
+    }
+}
+
+impl<T: Identifiable + 'static> Default for MockReference<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Identifiable + 'static> Referential<T> for MockReference<T> {
+    fn load(&self, id: Id<T>) -> Option<Arc<T>> {
+        let maybe_item = self.record(MockCall::Load(id)).items.get(&id).cloned();
+
+        match (maybe_item, &self.responder) {
+            (Some(item), _) => Some(item),
+            (None, Some(responder)) => responder.respond(id).map(Arc::new),
+            (None, None) => None,
+        }
+    }
+
+    fn contains(&self, id: Id<T>) -> bool {
+        self.record(MockCall::Contains(id)).items.contains_key(&id)
+    }
+
+    fn upsert(&self, item: T) -> Result<(), Error<T>> {
+        let mut state = self.record(MockCall::Upsert(item.id()));
+
+        if let Some(message) = &self.upsert_error {
+            return Err(Error::InsertError(message.clone()));
+        }
+
+        state.items.insert(item.id(), Arc::new(item));
+        Ok(())
+    }
+
+    fn remove(&self, id: Id<T>) -> Option<Arc<T>> {
+        self.record(MockCall::Remove(id)).items.remove(&id)
+    }
+}
+
+impl<T> fmt::Debug for MockReference<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockReference").finish_non_exhaustive()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A load responder type-erased for keeping in the mock.
+trait Responder<T>: MaybeSync {
+    fn respond(&self, id: Id<T>) -> Option<T>;
+}
+
+impl<T, F: Fn(Id<T>) -> Option<T> + MaybeSync> Responder<T> for F {
+    fn respond(&self, id: Id<T>) -> Option<T> {
+        self(id)
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use super::{Backend, Id, Identifiable, Reference};
+
+/// A copy-on-write view of a `Reference`. See `Reference::overlay`.
+///
+/// Changes made through the overlay are kept locally and shadow items of the base
+/// which remains unchanged. Unchanged items are read from the base so they reflect
+/// its concurrent changes.
+pub struct Overlay<'a, T: Identifiable + 'static, B: Backend<T>> {
+    base: &'a Reference<T, B>,
+    /// Local changes where `None` marks a removed item.
+    changes: FxHashMap<Id<T>, Option<Arc<T>>>,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Overlay<'_, T, B> {
+    /// Returns the item with the given `id` either changed locally or taken from the base.
+    pub fn get(&self, id: Id<T>) -> Option<Arc<T>> {
+        match self.changes.get(&id) {
+            Some(maybe_item) => maybe_item.clone(),
+            None => self.base.get(id).and_then(|entry| entry.load()),
+        }
+    }
+
+    /// Tells whether there's an item with the given `id` in the view.
+    pub fn contains(&self, id: Id<T>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Inserts or replaces an item locally and returns the previous one.
+    pub fn insert(&mut self, item: T) -> Option<Arc<T>> {
+        let maybe_prev = self.get(item.id());
+        self.changes.insert(item.id(), Some(Arc::new(item)));
+        maybe_prev
+    }
+
+    /// Removes an item locally and returns it.
+    pub fn remove(&mut self, id: Id<T>) -> Option<Arc<T>> {
+        let maybe_prev = self.get(id);
+        self.changes.insert(id, None);
+        maybe_prev
+    }
+
+    /// Drops local changes of the item with the given `id` so it's read from the base again.
+    pub fn reset(&mut self, id: Id<T>) {
+        self.changes.remove(&id);
+    }
+
+    /// Returns `true` if there are no local changes.
+    pub fn is_pristine(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Iterates over items of the view: unchanged items of the base first
+    /// and then items inserted locally in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = Arc<T>> + '_ {
+        let unchanged = self
+            .base
+            .iter()
+            .filter_map(|entry| entry.load())
+            .filter(|item| !self.changes.contains_key(&item.id()));
+
+        unchanged.chain(self.changes.values().flatten().cloned())
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> fmt::Debug for Overlay<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Overlay")
+            .field("changes", &self.changes.len())
+            .finish()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Creates a copy-on-write view of the reference for local changes which don't affect
+    /// the reference itself, e.g. in tests or simulations. Creating it copies nothing.
+    pub fn overlay(&self) -> Overlay<'_, T, B> {
+        Overlay {
+            base: self,
+            changes: FxHashMap::default(),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::error::Error as StdError;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use rustc_hash::FxHashSet;
+
+#[cfg(feature = "testing")]
+use super::fault;
+use super::poison::{FREE_LIST_LOCK, INDEX_LOCK};
+use super::{Backend, CapacityPolicy, DuplicateMode, Error, IdIndex, Identifiable, Reference};
+
+impl<T, B> Reference<T, B>
+where
+    T: Identifiable + Send + 'static,
+    B: Backend<T>,
+{
+    /// Bulk loading with parsing spread across the `rayon` thread pool. `parse` constructs
+    /// items of `sources` in parallel. Then the items are inserted in the order of `sources`
+    /// under a single index lock like `insert` would do. Returns the number of stored items
+    /// which excludes those skipped as unchanged. See `with_skip_unchanged`.
+    ///
+    /// Nothing gets inserted if `parse` fails for some source, the error of the first such
+    /// source is returned as `Error::Other`, or if the batch doesn't fit after evicting
+    /// according to the capacity policy or has a duplicate rejected by the duplicate mode.
+    pub fn par_load<S, F, E>(&self, sources: Vec<S>, parse: F) -> Result<usize, Error<T>>
+    where
+        S: Send,
+        F: Fn(S) -> Result<T, E> + Sync + Send,
+        E: Into<Box<dyn StdError + Send + Sync>> + Send,
+    {
+        let items = sources
+            .into_par_iter()
+            .map(parse)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect::<Result<Vec<_>, E>>()
+            .map_err(|err| Error::Other(err.into()))?;
+
+        self.check_writable()?;
+
+        #[cfg(feature = "testing")]
+        for _ in &items {
+            self.check_fault(fault::Call::Insert)?;
+        }
+
+        let mut vids = self.checked_lock(self.vids.write(), INDEX_LOCK)?;
+        let lacking = self.lacking_slots(&**vids, &items)?;
+
+        if lacking > 0 && self.capacity_policy == CapacityPolicy::EvictLeastRecentlyUsed {
+            // Eviction takes the index lock itself.
+            drop(vids);
+
+            for _ in 0..lacking {
+                self.evict();
+            }
+
+            vids = self.checked_lock(self.vids.write(), INDEX_LOCK)?;
+        }
+
+        let lacking = self.lacking_slots(&**vids, &items)?;
+
+        if lacking > 0 {
+            return Err(Error::InsertError(format!(
+                "Failed to load {} items: {lacking} more free slots are needed",
+                items.len()
+            )));
+        }
+
+        let mut stored = Vec::with_capacity(items.len());
+        let mut result = Ok(());
+
+        for item in items {
+            if self.duplicate_mode == DuplicateMode::Replace {
+                if let Some(vid) = vids.get(item.id()) {
+                    if self.skip_if_unchanged(&self.entry(vid)?, &item) {
+                        continue;
+                    }
+                }
+            }
+
+            let item = Arc::new(item);
+
+            match self.add_locked(
+                &mut vids,
+                item.id(),
+                Some(item.clone()),
+                self.duplicate_mode,
+            ) {
+                Ok((entry, maybe_prev)) => stored.push((item, entry, maybe_prev)),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+
+        drop(vids);
+
+        // Indexes and hooks are updated outside of the index lock as `insert` does. Items
+        // stored before an unexpected failure are inserted completely.
+        for (item, entry, maybe_prev) in &stored {
+            self.update_indexes(entry, maybe_prev.as_ref());
+            entry.swapped(Some(item), maybe_prev.as_ref());
+        }
+
+        result.map(|()| stored.len())
+    }
+
+    /// Returns how many more free slots `items` need to be added. Fails if some of them
+    /// are rejected as duplicates.
+    fn lacking_slots(&self, vids: &dyn IdIndex<T>, items: &[T]) -> Result<usize, Error<T>> {
+        let mut seen = FxHashSet::default();
+        let mut new = 0;
+
+        for item in items {
+            let id = item.id();
+            let is_repeated = !seen.insert(id);
+
+            let is_resolved = match vids.get(id) {
+                Some(vid) => self.entry(vid)?.load().is_some(),
+                None => {
+                    new += usize::from(!is_repeated);
+                    false
+                }
+            };
+
+            if self.duplicate_mode == DuplicateMode::Reject && (is_repeated || is_resolved) {
+                return Err(Error::DuplicateId(id));
+            }
+        }
+
+        let free = self.items.capacity() - self.items.len()
+            + self
+                .checked_lock(self.free_vids.lock(), FREE_LIST_LOCK)?
+                .len();
+
+        Ok(new.saturating_sub(free))
+    }
+}
@@ -0,0 +1,49 @@
+//! Exempting critical items from automatic removal.
+
+use rustc_hash::FxHashSet;
+
+use super::poison::INDEX_LOCK;
+use super::{Backend, Id, Identifiable, Reference};
+
+pub(crate) const PINS_LOCK: &str = "pins";
+
+/// Pinning.
+///
+/// A pinned item, e.g. a default one other items fall back to, is never evicted, collected
+/// by `graph::Registry::collect_garbage` or reported by `stale_ids`. It may still be removed
+/// explicitly which unpins it.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Pins the item or reservation with `id`. Returns `false` if there's no such id.
+    pub fn pin(&self, id: Id<T>) -> bool {
+        // Holding the index lock keeps the id from being removed until it's pinned.
+        let vids = self.recovered_lock(self.vids.read(), INDEX_LOCK);
+
+        if vids.get(id).is_none() {
+            return false;
+        }
+
+        self.recovered_lock(self.pins.write(), PINS_LOCK).insert(id);
+        true
+    }
+
+    /// Unpins the item with `id`. Returns `false` if it wasn't pinned.
+    pub fn unpin(&self, id: Id<T>) -> bool {
+        self.recovered_lock(self.pins.write(), PINS_LOCK)
+            .remove(&id)
+    }
+
+    pub fn is_pinned(&self, id: Id<T>) -> bool {
+        self.recovered_lock(self.pins.read(), PINS_LOCK)
+            .contains(&id)
+    }
+
+    /// Returns the number of pinned items.
+    pub(crate) fn pinned_count(&self) -> usize {
+        self.recovered_lock(self.pins.read(), PINS_LOCK).len()
+    }
+
+    /// Returns pinned ids for checking many items at once.
+    pub(crate) fn pinned_ids(&self) -> FxHashSet<Id<T>> {
+        self.recovered_lock(self.pins.read(), PINS_LOCK).clone()
+    }
+}
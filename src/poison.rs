@@ -0,0 +1,56 @@
+use std::any::type_name;
+
+use super::sync::LockResult;
+use super::{Backend, Error, Identifiable, Reference};
+
+pub(crate) const INDEX_LOCK: &str = "index";
+pub(crate) const FREE_LIST_LOCK: &str = "free list";
+
+/// What to do when an internal lock turns out to be poisoned by a panic in another thread.
+///
+/// Only `std-sync` locks get poisoned; `parking_lot` ones never do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Panic as well.
+    #[default]
+    Panic,
+    /// Return `Error::LockPoisoned` from methods returning `Result`.
+    /// Other methods ignore poisoning and go on with the data as is.
+    Propagate,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets the behavior on poisoned locks. The default is `PoisonPolicy::Panic`.
+    pub fn with_poison_policy(mut self, policy: PoisonPolicy) -> Self {
+        self.poison_policy = policy;
+        self
+    }
+
+    /// Applies the policy to a lock acquisition result in a method returning `Result`.
+    pub(crate) fn checked_lock<G>(
+        &self,
+        result: LockResult<G>,
+        lock: &'static str,
+    ) -> Result<G, Error<T>> {
+        result.map_err(|_| match self.poison_policy {
+            PoisonPolicy::Panic => panic_poisoned::<T>(lock),
+            PoisonPolicy::Propagate => Error::LockPoisoned(lock),
+        })
+    }
+
+    /// Applies the policy to a lock acquisition result in a method which can't fail.
+    pub(crate) fn recovered_lock<G>(&self, result: LockResult<G>, lock: &'static str) -> G {
+        result.unwrap_or_else(|err| match self.poison_policy {
+            PoisonPolicy::Panic => panic_poisoned::<T>(lock),
+            PoisonPolicy::Propagate => err.into_inner(),
+        })
+    }
+}
+
+fn panic_poisoned<T>(lock: &'static str) -> ! {
+    panic!(
+        "The {} lock of reference of {} is poisoned",
+        lock,
+        type_name::<T>()
+    )
+}
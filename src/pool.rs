@@ -0,0 +1,77 @@
+use std::fmt;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use super::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Maximum number of replaced `Arc`s kept for reuse.
+pub const POOL_CAPACITY: usize = 64;
+
+/// Keeps replaced `Arc<T>` allocations to reuse them for new values once they're not shared.
+/// The lock gets recovered if poisoned since the pool holds nothing but spare allocations.
+pub struct Pool<T> {
+    arcs: Mutex<Vec<Arc<T>>>,
+}
+
+impl<T> Pool<T> {
+    /// Size of an `Arc<T>` allocation: reference counters and the value.
+    const ARC_SIZE: usize = 2 * size_of::<usize>() + size_of::<T>();
+
+    pub fn new() -> Self {
+        Self {
+            arcs: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Wraps `item` into an `Arc` reusing a pooled allocation which is not referred
+    /// by anyone else. Allocates a new one if there's no such allocation.
+    pub fn wrap(&self, item: T) -> Arc<T> {
+        let mut arcs = self.lock();
+
+        match arcs.iter_mut().position(|arc| Arc::get_mut(arc).is_some()) {
+            Some(idx) => {
+                let mut arc = arcs.swap_remove(idx);
+                drop(arcs);
+                *Arc::get_mut(&mut arc).expect("Pooled Arc got shared") = item;
+                arc
+            }
+            None => Arc::new(item),
+        }
+    }
+
+    /// Puts a replaced `Arc` to the pool unless it's full.
+    /// It may still be shared by readers; it gets reused only after they drop it.
+    pub fn put(&self, arc: Arc<T>) {
+        let mut arcs = self.lock();
+
+        if arcs.len() < POOL_CAPACITY {
+            arcs.push(arc);
+        }
+    }
+
+    /// Drops pooled allocations and returns the approximate number of bytes freed.
+    /// Allocations still shared by readers get freed later when they drop them.
+    pub fn clear(&self) -> usize {
+        let arcs = std::mem::take(&mut *self.lock());
+
+        arcs.iter()
+            .filter(|arc| Arc::strong_count(arc) == 1)
+            .count()
+            * Self::ARC_SIZE
+    }
+
+    /// Returns the number of times the pool lock had to wait.
+    pub fn contentions(&self) -> usize {
+        self.arcs.contentions()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Arc<T>>> {
+        self.arcs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> fmt::Debug for Pool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool").finish()
+    }
+}
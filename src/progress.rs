@@ -0,0 +1,102 @@
+//! Reporting progress of bulk loads, e.g. on a health endpoint while hydrating on startup.
+
+use std::time::{Duration, Instant};
+
+/// Default number of items processed between progress reports.
+pub const DEFAULT_PROGRESS_EVERY: usize = 10_000;
+
+/// Progress of a bulk load passed to a progress callback.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// Number of items processed so far including failed ones.
+    pub processed: usize,
+    /// Number of items to process if known.
+    pub total: Option<usize>,
+    /// Time since the load has started by the reference's clock.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Returns the fraction of items processed if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total? {
+            0 => Some(1.0),
+            total => Some((self.processed as f64 / total as f64).min(1.0)),
+        }
+    }
+
+    /// Returns the average number of items processed per second.
+    pub fn rate(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.processed as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Estimates the time left at the average rate if the total is known.
+    pub fn eta(&self) -> Option<Duration> {
+        let left = self.total?.saturating_sub(self.processed);
+
+        match (left, self.rate()) {
+            (0, _) => Some(Duration::ZERO),
+            (_, rate) if rate > 0.0 => Some(Duration::from_secs_f64(left as f64 / rate)),
+            _ => None,
+        }
+    }
+}
+
+/// Counts processed items and calls back every `every` of them and once done.
+pub(crate) struct ProgressMeter<F> {
+    started_at: Instant,
+    total: Option<usize>,
+    processed: usize,
+    reported: Option<usize>,
+    every: usize,
+    callback: F,
+}
+
+impl<F: FnMut(&Progress)> ProgressMeter<F> {
+    pub(crate) fn new(
+        started_at: Instant,
+        total: Option<usize>,
+        every: usize,
+        callback: F,
+    ) -> Self {
+        Self {
+            started_at,
+            total,
+            processed: 0,
+            reported: None,
+            every: every.max(1),
+            callback,
+        }
+    }
+
+    /// Counts `count` more items reporting if a multiple of `every` has been passed.
+    pub(crate) fn advance(&mut self, count: usize, now: Instant) {
+        let before = self.processed / self.every;
+        self.processed += count;
+
+        if self.processed / self.every > before {
+            self.report(now);
+        }
+    }
+
+    /// Reports the final progress unless it's been reported already.
+    pub(crate) fn finish(mut self, now: Instant) {
+        if self.reported != Some(self.processed) {
+            self.report(now);
+        }
+    }
+
+    fn report(&mut self, now: Instant) {
+        self.reported = Some(self.processed);
+
+        (self.callback)(&Progress {
+            processed: self.processed,
+            total: self.total,
+            elapsed: now.saturating_duration_since(self.started_at),
+        });
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+use std::sync::{Arc, Weak};
+
+use super::sync::{Mutex, MutexGuard, PoisonError};
+use super::{ArcSwapBackend, Backend, Entry};
+
+/// A handle to one field of the item referred by an entry.
+///
+/// ```
+/// # use reference::{Id, Identifiable, Reference};
+/// #
+/// struct Product {
+///     id: Id<Self>,
+///     description: String,
+///     price: u64,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// let products = Reference::new(2);
+///
+/// let entry = products
+///     .insert(Product {
+///         id: 1.into(),
+///         description: "Very long description".to_string(),
+///         price: 100,
+///     })
+///     .unwrap();
+///
+/// let price = entry.map(|product: &Product| &product.price);
+/// assert_eq!(*price.load().unwrap(), 100);
+/// ```
+///
+/// The projected value is cloned once per version of the item and shared by subsequent loads
+/// until the item gets replaced.
+pub struct Projection<T: 'static, U, B: Backend<T> = ArcSwapBackend<T>> {
+    entry: Entry<T, B>,
+    project: fn(&T) -> &U,
+    cache: Mutex<Option<Cached<T, U>>>,
+}
+
+/// A projected value along with the version of the item it was taken from.
+/// The version is identified by the item's allocation. Holding a `Weak` keeps the allocation
+/// from being freed or reused by `Pool` so a new version can't get the same address.
+struct Cached<T, U> {
+    item: Weak<T>,
+    value: Arc<U>,
+}
+
+impl<T: 'static, B: Backend<T>> Entry<T, B> {
+    /// Makes a handle to the part of the item returned by `project`.
+    pub fn map<U>(self, project: fn(&T) -> &U) -> Projection<T, U, B> {
+        Projection {
+            entry: self,
+            project,
+            cache: Mutex::new(None),
+        }
+    }
+}
+
+impl<T: 'static, U: Clone, B: Backend<T>> Projection<T, U, B> {
+    /// Returns the projected value of the current item or `None` if the entry is empty or stale.
+    pub fn load(&self) -> Option<Arc<U>> {
+        let item = self.entry.load()?;
+        let mut cache = self.lock();
+
+        if let Some(cached) = &*cache {
+            if std::ptr::eq(cached.item.as_ptr(), Arc::as_ptr(&item)) {
+                return Some(cached.value.clone());
+            }
+        }
+
+        let value = Arc::new((self.project)(&item).clone());
+
+        *cache = Some(Cached {
+            item: Arc::downgrade(&item),
+            value: value.clone(),
+        });
+
+        Some(value)
+    }
+}
+
+impl<T: 'static, U, B: Backend<T>> Projection<T, U, B> {
+    pub fn entry(&self) -> &Entry<T, B> {
+        &self.entry
+    }
+
+    /// The lock gets recovered if poisoned since the cache is replaced as a whole.
+    fn lock(&self) -> MutexGuard<'_, Option<Cached<T, U>>> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: 'static, U, B: Backend<T>> fmt::Debug for Projection<T, U, B>
+where
+    B::Slot: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Projection").field(&self.entry).finish()
+    }
+}
@@ -0,0 +1,75 @@
+//! Recording which load has produced the current values of slots.
+//!
+//! With provenance enabled writes to a reference are attributed to its current batch.
+//! Reloads by `replace_all`, `refresh` and `load_stream` start a new batch each so when
+//! a wrong value shows up `Entry::provenance` tells which of them has introduced it.
+//! Changes made through entries, e.g. by `Entry::modify`, don't belong to any batch.
+
+use std::sync::atomic::AtomicUsize as StdAtomicUsize;
+use std::time::Instant;
+
+use super::sync::Ordering as AtomicOrdering;
+use super::{Backend, Entry, Identifiable, Reference};
+
+/// Batch ids are unique across all references of the process so a batch id also tells
+/// which reference the value comes from. Zero is reserved for no batch.
+static NEXT_BATCH: StdAtomicUsize = StdAtomicUsize::new(1);
+
+/// Origin of the current value of a slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Provenance {
+    /// The batch which has set the value. See `Reference::begin_batch`.
+    pub batch: usize,
+    /// When the value has been set.
+    pub updated_at: Instant,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Enables provenance recording and starts the first batch.
+    pub fn with_provenance(self) -> Self {
+        self.batch.store(next_batch(), AtomicOrdering::Relaxed);
+        self
+    }
+
+    /// Starts a new batch which following writes are attributed to and returns its id.
+    /// Returns `None` if provenance isn't enabled.
+    pub fn begin_batch(&self) -> Option<usize> {
+        self.batch()?;
+        let batch = next_batch();
+        self.batch.store(batch, AtomicOrdering::Relaxed);
+        Some(batch)
+    }
+
+    /// Returns the current batch or `None` if provenance isn't enabled.
+    pub fn batch(&self) -> Option<usize> {
+        match self.batch_id() {
+            0 => None,
+            batch => Some(batch),
+        }
+    }
+
+    /// Returns the current batch or zero if provenance isn't enabled.
+    pub(crate) fn batch_id(&self) -> usize {
+        self.batch.load(AtomicOrdering::Relaxed)
+    }
+}
+
+impl<T: 'static, B: Backend<T>> Entry<T, B> {
+    /// Returns the origin of the current value or `None` if it has been set outside of any
+    /// batch, the entry is a reservation or stale.
+    pub fn provenance(&self) -> Option<Provenance> {
+        let meta = B::meta(self.slot);
+        let batch = meta.batch()?;
+        let updated_at = meta.updated_at()?;
+
+        match self.is_stale() {
+            true => None,
+            false => Some(Provenance { batch, updated_at }),
+        }
+    }
+}
+
+fn next_batch() -> usize {
+    NEXT_BATCH.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+}
@@ -0,0 +1,218 @@
+//! Python bindings for inspecting references from an embedded interpreter.
+//!
+//! The Rust side wraps a shared reference into a `PyReference` along with a function
+//! converting items to Python objects and passes it to the interpreter, e.g. as a global.
+//! In Python it behaves like a read-only dict keyed by ids:
+//!
+//! ```python
+//! product = products[42]
+//! names = [p["name"] for p in products.values() if p["active"]]
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::{PyIterator, PyList};
+
+use super::{Backend, Entry, Identifiable, Reference};
+
+type Converted = PyResult<Py<PyAny>>;
+
+/// A read-only dict-like view of a reference in Python.
+#[pyclass(name = "Reference", frozen)]
+pub struct PyReference {
+    inner: Box<dyn ErasedReference>,
+}
+
+impl PyReference {
+    /// Wraps `reference` where `to_py` converts items to Python objects.
+    pub fn new<T, B, F>(reference: Arc<Reference<T, B>>, to_py: F) -> Self
+    where
+        T: Identifiable + 'static,
+        B: Backend<T>,
+        F: Fn(Python<'_>, &T) -> Converted + Send + Sync + 'static,
+        Reference<T, B>: Send + Sync,
+        Entry<T, B>: Send + Sync,
+    {
+        let erased = ErasedReferenceImpl {
+            reference,
+            to_py: Arc::new(to_py),
+        };
+
+        Self {
+            inner: Box::new(erased),
+        }
+    }
+}
+
+#[pymethods]
+impl PyReference {
+    fn __getitem__(&self, py: Python<'_>, id: i32) -> Converted {
+        match self.inner.get(py, id)? {
+            Some(item) => Ok(item),
+            None => Err(PyKeyError::new_err(id)),
+        }
+    }
+
+    fn __contains__(&self, py: Python<'_>, id: i32) -> PyResult<bool> {
+        Ok(self.inner.get(py, id)?.is_some())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.ids().len()
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyList::new(py, self.inner.ids())?.try_iter()
+    }
+
+    #[pyo3(signature = (id, default = None))]
+    fn get(
+        &self,
+        py: Python<'_>,
+        id: i32,
+        default: Option<Py<PyAny>>,
+    ) -> PyResult<Option<Py<PyAny>>> {
+        Ok(self.inner.get(py, id)?.or(default))
+    }
+
+    fn keys(&self) -> Vec<i32> {
+        self.inner.ids()
+    }
+
+    fn values(&self, py: Python<'_>) -> PyResult<Vec<Py<PyAny>>> {
+        let items = self.inner.items(py)?;
+        Ok(items.into_iter().map(|(_, item)| item).collect())
+    }
+
+    fn items(&self, py: Python<'_>) -> PyResult<Vec<(i32, Py<PyAny>)>> {
+        self.inner.items(py)
+    }
+
+    /// Returns an entry which follows changes of the item or `None` if there's no such item.
+    fn entry(&self, id: i32) -> Option<PyEntry> {
+        self.inner.entry(id)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Reference of {} items>", self.inner.ids().len())
+    }
+}
+
+impl fmt::Debug for PyReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PyReference").finish()
+    }
+}
+
+/// An entry of a reference in Python.
+#[pyclass(name = "Entry", frozen)]
+pub struct PyEntry {
+    inner: Box<dyn ErasedEntry>,
+}
+
+#[pymethods]
+impl PyEntry {
+    /// Returns the current item or `None` if it has been removed.
+    fn load(&self, py: Python<'_>) -> PyResult<Option<Py<PyAny>>> {
+        self.inner.load(py)
+    }
+
+    fn is_stale(&self) -> bool {
+        self.inner.is_stale()
+    }
+}
+
+impl fmt::Debug for PyEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PyEntry").finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+trait ErasedReference: Send + Sync {
+    fn get(&self, py: Python<'_>, id: i32) -> PyResult<Option<Py<PyAny>>>;
+    fn ids(&self) -> Vec<i32>;
+    fn items(&self, py: Python<'_>) -> PyResult<Vec<(i32, Py<PyAny>)>>;
+    fn entry(&self, id: i32) -> Option<PyEntry>;
+}
+
+trait ErasedEntry: Send + Sync {
+    fn load(&self, py: Python<'_>) -> PyResult<Option<Py<PyAny>>>;
+    fn is_stale(&self) -> bool;
+}
+
+type ToPy<T> = Arc<dyn Fn(Python<'_>, &T) -> Converted + Send + Sync>;
+
+struct ErasedReferenceImpl<T: Identifiable + 'static, B: Backend<T>> {
+    reference: Arc<Reference<T, B>>,
+    to_py: ToPy<T>,
+}
+
+impl<T, B> ErasedReference for ErasedReferenceImpl<T, B>
+where
+    T: Identifiable + 'static,
+    B: Backend<T>,
+    Reference<T, B>: Send + Sync,
+    Entry<T, B>: Send + Sync,
+{
+    fn get(&self, py: Python<'_>, id: i32) -> PyResult<Option<Py<PyAny>>> {
+        match self.reference.get(id.into()).and_then(|entry| entry.load()) {
+            Some(item) => (self.to_py)(py, &item).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn ids(&self) -> Vec<i32> {
+        self.reference
+            .iter()
+            .filter_map(|entry| entry.load())
+            .map(|item| item.id().as_i32())
+            .collect()
+    }
+
+    fn items(&self, py: Python<'_>) -> PyResult<Vec<(i32, Py<PyAny>)>> {
+        self.reference
+            .iter()
+            .filter_map(|entry| entry.load())
+            .map(|item| Ok((item.id().as_i32(), (self.to_py)(py, &item)?)))
+            .collect()
+    }
+
+    fn entry(&self, id: i32) -> Option<PyEntry> {
+        let erased = ErasedEntryImpl {
+            entry: self.reference.get(id.into())?,
+            to_py: self.to_py.clone(),
+        };
+
+        Some(PyEntry {
+            inner: Box::new(erased),
+        })
+    }
+}
+
+struct ErasedEntryImpl<T: 'static, B: Backend<T>> {
+    entry: Entry<T, B>,
+    to_py: ToPy<T>,
+}
+
+impl<T, B> ErasedEntry for ErasedEntryImpl<T, B>
+where
+    T: Identifiable + 'static,
+    B: Backend<T>,
+    Entry<T, B>: Send + Sync,
+{
+    fn load(&self, py: Python<'_>) -> PyResult<Option<Py<PyAny>>> {
+        match self.entry.load() {
+            Some(item) => (self.to_py)(py, &item).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.entry.is_stale()
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use super::{Backend, Entry, Identifiable, KeyIndex, Reference};
+
+type Filter<'a, T> = Box<dyn Fn(&T) -> bool + 'a>;
+type Order<'a, T> = Box<dyn Fn(&mut [Arc<T>]) + 'a>;
+
+/// A query over items of a reference. See `Reference::query`.
+///
+/// ```
+/// # use reference::{Id, Identifiable, Reference};
+/// #
+/// struct Product {
+///     id: Id<Self>,
+///     name: String,
+///     active: bool,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// let products = Reference::new(4);
+///
+/// for (id, name, active) in [(1, "b", true), (2, "c", false), (3, "a", true)] {
+///     let name = name.to_string();
+///     products.insert(Product { id: id.into(), name, active }).unwrap();
+/// }
+///
+/// let names = products
+///     .query()
+///     .filter(|p| p.active)
+///     .sort_by(|p| p.name.clone())
+///     .limit(50)
+///     .collect()
+///     .into_iter()
+///     .map(|p| p.name.clone())
+///     .collect::<Vec<_>>();
+///
+/// assert_eq!(names, ["a", "b"]);
+/// ```
+pub struct Query<'a, T: Identifiable + 'static, B: Backend<T>> {
+    reference: &'a Reference<T, B>,
+    candidates: Option<Vec<Entry<T, B>>>,
+    filters: Vec<Filter<'a, T>>,
+    order: Option<Order<'a, T>>,
+    limit: Option<usize>,
+}
+
+impl<'a, T: Identifiable + 'static, B: Backend<T>> Query<'a, T, B> {
+    fn new(reference: &'a Reference<T, B>) -> Self {
+        Self {
+            reference,
+            candidates: None,
+            filters: Vec::new(),
+            order: None,
+            limit: None,
+        }
+    }
+
+    /// Keeps only items matching the predicate.
+    pub fn filter<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&T) -> bool + 'a,
+    {
+        self.filters.push(Box::new(predicate));
+        self
+    }
+
+    /// Keeps only items having `key` in `index`. Unlike `filter` this doesn't scan
+    /// the whole reference when it's the first indexed filter of the query.
+    pub fn filter_by<K>(mut self, index: &'a KeyIndex<T, K, B>, key: K) -> Self
+    where
+        K: Eq + Hash + 'a,
+    {
+        match self.candidates {
+            None => self.candidates = Some(index.get(&key)),
+            Some(_) => self
+                .filters
+                .push(Box::new(move |item| index.key_of(item) == key)),
+        }
+
+        self
+    }
+
+    /// Sorts items by the key returned by `f`. The sort is stable.
+    /// `f` is called once per matching item and keys are cached while sorting.
+    pub fn sort_by<K, F>(mut self, f: F) -> Self
+    where
+        K: Ord,
+        F: Fn(&T) -> K + 'a,
+    {
+        self.order = Some(Box::new(move |items| {
+            items.sort_by_cached_key(|item| f(item))
+        }));
+        self
+    }
+
+    /// Returns at most `limit` items.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Runs the query.
+    pub fn collect(self) -> Vec<Arc<T>> {
+        let limit = self.limit.unwrap_or(usize::MAX);
+        let filters = self.filters;
+        let matches = |item: &Arc<T>| filters.iter().all(|filter| filter(item));
+
+        let mut items = match self.candidates {
+            Some(candidates) => candidates
+                .into_iter()
+                .filter_map(|entry| entry.load())
+                .filter(matches)
+                .collect::<Vec<_>>(),
+            // Without sorting there's no need to go further than the limit.
+            None if self.order.is_none() => {
+                return self
+                    .reference
+                    .iter()
+                    .filter_map(|entry| entry.load())
+                    .filter(matches)
+                    .take(limit)
+                    .collect();
+            }
+            None => self
+                .reference
+                .iter()
+                .filter_map(|entry| entry.load())
+                .filter(matches)
+                .collect(),
+        };
+
+        if let Some(order) = self.order {
+            order(&mut items);
+        }
+
+        items.truncate(limit);
+        items
+    }
+
+    /// Returns the number of matching items ignoring the limit.
+    pub fn count(self) -> usize {
+        Query {
+            limit: None,
+            order: None,
+            ..self
+        }
+        .collect()
+        .len()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> fmt::Debug for Query<'_, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Query")
+            .field("filters", &self.filters.len())
+            .field("indexed", &self.candidates.is_some())
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Starts a query over items.
+    pub fn query(&self) -> Query<'_, T, B> {
+        Query::new(self)
+    }
+}
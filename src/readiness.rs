@@ -0,0 +1,191 @@
+//! Telling whether a reference is ready to serve, e.g. for readiness probes.
+
+use std::time::Duration;
+
+use super::progress::{Progress, ProgressMeter};
+use super::sync::Ordering;
+use super::{Backend, Identifiable, Reference};
+
+pub(crate) const HYDRATION_LOCK: &str = "hydration";
+
+/// Values of `Reference::last_readiness` telling readiness as of the last check.
+pub(crate) const READY: u8 = 0;
+const LOADING: u8 = 1;
+const DEGRADED: u8 = 2;
+
+/// Availability of a reference's data. See `Reference::readiness`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Readiness {
+    Ready,
+    /// A bulk load reporting progress is going on.
+    Loading {
+        progress: Progress,
+    },
+    /// Too many items haven't been updated for long.
+    Degraded {
+        stale_fraction: f64,
+    },
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        matches!(self, Self::Ready)
+    }
+
+    /// Combines readiness of several references. Loading outweighs being degraded.
+    /// Progress of concurrent loads is summed up and the highest stale fraction is taken.
+    pub fn combine(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Loading { progress: a }, Self::Loading { progress: b }) => Self::Loading {
+                progress: Progress {
+                    processed: a.processed + b.processed,
+                    total: a.total.zip(b.total).map(|(a, b)| a + b),
+                    elapsed: a.elapsed.max(b.elapsed),
+                },
+            },
+            (loading @ Self::Loading { .. }, _) | (_, loading @ Self::Loading { .. }) => loading,
+            (Self::Degraded { stale_fraction: a }, Self::Degraded { stale_fraction: b }) => {
+                Self::Degraded {
+                    stale_fraction: a.max(b),
+                }
+            }
+            (degraded @ Self::Degraded { .. }, _) | (_, degraded @ Self::Degraded { .. }) => {
+                degraded
+            }
+            (Self::Ready, Self::Ready) => Self::Ready,
+        }
+    }
+}
+
+/// Progress of bulk loads going on. See `Reference::start_hydration`.
+#[derive(Debug, Default)]
+pub(crate) struct Hydrations {
+    next_id: usize,
+    /// Latest progress of each load by its id.
+    active: Vec<(usize, Progress)>,
+}
+
+impl Hydrations {
+    /// Returns `Readiness::Loading` with progress of all loads combined
+    /// or `None` if no load is going on.
+    fn readiness(&self) -> Option<Readiness> {
+        self.active
+            .iter()
+            .map(|(_, progress)| Readiness::Loading {
+                progress: *progress,
+            })
+            .reduce(Readiness::combine)
+    }
+}
+
+/// Readiness.
+///
+/// A reference is loading while `insert_many_with_progress` or `load_stream_with_progress`
+/// is going on and stays loading until all overlapping loads finish. Otherwise it's degraded if more than the configured fraction of items
+/// hasn't been updated for longer than the configured age. See `with_degradation`.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Makes the reference degraded once more than `stale_fraction` of items haven't been
+    /// updated for longer than `older_than`. References never degrade by default.
+    pub fn with_degradation(mut self, older_than: Duration, stale_fraction: f64) -> Self {
+        self.degradation = Some((older_than, stale_fraction));
+        self
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        let readiness = self.check_readiness();
+
+        let state = match readiness {
+            Readiness::Ready => READY,
+            Readiness::Loading { .. } => LOADING,
+            Readiness::Degraded { .. } => DEGRADED,
+        };
+
+        self.last_readiness.store(state, Ordering::Relaxed);
+        readiness
+    }
+
+    /// Tells whether the reference was ready as of the last call of `readiness`
+    /// or the last bulk load.
+    pub(crate) fn was_ready(&self) -> bool {
+        self.last_readiness.load(Ordering::Relaxed) == READY
+    }
+
+    fn check_readiness(&self) -> Readiness {
+        if let Some(loading) = self
+            .recovered_lock(self.hydration.lock(), HYDRATION_LOCK)
+            .readiness()
+        {
+            return loading;
+        }
+
+        let Some((older_than, max_stale_fraction)) = self.degradation else {
+            return Readiness::Ready;
+        };
+
+        match self.stale_fraction(older_than) {
+            stale_fraction if stale_fraction > max_stale_fraction => {
+                Readiness::Degraded { stale_fraction }
+            }
+            _ => Readiness::Ready,
+        }
+    }
+
+    /// Starts a progress meter of a bulk load making the reference loading until
+    /// `finish_hydration` is called with the returned id.
+    pub(crate) fn start_hydration<'a, F: FnMut(&Progress) + 'a>(
+        &'a self,
+        total: Option<usize>,
+        every: usize,
+        mut callback: F,
+    ) -> (usize, ProgressMeter<impl FnMut(&Progress) + 'a>) {
+        let started_at = self.now();
+
+        let id = {
+            let mut hydrations = self.recovered_lock(self.hydration.lock(), HYDRATION_LOCK);
+            let id = hydrations.next_id;
+            hydrations.next_id += 1;
+
+            let progress = Progress {
+                processed: 0,
+                total,
+                elapsed: Duration::ZERO,
+            };
+
+            hydrations.active.push((id, progress));
+            id
+        };
+
+        self.last_readiness.store(LOADING, Ordering::Relaxed);
+
+        let meter = ProgressMeter::new(started_at, total, every, move |progress: &Progress| {
+            self.update_hydration(id, *progress);
+            callback(progress);
+        });
+
+        (id, meter)
+    }
+
+    /// Finishes the load started by `start_hydration`. The reference stays loading
+    /// while other loads are going on.
+    pub(crate) fn finish_hydration<F: FnMut(&Progress)>(&self, id: usize, meter: ProgressMeter<F>) {
+        meter.finish(self.now());
+        let mut hydrations = self.recovered_lock(self.hydration.lock(), HYDRATION_LOCK);
+        hydrations.active.retain(|(active_id, _)| *active_id != id);
+
+        if hydrations.active.is_empty() {
+            self.last_readiness.store(READY, Ordering::Relaxed);
+        }
+    }
+
+    fn update_hydration(&self, id: usize, progress: Progress) {
+        let mut hydrations = self.recovered_lock(self.hydration.lock(), HYDRATION_LOCK);
+
+        if let Some((_, active)) = hydrations
+            .active
+            .iter_mut()
+            .find(|(active_id, _)| *active_id == id)
+        {
+            *active = progress;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Anti-entropy between replicas of a reference.
+//!
+//! A `Summary` splits a range of ids into buckets of a fixed width and keeps a hash of each
+//! bucket combined from content hashes of its items. A replica sends its summary and
+//! the other one finds buckets with different hashes with `Reference::reconcile`. Only items
+//! of those ranges have to be fetched and applied with `Reference::sync_range`.
+//!
+//! Like nodes of a Merkle tree, buckets may be refined: summaries of differing ranges with
+//! a smaller width narrow down what to fetch when buckets are large.
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use super::fingerprint::fingerprint_term;
+use super::{Backend, Identifiable, Reference};
+
+/// Width of buckets used by `Reference::summary`.
+pub const DEFAULT_BUCKET_WIDTH: u32 = 1024;
+
+/// Hashes of buckets of ids of a reference. See `Reference::summary_in`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Summary {
+    range: RangeInclusive<i32>,
+    width: u32,
+    /// Hashes of non-empty buckets by their first id.
+    buckets: BTreeMap<i32, u64>,
+}
+
+impl Summary {
+    /// Returns the range of ids summarized.
+    pub fn range(&self) -> &RangeInclusive<i32> {
+        &self.range
+    }
+
+    /// Returns the number of ids in a bucket.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the combined hash of all buckets which equals `Reference::fingerprint`
+    /// if the whole range of ids is summarized.
+    pub fn root(&self) -> u64 {
+        self.buckets
+            .values()
+            .fold(0, |acc, hash| acc.wrapping_add(*hash))
+    }
+
+    /// Returns ranges of ids of non-empty buckets with their hashes ordered by ids.
+    pub fn buckets(&self) -> impl Iterator<Item = (RangeInclusive<i32>, u64)> + '_ {
+        self.buckets
+            .iter()
+            .map(|(start, hash)| (self.bucket_range(*start), *hash))
+    }
+
+    fn bucket_start(&self, id: i32) -> i32 {
+        let id = id as i64;
+        (id - id.rem_euclid(self.width as i64)) as i32
+    }
+
+    /// Returns ids of the bucket starting with `start` within the summarized range.
+    fn bucket_range(&self, start: i32) -> RangeInclusive<i32> {
+        let end = (start as i64 + self.width as i64 - 1).min(i32::MAX as i64) as i32;
+        start.max(*self.range.start())..=end.min(*self.range.end())
+    }
+}
+
+/// Ranges of ids which differ between replicas. See `Reference::reconcile`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct FetchPlan {
+    /// Disjoint ranges ordered by ids. Adjacent buckets are merged.
+    pub ranges: Vec<RangeInclusive<i32>>,
+}
+
+impl FetchPlan {
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Summarizes all ids in buckets of `DEFAULT_BUCKET_WIDTH`. See `summary_in`.
+    pub fn summary(&self) -> Option<Summary> {
+        self.summary_in(i32::MIN..=i32::MAX, DEFAULT_BUCKET_WIDTH)
+    }
+
+    /// Returns hashes of buckets of `width` ids in `range` or `None` if no content hash
+    /// has been set with `with_content_hash` or `width` is zero. Takes a pass over all items.
+    pub fn summary_in(&self, range: RangeInclusive<i32>, width: u32) -> Option<Summary> {
+        let hasher = self.content_hasher.as_ref()?;
+
+        if width == 0 {
+            return None;
+        }
+
+        let mut summary = Summary {
+            range,
+            width,
+            buckets: BTreeMap::new(),
+        };
+
+        for item in self.iter().filter_map(|entry| entry.load()) {
+            let id = item.id();
+
+            if summary.range.contains(&id.as_i32()) {
+                let term = fingerprint_term(id, hasher.content_hash(&item));
+                let bucket = summary.buckets.entry(summary.bucket_start(id.as_i32()));
+                let hash = bucket.or_default();
+                *hash = hash.wrapping_add(term);
+            }
+        }
+
+        Some(summary)
+    }
+
+    /// Compares `remote` with a summary of this reference over the same buckets and
+    /// returns ranges of ids to fetch from the remote replica. Returns `None` if no content
+    /// hash has been set with `with_content_hash` or `remote` has a zero width which
+    /// a well-behaved replica never sends.
+    pub fn reconcile(&self, remote: &Summary) -> Option<FetchPlan> {
+        let local = self.summary_in(remote.range.clone(), remote.width)?;
+        let mut plan = FetchPlan::default();
+
+        let mut starts = local
+            .buckets
+            .keys()
+            .chain(remote.buckets.keys())
+            .copied()
+            .filter(|start| local.buckets.get(start) != remote.buckets.get(start))
+            .collect::<Vec<_>>();
+
+        starts.sort_unstable();
+        starts.dedup();
+
+        for range in starts.into_iter().map(|start| remote.bucket_range(start)) {
+            match plan.ranges.last_mut() {
+                Some(last) if *last.end() as i64 + 1 == *range.start() as i64 => {
+                    *last = *last.start()..=*range.end();
+                }
+                _ => plan.ranges.push(range),
+            }
+        }
+
+        Some(plan)
+    }
+}
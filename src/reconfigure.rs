@@ -0,0 +1,164 @@
+//! Changing tunables of a live reference, e.g. on reloading a manifest.
+
+use std::fmt;
+use std::time::Duration;
+
+use super::capacity::DEFAULT_UTILIZATION_WARNING_THRESHOLD;
+use super::sync::MaybeSync;
+use super::{Backend, Identifiable, Reference, ReferenceConfig};
+
+pub(crate) const TUNABLES_LOCK: &str = "tunables";
+
+/// Tunables which may be changed by `Reference::reconfigure`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Tunables {
+    pub(crate) default_ttl: Option<Duration>,
+    pub(crate) utilization_warning_threshold: f64,
+}
+
+impl Default for Tunables {
+    fn default() -> Self {
+        Self {
+            default_ttl: None,
+            utilization_warning_threshold: DEFAULT_UTILIZATION_WARNING_THRESHOLD,
+        }
+    }
+}
+
+/// Tunables to change by `Reference::reconfigure`. Those left `None` stay as they are.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigPatch {
+    /// The default TTL or `Some(None)` to make items without TTL overrides never expire.
+    pub ttl: Option<Option<Duration>>,
+    pub utilization_warning_threshold: Option<f64>,
+}
+
+impl ConfigPatch {
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn with_utilization_warning_threshold(mut self, threshold: f64) -> Self {
+        self.utilization_warning_threshold = Some(threshold);
+        self
+    }
+}
+
+/// Takes the tunables of a reloaded config leaving out those fixed on creation.
+impl From<&ReferenceConfig> for ConfigPatch {
+    fn from(config: &ReferenceConfig) -> Self {
+        Self {
+            ttl: Some(config.ttl_secs.map(Duration::from_secs)),
+            utilization_warning_threshold: Some(config.utilization_warning_threshold),
+        }
+    }
+}
+
+/// A change of a tunable made by `Reference::reconfigure`.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigChange {
+    Ttl {
+        from: Option<Duration>,
+        to: Option<Duration>,
+    },
+    UtilizationWarningThreshold {
+        from: f64,
+        to: f64,
+    },
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ttl { from, to } => write!(f, "TTL changed from {from:?} to {to:?}"),
+            Self::UtilizationWarningThreshold { from, to } => {
+                write!(
+                    f,
+                    "Utilization warning threshold changed from {from} to {to}"
+                )
+            }
+        }
+    }
+}
+
+/// Runtime reconfiguration.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Calls `listener` with each change made by `reconfigure`.
+    pub fn with_config_listener<L>(mut self, listener: L) -> Self
+    where
+        L: Fn(&ConfigChange) + MaybeSync + 'static,
+    {
+        self.config_listener = Some(Box::new(listener));
+        self
+    }
+
+    /// Changes the tunables set in `patch` and returns the changes actually made.
+    /// Changes are logged and passed to the config listener outside of the reference locks.
+    pub fn reconfigure(&self, patch: &ConfigPatch) -> Vec<ConfigChange> {
+        let mut changes = Vec::new();
+
+        {
+            let mut tunables = self.recovered_lock(self.tunables.write(), TUNABLES_LOCK);
+
+            if let Some(ttl) = patch.ttl.filter(|ttl| *ttl != tunables.default_ttl) {
+                changes.push(ConfigChange::Ttl {
+                    from: tunables.default_ttl,
+                    to: ttl,
+                });
+
+                tunables.default_ttl = ttl;
+            }
+
+            if let Some(threshold) = patch
+                .utilization_warning_threshold
+                .filter(|threshold| *threshold != tunables.utilization_warning_threshold)
+            {
+                changes.push(ConfigChange::UtilizationWarningThreshold {
+                    from: tunables.utilization_warning_threshold,
+                    to: threshold,
+                });
+
+                tunables.utilization_warning_threshold = threshold;
+            }
+        }
+
+        for change in &changes {
+            log::info!("Reference of {}: {change}", std::any::type_name::<T>());
+
+            if let Some(listener) = &self.config_listener {
+                listener.notify(change);
+            }
+        }
+
+        changes
+    }
+
+    /// Returns the current tunables.
+    pub(crate) fn tunables(&self) -> Tunables {
+        *self.recovered_lock(self.tunables.read(), TUNABLES_LOCK)
+    }
+
+    /// Changes the tunables while building the reference without emitting changes.
+    pub(crate) fn tune(&self, f: impl FnOnce(&mut Tunables)) {
+        let mut tunables = self.recovered_lock(self.tunables.write(), TUNABLES_LOCK);
+        f(&mut tunables);
+    }
+}
+
+pub(crate) trait ConfigListener: MaybeSync {
+    fn notify(&self, change: &ConfigChange);
+}
+
+impl<F: Fn(&ConfigChange) + MaybeSync> ConfigListener for F {
+    fn notify(&self, change: &ConfigChange) {
+        self(change)
+    }
+}
+
+impl fmt::Debug for dyn ConfigListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConfigListener")
+    }
+}
@@ -0,0 +1,295 @@
+//! Recording mutations of a reference to reproduce them later.
+//!
+//! A recording starts with a header: the magic bytes, the format version and the schema
+//! version of the `Codec`. Records follow, each laid out as the operation tag, microseconds
+//! since the recording has started, the id of the mutating thread, the id of the item and
+//! for stores the length of the encoded item and the item encoded with the codec.
+//! Thread ids are assigned sequentially in the order threads first mutate a reference.
+//! All integers are little-endian.
+//!
+//! Mutations are recorded in the order they take effect so replaying a recording made
+//! while a race has happened reproduces the same sequence of states deterministically.
+//! Changes made through entries, e.g. by `Entry::modify`, are not recorded.
+
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering as StdOrdering};
+use std::time::Instant;
+
+use super::sync::{MaybeSync, Mutex, MutexGuard};
+use super::{Backend, Codec, Error, Id, Identifiable, Reference};
+
+const MAGIC: &[u8; 8] = b"REFRECRD";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 5;
+const RECORD_HEADER_LEN: usize = 21;
+
+const STORE: u8 = 1;
+const RESERVE: u8 = 2;
+const REMOVE: u8 = 3;
+
+/// A mutation of a reference.
+pub(crate) enum Op<'a, T> {
+    /// The item has been inserted or has replaced the previous one.
+    Store(&'a T),
+    /// The id has been reserved.
+    Reserve(Id<T>),
+    /// The item or the reservation has been removed.
+    Remove(Id<T>),
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Makes the reference record its mutations encoded with `codec` to `writer`.
+    /// See `replay`.
+    ///
+    /// Writes are not buffered so wrap a file into a `BufWriter`. It's flushed when
+    /// the reference is dropped. If writing fails the error is logged and recording stops.
+    pub fn with_recording<W, C>(mut self, writer: W, codec: C) -> Self
+    where
+        W: Write + MaybeSync + 'static,
+        C: Codec<T>,
+    {
+        self.recorder = Some(Recorder::start(CodecSink {
+            writer,
+            codec,
+            buf: Vec::new(),
+        }));
+
+        self
+    }
+
+    /// Applies the mutations read from a recording written by a reference made
+    /// `with_recording` in the same order. Returns the number of applied mutations.
+    /// Meant to be called on an empty reference with the capacity of the recorded one.
+    pub fn replay<R: Read, C: Codec<T>>(
+        &self,
+        mut reader: R,
+        codec: &C,
+    ) -> Result<usize, Error<T>> {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header).map_err(io_error)?;
+
+        if &header[..MAGIC.len()] != MAGIC || header[MAGIC.len()] != FORMAT_VERSION {
+            return Err(io_error(invalid_data("Not a supported recording")));
+        }
+
+        let schema_version = u32::from_le_bytes(read_array(&header[MAGIC.len() + 1..]));
+        let needs_migration = schema_version != codec.schema_version();
+        let mut bytes = Vec::new();
+        let mut applied = 0;
+
+        while let Some(record) = read_record_header(&mut reader)? {
+            let [tag, ..] = record;
+            let micros = u64::from_le_bytes(read_array(&record[1..]));
+            let thread = u64::from_le_bytes(read_array(&record[9..]));
+            let id = Id::new(i32::from_le_bytes(read_array(&record[17..])));
+            log::trace!("Replaying {tag} of {id} by thread {thread} at {micros} us");
+
+            match tag {
+                STORE => {
+                    let mut len = [0; 4];
+                    reader.read_exact(&mut len).map_err(io_error)?;
+                    let len = u32::from_le_bytes(len) as u64;
+
+                    bytes.clear();
+                    let read = (&mut reader)
+                        .take(len)
+                        .read_to_end(&mut bytes)
+                        .map_err(io_error)?;
+
+                    if (read as u64) < len {
+                        return Err(io_error(invalid_data("Truncated item")));
+                    }
+
+                    let item = match needs_migration {
+                        true => codec.migrate(schema_version, &bytes),
+                        false => codec.decode(&bytes),
+                    };
+
+                    self.upsert(item.map_err(Error::CodecError)?)?;
+                }
+                RESERVE => {
+                    self.get_or_reserve(id)?;
+                }
+                REMOVE => {
+                    self.remove(id);
+                }
+                _ => return Err(io_error(invalid_data(format!("Unknown record {tag}")))),
+            }
+
+            applied += 1;
+        }
+
+        Ok(applied)
+    }
+
+    /// Records a mutation if recording is on.
+    pub(crate) fn record(&self, op: Op<'_, T>) {
+        if let Some(recorder) = &self.recorder {
+            recorder.lock().record(op);
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// An encoder of records type-erased for keeping in a reference.
+trait Sink<T>: MaybeSync {
+    fn write(&mut self, op: &Op<'_, T>, micros: u64, thread: u64) -> Result<(), String>;
+}
+
+struct CodecSink<W, C> {
+    writer: W,
+    codec: C,
+    buf: Vec<u8>,
+}
+
+impl<T, W, C> Sink<T> for CodecSink<W, C>
+where
+    T: Identifiable,
+    W: Write + MaybeSync,
+    C: Codec<T>,
+{
+    fn write(&mut self, op: &Op<'_, T>, micros: u64, thread: u64) -> Result<(), String> {
+        let (tag, id) = match op {
+            Op::Store(item) => (STORE, item.id()),
+            Op::Reserve(id) => (RESERVE, *id),
+            Op::Remove(id) => (REMOVE, *id),
+        };
+
+        self.buf.clear();
+        self.buf.push(tag);
+        self.buf.extend_from_slice(&micros.to_le_bytes());
+        self.buf.extend_from_slice(&thread.to_le_bytes());
+        self.buf.extend_from_slice(&id.as_i32().to_le_bytes());
+
+        if let Op::Store(item) = op {
+            let start = self.buf.len();
+            self.buf.extend_from_slice(&[0; 4]);
+
+            self.codec
+                .encode(item, &mut self.buf)
+                .map_err(|err| format!("Failed to encode {id}: {err}"))?;
+
+            let len = (self.buf.len() - start - 4) as u32;
+            self.buf[start..start + 4].copy_from_slice(&len.to_le_bytes());
+        }
+
+        self.writer
+            .write_all(&self.buf)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Recording state of a reference. The sink is dropped once it fails.
+pub(crate) struct Recorder<T> {
+    sink: Mutex<Option<Box<dyn Sink<T>>>>,
+    started: Instant,
+}
+
+impl<T: Identifiable + 'static> Recorder<T> {
+    fn start<W, C>(mut sink: CodecSink<W, C>) -> Self
+    where
+        W: Write + MaybeSync + 'static,
+        C: Codec<T>,
+    {
+        let mut header = MAGIC.to_vec();
+        header.push(FORMAT_VERSION);
+        header.extend_from_slice(&sink.codec.schema_version().to_le_bytes());
+
+        let sink = match sink.writer.write_all(&header) {
+            Ok(()) => Some(Box::new(sink) as Box<dyn Sink<T>>),
+            Err(err) => {
+                log::error!("Failed to start recording: {err}");
+                None
+            }
+        };
+
+        Self {
+            sink: Mutex::new(sink),
+            started: Instant::now(),
+        }
+    }
+
+    /// Takes the lock which has to be held while the mutation is applied and recorded
+    /// if the order of concurrent mutations isn't fixed otherwise.
+    pub(crate) fn lock(&self) -> RecorderGuard<'_, T> {
+        RecorderGuard {
+            sink: self.sink.lock().unwrap_or_else(|err| err.into_inner()),
+            started: self.started,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Recorder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+pub(crate) struct RecorderGuard<'a, T> {
+    sink: MutexGuard<'a, Option<Box<dyn Sink<T>>>>,
+    started: Instant,
+}
+
+impl<T> RecorderGuard<'_, T> {
+    pub(crate) fn record(&mut self, op: Op<'_, T>) {
+        let Some(sink) = self.sink.as_mut() else {
+            return;
+        };
+
+        let micros = self.started.elapsed().as_micros() as u64;
+
+        if let Err(err) = sink.write(&op, micros, thread_id()) {
+            log::error!("Failed to record a mutation, recording stopped: {err}");
+            *self.sink = None;
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Returns the sequential id of the current thread.
+fn thread_id() -> u64 {
+    static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+    thread_local! {
+        static THREAD: u64 = NEXT_THREAD.fetch_add(1, StdOrdering::Relaxed);
+    }
+
+    THREAD.with(|thread| *thread)
+}
+
+/// Reads the header of the next record or returns `None` at the end of the recording.
+fn read_record_header<T, R: Read>(
+    reader: &mut R,
+) -> Result<Option<[u8; RECORD_HEADER_LEN]>, Error<T>> {
+    let mut header = [0; RECORD_HEADER_LEN];
+    let mut read = 0;
+
+    while read < header.len() {
+        match reader.read(&mut header[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(io_error(invalid_data("Truncated record"))),
+            Ok(len) => read += len,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(io_error(err)),
+        }
+    }
+
+    Ok(Some(header))
+}
+
+fn read_array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes[..N].try_into().expect("Wrong slice length")
+}
+
+fn invalid_data(msg: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, msg.into())
+}
+
+fn io_error<T>(err: std::io::Error) -> Error<T> {
+    Error::Other(Box::new(err))
+}
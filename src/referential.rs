@@ -0,0 +1,68 @@
+//! Abstractions over entity storages for code which only looks items up and updates them
+//! or keeps entries of them.
+
+use std::sync::Arc;
+
+use super::{Backend, Entry, Error, Id, Identifiable, Reference};
+
+/// Basic operations of an entity storage of `T`.
+///
+/// Code depending on a reference through this trait works with a `Reference` of any backend
+/// and may be unit-tested with a stub, e.g. `testing::MockReference`.
+pub trait Referential<T: Identifiable + 'static> {
+    /// Returns the item with `id` or `None` if there's none.
+    fn load(&self, id: Id<T>) -> Option<Arc<T>>;
+
+    /// Tells whether there's an item or a reservation with `id`.
+    fn contains(&self, id: Id<T>) -> bool;
+
+    /// Inserts the item replacing the one with the same id if any.
+    fn upsert(&self, item: T) -> Result<(), Error<T>>;
+
+    /// Removes the item with `id` and returns it.
+    fn remove(&self, id: Id<T>) -> Option<Arc<T>>;
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Referential<T> for Reference<T, B> {
+    fn load(&self, id: Id<T>) -> Option<Arc<T>> {
+        self.get(id)?.load()
+    }
+
+    fn contains(&self, id: Id<T>) -> bool {
+        Reference::contains(self, id)
+    }
+
+    fn upsert(&self, item: T) -> Result<(), Error<T>> {
+        Reference::upsert(self, item).map(|_| ())
+    }
+
+    fn remove(&self, id: Id<T>) -> Option<Arc<T>> {
+        Reference::remove(self, id)
+    }
+}
+
+/// Entity storages handing out entries which follow the items kept in them.
+///
+/// Unlike `Referential` this can't be stubbed with a map since entries point into slots
+/// of a reference.
+pub trait Enterable<T: Identifiable + 'static>: Referential<T> {
+    type Backend: Backend<T>;
+
+    /// Returns an entry of the item with `id` or `None` if there's none.
+    fn entry(&self, id: Id<T>) -> Option<Entry<T, Self::Backend>>;
+
+    /// Returns an entry of the item with `id` reserving one if there's none.
+    fn entry_or_reserve(&self, id: Id<T>) -> Result<Entry<T, Self::Backend>, Error<T>>;
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Enterable<T> for Reference<T, B> {
+    type Backend = B;
+
+    fn entry(&self, id: Id<T>) -> Option<Entry<T, B>> {
+        self.get(id)
+    }
+
+    fn entry_or_reserve(&self, id: Id<T>) -> Result<Entry<T, B>, Error<T>> {
+        self.get_or_reserve(id)
+    }
+}
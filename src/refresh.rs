@@ -0,0 +1,121 @@
+//! Bringing references up to date with their source.
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::sync::Arc;
+
+use rustc_hash::FxHashSet;
+
+use super::{Backend, DuplicateMode, Error, Id, Identifiable, Reference};
+
+/// Result of applying a batch of items to a reference.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RefreshSummary {
+    /// Number of items inserted or replaced.
+    pub upserted: usize,
+    /// Number of items removed since they are absent in the batch.
+    pub removed: usize,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Makes the reference contain exactly `items`: upserts each of them and removes
+    /// the items with other ids. Reservations are kept.
+    ///
+    /// Items are replaced one by one so concurrent readers may observe a mix of old and new
+    /// items until it returns. Starts a new batch if provenance is enabled.
+    pub fn replace_all<I>(&self, items: I) -> Result<RefreshSummary, Error<T>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.begin_batch();
+        let mut ids = FxHashSet::default();
+        let mut summary = RefreshSummary::default();
+
+        for item in items {
+            ids.insert(item.id());
+            self.insert_arc(Arc::new(item), DuplicateMode::Replace)?;
+            summary.upserted += 1;
+        }
+
+        let stale_ids = self
+            .iter()
+            .filter_map(|entry| entry.load())
+            .map(|item| item.id())
+            .filter(|id| !ids.contains(id))
+            .collect::<Vec<_>>();
+
+        for id in stale_ids {
+            if self.remove(id).is_some() {
+                summary.removed += 1;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Re-fetches items with `ids` by `loader` and replaces just their slots leaving
+    /// other items intact. Each slot is replaced atomically.
+    ///
+    /// Items of the requested ids which `loader` doesn't return are considered deleted
+    /// from the source and get removed. Returned items with other ids are ignored.
+    pub fn refresh<I, L, E>(&self, ids: I, loader: L) -> Result<RefreshSummary, Error<T>>
+    where
+        I: IntoIterator<Item = Id<T>>,
+        L: FnOnce(&[Id<T>]) -> Result<Vec<T>, E>,
+        E: Into<Box<dyn StdError>>,
+    {
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        #[cfg(feature = "testing")]
+        self.check_fault(super::fault::Call::Load)?;
+        let items = loader(&ids).map_err(|err| Error::Other(err.into()))?;
+        self.apply_refreshed(&ids, items)
+    }
+
+    /// Like `refresh` but with an async `loader`.
+    pub async fn refresh_async<I, L, F, E>(
+        &self,
+        ids: I,
+        loader: L,
+    ) -> Result<RefreshSummary, Error<T>>
+    where
+        I: IntoIterator<Item = Id<T>>,
+        L: FnOnce(Vec<Id<T>>) -> F,
+        F: Future<Output = Result<Vec<T>, E>>,
+        E: Into<Box<dyn StdError>>,
+    {
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        #[cfg(feature = "testing")]
+        self.check_fault(super::fault::Call::Load)?;
+        let items = loader(ids.clone())
+            .await
+            .map_err(|err| Error::Other(err.into()))?;
+        self.apply_refreshed(&ids, items)
+    }
+
+    fn apply_refreshed(&self, ids: &[Id<T>], items: Vec<T>) -> Result<RefreshSummary, Error<T>> {
+        self.begin_batch();
+        let mut missing_ids = ids.iter().copied().collect::<FxHashSet<_>>();
+        let mut summary = RefreshSummary::default();
+
+        for item in items {
+            let id = item.id();
+
+            if !missing_ids.remove(&id) {
+                log::warn!("Ignoring refreshed item {id} which wasn't requested");
+                continue;
+            }
+
+            self.insert_arc(Arc::new(item), DuplicateMode::Replace)?;
+            summary.upserted += 1;
+        }
+
+        for id in missing_ids {
+            if self.contains_resolved(id) && self.remove(id).is_some() {
+                summary.removed += 1;
+            }
+        }
+
+        Ok(summary)
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use super::{ArcSwapBackend, Backend, Entry, Error, Id, Identifiable, Reference};
+
+/// What to do with dependent items when the item they refer to gets removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cascade {
+    /// Reject the removal with `Error::RemoveRestricted` if there are dependents.
+    Restrict,
+    /// Remove the item anyway. Entries of dependents become stale and load `None`.
+    #[default]
+    SetNone,
+    /// Remove dependents as well. Dependents of dependents are not touched.
+    CascadeRemove,
+}
+
+/// A relation of dependent items `D` referring to items `T` through an `Entry` field.
+///
+/// ```
+/// # use reference::{Cascade, Entry, Id, Identifiable, Reference, Relation};
+/// #
+/// struct Subject {
+///     id: Id<Self>,
+/// }
+/// #
+/// # impl Identifiable for Subject {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// struct Product {
+///     id: Id<Self>,
+///     subject: Entry<Subject>,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// let subjects = Reference::new(2);
+/// let products = Reference::new(2);
+/// let subject = subjects.insert(Subject { id: 1.into() }).unwrap();
+/// products.insert(Product { id: 1.into(), subject }).unwrap();
+///
+/// let relation = Relation::new(|product: &Product| &product.subject, Cascade::Restrict);
+/// assert!(relation.remove(&subjects, &products, 1.into()).is_err());
+/// ```
+///
+/// Dependents are found by scanning the dependent reference. Dependents added concurrently
+/// with the removal may be missed.
+pub struct Relation<D, T: 'static, B: Backend<T> = ArcSwapBackend<T>> {
+    link: fn(&D) -> &Entry<T, B>,
+    cascade: Cascade,
+}
+
+impl<D, T, B> Relation<D, T, B>
+where
+    D: Identifiable + 'static,
+    T: Identifiable + 'static,
+    B: Backend<T>,
+{
+    /// Creates a relation where `link` returns the entry a dependent refers to.
+    pub fn new(link: fn(&D) -> &Entry<T, B>, cascade: Cascade) -> Self {
+        Self { link, cascade }
+    }
+
+    pub fn cascade(&self) -> Cascade {
+        self.cascade
+    }
+
+    /// Returns entries of items in `dependents` referring to `entry`.
+    pub fn dependents<DB: Backend<D>>(
+        &self,
+        dependents: &Reference<D, DB>,
+        entry: &Entry<T, B>,
+    ) -> Vec<Entry<D, DB>> {
+        dependents
+            .iter()
+            .filter(|dependent| match dependent.load() {
+                Some(item) => (self.link)(&item) == entry,
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Builds an index of ids of dependents by ids of items they refer to in a single pass.
+    /// Dependents with empty links are skipped.
+    pub fn back_references<DB: Backend<D>>(
+        &self,
+        dependents: &Reference<D, DB>,
+    ) -> FxHashMap<Id<T>, Vec<Id<D>>> {
+        let mut index: FxHashMap<Id<T>, Vec<Id<D>>> = FxHashMap::default();
+
+        for dependent in dependents.iter() {
+            if let Some(item) = dependent.load() {
+                if let Some(target) = (self.link)(&item).load() {
+                    index.entry(target.id()).or_default().push(item.id());
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Returns ids of dependents with dangling links, i.e. referring to reservations
+    /// which have never been resolved or to removed items.
+    pub fn validate<DB: Backend<D>>(&self, dependents: &Reference<D, DB>) -> Vec<Id<D>> {
+        dependents
+            .iter()
+            .filter_map(|dependent| dependent.load())
+            .filter(|item| (self.link)(item).load().is_none())
+            .map(|item| item.id())
+            .collect()
+    }
+
+    /// Removes the item with the given `id` from `reference` applying the cascade policy
+    /// to `dependents`. Returns the removed item if any.
+    pub fn remove<DB: Backend<D>>(
+        &self,
+        reference: &Reference<T, B>,
+        dependents: &Reference<D, DB>,
+        id: Id<T>,
+    ) -> Result<Option<Arc<T>>, Error<T>> {
+        let entry = match reference.get(id) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        if self.cascade == Cascade::SetNone {
+            return Ok(reference.remove(id));
+        }
+
+        let dependent_entries = self.dependents(dependents, &entry);
+
+        match self.cascade {
+            Cascade::Restrict if !dependent_entries.is_empty() => {
+                return Err(Error::RemoveRestricted {
+                    id,
+                    dependents: dependent_entries.len(),
+                });
+            }
+            Cascade::CascadeRemove => {
+                for dependent in dependent_entries {
+                    if let Some(item) = dependent.load() {
+                        dependents.remove(item.id());
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        Ok(reference.remove(id))
+    }
+}
+
+impl<D, T: 'static, B: Backend<T>> fmt::Debug for Relation<D, T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Relation")
+            .field("cascade", &self.cascade)
+            .finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Declares relations between entity types.
+///
+/// For each `Name: Dependent.field -> Target [Cascade];` declaration generates
+/// a unit struct `Name` with `Name::relation()` returning the `Relation` and an accessor
+/// method `Dependent::field()` loading the referred item.
+///
+/// ```
+/// # use reference::{relations, Entry, Id, Identifiable, Reference};
+/// #
+/// struct Subject {
+///     id: Id<Self>,
+/// }
+/// #
+/// # impl Identifiable for Subject {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// struct Product {
+///     id: Id<Self>,
+///     subject: Entry<Subject>,
+/// }
+/// #
+/// # impl Identifiable for Product {
+/// #     fn id(&self) -> Id<Self> {
+/// #         self.id
+/// #     }
+/// # }
+///
+/// relations! {
+///     ProductSubject: Product.subject -> Subject [Restrict];
+/// }
+///
+/// let subjects = Reference::new(2);
+/// let products = Reference::new(2);
+/// let subject = subjects.get_or_reserve(1.into()).unwrap();
+/// let product = products.insert(Product { id: 1.into(), subject }).unwrap();
+///
+/// assert_eq!(ProductSubject::relation().validate(&products), [1.into()]);
+/// subjects.insert(Subject { id: 1.into() }).unwrap();
+/// assert!(ProductSubject::relation().validate(&products).is_empty());
+/// assert_eq!(product.load().unwrap().subject().unwrap().id, 1.into());
+/// ```
+#[macro_export]
+macro_rules! relations {
+    ($(
+        $vis:vis $name:ident: $dependent:ident.$field:ident -> $target:ty [$cascade:ident];
+    )*) => {
+        $(
+            #[derive(Clone, Copy, Debug)]
+            $vis struct $name;
+
+            impl $name {
+                #[allow(dead_code)]
+                $vis fn relation() -> $crate::Relation<$dependent, $target> {
+                    $crate::Relation::new(
+                        |dependent: &$dependent| &dependent.$field,
+                        $crate::Cascade::$cascade,
+                    )
+                }
+            }
+
+            impl $dependent {
+                #[allow(dead_code)]
+                $vis fn $field(&self) -> ::std::option::Option<::std::sync::Arc<$target>> {
+                    self.$field.load()
+                }
+            }
+        )*
+    };
+}
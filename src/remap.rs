@@ -0,0 +1,268 @@
+//! Following renumbering of ids by the source.
+//!
+//! When the source assigns new ids to existing entities the slots of the old ids are moved
+//! under the new ones instead of adding new slots. Entries taken before the renumbering,
+//! e.g. with `get_or_reserve` by dependents, keep pointing to the same slots and so observe
+//! the items imported under the new ids.
+//!
+//! The applied renumbering is recorded in an `IdRemap` which may be persisted to translate
+//! old ids coming from other sources later.
+
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::poison::INDEX_LOCK;
+use super::update_lock;
+use super::{Backend, DuplicateMode, Error, Id, Identifiable, Reference};
+
+const MAGIC: &[u8; 8] = b"REFREMAP";
+
+/// A table of old ids mapped to new ones.
+pub struct IdRemap<T> {
+    ids: FxHashMap<i32, i32>,
+    phantom: PhantomData<T>,
+}
+
+impl<T> IdRemap<T> {
+    pub fn new() -> Self {
+        Self {
+            ids: FxHashMap::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Records that `old` has become `new`. Ids which have become `old` before
+    /// are mapped to `new` too so chains of renumberings collapse.
+    pub fn insert(&mut self, old: Id<T>, new: Id<T>) {
+        let (old, new) = (old.as_i32(), new.as_i32());
+
+        for target in self.ids.values_mut() {
+            if *target == old {
+                *target = new;
+            }
+        }
+
+        self.ids.insert(old, new);
+        self.ids.retain(|old, new| old != new);
+    }
+
+    /// Returns the new id of `old` if it has been renumbered.
+    pub fn get(&self, old: Id<T>) -> Option<Id<T>> {
+        self.ids.get(&old.as_i32()).copied().map(Id::new)
+    }
+
+    /// Returns the current id of `id` which is itself unless it has been renumbered.
+    pub fn translate(&self, id: Id<T>) -> Id<T> {
+        self.get(id).unwrap_or(id)
+    }
+
+    /// Records all renumberings of `other` after the ones of `self`.
+    pub fn merge(&mut self, other: &Self) {
+        for (old, new) in other.iter() {
+            self.insert(old, new);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Iterates over pairs of old and new ids in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, Id<T>)> + '_ {
+        self.ids
+            .iter()
+            .map(|(old, new)| (Id::new(*old), Id::new(*new)))
+    }
+
+    /// Writes the table to `writer` in a compact binary format.
+    pub fn save_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.ids.len() as u64).to_le_bytes())?;
+
+        let mut pairs = self.ids.iter().collect::<Vec<_>>();
+        pairs.sort_unstable();
+
+        for (old, new) in pairs {
+            writer.write_all(&old.to_le_bytes())?;
+            writer.write_all(&new.to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    /// Reads a table written by `save_to`.
+    pub fn load_from<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "Not an id remap table",
+            ));
+        }
+
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+        let mut remap = Self::new();
+
+        for _ in 0..u64::from_le_bytes(len) {
+            let mut pair = [0; 8];
+            reader.read_exact(&mut pair)?;
+            let old = i32::from_le_bytes([pair[0], pair[1], pair[2], pair[3]]);
+            let new = i32::from_le_bytes([pair[4], pair[5], pair[6], pair[7]]);
+            remap.ids.insert(old, new);
+        }
+
+        Ok(remap)
+    }
+}
+
+impl<T> Default for IdRemap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for IdRemap<T> {
+    fn clone(&self) -> Self {
+        Self {
+            ids: self.ids.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for IdRemap<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ids == other.ids
+    }
+}
+
+impl<T> Eq for IdRemap<T> {}
+
+impl<T> fmt::Debug for IdRemap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.ids.iter()).finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Moves slots of existing ids to the new ids returned by `remap` and then upserts
+    /// `items` which are expected to carry the new ids. Returns the applied renumbering.
+    ///
+    /// Items of moved slots are cleared so a slot whose item is missing from `items`
+    /// becomes a reservation of the new id. Ids `remap` leaves as they are are untouched.
+    ///
+    /// Fails with `Error::DuplicateIds` without changing anything if a new id is already
+    /// taken by a slot which doesn't move or two ids get renumbered to the same one.
+    ///
+    /// `remap` is called without holding locks so it may call into the reference. It's called
+    /// again for all ids if some of them are removed or moved concurrently. Holders of update
+    /// guards of moving items are waited for so calling this while holding one may deadlock.
+    pub fn import_with_remap<I, F>(&self, items: I, mut remap: F) -> Result<IdRemap<T>, Error<T>>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(Id<T>) -> Id<T>,
+    {
+        self.check_writable()?;
+        let mut applied = IdRemap::new();
+
+        let cleared = {
+            // `remap` is called without locks so it may call into the reference. Update locks
+            // of moving slots go before the index lock and stop guard holders from storing
+            // items with old ids into slots of new ones.
+            let (moves, _guards, mut vids) = loop {
+                let moves = self
+                    .checked_lock(self.vids.read(), INDEX_LOCK)?
+                    .iter()
+                    .filter(|(_, vid)| *vid != 0)
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .map(|(old, vid)| (old, remap(old), vid))
+                    .filter(|(old, new, _)| old != new)
+                    .collect::<Vec<_>>();
+
+                let slots = moves
+                    .iter()
+                    .map(|(.., vid)| self.entry(*vid).map(|entry| entry.slot))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let guards = update_lock::lock_slots(slots);
+                let vids = self.checked_lock(self.vids.write(), INDEX_LOCK)?;
+
+                // Ids might have been removed or moved to other slots meanwhile.
+                if moves
+                    .iter()
+                    .all(|(old, _, vid)| vids.get(*old) == Some(*vid))
+                {
+                    break (moves, guards, vids);
+                }
+            };
+
+            let moved = moves.iter().map(|(old, ..)| *old).collect::<FxHashSet<_>>();
+            let mut targets = FxHashSet::default();
+
+            let conflicts = moves
+                .iter()
+                .map(|(_, new, _)| *new)
+                .filter(|new| {
+                    !targets.insert(*new) || (vids.get(*new).is_some() && !moved.contains(new))
+                })
+                .collect::<Vec<_>>();
+
+            if !conflicts.is_empty() {
+                return Err(Error::DuplicateIds(conflicts));
+            }
+
+            for (old, ..) in &moves {
+                vids.remove(*old);
+            }
+
+            let mut cleared = Vec::new();
+
+            for (old, new, vid) in moves {
+                let entry = self.entry(vid)?;
+
+                // Before the index so `get` never misses an indexed id because of the filter.
+                if let Some(bloom) = &self.bloom {
+                    bloom.insert(new);
+                }
+
+                // Renumberings of a batch are simultaneous so they don't chain.
+                B::assign_id(entry.slot, new);
+                vids.insert(new, vid);
+                applied.ids.insert(old.as_i32(), new.as_i32());
+
+                let maybe_prev = B::store(entry.slot, None);
+
+                if let Some(prev) = maybe_prev {
+                    cleared.push((entry, prev));
+                }
+            }
+
+            cleared
+        };
+
+        for (entry, prev) in cleared {
+            self.update_indexes(&entry, Some(&prev));
+            entry.swapped(None, Some(&prev));
+        }
+
+        for item in items {
+            self.insert_arc(Arc::new(item), DuplicateMode::Replace)?;
+        }
+
+        Ok(applied)
+    }
+}
@@ -0,0 +1,225 @@
+//! Periodic reloading of references from their source.
+//!
+//! Slots never move nor get deallocated so a reference can't be swapped for a freshly loaded
+//! one without leaking it. Instead the loader fills a staging batch of items which is then
+//! applied to the live reference with `Reference::replace_all`. Entries held by readers
+//! stay valid and observe the new values.
+
+use std::collections::hash_map::RandomState;
+use std::error::Error as StdError;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::{Backend, Identifiable, Reference, RefreshSummary};
+
+/// Default fraction of the interval by which refreshes are randomly shifted.
+pub const DEFAULT_REFRESH_JITTER: f64 = 0.1;
+
+type Loader<T> = Box<dyn FnMut() -> Result<Vec<T>, Box<dyn StdError + Send + Sync>> + Send>;
+type SuccessHook = Box<dyn Fn(&RefreshSummary, Duration) + Send>;
+type FailureHook = Box<dyn Fn(&dyn StdError, u32) + Send>;
+
+/// Runs a loader periodically in a background thread applying its results to a reference.
+///
+/// Each run is delayed by the interval shifted randomly by up to the jitter fraction of it
+/// so that many instances don't hit the source at once. After a failure the delay doubles
+/// with each consecutive failure up to the maximum backoff.
+pub struct RefreshScheduler<T> {
+    loader: Loader<T>,
+    interval: Duration,
+    jitter: f64,
+    max_backoff: Duration,
+    on_success: Option<SuccessHook>,
+    on_failure: Option<FailureHook>,
+}
+
+impl<T: Identifiable + Send + Sync + 'static> RefreshScheduler<T> {
+    /// Creates a scheduler running `loader` every `interval`.
+    /// The maximum backoff is 8 intervals by default.
+    pub fn new<L>(interval: Duration, loader: L) -> Self
+    where
+        L: FnMut() -> Result<Vec<T>, Box<dyn StdError + Send + Sync>> + Send + 'static,
+    {
+        Self {
+            loader: Box::new(loader),
+            interval,
+            jitter: DEFAULT_REFRESH_JITTER,
+            max_backoff: interval * 8,
+            on_success: None,
+            on_failure: None,
+        }
+    }
+
+    /// Sets the fraction of the interval by which refreshes are randomly shifted.
+    /// The default is `DEFAULT_REFRESH_JITTER`.
+    pub fn with_jitter(mut self, fraction: f64) -> Self {
+        self.jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the maximum delay after consecutive failures.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets a hook called after each successful refresh with its summary and duration.
+    pub fn with_success_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&RefreshSummary, Duration) + Send + 'static,
+    {
+        self.on_success = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets a hook called after each failed refresh with the error and the number
+    /// of consecutive failures.
+    pub fn with_failure_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&dyn StdError, u32) + Send + 'static,
+    {
+        self.on_failure = Some(Box::new(hook));
+        self
+    }
+
+    /// Starts refreshing `reference` in a background thread. The first refresh happens
+    /// after the first delay. Refreshing stops when the returned handle is dropped.
+    pub fn start<B>(mut self, reference: Arc<Reference<T, B>>) -> RefreshHandle
+    where
+        B: Backend<T>,
+        Reference<T, B>: Send + Sync,
+    {
+        let stop = Arc::new(Stop::default());
+        let thread_stop = stop.clone();
+
+        let thread = std::thread::Builder::new()
+            .name("reference-refresh".to_string())
+            .spawn(move || {
+                let mut failures = 0;
+
+                while thread_stop.wait(self.delay(failures)) {
+                    self.refresh(&reference, &mut failures);
+                }
+            })
+            .expect("Failed to spawn refresh thread");
+
+        RefreshHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Runs the loader once and applies the result calling the hooks.
+    fn refresh<B: Backend<T>>(&mut self, reference: &Reference<T, B>, failures: &mut u32) {
+        let started = Instant::now();
+
+        let result = (self.loader)().and_then(|items| {
+            reference
+                .replace_all(items)
+                .map_err(|err| err.to_string().into())
+        });
+
+        match result {
+            Ok(summary) => {
+                *failures = 0;
+
+                if let Some(hook) = &self.on_success {
+                    hook(&summary, started.elapsed());
+                }
+            }
+            Err(err) => {
+                *failures += 1;
+                log::error!("Failed to refresh reference: {err}");
+
+                if let Some(hook) = &self.on_failure {
+                    hook(&*err, *failures);
+                }
+            }
+        }
+    }
+
+    /// Returns the delay before the next refresh after `failures` consecutive failures.
+    fn delay(&self, failures: u32) -> Duration {
+        let base = match failures {
+            0 => self.interval,
+            _ => self
+                .interval
+                .saturating_mul(1 << failures.min(16))
+                .min(self.max_backoff),
+        };
+
+        let random = RandomState::new().build_hasher().finish();
+        let shift = (random as f64 / u64::MAX as f64 * 2.0 - 1.0) * self.jitter;
+        base.mul_f64(1.0 + shift)
+    }
+}
+
+impl<T> fmt::Debug for RefreshScheduler<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshScheduler")
+            .field("interval", &self.interval)
+            .field("jitter", &self.jitter)
+            .field("max_backoff", &self.max_backoff)
+            .finish()
+    }
+}
+
+/// Stops the refresh thread of a `RefreshScheduler` when dropped.
+/// A refresh in progress is completed first.
+#[derive(Debug)]
+pub struct RefreshHandle {
+    stop: Arc<Stop>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RefreshHandle {
+    /// Stops refreshing and waits for the thread to finish.
+    pub fn stop(self) {}
+}
+
+impl Drop for RefreshHandle {
+    fn drop(&mut self) {
+        self.stop.set();
+
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Refresh thread panicked");
+            }
+        }
+    }
+}
+
+/// A stop flag a background thread waits on between runs.
+#[derive(Debug, Default)]
+pub(crate) struct Stop {
+    is_stopped: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Stop {
+    /// Waits for `timeout` returning `false` if stopped meanwhile.
+    pub(crate) fn wait(&self, timeout: Duration) -> bool {
+        let is_stopped = self
+            .is_stopped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let (is_stopped, _) = self
+            .condvar
+            .wait_timeout_while(is_stopped, timeout, |is_stopped| !*is_stopped)
+            .unwrap_or_else(PoisonError::into_inner);
+
+        !*is_stopped
+    }
+
+    pub(crate) fn set(&self) {
+        *self
+            .is_stopped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = true;
+        self.condvar.notify_all();
+    }
+}
@@ -0,0 +1,123 @@
+//! A seqlock holding plain data.
+//!
+//! Writers make the sequence odd, write the value and make it even again. Readers copy
+//! the value out and retry if the sequence was odd or has changed meanwhile so they never
+//! observe a torn value. Reads take no locks and never block writers.
+
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+
+/// A cell holding an optional `Copy` value. All-zero bytes are a valid empty cell
+/// so cells may live in zero-filled memory, e.g. a fresh shared memory segment.
+#[repr(C)]
+pub struct SeqCell<T> {
+    /// Odd while the value is being written.
+    seq: AtomicUsize,
+    is_some: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// Access to the value is synchronized with the sequence.
+unsafe impl<T: Send> Sync for SeqCell<T> {}
+
+impl<T: Copy> SeqCell<T> {
+    pub fn new(value: Option<T>) -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            is_some: AtomicBool::new(value.is_some()),
+            value: UnsafeCell::new(value.map_or(MaybeUninit::uninit(), MaybeUninit::new)),
+        }
+    }
+
+    pub fn load(&self) -> Option<T> {
+        self.read().0
+    }
+
+    /// Returns the value along with the sequence it has been read at.
+    fn read(&self) -> (Option<T>, usize) {
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+
+            if seq % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+
+            let is_some = self.is_some.load(Ordering::Relaxed);
+            // The value may be torn here so it stays uninit until the sequence is checked.
+            let value = unsafe { std::ptr::read_volatile(self.value.get()) };
+            fence(Ordering::Acquire);
+
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return (is_some.then(|| unsafe { value.assume_init() }), seq);
+            }
+        }
+    }
+
+    /// Sets `value` and returns the previous one.
+    pub fn replace(&self, value: Option<T>) -> Option<T> {
+        let seq = loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+
+            match seq % 2 == 1 || !self.begin_write(seq) {
+                true => std::hint::spin_loop(),
+                false => break seq,
+            }
+        };
+
+        self.finish_write(seq, value)
+    }
+
+    /// Read-copy-update: sets the value returned by `f` called with the current one and
+    /// returns the previous value. `f` is called without blocking other writers and
+    /// called again if one of them has written the value meanwhile.
+    pub fn rcu<F>(&self, mut f: F) -> Option<T>
+    where
+        F: FnMut(Option<T>) -> Option<T>,
+    {
+        loop {
+            let (current, seq) = self.read();
+            let new = f(current);
+
+            if self.begin_write(seq) {
+                return self.finish_write(seq, new);
+            }
+        }
+    }
+
+    /// Makes the even sequence `seq` odd so no one else writes the value.
+    /// Fails if the sequence has changed.
+    fn begin_write(&self, seq: usize) -> bool {
+        self.seq
+            .compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn finish_write(&self, seq: usize, new: Option<T>) -> Option<T> {
+        fence(Ordering::Release);
+
+        // The sequence is odd so no one else writes the value.
+        let prev = match self.is_some.load(Ordering::Relaxed) {
+            true => Some(unsafe { (*self.value.get()).assume_init() }),
+            false => None,
+        };
+
+        if let Some(value) = new {
+            unsafe { std::ptr::write_volatile(self.value.get(), MaybeUninit::new(value)) };
+        }
+
+        self.is_some.store(new.is_some(), Ordering::Relaxed);
+        self.seq.store(seq + 2, Ordering::Release);
+        prev
+    }
+}
+
+impl<T> fmt::Debug for SeqCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeqCell")
+            .field("seq", &self.seq.load(Ordering::Relaxed))
+            .finish()
+    }
+}
@@ -0,0 +1,104 @@
+//! Serialization of reference data with `serde`.
+//!
+//! - `Id` serializes and deserializes as a number.
+//! - `Entry` serializes compactly as the id of its referent or `None` if it's empty or stale.
+//!   Entry fields annotated with `#[serde(serialize_with = "reference::serialize::expanded")]`
+//!   inline the referent instead.
+//! - `Reference` serializes as a map of ids to items ordered by id. Reservations are skipped.
+
+use std::any::TypeId;
+use std::cell::RefCell;
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use super::{Backend, Entry, Id, Identifiable, Reference};
+
+thread_local! {
+    /// Referents being expanded by the current thread down the stack.
+    static EXPANDING: RefCell<Vec<(TypeId, i32)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Serializes the referent of `entry` inline or `None` if the entry is empty or stale.
+///
+/// A referent already being expanded up the stack is serialized as its id to break cycles,
+/// e.g. a category whose parent chain leads back to itself.
+pub fn expanded<T, B, S>(entry: &Entry<T, B>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Identifiable + Serialize + 'static,
+    B: Backend<T>,
+    S: Serializer,
+{
+    let Some(item) = entry.load() else {
+        return serializer.serialize_none();
+    };
+
+    let key = (TypeId::of::<T>(), item.id().as_i32());
+
+    if EXPANDING.with(|expanding| expanding.borrow().contains(&key)) {
+        return serializer.serialize_some(&item.id());
+    }
+
+    EXPANDING.with(|expanding| expanding.borrow_mut().push(key));
+    let _guard = ExpandingGuard;
+    serializer.serialize_some(&*item)
+}
+
+/// Pops the current referent off the stack even if serialization panics.
+struct ExpandingGuard;
+
+impl Drop for ExpandingGuard {
+    fn drop(&mut self) {
+        EXPANDING.with(|expanding| expanding.borrow_mut().pop());
+    }
+}
+
+/// An entry serialized with its referent inlined like `expanded` does.
+pub struct Expanded<'a, T: 'static, B: Backend<T>>(pub &'a Entry<T, B>);
+
+impl<T, B> Serialize for Expanded<'_, T, B>
+where
+    T: Identifiable + Serialize + 'static,
+    B: Backend<T>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        expanded(self.0, serializer)
+    }
+}
+
+impl<T> Serialize for Id<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.as_i32())
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Id<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        i32::deserialize(deserializer).map(Id::new)
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Serialize for Entry<T, B> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.load() {
+            Some(item) => serializer.serialize_some(&item.id()),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<T, B> Serialize for Reference<T, B>
+where
+    T: Identifiable + Serialize + 'static,
+    B: Backend<T>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        for item in self.iter_ordered().filter_map(|entry| entry.load()) {
+            map.serialize_entry(&item.id(), &*item)?;
+        }
+
+        map.end()
+    }
+}
@@ -0,0 +1,472 @@
+//! Shared memory backend for references shared between processes.
+//!
+//! One process creates the segment with `ShmBackend::create` and maintains a reference
+//! on top of it as usual. Other processes open it with `ShmReader::open` and get a read-only
+//! view with `Reference::from_shm_reader`. Segments are plain files so on Linux they're
+//! supposed to live in `/dev/shm`.
+//!
+//! # Epoch protocol
+//!
+//! Each slot is a seqlock so readers never observe a torn value. The segment header has a global epoch
+//! incremented after each write. Slots keep the ids they're taken by so readers index
+//! reservations as well as items. Readers index ids when the view is created and call
+//! `Reference::sync_with_writer` to pick up slots added or removed by the writer since then.
+//! Replaced values are visible without syncing.
+//!
+//! Items are copied out of the segment on each load so `T` must be plain data.
+//! Update times of slots are relative to the clock of the writer process so
+//! `Reference::stale_ids` and `Stats::oldest_update_age` are meaningful only there.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use memmap2::MmapOptions;
+
+use super::id_index::default_id_index;
+use super::poison::INDEX_LOCK;
+use super::seq_cell::SeqCell;
+use super::{Backend, Error, Id, Identifiable, Reference, SlotMeta};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"refshm02");
+
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    /// Size of a slot to detect mismatching item types.
+    slot_size: AtomicU64,
+    capacity: AtomicU64,
+    len: AtomicUsize,
+    epoch: AtomicU64,
+}
+
+/// A slot of a shared memory segment.
+#[repr(C)]
+pub struct ShmSlot<T> {
+    meta: SlotMeta,
+    /// The slot's own vid to find the header from the slot.
+    vid: AtomicUsize,
+    /// The id the slot is taken by. Zero until it's assigned except for the zero element.
+    id: AtomicI32,
+    value: SeqCell<T>,
+}
+
+impl<T: Copy + 'static> ShmSlot<T> {
+    fn load(&self) -> Option<T> {
+        self.value.load()
+    }
+
+    /// Sets `value` and returns the previous one.
+    fn replace(&self, value: Option<T>) -> Option<T> {
+        let prev = self.value.replace(value);
+        self.header().epoch.fetch_add(1, Ordering::Release);
+        prev
+    }
+
+    /// Sets a value computed from the current one and returns the current one.
+    fn rcu<F>(&self, f: F) -> Option<T>
+    where
+        F: FnMut(Option<T>) -> Option<T>,
+    {
+        let prev = self.value.rcu(f);
+        self.header().epoch.fetch_add(1, Ordering::Release);
+        prev
+    }
+
+    /// Slots are placed right after the header so it's found by the slot's vid.
+    fn header(&self) -> &Header {
+        let vid = self.vid.load(Ordering::Relaxed);
+        let offset = Segment::<Self>::slots_offset() + vid * size_of::<Self>();
+        unsafe { &*((self as *const Self as *const u8).sub(offset) as *const Header) }
+    }
+}
+
+impl<T> fmt::Debug for ShmSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmSlot")
+            .field("meta", &self.meta)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// A read-only slot of a segment opened by `ShmReader`.
+#[repr(transparent)]
+pub struct ShmReadSlot<T>(ShmSlot<T>);
+
+impl<T> fmt::Debug for ShmReadSlot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A mapped segment. Mappings are never unmapped since entries keep `'static` references
+/// to slots.
+struct Segment<S: 'static> {
+    header: &'static Header,
+    slots: *const S,
+}
+
+unsafe impl<S: Sync> Send for Segment<S> {}
+unsafe impl<S: Sync> Sync for Segment<S> {}
+
+impl<S: 'static> Segment<S> {
+    fn slots_offset() -> usize {
+        size_of::<Header>().div_ceil(align_of::<S>()) * align_of::<S>()
+    }
+
+    fn size(capacity: usize) -> usize {
+        Self::slots_offset() + capacity * size_of::<S>()
+    }
+
+    /// Maps the whole `file` which must be at least `size` bytes long.
+    unsafe fn map(file: &File, size: usize, read_only: bool) -> io::Result<Self> {
+        let mut options = MmapOptions::new();
+        options.len(size);
+
+        let map = match read_only {
+            true => options.map_raw_read_only(file)?,
+            false => options.map_raw(file)?,
+        };
+
+        let ptr = map.as_ptr();
+        std::mem::forget(map);
+
+        Ok(Self {
+            header: &*(ptr as *const Header),
+            slots: ptr.add(Self::slots_offset()) as *const S,
+        })
+    }
+
+    fn capacity(&self) -> usize {
+        self.header.capacity.load(Ordering::Relaxed) as usize
+    }
+
+    fn len(&self) -> usize {
+        self.header.len.load(Ordering::Acquire)
+    }
+
+    fn slot(&self, vid: usize) -> Option<&'static S> {
+        match vid < self.len() {
+            true => Some(unsafe { &*self.slots.add(vid) }),
+            false => None,
+        }
+    }
+
+    fn iter(&self) -> ShmIter<S> {
+        ShmIter {
+            slots: self.slots,
+            len: self.len(),
+            vid: 0,
+        }
+    }
+}
+
+/// Iterates over slots of a segment.
+pub struct ShmIter<S: 'static> {
+    slots: *const S,
+    len: usize,
+    vid: usize,
+}
+
+unsafe impl<S: Sync> Send for ShmIter<S> {}
+unsafe impl<S: Sync> Sync for ShmIter<S> {}
+
+impl<S: 'static> Iterator for ShmIter<S> {
+    type Item = &'static S;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.vid < self.len {
+            let slot = unsafe { &*self.slots.add(self.vid) };
+            self.vid += 1;
+            Some(slot)
+        } else {
+            None
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The writer side backend keeping slots in a shared memory segment.
+///
+/// Only one process may write a segment at a time.
+pub struct ShmBackend<T: 'static> {
+    segment: Segment<ShmSlot<T>>,
+}
+
+impl<T: Copy + 'static> ShmBackend<T> {
+    /// Creates a segment file at `path` for `capacity` slots replacing an existing one.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be plain data meaningful in other processes, i.e. having no pointers,
+    /// and all processes must be built from the same code so slot layouts match.
+    /// The file must not be changed other than through this backend.
+    pub unsafe fn create(path: impl AsRef<Path>, capacity: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let size = Segment::<ShmSlot<T>>::size(capacity);
+        file.set_len(size as u64)?;
+        let segment = Segment::<ShmSlot<T>>::map(&file, size, false)?;
+
+        // The file is zero-filled which is a valid state for the rest of the header and slots.
+        let header = segment.header;
+        let slot_size = size_of::<ShmSlot<T>>() as u64;
+        header.slot_size.store(slot_size, Ordering::Relaxed);
+        header.capacity.store(capacity as u64, Ordering::Relaxed);
+        header.magic.store(MAGIC, Ordering::Release);
+
+        Ok(Self { segment })
+    }
+}
+
+impl<T: Copy + 'static> Backend<T> for ShmBackend<T> {
+    type Slot = ShmSlot<T>;
+    type Iter = ShmIter<ShmSlot<T>>;
+
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot> {
+        self.segment.slot(vid)
+    }
+
+    fn push_slot(&self, item: Option<Arc<T>>) -> Result<usize, Error<T>> {
+        let vid = self.segment.len();
+
+        if vid >= self.segment.capacity() {
+            return Err(Error::InsertError(format!(
+                "Capacity exceeded ({})",
+                self.segment.capacity()
+            )));
+        }
+
+        let slot = unsafe { &*self.segment.slots.add(vid) };
+        slot.vid.store(vid, Ordering::Relaxed);
+        slot.replace(item.as_deref().copied());
+        self.segment.header.len.store(vid + 1, Ordering::Release);
+        Ok(vid)
+    }
+
+    fn len(&self) -> usize {
+        self.segment.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.segment.capacity()
+    }
+
+    fn iter(&self) -> Self::Iter {
+        self.segment.iter()
+    }
+
+    fn load(slot: &Self::Slot) -> Option<Arc<T>> {
+        slot.load().map(Arc::new)
+    }
+
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        f(slot.load().as_ref())
+    }
+
+    fn meta(slot: &Self::Slot) -> &SlotMeta {
+        &slot.meta
+    }
+
+    fn assign_id(slot: &Self::Slot, id: Id<T>) {
+        slot.id.store(id.as_i32(), Ordering::Relaxed);
+        // Readers sync again if they've indexed the slot before the id is set.
+        slot.header().epoch.fetch_add(1, Ordering::Release);
+    }
+
+    fn store(slot: &Self::Slot, item: Option<Arc<T>>) -> Option<Arc<T>> {
+        slot.replace(item.as_deref().copied()).map(Arc::new)
+    }
+
+    fn rcu<F>(slot: &Self::Slot, mut f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>,
+    {
+        slot.rcu(|current| f(&current.map(Arc::new)).as_deref().copied())
+            .map(Arc::new)
+    }
+}
+
+impl<T: 'static> fmt::Debug for ShmBackend<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmBackend")
+            .field("len", &self.segment.len())
+            .field("capacity", &self.segment.capacity())
+            .finish()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The reader side backend mapping a segment created by `ShmBackend` read-only.
+/// See `Reference::from_shm_reader`.
+pub struct ShmReader<T: 'static> {
+    segment: Segment<ShmReadSlot<T>>,
+    /// The epoch of the last indexing.
+    indexed_epoch: AtomicU64,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: Copy + 'static> ShmReader<T> {
+    /// Opens the segment file at `path` created by `ShmBackend::create`.
+    ///
+    /// # Safety
+    ///
+    /// Same as for `ShmBackend::create`.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let header_size = size_of::<Header>();
+        let header_segment = Segment::<ShmReadSlot<T>>::map(&file, header_size, true)?;
+        let header = header_segment.header;
+
+        let slot_size = size_of::<ShmSlot<T>>() as u64;
+
+        if header.magic.load(Ordering::Acquire) != MAGIC
+            || header.slot_size.load(Ordering::Relaxed) != slot_size
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Failed to open shared memory reference: layout mismatch",
+            ));
+        }
+
+        let size = Segment::<ShmReadSlot<T>>::size(header_segment.capacity());
+
+        Ok(Self {
+            segment: Segment::map(&file, size, true)?,
+            indexed_epoch: AtomicU64::new(u64::MAX),
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<T: Copy + 'static> Backend<T> for ShmReader<T> {
+    type Slot = ShmReadSlot<T>;
+    type Iter = ShmIter<ShmReadSlot<T>>;
+
+    fn slot(&self, vid: usize) -> Option<&'static Self::Slot> {
+        self.segment.slot(vid)
+    }
+
+    fn push_slot(&self, _item: Option<Arc<T>>) -> Result<usize, Error<T>> {
+        Err(Error::InsertError(
+            "Failed to add a slot to a read-only shared memory segment".into(),
+        ))
+    }
+
+    fn len(&self) -> usize {
+        self.segment.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.segment.capacity()
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    fn iter(&self) -> Self::Iter {
+        self.segment.iter()
+    }
+
+    fn load(slot: &Self::Slot) -> Option<Arc<T>> {
+        slot.0.load().map(Arc::new)
+    }
+
+    fn peek<R, F>(slot: &Self::Slot, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        f(slot.0.load().as_ref())
+    }
+
+    fn meta(slot: &Self::Slot) -> &SlotMeta {
+        &slot.0.meta
+    }
+
+    fn store(_slot: &Self::Slot, _item: Option<Arc<T>>) -> Option<Arc<T>> {
+        panic!("Failed to store into a read-only shared memory segment");
+    }
+
+    fn rcu<F>(_slot: &Self::Slot, _f: F) -> Option<Arc<T>>
+    where
+        F: FnMut(&Option<Arc<T>>) -> Option<Arc<T>>,
+    {
+        panic!("Failed to store into a read-only shared memory segment");
+    }
+}
+
+impl<T: 'static> fmt::Debug for ShmReader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmReader")
+            .field("len", &self.segment.len())
+            .field("capacity", &self.segment.capacity())
+            .finish()
+    }
+}
+
+impl<T: Identifiable + Copy + 'static> Reference<T, ShmReader<T>> {
+    /// Creates a read-only view of a reference maintained by another process.
+    /// Changing methods fail and `Entry::modify` panics.
+    pub fn from_shm_reader(reader: ShmReader<T>) -> Self {
+        let reference = Self::from_parts(reader, default_id_index(0));
+        reference.sync_with_writer();
+        reference
+    }
+
+    /// Indexes ids added or removed by the writer since the last sync.
+    /// Returns `false` if there were no changes.
+    pub fn sync_with_writer(&self) -> bool {
+        let epoch = self.items.segment.header.epoch.load(Ordering::Acquire);
+
+        if self.items.indexed_epoch.swap(epoch, Ordering::AcqRel) == epoch {
+            return false;
+        }
+
+        let mut vids = self.recovered_lock(self.vids.write(), INDEX_LOCK);
+        let ids = vids.iter().map(|(id, _)| id).collect::<Vec<_>>();
+
+        for id in ids {
+            vids.remove(id);
+        }
+
+        for (vid, slot) in self.items.iter().enumerate() {
+            if ShmReader::meta(slot).is_free() {
+                continue;
+            }
+
+            let id = Id::new(slot.0.id.load(Ordering::Relaxed));
+
+            // Only the zero element has id 0 while other slots may be yet to get their ids.
+            if id == 0.into() && vid != 0 {
+                continue;
+            }
+
+            if let Some(bloom) = &self.bloom {
+                bloom.insert(id);
+            }
+
+            vids.insert(id, vid as u32);
+        }
+
+        true
+    }
+}
@@ -0,0 +1,415 @@
+//! Persisting references to files and restoring them.
+//!
+//! A snapshot starts with a header: the magic bytes, the format version, the compression method,
+//! the capacity of the saved reference, the schema version of its `Codec` and a CRC-32 of them
+//! all so a damaged capacity can't cause a huge allocation. Chunks of items follow, each prefixed by
+//! its uncompressed and stored lengths and a CRC-32 of them along with the stored bytes.
+//! A chunk header of zeros ends the chunks and is followed by a trailer with the numbers
+//! of items and chunks and their CRC-32. Items in a chunk are laid out as the id, the length
+//! of the encoded item and the item encoded with a `Codec`. All integers are little-endian.
+//!
+//! Chunks are compressed independently so neither saving nor loading holds more than a chunk
+//! of encoded items in memory.
+
+use std::io::{ErrorKind, Read, Write};
+
+use super::{Backend, Codec, Error, Identifiable, Reference};
+
+const MAGIC: &[u8; 8] = b"REFSNAP\0";
+const FORMAT_VERSION: u8 = 4;
+const HEADER_LEN: usize = MAGIC.len() + 18;
+const CHUNK_HEADER_LEN: usize = 12;
+const TRAILER_LEN: usize = 20;
+
+/// Number of bytes of encoded items to collect before compressing them into a chunk.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Compression of snapshot chunks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    #[default]
+    None,
+    /// Zstandard with the given level. Zero means the library default.
+    #[cfg(feature = "zstd")]
+    Zstd { level: i32 },
+    #[cfg(feature = "lz4_flex")]
+    Lz4,
+}
+
+impl Compression {
+    fn tag(self) -> u8 {
+        match self {
+            Self::None => 0,
+            #[cfg(feature = "zstd")]
+            Self::Zstd { .. } => 1,
+            #[cfg(feature = "lz4_flex")]
+            Self::Lz4 => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::None),
+            #[cfg(feature = "zstd")]
+            1 => Some(Self::Zstd { level: 0 }),
+            #[cfg(feature = "lz4_flex")]
+            2 => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    fn compress(self, raw: &[u8], out: &mut Vec<u8>) -> std::io::Result<()> {
+        match self {
+            Self::None => out.extend_from_slice(raw),
+            #[cfg(feature = "zstd")]
+            Self::Zstd { level } => out.extend(zstd::bulk::compress(raw, level)?),
+            #[cfg(feature = "lz4_flex")]
+            Self::Lz4 => out.extend(lz4_flex::block::compress(raw)),
+        }
+
+        Ok(())
+    }
+
+    fn decompress(self, stored: &[u8], raw_len: usize, out: &mut Vec<u8>) -> std::io::Result<()> {
+        match self {
+            Self::None => out.extend_from_slice(stored),
+            #[cfg(feature = "zstd")]
+            Self::Zstd { .. } => out.extend(zstd::bulk::decompress(stored, raw_len)?),
+            #[cfg(feature = "lz4_flex")]
+            Self::Lz4 => out.extend(
+                lz4_flex::block::decompress(stored, raw_len)
+                    .map_err(|err| std::io::Error::new(ErrorKind::InvalidData, err))?,
+            ),
+        }
+
+        match out.len() == raw_len {
+            true => Ok(()),
+            false => Err(invalid_data("Chunk length mismatch")),
+        }
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Writes all items encoded with `codec` to `writer` without compression.
+    /// See `save_compressed_to`.
+    pub fn save_to<W, C>(&self, writer: W, codec: &C) -> Result<(), Error<T>>
+    where
+        W: Write,
+        C: Codec<T> + ?Sized,
+    {
+        self.save_compressed_to(writer, codec, Compression::None)
+    }
+
+    /// Writes all items encoded with `codec` to `writer` compressing them chunk by chunk.
+    ///
+    /// Items changed while saving may be written either in the old or in the new state.
+    pub fn save_compressed_to<W, C>(
+        &self,
+        mut writer: W,
+        codec: &C,
+        compression: Compression,
+    ) -> Result<(), Error<T>>
+    where
+        W: Write,
+        C: Codec<T> + ?Sized,
+    {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(FORMAT_VERSION);
+        header.push(compression.tag());
+        header.extend_from_slice(&(self.items.capacity() as u64).to_le_bytes());
+        header.extend_from_slice(&codec.schema_version().to_le_bytes());
+        header.extend_from_slice(&crc32fast::hash(&header).to_le_bytes());
+        writer.write_all(&header).map_err(io_error)?;
+
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        let mut buf = Vec::new();
+        let mut stored = Vec::new();
+        let mut counts = Counts::default();
+
+        for item in self.iter().filter_map(|entry| entry.load()) {
+            buf.clear();
+            codec.encode(&item, &mut buf).map_err(Error::CodecError)?;
+            chunk.extend_from_slice(&item.id().as_i32().to_le_bytes());
+            chunk.extend_from_slice(&(buf.len() as u32).to_le_bytes());
+            chunk.extend_from_slice(&buf);
+            counts.items += 1;
+
+            if chunk.len() >= CHUNK_SIZE {
+                write_chunk(&mut writer, &chunk, &mut stored, compression)?;
+                counts.chunks += 1;
+                chunk.clear();
+            }
+        }
+
+        if !chunk.is_empty() {
+            write_chunk(&mut writer, &chunk, &mut stored, compression)?;
+            counts.chunks += 1;
+        }
+
+        writer.write_all(&[0; CHUNK_HEADER_LEN]).map_err(io_error)?;
+
+        writer.write_all(&counts.to_bytes()).map_err(io_error)?;
+        writer.flush().map_err(io_error)
+    }
+}
+
+impl<T: Identifiable + 'static> Reference<T> {
+    /// Creates a `Reference<T>` with the capacity of the saved one and fills it with items
+    /// read from a snapshot written by `save_to` or `save_compressed_to`.
+    ///
+    /// Checksums of chunks and counts in the trailer are verified so a damaged or truncated
+    /// snapshot fails with `Error::CorruptSnapshot` instead of loading partially.
+    ///
+    /// Items saved with another `Codec::schema_version` are decoded with `Codec::migrate`.
+    pub fn load_from<R: Read, C: Codec<T>>(reader: R, codec: &C) -> Result<Self, Error<T>> {
+        read_snapshot(reader, codec, Self::try_new)
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Like `load_from` but decodes items without inserting them anywhere.
+    pub(crate) fn decode_snapshot<R, C>(reader: R, codec: &C) -> Result<Vec<T>, Error<T>>
+    where
+        R: Read,
+        C: Codec<T> + ?Sized,
+    {
+        read_snapshot(reader, codec, |_| Ok(Vec::new()))
+    }
+}
+
+/// Where decoded items of a snapshot go.
+trait Sink<T> {
+    fn push(&mut self, item: T) -> Result<(), Error<T>>;
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Sink<T> for Reference<T, B> {
+    fn push(&mut self, item: T) -> Result<(), Error<T>> {
+        self.insert(item).map(|_| ())
+    }
+}
+
+impl<T> Sink<T> for Vec<T> {
+    fn push(&mut self, item: T) -> Result<(), Error<T>> {
+        Vec::push(self, item);
+        Ok(())
+    }
+}
+
+/// Reads a snapshot pushing items to the sink returned by `start` called with
+/// the saved capacity.
+fn read_snapshot<T, R, C, F, S>(reader: R, codec: &C, start: F) -> Result<S, Error<T>>
+where
+    T: Identifiable + 'static,
+    R: Read,
+    C: Codec<T> + ?Sized,
+    F: FnOnce(usize) -> Result<S, Error<T>>,
+    S: Sink<T>,
+{
+    let mut reader = OffsetReader {
+        inner: reader,
+        offset: 0,
+    };
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(Error::CorruptSnapshot { offset: 0 });
+    }
+
+    let version = header[MAGIC.len()];
+
+    if version != FORMAT_VERSION {
+        let msg = format!("Unsupported snapshot format version {version}");
+        return Err(io_error(invalid_data(msg)));
+    }
+
+    let checksum = u32::from_le_bytes(read_array(&header[HEADER_LEN - 4..]));
+
+    if crc32fast::hash(&header[..HEADER_LEN - 4]) != checksum {
+        return Err(Error::CorruptSnapshot { offset: 0 });
+    }
+
+    let tag = header[MAGIC.len() + 1];
+
+    let compression = Compression::from_tag(tag).ok_or_else(|| {
+        io_error(invalid_data(format!(
+            "Unsupported snapshot compression {tag}"
+        )))
+    })?;
+
+    let capacity = u64::from_le_bytes(read_array(&header[MAGIC.len() + 2..])) as usize;
+    let schema_version = u32::from_le_bytes(read_array(&header[MAGIC.len() + 10..]));
+    let needs_migration = schema_version != codec.schema_version();
+    let mut sink = start(capacity)?;
+    let mut stored = Vec::new();
+    let mut chunk = Vec::new();
+    let mut counts = Counts::default();
+
+    loop {
+        let offset = reader.offset;
+        let mut chunk_header = [0; CHUNK_HEADER_LEN];
+        reader.read_exact(&mut chunk_header)?;
+        let raw_len = u32::from_le_bytes(read_array(&chunk_header[..4])) as usize;
+        let stored_len = u32::from_le_bytes(read_array(&chunk_header[4..8]));
+        let checksum = u32::from_le_bytes(read_array(&chunk_header[8..]));
+
+        if stored_len == 0 {
+            break;
+        }
+
+        // Reading through `take` allocates only as much as the snapshot actually has
+        // so a damaged length can't cause a huge allocation.
+        stored.clear();
+        reader.read_to_vec(stored_len as u64, &mut stored)?;
+
+        if chunk_checksum(&chunk_header[..8], &stored) != checksum {
+            return Err(Error::CorruptSnapshot { offset });
+        }
+
+        chunk.clear();
+
+        compression
+            .decompress(&stored, raw_len, &mut chunk)
+            .map_err(|_| Error::CorruptSnapshot { offset })?;
+
+        let mut rest = chunk.as_slice();
+
+        while !rest.is_empty() {
+            let (bytes, tail) = split_item(rest).map_err(|_| Error::CorruptSnapshot { offset })?;
+
+            rest = tail;
+            let item = match needs_migration {
+                true => codec.migrate(schema_version, bytes),
+                false => codec.decode(bytes),
+            };
+
+            sink.push(item.map_err(Error::CodecError)?)?;
+            counts.items += 1;
+        }
+
+        counts.chunks += 1;
+    }
+
+    let offset = reader.offset;
+    let mut trailer = [0; TRAILER_LEN];
+    reader.read_exact(&mut trailer)?;
+
+    match trailer == counts.to_bytes() {
+        true => Ok(sink),
+        false => Err(Error::CorruptSnapshot { offset }),
+    }
+}
+
+/// Numbers of items and chunks written to the trailer.
+#[derive(Default)]
+struct Counts {
+    items: u64,
+    chunks: u64,
+}
+
+impl Counts {
+    /// Serializes the counts followed by their checksum.
+    fn to_bytes(&self) -> [u8; TRAILER_LEN] {
+        let mut bytes = [0; TRAILER_LEN];
+        bytes[..8].copy_from_slice(&self.items.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.chunks.to_le_bytes());
+        let checksum = crc32fast::hash(&bytes[..16]);
+        bytes[16..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+}
+
+/// Tracks the position in the snapshot to report where it's corrupt. A part which fails
+/// to be read entirely is reported at its start.
+struct OffsetReader<R> {
+    inner: R,
+    offset: u64,
+}
+
+impl<R: Read> OffsetReader<R> {
+    fn read_exact<T>(&mut self, buf: &mut [u8]) -> Result<(), Error<T>> {
+        self.inner.read_exact(buf).map_err(|err| self.error(err))?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    fn read_to_vec<T>(&mut self, len: u64, buf: &mut Vec<u8>) -> Result<(), Error<T>> {
+        let read = (&mut self.inner)
+            .take(len)
+            .read_to_end(buf)
+            .map_err(|err| self.error(err))?;
+
+        if (read as u64) < len {
+            return Err(Error::CorruptSnapshot {
+                offset: self.offset,
+            });
+        }
+
+        self.offset += len;
+        Ok(())
+    }
+
+    fn error<T>(&self, err: std::io::Error) -> Error<T> {
+        match err.kind() {
+            ErrorKind::UnexpectedEof => Error::CorruptSnapshot {
+                offset: self.offset,
+            },
+            _ => io_error(err),
+        }
+    }
+}
+
+fn write_chunk<T, W: Write>(
+    writer: &mut W,
+    chunk: &[u8],
+    stored: &mut Vec<u8>,
+    compression: Compression,
+) -> Result<(), Error<T>> {
+    stored.clear();
+    compression.compress(chunk, stored).map_err(io_error)?;
+
+    let mut header = [0; CHUNK_HEADER_LEN];
+    header[..4].copy_from_slice(&(chunk.len() as u32).to_le_bytes());
+    header[4..8].copy_from_slice(&(stored.len() as u32).to_le_bytes());
+    let checksum = chunk_checksum(&header[..8], stored);
+    header[8..].copy_from_slice(&checksum.to_le_bytes());
+
+    writer.write_all(&header).map_err(io_error)?;
+    writer.write_all(stored).map_err(io_error)
+}
+
+/// Checksum of a chunk covering its lengths so a damaged length is detected too.
+fn chunk_checksum(lengths: &[u8], stored: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(lengths);
+    hasher.update(stored);
+    hasher.finalize()
+}
+
+/// Splits encoded bytes of the first item off `chunk` skipping its id.
+fn split_item(chunk: &[u8]) -> std::io::Result<(&[u8], &[u8])> {
+    let len = chunk
+        .get(4..8)
+        .map(|bytes| u32::from_le_bytes(read_array(bytes)) as usize)
+        .ok_or_else(|| invalid_data("Truncated item header"))?;
+
+    let bytes = chunk
+        .get(8..8 + len)
+        .ok_or_else(|| invalid_data("Truncated item"))?;
+
+    Ok((bytes, &chunk[8 + len..]))
+}
+
+fn read_array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes[..N].try_into().expect("Wrong slice length")
+}
+
+fn invalid_data(msg: impl Into<String>) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, msg.into())
+}
+
+fn io_error<T>(err: std::io::Error) -> Error<T> {
+    Error::Other(Box::new(err))
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use super::{Backend, Id, Identifiable, Reference};
+
+/// Detection of items which haven't been updated for long.
+///
+/// Each slot records when its value was last set by inserting, replacing or modifying the item.
+/// Times are taken from the reference's clock. See `with_clock`.
+/// Reservations which have never been resolved have no update time and aren't considered.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Returns ids of unpinned items last updated more than `older_than` ago.
+    pub fn stale_ids(&self, older_than: Duration) -> Vec<Id<T>> {
+        let now = self.now();
+        let pins = self.pinned_ids();
+
+        self.update_times()
+            .filter(|(_, updated_at)| now.saturating_duration_since(*updated_at) > older_than)
+            .filter(|(id, _)| !pins.contains(id))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Returns the fraction of items last updated more than `older_than` ago counting
+    /// pinned ones as fresh.
+    pub(crate) fn stale_fraction(&self, older_than: Duration) -> f64 {
+        let now = self.now();
+        let pins = self.pinned_ids();
+        let (mut stale, mut total) = (0, 0);
+
+        for (id, updated_at) in self.update_times() {
+            total += 1;
+
+            if now.saturating_duration_since(updated_at) > older_than && !pins.contains(&id) {
+                stale += 1;
+            }
+        }
+
+        match total {
+            0 => 0.0,
+            total => stale as f64 / total as f64,
+        }
+    }
+
+    /// Returns the time since the least recently updated item was updated.
+    pub(crate) fn oldest_update_age(&self) -> Option<Duration> {
+        let oldest = self
+            .update_times()
+            .map(|(_, updated_at)| updated_at)
+            .min()?;
+        Some(self.now().saturating_duration_since(oldest))
+    }
+
+    /// Iterates over ids of items along with their update times.
+    fn update_times(&self) -> impl Iterator<Item = (Id<T>, Instant)> + '_ {
+        self.items
+            .iter()
+            .filter(|slot| !B::meta(slot).is_free())
+            .filter_map(|slot| {
+                let updated_at = B::meta(slot).updated_at()?;
+                let id = B::peek(slot, |maybe_item| maybe_item.map(T::id))?;
+                Some((id, updated_at))
+            })
+    }
+}
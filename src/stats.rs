@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use super::sync::Ordering;
+use super::{Backend, Identifiable, Reference};
+
+/// Runtime statistics of a `Reference`. See `Reference::stats`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "http", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct Stats {
+    /// Number of slots in use including reservations and the zero element.
+    pub len: usize,
+    /// Maximum number of slots.
+    pub capacity: usize,
+    /// Approximate number of bytes allocated by the id index.
+    pub id_index_memory: usize,
+    /// Number of times the id index lock had to wait for another holder.
+    pub index_lock_contentions: usize,
+    /// Number of times the free slot list lock had to wait for another holder.
+    pub free_list_lock_contentions: usize,
+    /// Number of times the `insert_pooled` allocation pool lock had to wait for another holder.
+    pub pool_lock_contentions: usize,
+    /// Time since the least recently updated item was updated. See `Reference::stale_ids`.
+    pub oldest_update_age: Option<Duration>,
+    /// Number of items evicted to make room for new ones. See `CapacityPolicy`.
+    pub evictions: usize,
+    /// Number of pinned items. See `Reference::pin`.
+    pub pinned: usize,
+    /// Fingerprint of all items if a content hash is set. See `Reference::fingerprint`.
+    pub fingerprint: Option<u64>,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Collects the current statistics. Counters are relaxed so they may lag a bit
+    /// under concurrent access. Finding the oldest update takes a pass over all slots
+    /// and so does the fingerprint if a content hash is set.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            len: self.used_slots(),
+            capacity: self.items.capacity(),
+            id_index_memory: self.id_index_memory_usage(),
+            index_lock_contentions: self.vids.contentions(),
+            free_list_lock_contentions: self.free_vids.contentions(),
+            pool_lock_contentions: self.pool.contentions(),
+            oldest_update_age: self.oldest_update_age(),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            pinned: self.pinned_count(),
+            fingerprint: self.fingerprint(),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use super::progress::{Progress, ProgressMeter};
+use super::{Backend, Entry, Identifiable, Iter, Reference};
+
+/// Default number of entries yielded by `EntryStream` before giving control back to the runtime.
+pub const DEFAULT_YIELD_EVERY: usize = 1024;
+
+/// Default number of items `load_stream` takes from the source stream at once.
+pub const DEFAULT_LOAD_BATCH_SIZE: usize = 1024;
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Creates a stream over entries which cooperatively yields to the runtime
+    /// every `DEFAULT_YIELD_EVERY` items.
+    pub fn stream(&self) -> EntryStream<T, B> {
+        self.stream_with_yield_every(DEFAULT_YIELD_EVERY)
+    }
+
+    /// Like `stream` but yields to the runtime every `yield_every` items.
+    /// Zero `yield_every` disables yielding.
+    pub fn stream_with_yield_every(&self, yield_every: usize) -> EntryStream<T, B> {
+        EntryStream::new(Iter::new(self.items.iter()), yield_every)
+    }
+
+    /// Consumes a stream of items inserting them in batches of `DEFAULT_LOAD_BATCH_SIZE`.
+    /// Errors either from the stream or from insertion are counted and skipped.
+    pub async fn load_stream<S, E>(&self, stream: S) -> LoadSummary
+    where
+        S: Stream<Item = Result<T, E>>,
+    {
+        self.load_stream_with_batch_size(stream, DEFAULT_LOAD_BATCH_SIZE)
+            .await
+    }
+
+    /// Like `load_stream` but with custom batch size.
+    ///
+    /// The source stream is polled only when the previous batch is inserted so a fast producer
+    /// can't outrun insertion. After each batch the task yields to the runtime.
+    pub async fn load_stream_with_batch_size<S, E>(
+        &self,
+        stream: S,
+        batch_size: usize,
+    ) -> LoadSummary
+    where
+        S: Stream<Item = Result<T, E>>,
+    {
+        self.load_stream_inner(
+            stream,
+            batch_size,
+            &mut None::<ProgressMeter<fn(&Progress)>>,
+        )
+        .await
+    }
+
+    /// Like `load_stream` but calls `callback` with the progress after each batch and once
+    /// done. The progress has `total` as the expected number of items if known.
+    /// The reference is loading meanwhile. See `readiness`.
+    pub async fn load_stream_with_progress<S, E, F>(
+        &self,
+        stream: S,
+        total: Option<usize>,
+        callback: F,
+    ) -> LoadSummary
+    where
+        S: Stream<Item = Result<T, E>>,
+        F: FnMut(&Progress),
+    {
+        let (hydration, meter) = self.start_hydration(total, DEFAULT_LOAD_BATCH_SIZE, callback);
+        let mut meter = Some(meter);
+
+        let summary = self
+            .load_stream_inner(stream, DEFAULT_LOAD_BATCH_SIZE, &mut meter)
+            .await;
+
+        if let Some(meter) = meter {
+            self.finish_hydration(hydration, meter);
+        }
+
+        summary
+    }
+
+    async fn load_stream_inner<S, E, F>(
+        &self,
+        stream: S,
+        batch_size: usize,
+        meter: &mut Option<ProgressMeter<F>>,
+    ) -> LoadSummary
+    where
+        S: Stream<Item = Result<T, E>>,
+        F: FnMut(&Progress),
+    {
+        self.begin_batch();
+        let mut stream = pin!(stream);
+        let batch_size = batch_size.max(1);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut summary = LoadSummary::default();
+        let mut is_exhausted = false;
+
+        while !is_exhausted {
+            let mut stream_errors = 0;
+
+            while batch.len() < batch_size {
+                match poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+                    Some(Ok(item)) => batch.push(item),
+                    Some(Err(_)) => stream_errors += 1,
+                    None => {
+                        is_exhausted = true;
+                        break;
+                    }
+                }
+            }
+
+            let processed = batch.len() + stream_errors;
+            summary.failed += stream_errors;
+
+            for item in batch.drain(..) {
+                match self.insert_inner(item) {
+                    Ok((_, false)) => summary.inserted += 1,
+                    Ok((_, true)) => summary.replaced += 1,
+                    Err(_) => summary.failed += 1,
+                }
+            }
+
+            if let Some(meter) = meter {
+                meter.advance(processed, self.now());
+            }
+
+            YieldNow::default().await;
+        }
+
+        summary
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Result of `Reference::load_stream`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadSummary {
+    /// Number of items added to the reference.
+    pub inserted: usize,
+    /// Number of items which replaced already resolved entries.
+    pub replaced: usize,
+    /// Number of stream errors and failed insertions.
+    pub failed: usize,
+}
+
+/// Returns `Poll::Pending` once waking the task immediately.
+#[derive(Default)]
+struct YieldNow {
+    is_yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.is_yielded {
+            return Poll::Ready(());
+        }
+
+        self.is_yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Streams entries of `Reference<T>`.
+///
+/// Scanning a huge reference inside an async task may take a while, so the stream returns
+/// `Poll::Pending` after every `yield_every` items waking the task immediately.
+/// This lets the runtime schedule other tasks in between.
+pub struct EntryStream<T: Identifiable + 'static, B: Backend<T>> {
+    inner: Iter<T, B>,
+    yield_every: usize,
+    budget: usize,
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> EntryStream<T, B> {
+    fn new(inner: Iter<T, B>, yield_every: usize) -> Self {
+        Self {
+            inner,
+            yield_every,
+            budget: yield_every,
+        }
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> fmt::Debug for EntryStream<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntryStream")
+            .field("yield_every", &self.yield_every)
+            .finish()
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Stream for EntryStream<T, B> {
+    type Item = Entry<T, B>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.yield_every > 0 {
+            if self.budget == 0 {
+                self.budget = self.yield_every;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            self.budget -= 1;
+        }
+
+        Poll::Ready(self.inner.next())
+    }
+}
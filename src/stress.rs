@@ -0,0 +1,314 @@
+//! Multi-threaded stress testing of references on the target hardware.
+//!
+//! Weakly ordered CPUs like aarch64 may expose races which never show up on x86. A
+//! `StressTest` hammers a reference with concurrent lookups, upserts, reservations, removals
+//! and scans for a while checking that every item observed is intact and belongs to the id
+//! it was looked up by. Internal invariants are verified in the end.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::fault::xorshift;
+use super::{ArcSwapBackend, Backend, Id, Identifiable, Reference};
+
+/// An item which can tell whether it's been observed partially written.
+#[derive(Clone, Copy, Debug)]
+pub struct StressItem {
+    id: Id<Self>,
+    version: u64,
+    payload: [u64; 4],
+}
+
+impl StressItem {
+    fn new(id: Id<Self>, version: u64) -> Self {
+        Self {
+            id,
+            version,
+            payload: Self::payload(id, version),
+        }
+    }
+
+    fn payload(id: Id<Self>, version: u64) -> [u64; 4] {
+        let seed = (id.as_i32() as u32 as u64) << 32 ^ version;
+        [seed, !seed, seed.rotate_left(17), seed.wrapping_mul(31)]
+    }
+
+    fn is_intact(&self) -> bool {
+        self.payload == Self::payload(self.id, self.version)
+    }
+}
+
+impl Identifiable for StressItem {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+/// Counts of operations made by a stress test.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StressReport {
+    /// Lookups by id.
+    pub reads: usize,
+    /// Lookups which have found an item.
+    pub hits: usize,
+    /// Upserts, reservations and removals.
+    pub writes: usize,
+    /// Passes over all the slots.
+    pub scans: usize,
+    pub elapsed: Duration,
+}
+
+impl StressReport {
+    /// Returns the number of lookups and writes per second.
+    pub fn ops_per_sec(&self) -> f64 {
+        (self.reads + self.writes) as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.reads += other.reads;
+        self.hits += other.hits;
+        self.writes += other.writes;
+        self.scans += other.scans;
+    }
+}
+
+/// A configurable stress test.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use reference::testing::StressTest;
+/// let report = StressTest::new()
+///     .with_threads(4)
+///     .with_duration(Duration::from_millis(50))
+///     .run()
+///     .unwrap();
+///
+/// assert!(report.reads > 0);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct StressTest {
+    threads: usize,
+    duration: Duration,
+    ids: i32,
+    write_fraction: f64,
+    seed: u64,
+}
+
+/// Operations between scans of each thread.
+const SCAN_EVERY: usize = 1024;
+
+impl StressTest {
+    /// Creates a test running a thread per CPU for a second on 1024 ids with 10% of writes.
+    pub fn new() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(4, |threads| threads.get()),
+            duration: Duration::from_secs(1),
+            ids: 1024,
+            write_fraction: 0.1,
+            seed: 1,
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Sets the number of distinct ids. Fewer ids make threads collide more often.
+    pub fn with_ids(mut self, ids: i32) -> Self {
+        self.ids = ids.max(1);
+        self
+    }
+
+    /// Sets the fraction of operations which are writes.
+    pub fn with_write_fraction(mut self, fraction: f64) -> Self {
+        self.write_fraction = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the seed of the operations each thread picks.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the test on a reference with the default backend. Returns a description
+    /// of the first inconsistency observed if any.
+    pub fn run(&self) -> Result<StressReport, String> {
+        self.run_with_backend(ArcSwapBackend::new)
+    }
+
+    /// Runs the test on a reference with a backend made by `backend` for the given capacity.
+    pub fn run_with_backend<B, F>(&self, backend: F) -> Result<StressReport, String>
+    where
+        B: Backend<StressItem>,
+        F: FnOnce(usize) -> B,
+        Reference<StressItem, B>: Sync,
+    {
+        let reference = Reference::with_backend(backend(self.ids as usize + 1));
+        let stop = AtomicBool::new(false);
+        let failure = Mutex::new(None);
+        let started = Instant::now();
+
+        let mut report = thread::scope(|scope| {
+            let handles = (0..self.threads)
+                .map(|idx| {
+                    let worker = Worker {
+                        test: self,
+                        reference: &reference,
+                        stop: &stop,
+                        state: self.seed.wrapping_add(idx as u64).max(1),
+                        report: StressReport::default(),
+                    };
+
+                    let (stop, failure) = (&stop, &failure);
+
+                    scope.spawn(move || {
+                        let report = worker.run(started);
+
+                        if let Err(ref message) = report {
+                            stop.store(true, Ordering::Relaxed);
+                            let mut failure = failure.lock().unwrap_or_else(|err| err.into_inner());
+                            failure.get_or_insert_with(|| message.clone());
+                        }
+
+                        report.ok()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut report = StressReport::default();
+
+            for handle in handles {
+                match handle.join() {
+                    Ok(Some(worker_report)) => report.add(&worker_report),
+                    Ok(None) => (),
+                    Err(_) => return Err(String::from("Stress test thread panicked")),
+                }
+            }
+
+            Ok(report)
+        })?;
+
+        if let Some(message) = failure.into_inner().unwrap_or_else(|err| err.into_inner()) {
+            return Err(message);
+        }
+
+        scan(&reference)?;
+        let invariants = reference.verify_invariants();
+
+        if !invariants.is_ok() {
+            return Err(invariants.to_string());
+        }
+
+        report.elapsed = started.elapsed();
+        Ok(report)
+    }
+}
+
+impl Default for StressTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Worker<'a, B: Backend<StressItem>> {
+    test: &'a StressTest,
+    reference: &'a Reference<StressItem, B>,
+    stop: &'a AtomicBool,
+    state: u64,
+    report: StressReport,
+}
+
+impl<B: Backend<StressItem>> Worker<'_, B> {
+    fn run(mut self, started: Instant) -> Result<StressReport, String> {
+        let mut ops = 0;
+
+        while !self.stop.load(Ordering::Relaxed) && started.elapsed() < self.test.duration {
+            ops += 1;
+
+            if ops % SCAN_EVERY == 0 {
+                scan(self.reference)?;
+                self.report.scans += 1;
+            }
+
+            let id = Id::new((self.next() % self.test.ids as u64) as i32 + 1);
+            let draw = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+            let writes = self.test.write_fraction;
+
+            if draw >= writes {
+                self.read(id)?;
+                continue;
+            }
+
+            self.report.writes += 1;
+
+            // Upserts make 60% of writes, removals and reservations 20% each.
+            let result = if draw < writes * 0.6 {
+                let version = self.next();
+                self.reference
+                    .upsert(StressItem::new(id, version))
+                    .map(|_| ())
+            } else if draw < writes * 0.8 {
+                self.reference.remove(id);
+                Ok(())
+            } else {
+                self.reference.get_or_reserve(id).map(|_| ())
+            };
+
+            result.map_err(|err| format!("Failed to write {id:?}: {err}"))?;
+        }
+
+        Ok(self.report)
+    }
+
+    fn read(&mut self, id: Id<StressItem>) -> Result<(), String> {
+        self.report.reads += 1;
+
+        if let Some(item) = self.reference.get(id).and_then(|entry| entry.load()) {
+            self.report.hits += 1;
+            check(id, &item)?;
+        }
+
+        Ok(())
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = xorshift(self.state);
+        self.state
+    }
+}
+
+/// Checks every item observed by iterating.
+fn scan<B: Backend<StressItem>>(reference: &Reference<StressItem, B>) -> Result<(), String> {
+    for item in reference.iter().filter_map(|entry| entry.load()) {
+        if !item.is_intact() {
+            return Err(format!("Observed a torn item of {:?}", item.id));
+        }
+    }
+
+    Ok(())
+}
+
+fn check(id: Id<StressItem>, item: &StressItem) -> Result<(), String> {
+    if item.id != id {
+        return Err(format!(
+            "Lookup of {id:?} returned an item of {:?}",
+            item.id
+        ));
+    }
+
+    if !item.is_intact() {
+        return Err(format!("Observed a torn item of {id:?}"));
+    }
+
+    Ok(())
+}
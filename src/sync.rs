@@ -0,0 +1,464 @@
+//! Synchronization primitives used internally.
+//!
+//! By default these are `parking_lot` locks and std atomics. With `std-sync` feature locks
+//! are taken from `std::sync` instead. With `single-thread` feature they're replaced with cheap
+//! `Cell`/`RefCell` based equivalents having the same API which makes the containing types `!Sync`.
+//!
+//! Building with `--cfg loom` replaces them with `loom` equivalents for model checking.
+//! `Arc`s stored in `ArcSwapOption` remain std ones since `arc-swap` doesn't support loom.
+//!
+//! Locks of all the flavours return `LockResult` though only `std` and `loom` ones
+//! may actually get poisoned. The caller decides whether to panic, propagate or recover.
+//! Each lock counts acquisitions which had to wait for another holder.
+//!
+//! Lengths of slot arrays are published with `LEN_PUBLISH` and read with `LEN_OBSERVE`.
+//! They're relaxed by default: a slot is reached through the id index lock which orders
+//! its initialization anyway. Iterating over slots relies on the length alone though, so
+//! `strict-ordering` feature makes them release and acquire for weakly ordered CPUs.
+
+use std::fmt;
+
+pub use std::sync::atomic::Ordering;
+pub use std::sync::{LockResult, PoisonError};
+
+/// Ordering of increments of lengths making initialized slots visible.
+#[cfg(not(feature = "strict-ordering"))]
+pub const LEN_PUBLISH: Ordering = Ordering::Relaxed;
+#[cfg(feature = "strict-ordering")]
+pub const LEN_PUBLISH: Ordering = Ordering::Release;
+
+/// Ordering of loads of lengths bounding slots which may be read.
+#[cfg(not(feature = "strict-ordering"))]
+pub const LEN_OBSERVE: Ordering = Ordering::Relaxed;
+#[cfg(feature = "strict-ordering")]
+pub const LEN_OBSERVE: Ordering = Ordering::Acquire;
+
+#[cfg(loom)]
+use self::loom as imp;
+#[cfg(all(not(loom), not(feature = "single-thread"), not(feature = "std-sync")))]
+use self::parking as imp;
+#[cfg(all(not(loom), feature = "single-thread"))]
+use self::single as imp;
+#[cfg(all(not(loom), not(feature = "single-thread"), feature = "std-sync"))]
+use self::std_sync as imp;
+
+pub use self::imp::{AtomicU8, AtomicUsize, MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+pub use self::stripe::{StripeGuard, StripeMutex};
+
+/// `Send + Sync` unless `single-thread` feature is on. A bound of type-erased parts
+/// of `Reference` so they don't make it `!Sync` in multi-threaded mode.
+#[cfg(not(feature = "single-thread"))]
+pub trait MaybeSync: Send + Sync {}
+
+#[cfg(not(feature = "single-thread"))]
+impl<T: Send + Sync + ?Sized> MaybeSync for T {}
+
+#[cfg(feature = "single-thread")]
+pub trait MaybeSync {}
+
+#[cfg(feature = "single-thread")]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Mutual exclusion lock counting contended acquisitions.
+pub struct Mutex<T> {
+    inner: imp::Mutex<T>,
+    contentions: AtomicUsize,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: imp::Mutex::new(value),
+            contentions: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        match self.inner.try_lock() {
+            Some(result) => result,
+            None => {
+                self.contentions.fetch_add(1, Ordering::Relaxed);
+                self.inner.lock()
+            }
+        }
+    }
+
+    /// Returns the number of times `lock` had to wait.
+    pub fn contentions(&self) -> usize {
+        self.contentions.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> fmt::Debug for Mutex<T>
+where
+    imp::Mutex<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.inner, f)
+    }
+}
+
+/// Reader-writer lock counting contended acquisitions and write acquisitions.
+pub struct RwLock<T> {
+    inner: imp::RwLock<T>,
+    contentions: AtomicUsize,
+    writes: AtomicUsize,
+}
+
+impl<T> RwLock<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: imp::RwLock::new(value),
+            contentions: AtomicUsize::new(0),
+            writes: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        match self.inner.try_read() {
+            Some(result) => result,
+            None => {
+                self.contentions.fetch_add(1, Ordering::Relaxed);
+                self.inner.read()
+            }
+        }
+    }
+
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        let result = match self.inner.try_write() {
+            Some(result) => result,
+            None => {
+                self.contentions.fetch_add(1, Ordering::Relaxed);
+                self.inner.write()
+            }
+        };
+
+        // Counted with the lock taken so a holder of any guard sees a stable number.
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Returns the number of times `read` or `write` had to wait.
+    pub fn contentions(&self) -> usize {
+        self.contentions.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times `write` has been called. Comparing the numbers tells
+    /// whether the value may have changed between releasing a guard and taking another.
+    pub fn writes(&self) -> usize {
+        self.writes.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> fmt::Debug for RwLock<T>
+where
+    imp::RwLock<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.inner, f)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Generates a module wrapping `std::sync`-like locks which report poisoning and
+/// `WouldBlock` through `TryLockError`.
+#[cfg(any(loom, all(not(feature = "single-thread"), feature = "std-sync")))]
+macro_rules! poisoning_locks {
+    ($module:ident, $($sync:ident)::+) => {
+        mod $module {
+            use std::fmt;
+            use std::sync::{LockResult, TryLockError, TryLockResult};
+
+            use $($sync)::+ as sync;
+            pub use $($sync)::+::atomic::{AtomicU8, AtomicUsize};
+            pub use $($sync)::+::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+
+            fn try_result<G>(result: TryLockResult<G>) -> Option<LockResult<G>> {
+                match result {
+                    Ok(guard) => Some(Ok(guard)),
+                    Err(TryLockError::Poisoned(err)) => Some(Err(err)),
+                    Err(TryLockError::WouldBlock) => None,
+                }
+            }
+
+            pub struct Mutex<T>(sync::Mutex<T>);
+
+            impl<T> Mutex<T> {
+                pub fn new(value: T) -> Self {
+                    Self(sync::Mutex::new(value))
+                }
+
+                pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+                    self.0.lock()
+                }
+
+                pub fn try_lock(&self) -> Option<LockResult<MutexGuard<'_, T>>> {
+                    try_result(self.0.try_lock())
+                }
+            }
+
+            impl<T> fmt::Debug for Mutex<T> {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.debug_struct("Mutex").finish()
+                }
+            }
+
+            pub struct RwLock<T>(sync::RwLock<T>);
+
+            impl<T> RwLock<T> {
+                pub fn new(value: T) -> Self {
+                    Self(sync::RwLock::new(value))
+                }
+
+                pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+                    self.0.read()
+                }
+
+                pub fn try_read(&self) -> Option<LockResult<RwLockReadGuard<'_, T>>> {
+                    try_result(self.0.try_read())
+                }
+
+                pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+                    self.0.write()
+                }
+
+                pub fn try_write(&self) -> Option<LockResult<RwLockWriteGuard<'_, T>>> {
+                    try_result(self.0.try_write())
+                }
+            }
+
+            impl<T> fmt::Debug for RwLock<T> {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.debug_struct("RwLock").finish()
+                }
+            }
+        }
+    };
+}
+
+#[cfg(loom)]
+poisoning_locks!(loom, loom::sync);
+
+#[cfg(all(not(loom), not(feature = "single-thread"), feature = "std-sync"))]
+poisoning_locks!(std_sync, std::sync);
+
+#[cfg(all(not(loom), not(feature = "single-thread"), not(feature = "std-sync")))]
+mod parking {
+    use std::sync::LockResult;
+
+    pub use parking_lot::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+    pub use std::sync::atomic::{AtomicU8, AtomicUsize};
+
+    /// `parking_lot` locks never get poisoned.
+    #[derive(Debug)]
+    pub struct Mutex<T>(parking_lot::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub fn new(value: T) -> Self {
+            Self(parking_lot::Mutex::new(value))
+        }
+
+        pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+            Ok(self.0.lock())
+        }
+
+        pub fn try_lock(&self) -> Option<LockResult<MutexGuard<'_, T>>> {
+            self.0.try_lock().map(Ok)
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct RwLock<T>(parking_lot::RwLock<T>);
+
+    impl<T> RwLock<T> {
+        pub fn new(value: T) -> Self {
+            Self(parking_lot::RwLock::new(value))
+        }
+
+        pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+            Ok(self.0.read())
+        }
+
+        pub fn try_read(&self) -> Option<LockResult<RwLockReadGuard<'_, T>>> {
+            self.0.try_read().map(Ok)
+        }
+
+        pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+            Ok(self.0.write())
+        }
+
+        pub fn try_write(&self) -> Option<LockResult<RwLockWriteGuard<'_, T>>> {
+            self.0.try_write().map(Ok)
+        }
+    }
+}
+
+#[cfg(all(not(loom), feature = "single-thread"))]
+mod single {
+    use std::cell::{Cell, Ref, RefCell, RefMut};
+    use std::fmt;
+    use std::sync::LockResult;
+
+    use super::Ordering;
+
+    pub type MutexGuard<'a, T> = RefMut<'a, T>;
+    pub type RwLockReadGuard<'a, T> = Ref<'a, T>;
+    pub type RwLockWriteGuard<'a, T> = RefMut<'a, T>;
+
+    /// `Mutex` replacement backed by `RefCell`. Panics on conflicting borrows.
+    #[derive(Default)]
+    pub struct Mutex<T>(RefCell<T>);
+
+    impl<T> Mutex<T> {
+        pub fn new(value: T) -> Self {
+            Self(RefCell::new(value))
+        }
+
+        pub fn lock(&self) -> LockResult<RefMut<'_, T>> {
+            Ok(self.0.borrow_mut())
+        }
+
+        pub fn try_lock(&self) -> Option<LockResult<RefMut<'_, T>>> {
+            self.0.try_borrow_mut().ok().map(Ok)
+        }
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("Mutex").field(&self.0).finish()
+        }
+    }
+
+    /// `RwLock` replacement backed by `RefCell`. Panics on conflicting borrows.
+    #[derive(Default)]
+    pub struct RwLock<T>(RefCell<T>);
+
+    impl<T> RwLock<T> {
+        pub fn new(value: T) -> Self {
+            Self(RefCell::new(value))
+        }
+
+        pub fn read(&self) -> LockResult<Ref<'_, T>> {
+            Ok(self.0.borrow())
+        }
+
+        pub fn try_read(&self) -> Option<LockResult<Ref<'_, T>>> {
+            self.0.try_borrow().ok().map(Ok)
+        }
+
+        pub fn write(&self) -> LockResult<RefMut<'_, T>> {
+            Ok(self.0.borrow_mut())
+        }
+
+        pub fn try_write(&self) -> Option<LockResult<RefMut<'_, T>>> {
+            self.0.try_borrow_mut().ok().map(Ok)
+        }
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for RwLock<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("RwLock").field(&self.0).finish()
+        }
+    }
+
+    /// `AtomicUsize` replacement backed by `Cell`. Orderings are ignored.
+    #[derive(Default)]
+    pub struct AtomicUsize(Cell<usize>);
+
+    impl AtomicUsize {
+        pub const fn new(value: usize) -> Self {
+            Self(Cell::new(value))
+        }
+
+        pub fn load(&self, _order: Ordering) -> usize {
+            self.0.get()
+        }
+
+        pub fn fetch_add(&self, value: usize, _order: Ordering) -> usize {
+            let prev = self.0.get();
+            self.0.set(prev + value);
+            prev
+        }
+
+        pub fn fetch_sub(&self, value: usize, _order: Ordering) -> usize {
+            let prev = self.0.get();
+            self.0.set(prev - value);
+            prev
+        }
+
+        pub fn fetch_or(&self, value: usize, _order: Ordering) -> usize {
+            let prev = self.0.get();
+            self.0.set(prev | value);
+            prev
+        }
+
+        pub fn store(&self, value: usize, _order: Ordering) {
+            self.0.set(value);
+        }
+    }
+
+    impl fmt::Debug for AtomicUsize {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Debug::fmt(&self.0.get(), f)
+        }
+    }
+
+    /// `AtomicU8` replacement backed by `Cell`. Orderings are ignored.
+    #[derive(Default)]
+    pub struct AtomicU8(Cell<u8>);
+
+    impl AtomicU8 {
+        pub const fn new(value: u8) -> Self {
+            Self(Cell::new(value))
+        }
+
+        pub fn load(&self, _order: Ordering) -> u8 {
+            self.0.get()
+        }
+
+        pub fn store(&self, value: u8, _order: Ordering) {
+            self.0.set(value);
+        }
+    }
+
+    impl fmt::Debug for AtomicU8 {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Debug::fmt(&self.0.get(), f)
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// A mutex guarding no data which is usable in statics.
+///
+/// Statics must be `Sync` and const-constructible so this one ignores `single-thread` and loom.
+/// Since there's no data to get inconsistent poisoning is ignored.
+mod stripe {
+    #[cfg(feature = "std-sync")]
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    #[cfg(not(feature = "std-sync"))]
+    use parking_lot::{Mutex, MutexGuard};
+
+    pub struct StripeMutex(Mutex<()>);
+
+    impl StripeMutex {
+        pub const fn new() -> Self {
+            Self(Mutex::new(()))
+        }
+
+        #[cfg(feature = "std-sync")]
+        pub fn lock(&self) -> MutexGuard<'_, ()> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        #[cfg(not(feature = "std-sync"))]
+        pub fn lock(&self) -> MutexGuard<'_, ()> {
+            self.0.lock()
+        }
+    }
+
+    pub type StripeGuard = MutexGuard<'static, ()>;
+}
@@ -0,0 +1,293 @@
+//! `proptest` strategies generating references in realistic states.
+//!
+//! References are populated by applying random sequences of operations so they end up with
+//! resolved items, reservations, replaced duplicates and slots of removed items reused for
+//! other ids. Ids are drawn from a small range to make collisions frequent.
+//!
+//! A `FaultInjector` makes calls of a reference fail or slow down to check how the code
+//! using it copes with that. A `MockReference` stands in for a reference in unit tests
+//! of code depending on `Referential`. `assert_matches_snapshot` compares the contents
+//! of a reference with a golden file. A `StressTest` checks references under concurrent load
+//! on the target hardware.
+//!
+//! ```
+//! # use proptest::prelude::*;
+//! # use reference::testing::references;
+//! # use reference::{Id, Identifiable};
+//! #
+//! #[derive(Clone, Debug)]
+//! struct Foo {
+//!     id: Id<Self>,
+//!     value: u8,
+//! }
+//! #
+//! # impl Identifiable for Foo {
+//! #     fn id(&self) -> Id<Self> {
+//! #         self.id
+//! #     }
+//! # }
+//!
+//! fn foo(id: Id<Foo>) -> impl Strategy<Value = Foo> {
+//!     any::<u8>().prop_map(move |value| Foo { id, value })
+//! }
+//!
+//! proptest!(|(reference in references(16, 32, foo))| {
+//!     for entry in reference.iter() {
+//!         if let Some(item) = entry.load() {
+//!             prop_assert!(reference.contains_resolved(item.id));
+//!         }
+//!     }
+//! });
+//! ```
+
+use std::fmt;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use proptest::arbitrary::Arbitrary;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::strategy::BoxedStrategy;
+
+use super::{Backend, Error, Id, Identifiable, Reference};
+
+pub use super::fault::{Call, FaultInjector};
+pub use super::mock::{MockCall, MockReference};
+#[cfg(not(feature = "single-thread"))]
+pub use super::stress::{StressItem, StressReport, StressTest};
+
+/// Generates ids of any value.
+impl<T: 'static> Arbitrary for Id<T> {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<i32>().prop_map(Id::new).boxed()
+    }
+}
+
+/// An operation changing a reference.
+#[derive(Clone)]
+pub enum Op<T> {
+    Insert(T),
+    Upsert(T),
+    Reserve(Id<T>),
+    Remove(Id<T>),
+}
+
+impl<T: Identifiable + 'static> Op<T> {
+    /// Applies the operation to `reference`.
+    pub fn apply<B: Backend<T>>(self, reference: &Reference<T, B>) -> Result<(), Error<T>> {
+        match self {
+            Self::Insert(item) => reference.insert(item).map(|_| ()),
+            Self::Upsert(item) => reference.upsert(item).map(|_| ()),
+            Self::Reserve(id) => reference.get_or_reserve(id).map(|_| ()),
+            Self::Remove(id) => {
+                reference.remove(id);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<T: Identifiable + fmt::Debug> fmt::Debug for Op<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Insert(item) => f.debug_tuple("Insert").field(item).finish(),
+            Self::Upsert(item) => f.debug_tuple("Upsert").field(item).finish(),
+            Self::Reserve(id) => f.debug_tuple("Reserve").field(id).finish(),
+            Self::Remove(id) => f.debug_tuple("Remove").field(id).finish(),
+        }
+    }
+}
+
+/// Generates ids from 1 to `max_id`.
+pub fn ids<T: 'static>(max_id: i32) -> impl Strategy<Value = Id<T>> {
+    (1..=max_id.max(1)).prop_map(Id::new)
+}
+
+/// Generates operations on ids from 1 to `max_id` with items built by `item` strategies.
+pub fn ops<T, S, F>(max_id: i32, item: F) -> impl Strategy<Value = Op<T>>
+where
+    T: Identifiable + fmt::Debug + 'static,
+    S: Strategy<Value = T> + 'static,
+    F: Fn(Id<T>) -> S + Clone + 'static,
+{
+    let (insert, upsert) = (item.clone(), item);
+
+    prop_oneof![
+        3 => ids(max_id).prop_flat_map(insert).prop_map(Op::Insert),
+        2 => ids(max_id).prop_flat_map(upsert).prop_map(Op::Upsert),
+        1 => ids(max_id).prop_map(Op::Reserve),
+        1 => ids(max_id).prop_map(Op::Remove),
+    ]
+}
+
+/// Generates references of capacity for all ids from 1 to `max_id` populated by applying
+/// up to `max_ops` operations of `ops`.
+pub fn references<T, S, F>(
+    max_id: i32,
+    max_ops: usize,
+    item: F,
+) -> impl Strategy<Value = Reference<T>>
+where
+    T: Identifiable + fmt::Debug + 'static,
+    S: Strategy<Value = T> + 'static,
+    F: Fn(Id<T>) -> S + Clone + 'static,
+{
+    vec(ops(max_id, item), 0..=max_ops).prop_map(move |ops| {
+        let reference = Reference::new(max_id.max(1) as usize + 1);
+
+        for op in ops {
+            op.apply(&reference).expect("Failed to apply an operation");
+        }
+
+        reference
+    })
+}
+
+/// Generates `threads` sequences of up to `max_ops` operations each to be applied
+/// concurrently with `apply_concurrently`.
+pub fn concurrent_ops<T, S, F>(
+    threads: usize,
+    max_id: i32,
+    max_ops: usize,
+    item: F,
+) -> impl Strategy<Value = Vec<Vec<Op<T>>>>
+where
+    T: Identifiable + fmt::Debug + 'static,
+    S: Strategy<Value = T> + 'static,
+    F: Fn(Id<T>) -> S + Clone + 'static,
+{
+    vec(vec(ops(max_id, item), 0..=max_ops), threads)
+}
+
+/// Applies each sequence of operations in a thread of its own. Errors are returned
+/// as messages since `Error` isn't `Send`.
+#[cfg(not(feature = "single-thread"))]
+pub fn apply_concurrently<T, B>(
+    reference: &Reference<T, B>,
+    sequences: Vec<Vec<Op<T>>>,
+) -> Result<(), String>
+where
+    T: Identifiable + Send + 'static,
+    B: Backend<T>,
+    Reference<T, B>: Sync,
+{
+    std::thread::scope(|scope| {
+        let handles = sequences
+            .into_iter()
+            .map(|ops| {
+                scope.spawn(move || {
+                    ops.into_iter()
+                        .try_for_each(|op| op.apply(reference))
+                        .map_err(|err| err.to_string())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("Operations panicked"))
+    })
+}
+
+/// Environment variable which makes `assert_matches_snapshot` overwrite golden files.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "UPDATE_SNAPSHOTS";
+
+/// Compares the contents of `reference` with the golden file at `path` and panics showing
+/// the differing items if they don't match.
+///
+/// The contents are rendered one id per line in id order as the id followed by the `Debug`
+/// representation of the item or `reserved`. The file is written instead of comparing if it
+/// doesn't exist or `UPDATE_SNAPSHOTS_VAR` is set.
+#[track_caller]
+pub fn assert_matches_snapshot<T, B>(reference: &Reference<T, B>, path: impl AsRef<Path>)
+where
+    T: Identifiable + fmt::Debug + 'static,
+    B: Backend<T>,
+{
+    let path = path.as_ref();
+    let actual = render_snapshot(reference);
+
+    if std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() || !path.exists() {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).expect("Failed to create snapshot directory");
+        }
+
+        std::fs::write(path, &actual).expect("Failed to write snapshot");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).expect("Failed to read snapshot");
+
+    if let Some(diff) = diff_snapshots(&expected, &actual) {
+        panic!(
+            "Reference doesn't match snapshot {} (- expected, + actual); \
+             set {UPDATE_SNAPSHOTS_VAR} to update it:\n{diff}",
+            path.display()
+        );
+    }
+}
+
+fn render_snapshot<T, B>(reference: &Reference<T, B>) -> String
+where
+    T: Identifiable + fmt::Debug + 'static,
+    B: Backend<T>,
+{
+    let mut lines = reference
+        .iter_ordered()
+        .filter_map(|entry| entry.load())
+        .map(|item| (item.id().as_i32(), format!("{item:?}")))
+        .chain(
+            reference
+                .unresolved_ids()
+                .into_iter()
+                .map(|id| (id.as_i32(), String::from("reserved"))),
+        )
+        .collect::<Vec<_>>();
+
+    lines.sort_by_key(|(id, _)| *id);
+
+    lines
+        .into_iter()
+        .fold(String::new(), |mut out, (id, line)| {
+            let _ = writeln!(out, "{id}: {line}");
+            out
+        })
+}
+
+/// Diffs snapshots line by line matching lines by the leading id.
+fn diff_snapshots(expected: &str, actual: &str) -> Option<String> {
+    let key = |line: &str| line.split(':').next().and_then(|id| id.parse::<i64>().ok());
+    let mut expected = expected.lines().peekable();
+    let mut actual = actual.lines().peekable();
+    let mut diff = String::new();
+
+    loop {
+        let (line, sign) = match (expected.peek(), actual.peek()) {
+            (None, None) => break,
+            (Some(e), Some(a)) if e == a => {
+                expected.next();
+                actual.next();
+                continue;
+            }
+            (Some(e), Some(a)) if key(e) == key(a) => {
+                let _ = writeln!(diff, "- {e}");
+                let _ = writeln!(diff, "+ {a}");
+                expected.next();
+                actual.next();
+                continue;
+            }
+            (Some(e), Some(a)) if key(e) < key(a) => (expected.next(), '-'),
+            (Some(_), Some(_)) | (None, Some(_)) => (actual.next(), '+'),
+            (Some(_), None) => (expected.next(), '-'),
+        };
+
+        if let Some(line) = line {
+            let _ = writeln!(diff, "{sign} {line}");
+        }
+    }
+
+    (!diff.is_empty()).then_some(diff)
+}
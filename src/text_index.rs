@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use rustc_hash::FxHashSet;
+
+use super::index::{SecondaryIndex, SlotRef};
+use super::sync::{MaybeSync, Mutex, MutexGuard, PoisonError};
+use super::{ArcSwapBackend, Backend, Entry, Identifiable, Reference};
+
+#[cfg(not(feature = "single-thread"))]
+type TextFn<T> = Box<dyn for<'a> Fn(&'a T) -> &'a str + Send + Sync>;
+
+#[cfg(feature = "single-thread")]
+type TextFn<T> = Box<dyn for<'a> Fn(&'a T) -> &'a str>;
+
+type Words<S> = BTreeMap<String, FxHashSet<SlotRef<S>>>;
+
+/// Splits the text into lowercase words.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace().map(str::to_lowercase)
+}
+
+struct TextIndexInner<T, B: Backend<T>> {
+    text: TextFn<T>,
+    words: Mutex<Words<B::Slot>>,
+}
+
+impl<T, B: Backend<T>> TextIndexInner<T, B> {
+    /// The lock gets recovered if poisoned since the index is verified on lookups anyway.
+    fn lock(&self) -> MutexGuard<'_, Words<B::Slot>> {
+        self.words.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T, B: Backend<T>> fmt::Debug for TextIndexInner<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextIndex")
+            .field("words", &self.lock().len())
+            .finish()
+    }
+}
+
+impl<T, B> SecondaryIndex<T, B> for TextIndexInner<T, B>
+where
+    B: Backend<T>,
+    Self: MaybeSync,
+{
+    fn update(&self, slot: &'static B::Slot, maybe_prev: Option<&T>) {
+        let mut index = self.lock();
+
+        if let Some(prev) = maybe_prev {
+            for word in words((self.text)(prev)) {
+                if let Some(bucket) = index.get_mut(&word) {
+                    bucket.remove(&SlotRef(slot));
+
+                    if bucket.is_empty() {
+                        index.remove(&word);
+                    }
+                }
+            }
+        }
+
+        // See `KeyIndex` on why the current value gets loaded.
+        if let Some(current) = B::load(slot) {
+            for word in words((self.text)(&current)) {
+                index.entry(word).or_default().insert(SlotRef(slot));
+            }
+        }
+    }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+/// A case-insensitive index of items by prefixes of words of a text field.
+/// See `Reference::add_text_index`.
+///
+/// Maintained the same way as `KeyIndex`.
+pub struct TextIndex<T: 'static, B: Backend<T> = ArcSwapBackend<T>> {
+    inner: Arc<TextIndexInner<T, B>>,
+}
+
+impl<T: 'static, B: Backend<T>> TextIndex<T, B> {
+    /// Returns entries of items having a word starting with `prefix`.
+    pub fn search_prefix(&self, prefix: &str) -> Vec<Entry<T, B>> {
+        let prefix = prefix.to_lowercase();
+        let mut candidates = FxHashSet::default();
+
+        {
+            let index = self.inner.lock();
+            let range = (Bound::Included(prefix.clone()), Bound::Unbounded);
+
+            for (word, bucket) in index.range::<String, _>(range) {
+                if !word.starts_with(&prefix) {
+                    break;
+                }
+
+                candidates.extend(bucket.iter().copied());
+            }
+        }
+
+        candidates
+            .into_iter()
+            .map(|slot| Entry::new(slot.0))
+            .filter(|entry| match entry.load() {
+                Some(item) => words((self.inner.text)(&item)).any(|word| word.starts_with(&prefix)),
+                None => false,
+            })
+            .collect()
+    }
+}
+
+impl<T: 'static, B: Backend<T>> Clone for TextIndex<T, B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: 'static, B: Backend<T>> fmt::Debug for TextIndex<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.inner, f)
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Adds a text index over the field returned by `text` and fills it with the existing items.
+    /// It's useful for type-ahead search without scanning the whole reference.
+    pub fn add_text_index<F>(&self, text: F) -> TextIndex<T, B>
+    where
+        F: for<'a> Fn(&'a T) -> &'a str + MaybeSync + 'static,
+        B::Slot: MaybeSync,
+    {
+        let inner = Arc::new(TextIndexInner {
+            text: Box::new(text),
+            words: Mutex::new(BTreeMap::new()),
+        });
+
+        self.register_index(inner.clone());
+        TextIndex { inner }
+    }
+}
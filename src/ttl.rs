@@ -0,0 +1,94 @@
+//! Expiration of items after a time to live.
+
+use std::time::Duration;
+
+use super::{Backend, Entry, Error, Id, Identifiable, Reference};
+
+pub(crate) const TTLS_LOCK: &str = "TTLs";
+
+/// Time to live.
+///
+/// An item expires once its TTL has passed since it was last set or, with refresh on read,
+/// looked up by `get`. The TTL is the one given to `insert_with_ttl` for the id if any or
+/// the default one set by `with_ttl` or `reconfigure`. Items without a TTL, reservations and pinned items
+/// never expire. Expired items stay available until removed by `remove_expired`.
+/// Times are taken from the reference's clock. See `with_clock`.
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Sets the TTL of items inserted without one. There's none by default.
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        self.tune(|tunables| tunables.default_ttl = Some(ttl));
+        self
+    }
+
+    /// Makes each lookup by `get` extend the expiration of the item as if it was just set.
+    pub fn with_refresh_on_read(mut self) -> Self {
+        self.refresh_on_read = true;
+        self
+    }
+
+    /// Like `insert` but the item expires after `ttl` instead of the default TTL.
+    /// The override sticks to the id until the item is removed.
+    pub fn insert_with_ttl(&self, item: T, ttl: Duration) -> Result<Entry<T, B>, Error<T>> {
+        let id = item.id();
+        let entry = self.insert(item)?;
+
+        self.recovered_lock(self.ttls.lock(), TTLS_LOCK)
+            .insert(id, ttl);
+
+        Ok(entry)
+    }
+
+    /// Returns the TTL of the item with `id` or `None` if it never expires.
+    pub fn ttl(&self, id: Id<T>) -> Option<Duration> {
+        self.recovered_lock(self.ttls.lock(), TTLS_LOCK)
+            .get(&id)
+            .copied()
+            .or(self.tunables().default_ttl)
+    }
+
+    /// Returns ids of unpinned items outliving their TTLs.
+    pub fn expired_ids(&self) -> Vec<Id<T>> {
+        let ttls = self.recovered_lock(self.ttls.lock(), TTLS_LOCK).clone();
+        let default_ttl = self.tunables().default_ttl;
+
+        if ttls.is_empty() && default_ttl.is_none() {
+            return Vec::new();
+        }
+
+        let now = self.now();
+        let pins = self.pinned_ids();
+
+        self.items
+            .iter()
+            .filter(|slot| !B::meta(slot).is_free())
+            .filter_map(|slot| {
+                let refreshed_at = B::meta(slot).refreshed_at()?;
+                let id = B::peek(slot, |maybe_item| maybe_item.map(T::id))?;
+                let ttl = ttls.get(&id).copied().or(default_ttl)?;
+                (now.saturating_duration_since(refreshed_at) > ttl).then_some(id)
+            })
+            .filter(|id| !pins.contains(id))
+            .collect()
+    }
+
+    /// Removes expired items and returns their number.
+    pub fn remove_expired(&self) -> usize {
+        self.expired_ids()
+            .into_iter()
+            .filter_map(|id| self.remove(id))
+            .count()
+    }
+
+    /// Extends the expiration of the item in `slot` if lookups do so.
+    pub(crate) fn refresh_on_read(&self, slot: &B::Slot) {
+        // A read-only backend may be mapped to read-only memory.
+        if self.refresh_on_read && !self.items.is_read_only() {
+            B::meta(slot).refresh_at(self.now());
+        }
+    }
+
+    /// Drops the TTL override of a removed item.
+    pub(crate) fn forget_ttl(&self, id: Id<T>) {
+        self.recovered_lock(self.ttls.lock(), TTLS_LOCK).remove(&id);
+    }
+}
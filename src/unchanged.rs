@@ -0,0 +1,92 @@
+//! Skipping replaces of items with unchanged content.
+
+use std::fmt;
+use std::sync::Arc;
+
+use super::poison::INDEX_LOCK;
+use super::sync::MaybeSync;
+use super::{Backend, DuplicateMode, Entry, Identifiable, Reference};
+
+/// Tells whether an incoming item has the same content as the current one with its id
+/// so replacing it may be skipped. See `Reference::with_skip_unchanged`.
+pub trait SameContent<T>: MaybeSync + fmt::Debug + 'static {
+    fn same_content(&self, current: &T, incoming: &T) -> bool;
+}
+
+/// Compares items with `PartialEq`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ByValue;
+
+impl<T: PartialEq> SameContent<T> for ByValue {
+    fn same_content(&self, current: &T, incoming: &T) -> bool {
+        current == incoming
+    }
+}
+
+/// Compares hashes of items computed by a function, e.g. of fields which matter.
+/// Cheaper than `ByValue` for large items but hash collisions make distinct items equal.
+#[derive(Clone, Copy)]
+pub struct ByHash<F>(pub F);
+
+impl<F> fmt::Debug for ByHash<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ByHash")
+    }
+}
+
+impl<T, F> SameContent<T> for ByHash<F>
+where
+    F: Fn(&T) -> u64 + MaybeSync + 'static,
+{
+    fn same_content(&self, current: &T, incoming: &T) -> bool {
+        (self.0)(current) == (self.0)(incoming)
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> Reference<T, B> {
+    /// Makes replacing inserts skip items having the same content as the current ones
+    /// by `same_content`. The current `Arc` is kept so watchers aren't notified, indexes
+    /// aren't updated and caches keyed by the `Arc` stay valid. The update time is still
+    /// set since the item has been confirmed.
+    ///
+    /// Inserts rejecting duplicates aren't affected.
+    pub fn with_skip_unchanged<S: SameContent<T>>(mut self, same_content: S) -> Self {
+        self.skip_unchanged = Some(Box::new(same_content));
+        self
+    }
+
+    /// Returns the entry of `item` if inserting it in `mode` may be skipped
+    /// as it's unchanged.
+    pub(crate) fn unchanged_entry(
+        &self,
+        item: &Arc<T>,
+        mode: DuplicateMode,
+    ) -> Option<Entry<T, B>> {
+        if self.skip_unchanged.is_none() || mode != DuplicateMode::Replace {
+            return None;
+        }
+
+        let vid = self
+            .recovered_lock(self.vids.read(), INDEX_LOCK)
+            .get(item.id())?;
+
+        let entry = self.entry(vid).ok()?;
+        self.skip_if_unchanged(&entry, item).then_some(entry)
+    }
+
+    /// Tells whether replacing the item of `entry` with `item` may be skipped as it's
+    /// unchanged and if so sets the update time.
+    pub(crate) fn skip_if_unchanged(&self, entry: &Entry<T, B>, item: &T) -> bool {
+        let Some(same_content) = &self.skip_unchanged else {
+            return false;
+        };
+
+        match entry.load() {
+            Some(current) if same_content.same_content(&current, item) => {
+                B::meta(entry.slot).touch_at(self.now(), self.batch_id());
+                true
+            }
+            _ => false,
+        }
+    }
+}
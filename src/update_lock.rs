@@ -0,0 +1,126 @@
+use std::fmt;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::time::Instant;
+
+use rustc_hash::FxHasher;
+
+use super::sync::{StripeGuard, StripeMutex};
+use super::{Backend, Clock, Entry, Error, Identifiable};
+
+/// Number of mutexes in the stripe pool shared by all references.
+pub const UPDATE_LOCK_STRIPES: usize = 64;
+
+static UPDATE_LOCKS: [StripeMutex; UPDATE_LOCK_STRIPES] =
+    [const { StripeMutex::new() }; UPDATE_LOCK_STRIPES];
+
+impl<T: 'static, B: Backend<T>> Entry<T, B> {
+    /// Locks the entry for read-modify-write.
+    ///
+    /// Writers going through `lock_for_update` are serialized per slot while readers
+    /// remain lock-free. Writers using `insert` or `modify` don't take the lock though.
+    ///
+    /// Locks are taken from a fixed pool of `UPDATE_LOCK_STRIPES` mutexes keyed by slot and
+    /// shared by all references so different entries, even of different references, may
+    /// share a mutex. Holding more than one guard at a time may deadlock as well as calling
+    /// `Reference::remove` while holding a guard.
+    ///
+    /// The lock order is an update lock before the index lock of a reference: guard holders
+    /// may call `get` or `insert` and `Reference::remove` takes the locks in the same order.
+    /// Nothing takes an update lock while holding an index lock.
+    ///
+    /// Stores through the guard are timed by the system clock since the entry doesn't know
+    /// its reference. See `Reference::lock_for_update`.
+    pub fn lock_for_update(&self) -> UpdateGuard<T, B> {
+        self.lock_for_update_at(None)
+    }
+
+    /// Like `lock_for_update` but stores are timed by `clock` if it's set.
+    pub(crate) fn lock_for_update_at(&self, clock: Option<Arc<dyn Clock>>) -> UpdateGuard<T, B> {
+        UpdateGuard {
+            slot: self.slot,
+            generation: self.generation,
+            clock,
+            _guard: lock_slot(self.slot),
+        }
+    }
+}
+
+/// Locks the stripe mutex of the slot.
+pub(crate) fn lock_slot<S>(slot: &S) -> StripeGuard {
+    UPDATE_LOCKS[stripe(slot)].lock()
+}
+
+/// Locks stripe mutexes of all the slots. Each mutex is locked once and in the order
+/// of stripes so concurrent callers don't deadlock each other.
+pub(crate) fn lock_slots<'a, S: 'a>(slots: impl IntoIterator<Item = &'a S>) -> Vec<StripeGuard> {
+    let mut stripes = slots.into_iter().map(stripe).collect::<Vec<_>>();
+    stripes.sort_unstable();
+    stripes.dedup();
+    stripes
+        .into_iter()
+        .map(|idx| UPDATE_LOCKS[idx].lock())
+        .collect()
+}
+
+fn stripe<S>(slot: &S) -> usize {
+    let mut hasher = FxHasher::default();
+    hasher.write_usize(slot as *const S as usize);
+    hasher.finish() as usize % UPDATE_LOCK_STRIPES
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Exclusive update access to an entry. See `Entry::lock_for_update`.
+pub struct UpdateGuard<T: 'static, B: Backend<T>> {
+    slot: &'static B::Slot,
+    generation: usize,
+    /// The clock of the reference or `None` for the system one.
+    clock: Option<Arc<dyn Clock>>,
+    _guard: StripeGuard,
+}
+
+impl<T: 'static, B: Backend<T>> UpdateGuard<T, B> {
+    /// Returns the current value or `None` if the item has been removed.
+    pub fn load(&self) -> Option<Arc<T>> {
+        // Removal takes the lock too so the generation can't change while the guard is held.
+        match B::meta(self.slot).generation() == self.generation {
+            true => B::load(self.slot),
+            false => None,
+        }
+    }
+}
+
+impl<T: Identifiable + 'static, B: Backend<T>> UpdateGuard<T, B> {
+    /// Replaces the value and returns the previous one. The id must stay the same.
+    /// Fails if the item has been removed.
+    pub fn store(&self, item: T) -> Result<Option<Arc<T>>, Error<T>> {
+        if B::meta(self.slot).generation() != self.generation {
+            return Err(Error::UpdateError(
+                "Failed to update item because it's removed".into(),
+            ));
+        }
+
+        let new = Arc::new(item);
+        let maybe_prev = B::store(self.slot, Some(new.clone()));
+        let now = self
+            .clock
+            .as_ref()
+            .map_or_else(Instant::now, |clock| clock.now());
+        B::meta(self.slot).touch_at(now, 0);
+
+        let entry = Entry::<T, B> {
+            slot: self.slot,
+            generation: self.generation,
+        };
+
+        entry.swapped(Some(&new), maybe_prev.as_ref());
+        Ok(maybe_prev)
+    }
+}
+
+impl<T: 'static, B: Backend<T>> fmt::Debug for UpdateGuard<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpdateGuard").finish()
+    }
+}
@@ -0,0 +1,11 @@
+//! The first version of the public API where references are used through inherent methods
+//! of `Reference`.
+//!
+//! This is what the crate root exports now. Depending on `reference::v1` instead of the root
+//! keeps the code compiling while the root moves on and lets it migrate to `v2` module
+//! by module.
+
+pub use super::{
+    ArcSwapBackend, Backend, Enterable, Entry, Error, Id, Identifiable, Reference, Referential,
+    RwLockBackend,
+};
@@ -0,0 +1,32 @@
+//! The trait-based version of the public API.
+//!
+//! Code looks items up and updates them through `Referential` and keeps entries through
+//! `Enterable` rather than calling `Reference` directly so it doesn't depend on the backend
+//! and may be unit-tested with a stub. `Reference` is only exported for constructing
+//! references. Types are shared with `v1` so values pass between code on either version.
+//!
+//! ```
+//! # use reference::v2::{Enterable, Id, Identifiable, Reference, Referential};
+//! # struct Foo {
+//! #     id: Id<Self>,
+//! # }
+//! #
+//! # impl Identifiable for Foo {
+//! #     fn id(&self) -> Id<Self> {
+//! #         self.id
+//! #     }
+//! # }
+//! fn link<R: Enterable<Foo>>(reference: &R, id: Id<Foo>) -> bool {
+//!     reference.entry_or_reserve(id).is_ok()
+//! }
+//!
+//! let reference = Reference::new(3);
+//! reference.upsert(Foo { id: 1.into() }).unwrap();
+//!
+//! assert!(link(&reference, 2.into()));
+//! assert!(reference.load(1.into()).is_some());
+//! assert!(reference.load(2.into()).is_none());
+//! assert!(reference.entry(2.into()).is_some());
+//! ```
+
+pub use super::{Enterable, Entry, Error, Id, Identifiable, Reference, Referential};
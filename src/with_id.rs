@@ -0,0 +1,69 @@
+//! Storing values of foreign types which can't implement `Identifiable` themselves.
+
+use std::ops::{Deref, DerefMut};
+
+use super::{Id, Identifiable};
+
+/// A value paired with its id. Dereferences to the value so its fields and methods
+/// are accessible as is.
+///
+/// ```
+/// # use reference::{Reference, WithId};
+/// let reference = Reference::new(2);
+/// reference.insert(WithId::new(1, String::from("one"))).unwrap();
+///
+/// let item = reference.get(1.into()).and_then(|entry| entry.load()).unwrap();
+/// assert_eq!(item.len(), 3);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WithId<T> {
+    pub id: Id<Self>,
+    pub value: T,
+}
+
+impl<T> WithId<T> {
+    pub fn new(id: impl Into<Id<Self>>, value: T) -> Self {
+        Self {
+            id: id.into(),
+            value,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Identifiable for WithId<T> {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+impl<T> Deref for WithId<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for WithId<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> AsRef<T> for WithId<T> {
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Pairs of ids and values are identified by the first element,
+/// e.g. for collecting `Reference<(Id<T>, T)>` right from an iterator of pairs.
+impl<T> Identifiable for (Id<T>, T) {
+    fn id(&self) -> Id<Self> {
+        Id::new(self.0.as_i32())
+    }
+}
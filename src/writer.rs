@@ -0,0 +1,331 @@
+//! Funneling writes to a reference through a single writer thread.
+//!
+//! With many concurrent writers most of the write time goes into waiting for the index lock.
+//! A `WriterHandle` sends mutations over a channel to a dedicated thread which applies them
+//! one by one so the lock is never contended. Readers are not affected and keep reading
+//! the reference directly.
+//!
+//! A `Throttle` limits how fast the thread applies mutations so a bulk replay of upstream
+//! changes doesn't flood readers with invalidations, and bounds the queue of pending ones.
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Backend, Entry, Error, Id, Identifiable, Reference};
+
+type Reply<R, T> = Sender<Result<R, SendableError<T>>>;
+
+enum Command<T: 'static, B: Backend<T>> {
+    Insert(T, Reply<Entry<T, B>, T>),
+    Upsert(T, Reply<Entry<T, B>, T>),
+    Remove(Id<T>, Reply<Option<Arc<T>>, T>),
+}
+
+impl<T: 'static, B: Backend<T>> Command<T, B> {
+    fn item(&self) -> Option<&T> {
+        match self {
+            Self::Insert(item, _) | Self::Upsert(item, _) => Some(item),
+            Self::Remove(..) => None,
+        }
+    }
+}
+
+/// What queuing a mutation does when the queue of a throttled writer is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait for room in the queue.
+    #[default]
+    Block,
+    /// Fail the mutation with `Error::InsertError` right away.
+    Reject,
+}
+
+/// Limits of a writer thread. See `WriterHandle::spawn_throttled`.
+///
+/// Rates allow bursts of up to a second worth of mutations after idling.
+pub struct Throttle<T> {
+    queue_capacity: usize,
+    overflow: Overflow,
+    ops_per_sec: Option<f64>,
+    bytes_per_sec: Option<f64>,
+    item_size: fn(&T) -> usize,
+}
+
+impl<T> Throttle<T> {
+    /// Creates a throttle bounding the queue to `queue_capacity` pending mutations
+    /// without rate limits.
+    pub fn new(queue_capacity: usize) -> Self {
+        Self {
+            queue_capacity,
+            overflow: Overflow::default(),
+            ops_per_sec: None,
+            bytes_per_sec: None,
+            item_size: |_| 0,
+        }
+    }
+
+    /// Sets what queuing does when the queue is full. The default is `Overflow::Block`.
+    pub fn with_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Limits the number of mutations applied per second.
+    pub fn with_ops_per_sec(mut self, ops_per_sec: u32) -> Self {
+        self.ops_per_sec = Some(f64::from(ops_per_sec));
+        self
+    }
+
+    /// Limits the number of bytes of inserted items applied per second
+    /// where `size` tells the bytes of an item. Removals are free.
+    pub fn with_bytes_per_sec(mut self, bytes_per_sec: usize, size: fn(&T) -> usize) -> Self {
+        self.bytes_per_sec = Some(bytes_per_sec as f64);
+        self.item_size = size;
+        self
+    }
+}
+
+impl<T> Clone for Throttle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Throttle<T> {}
+
+impl<T> fmt::Debug for Throttle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Throttle")
+            .field("queue_capacity", &self.queue_capacity)
+            .field("overflow", &self.overflow)
+            .field("ops_per_sec", &self.ops_per_sec)
+            .field("bytes_per_sec", &self.bytes_per_sec)
+            .finish()
+    }
+}
+
+/// A token bucket which goes into debt instead of rejecting amounts over its size.
+struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes `amount` tokens sleeping until the debt is paid off if there are not enough.
+    fn acquire(&mut self, amount: f64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - amount;
+        self.refilled_at = now;
+
+        if self.tokens < 0.0 && self.rate > 0.0 {
+            thread::sleep(Duration::from_secs_f64(-self.tokens / self.rate));
+        }
+    }
+}
+
+enum Queue<T: 'static, B: Backend<T>> {
+    Unbounded(Sender<Command<T, B>>),
+    Bounded(SyncSender<Command<T, B>>, Overflow),
+}
+
+impl<T: 'static, B: Backend<T>> Clone for Queue<T, B> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Unbounded(sender) => Self::Unbounded(sender.clone()),
+            Self::Bounded(sender, overflow) => Self::Bounded(sender.clone(), *overflow),
+        }
+    }
+}
+
+/// A cloneable handle sending mutations to the writer thread.
+/// The thread stops once all the handles are dropped and pending commands are applied.
+pub struct WriterHandle<T: 'static, B: Backend<T>> {
+    queue: Queue<T, B>,
+}
+
+impl<T, B> WriterHandle<T, B>
+where
+    T: Identifiable + Send + Sync + 'static,
+    B: Backend<T>,
+    Reference<T, B>: Send + Sync,
+    Entry<T, B>: Send,
+{
+    /// Spawns the writer thread of `reference`. Writes made to the reference directly still
+    /// work but contend with the thread for the lock.
+    pub fn spawn(reference: Arc<Reference<T, B>>) -> Self {
+        let (sender, receiver) = mpsc::channel::<Command<T, B>>();
+        Self::spawn_thread(reference, receiver, None);
+
+        Self {
+            queue: Queue::Unbounded(sender),
+        }
+    }
+
+    /// Like `spawn` but the thread applies mutations no faster and queues no more of them
+    /// than `throttle` allows.
+    pub fn spawn_throttled(reference: Arc<Reference<T, B>>, throttle: Throttle<T>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Command<T, B>>(throttle.queue_capacity);
+        Self::spawn_thread(reference, receiver, Some(throttle));
+
+        Self {
+            queue: Queue::Bounded(sender, throttle.overflow),
+        }
+    }
+
+    fn spawn_thread(
+        reference: Arc<Reference<T, B>>,
+        receiver: Receiver<Command<T, B>>,
+        throttle: Option<Throttle<T>>,
+    ) {
+        let throttle = throttle.unwrap_or(Throttle::new(0));
+        let mut ops_limiter = throttle.ops_per_sec.map(RateLimiter::new);
+        let mut bytes_limiter = throttle.bytes_per_sec.map(RateLimiter::new);
+
+        thread::spawn(move || {
+            for command in receiver {
+                if let Some(ref mut limiter) = ops_limiter {
+                    limiter.acquire(1.0);
+                }
+
+                if let (Some(limiter), Some(item)) = (&mut bytes_limiter, command.item()) {
+                    limiter.acquire((throttle.item_size)(item) as f64);
+                }
+
+                // A requester may have stopped waiting for the reply so it's fine to fail.
+                match command {
+                    Command::Insert(item, reply) => {
+                        let _ = reply.send(reference.insert(item).map_err(SendableError::from));
+                    }
+                    Command::Upsert(item, reply) => {
+                        let _ = reply.send(reference.upsert(item).map_err(SendableError::from));
+                    }
+                    Command::Remove(id, reply) => {
+                        let _ = reply.send(Ok(reference.remove(id)));
+                    }
+                }
+            }
+        });
+    }
+
+    /// Queues `Reference::insert` of `item`.
+    pub fn insert(&self, item: T) -> Pending<Entry<T, B>, T> {
+        self.send(|reply| Command::Insert(item, reply))
+    }
+
+    /// Queues `Reference::upsert` of `item`.
+    pub fn upsert(&self, item: T) -> Pending<Entry<T, B>, T> {
+        self.send(|reply| Command::Upsert(item, reply))
+    }
+
+    /// Queues `Reference::remove` of `id`.
+    pub fn remove(&self, id: Id<T>) -> Pending<Option<Arc<T>>, T> {
+        self.send(|reply| Command::Remove(id, reply))
+    }
+
+    fn send<R, F>(&self, command: F) -> Pending<R, T>
+    where
+        F: FnOnce(Reply<R, T>) -> Command<T, B>,
+    {
+        let (reply, receiver) = mpsc::channel();
+
+        // If the thread is gone the reply sender gets dropped and `wait` reports it.
+        match &self.queue {
+            Queue::Unbounded(sender) => {
+                let _ = sender.send(command(reply));
+            }
+            Queue::Bounded(sender, Overflow::Block) => {
+                let _ = sender.send(command(reply));
+            }
+            Queue::Bounded(sender, Overflow::Reject) => {
+                if let Err(TrySendError::Full(_)) = sender.try_send(command(reply.clone())) {
+                    let message = String::from("Writer queue is full");
+                    let _ = reply.send(Err(SendableError::Message(message)));
+                }
+            }
+        }
+
+        Pending { receiver }
+    }
+}
+
+impl<T: 'static, B: Backend<T>> Clone for WriterHandle<T, B> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<T: 'static, B: Backend<T>> fmt::Debug for WriterHandle<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriterHandle").finish()
+    }
+}
+
+/// The result of a queued mutation which is yet to be applied by the writer thread.
+pub struct Pending<R, T> {
+    receiver: Receiver<Result<R, SendableError<T>>>,
+}
+
+impl<R, T> Pending<R, T> {
+    /// Blocks until the mutation is applied and returns its result.
+    pub fn wait(self) -> Result<R, Error<T>> {
+        match self.receiver.recv() {
+            Ok(result) => result.map_err(Error::from),
+            Err(_) => Err(Error::InsertError(String::from(
+                "Writer thread has stopped",
+            ))),
+        }
+    }
+}
+
+impl<R, T> fmt::Debug for Pending<R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pending").finish()
+    }
+}
+
+/// `Error` passed between threads. Errors with sources which are not `Send` are
+/// passed as messages and turn into `Error::InsertError`.
+enum SendableError<T> {
+    DuplicateId(Id<T>),
+    ReserveFailed { id: Id<T>, capacity: usize },
+    LockPoisoned(&'static str),
+    Message(String),
+}
+
+impl<T> From<Error<T>> for SendableError<T> {
+    fn from(err: Error<T>) -> Self {
+        match err {
+            Error::DuplicateId(id) => Self::DuplicateId(id),
+            Error::ReserveFailed { id, capacity } => Self::ReserveFailed { id, capacity },
+            Error::LockPoisoned(lock) => Self::LockPoisoned(lock),
+            Error::InsertError(message) => Self::Message(message),
+            other => Self::Message(other.to_string()),
+        }
+    }
+}
+
+impl<T> From<SendableError<T>> for Error<T> {
+    fn from(err: SendableError<T>) -> Self {
+        match err {
+            SendableError::DuplicateId(id) => Self::DuplicateId(id),
+            SendableError::ReserveFailed { id, capacity } => Self::ReserveFailed { id, capacity },
+            SendableError::LockPoisoned(lock) => Self::LockPoisoned(lock),
+            SendableError::Message(message) => Self::InsertError(message),
+        }
+    }
+}
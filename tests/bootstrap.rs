@@ -0,0 +1,189 @@
+use std::error::Error as StdError;
+
+use reference::bootstrap::{Bootstrap, BootstrapError};
+use reference::{Entry, Id, Identifiable, Reference};
+
+#[derive(Debug)]
+struct Category {
+    id: Id<Self>,
+}
+
+impl Identifiable for Category {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[derive(Debug)]
+struct Subject {
+    id: Id<Self>,
+    main_product: Option<Entry<Product>>,
+}
+
+impl Identifiable for Subject {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[derive(Debug)]
+struct Product {
+    id: Id<Self>,
+    subject: Entry<Subject>,
+    category: Entry<Category>,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+type LoadResult = Result<(), Box<dyn StdError>>;
+
+#[test]
+fn bootstrap_order() {
+    let categories = Reference::<Category>::new(2);
+    let subjects = Reference::<Subject>::new(2);
+    let products = Reference::<Product>::new(2);
+    let mut bootstrap = Bootstrap::new();
+
+    bootstrap
+        .add(&products, |products| -> LoadResult {
+            products.insert(Product {
+                id: 1.into(),
+                subject: subjects.get(1.into()).ok_or("Subject not loaded")?,
+                category: categories.get(1.into()).ok_or("Category not loaded")?,
+            })?;
+
+            Ok(())
+        })
+        .add(&subjects, |subjects| -> LoadResult {
+            subjects.insert(Subject {
+                id: 1.into(),
+                main_product: None,
+            })?;
+
+            Ok(())
+        })
+        .add(&categories, |categories| -> LoadResult {
+            categories.insert(Category { id: 1.into() })?;
+
+            Ok(())
+        })
+        .dependency::<Product, Subject>()
+        .dependency::<Product, Category>();
+
+    let order = bootstrap.order();
+    assert_eq!(order.last(), Some(&std::any::type_name::<Product>()));
+
+    bootstrap.run().expect("Failed to bootstrap");
+
+    let product = products.get(1.into()).expect("Product not found");
+    let product = product.load().expect("Product not resolved");
+    assert!(product.subject.load().is_some());
+    assert!(product.category.load().is_some());
+}
+
+#[test]
+fn bootstrap_cycle() {
+    let subjects = Reference::<Subject>::new(2);
+    let products = Reference::<Product>::new(2);
+    let categories = Reference::<Category>::new(2);
+
+    categories
+        .insert(Category { id: 1.into() })
+        .expect("Failed to insert category");
+
+    let mut bootstrap = Bootstrap::new();
+
+    bootstrap
+        .add(&subjects, |subjects| -> LoadResult {
+            subjects.insert(Subject {
+                id: 1.into(),
+                main_product: Some(products.get_or_reserve(1.into())?),
+            })?;
+
+            Ok(())
+        })
+        .add(&products, |products| -> LoadResult {
+            products.insert(Product {
+                id: 1.into(),
+                subject: subjects.get_or_reserve(1.into())?,
+                category: categories.get_or_reserve(1.into())?,
+            })?;
+
+            Ok(())
+        })
+        .dependency::<Subject, Product>()
+        .dependency::<Product, Subject>();
+
+    let subject = std::any::type_name::<Subject>();
+    let product = std::any::type_name::<Product>();
+    assert_eq!(bootstrap.order(), [subject, product]);
+
+    bootstrap.run().expect("Failed to bootstrap");
+
+    let subject = subjects.get(1.into()).expect("Subject not found");
+    let subject = subject.load().expect("Subject not resolved");
+    let main_product = subject.main_product.as_ref().expect("No main product");
+    assert!(main_product.load().is_some());
+}
+
+#[test]
+fn bootstrap_unresolved() {
+    let subjects = Reference::<Subject>::new(2);
+    let products = Reference::<Product>::new(2);
+    let mut bootstrap = Bootstrap::new();
+
+    bootstrap
+        .add(&subjects, |subjects| -> LoadResult {
+            subjects.insert(Subject {
+                id: 1.into(),
+                main_product: Some(products.get_or_reserve(5.into())?),
+            })?;
+
+            Ok(())
+        })
+        .add(&products, |_products| -> LoadResult { Ok(()) });
+
+    match bootstrap.run() {
+        Err(BootstrapError::Unresolved(nodes)) => {
+            assert_eq!(nodes.len(), 1);
+            assert_eq!(nodes[0].id::<Product>(), Some(5.into()));
+        }
+        other => panic!("Unexpected result: {other:?}"),
+    }
+
+    assert_eq!(products.unresolved_ids(), [Id::new(5)]);
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn bootstrap_parallel() {
+    let categories = Reference::<Category>::new(3);
+    let subjects = Reference::<Subject>::new(2);
+    let mut bootstrap = Bootstrap::new();
+
+    bootstrap
+        .add(&categories, |categories| -> Result<(), String> {
+            for id in 1..=2 {
+                categories
+                    .insert(Category { id: id.into() })
+                    .map_err(|err| err.to_string())?;
+            }
+
+            Ok(())
+        })
+        .add(&subjects, |_subjects| Err("Source is unavailable"));
+
+    match bootstrap.run_parallel() {
+        Err(BootstrapError::LoadFailed { type_name, source }) => {
+            assert_eq!(type_name, std::any::type_name::<Subject>());
+            assert_eq!(source.to_string(), "Source is unavailable");
+        }
+        other => panic!("Unexpected result: {other:?}"),
+    }
+
+    assert!(categories.contains_resolved(2.into()));
+}
@@ -0,0 +1,129 @@
+use reference::context::{ContextExt, HasReference};
+use reference::{Entry, Id, Identifiable, Reference};
+
+#[derive(Debug)]
+struct Subject {
+    id: Id<Self>,
+    name: String,
+}
+
+impl Identifiable for Subject {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[derive(Debug)]
+struct Product {
+    id: Id<Self>,
+    subject: Entry<Subject>,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+struct Ctx {
+    products: Reference<Product>,
+    subjects: Reference<Subject>,
+}
+
+impl HasReference<Product> for Ctx {
+    fn reference(&self) -> &Reference<Product> {
+        &self.products
+    }
+}
+
+impl HasReference<Subject> for Ctx {
+    fn reference(&self) -> &Reference<Subject> {
+        &self.subjects
+    }
+}
+
+fn subject_name<C>(ctx: &C, product_id: Id<Product>) -> Option<String>
+where
+    C: HasReference<Product> + HasReference<Subject>,
+{
+    let product = ctx.get::<Product>(product_id)?.load()?;
+    let subject_id = product.subject.load()?.id;
+    Some(ctx.get::<Subject>(subject_id)?.load()?.name.clone())
+}
+
+#[test]
+fn has_reference() {
+    let ctx = Ctx {
+        products: Reference::new(2),
+        subjects: Reference::new(2),
+    };
+
+    let subject = ctx
+        .get_or_reserve::<Subject>(1.into())
+        .expect("Failed to reserve subject");
+
+    ctx.products
+        .insert(Product {
+            id: 1.into(),
+            subject,
+        })
+        .expect("Failed to insert product");
+
+    assert!(!ctx.contains_resolved::<Subject>(1.into()));
+    assert_eq!(subject_name(&ctx, 1.into()), None);
+
+    ctx.subjects
+        .insert(Subject {
+            id: 1.into(),
+            name: String::from("Subject 1"),
+        })
+        .expect("Failed to insert subject");
+
+    assert_eq!(subject_name(&ctx, 1.into()).as_deref(), Some("Subject 1"));
+    assert_eq!(subject_name(&ctx, 2.into()), None);
+
+    // A bare reference is a context too.
+    assert!(ContextExt::get(&ctx.subjects, 1.into()).is_some());
+}
+
+#[test]
+fn link() {
+    let ctx = Ctx {
+        products: Reference::new(4),
+        subjects: Reference::new(4),
+    };
+
+    ctx.subjects
+        .insert(Subject {
+            id: 1.into(),
+            name: String::from("Subject 1"),
+        })
+        .expect("Failed to insert subject");
+
+    // Rows of (product id, subject id) as they come from a source.
+    let rows = [(1, 1), (2, 2), (3, 2)];
+
+    let unresolved = ctx
+        .link::<Product, Subject, _>(
+            rows,
+            |(_, subject_id)| Id::new(*subject_id),
+            |(id, _), subject| Product {
+                id: id.into(),
+                subject,
+            },
+        )
+        .expect("Failed to link products");
+
+    assert_eq!(unresolved, [Id::new(2)]);
+    assert_eq!(subject_name(&ctx, 1.into()).as_deref(), Some("Subject 1"));
+    assert_eq!(subject_name(&ctx, 3.into()), None);
+
+    ctx.subjects
+        .insert(Subject {
+            id: 2.into(),
+            name: String::from("Subject 2"),
+        })
+        .expect("Failed to insert subject");
+
+    assert_eq!(subject_name(&ctx, 3.into()).as_deref(), Some("Subject 2"));
+}
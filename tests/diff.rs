@@ -0,0 +1,288 @@
+use reference::{Id, Identifiable, Reference};
+
+#[derive(Clone, Debug, PartialEq, Hash)]
+#[cfg_attr(
+    all(feature = "serde", feature = "serde_json"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+struct Product {
+    id: Id<Self>,
+    price: u64,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+fn products(items: &[(i32, u64)]) -> Reference<Product> {
+    let reference = Reference::new(items.len() + 2);
+
+    for (id, price) in items {
+        reference
+            .insert(Product {
+                id: (*id).into(),
+                price: *price,
+            })
+            .expect("Failed to insert product");
+    }
+
+    reference
+}
+
+fn ids(items: &[std::sync::Arc<Product>]) -> Vec<i32> {
+    items.iter().map(|item| item.id.as_i32()).collect()
+}
+
+#[test]
+fn diff_and_apply() {
+    let old = products(&[(1, 100), (2, 200), (3, 300)]);
+    let new = products(&[(4, 400), (3, 300), (2, 250)]);
+    new.get_or_reserve(5.into()).expect("Failed to reserve");
+
+    let changes = old.diff(&new);
+    assert_eq!(ids(&changes.added), [4]);
+    assert_eq!(ids(&changes.updated), [2]);
+    assert_eq!(changes.removed, [Id::new(1)]);
+
+    old.apply(&changes).expect("Failed to apply changes");
+    assert!(old.diff(&new).is_empty());
+}
+
+#[test]
+fn sync_with_full_dump() {
+    let reference = products(&[(1, 100), (2, 200), (3, 300)]);
+    reference
+        .get_or_reserve(5.into())
+        .expect("Failed to reserve");
+
+    let unchanged = reference
+        .get(3.into())
+        .and_then(|entry| entry.load())
+        .expect("Product not found");
+
+    let dump = [(4, 400), (3, 300), (2, 250)].map(|(id, price)| Product {
+        id: id.into(),
+        price,
+    });
+
+    let changes = reference.sync_with(dump).expect("Failed to sync");
+    assert_eq!(ids(&changes.added), [4]);
+    assert_eq!(ids(&changes.updated), [2]);
+    assert_eq!(changes.removed, [Id::new(1)]);
+    assert!(!reference.contains(1.into()));
+    assert!(reference.contains(5.into()));
+
+    let item = reference.get(3.into()).and_then(|entry| entry.load());
+    assert!(item.is_some_and(|item| std::sync::Arc::ptr_eq(&item, &unchanged)));
+
+    let item = reference.get(2.into()).and_then(|entry| entry.load());
+    assert_eq!(item.map(|item| item.price), Some(250));
+
+    // Nothing is applied if the additions don't fit.
+    let dump = [(2, 260), (3, 300), (6, 600), (7, 700)].map(|(id, price)| Product {
+        id: id.into(),
+        price,
+    });
+
+    assert!(reference.sync_with(dump).is_err());
+    assert!(reference.contains(4.into()));
+
+    let item = reference.get(2.into()).and_then(|entry| entry.load());
+    assert_eq!(item.map(|item| item.price), Some(250));
+}
+
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+#[test]
+fn json_patch() {
+    use reference::ChangeSet;
+    use serde_json::json;
+
+    let old = products(&[(1, 100), (2, 200)]);
+    let new = products(&[(2, 250), (3, 300)]);
+    let patch = old
+        .diff(&new)
+        .to_json_patch()
+        .expect("Failed to make patch");
+
+    assert_eq!(
+        patch,
+        json!([
+            {"op": "add", "path": "/3", "value": {"id": 3, "price": 300}},
+            {"op": "replace", "path": "/2", "value": {"id": 2, "price": 250}},
+            {"op": "remove", "path": "/1"},
+        ])
+    );
+
+    let changes = ChangeSet::from_json_patch(&patch).expect("Failed to parse patch");
+    old.apply(&changes).expect("Failed to apply changes");
+    assert!(old.diff(&new).is_empty());
+
+    let unsupported = json!([{"op": "move", "from": "/1", "path": "/2"}]);
+    assert!(ChangeSet::<Product>::from_json_patch(&unsupported).is_err());
+}
+
+#[test]
+fn merge_replicated_changes() {
+    use std::sync::Arc;
+
+    use reference::{ChangeSet, Merge, Timestamp};
+
+    let product = |id: i32, price| {
+        Arc::new(Product {
+            id: id.into(),
+            price,
+        })
+    };
+
+    let first = ChangeSet::new(vec![product(1, 100), product(2, 200)], vec![], vec![])
+        .with_timestamp(Timestamp::new(1, 1));
+    let concurrent = ChangeSet::new(vec![], vec![product(1, 150)], vec![2.into()])
+        .with_timestamp(Timestamp::new(2, 2));
+    let stale = ChangeSet::new(vec![], vec![product(1, 120), product(2, 220)], vec![])
+        .with_timestamp(Timestamp::new(2, 1));
+
+    let replicas = [Reference::new(4), Reference::new(4), Reference::new(4)];
+    replicas[0].apply(&first).expect("Failed to apply first");
+    replicas[0]
+        .apply(&concurrent)
+        .expect("Failed to apply concurrent");
+    replicas[0].apply(&stale).expect("Failed to apply stale");
+
+    replicas[1].apply(&stale).expect("Failed to apply stale");
+    replicas[1]
+        .apply(&concurrent)
+        .expect("Failed to apply concurrent");
+    replicas[1].apply(&first).expect("Failed to apply first");
+
+    replicas[2]
+        .apply(&concurrent)
+        .expect("Failed to apply concurrent");
+    replicas[2].apply(&first).expect("Failed to apply first");
+    replicas[2].apply(&stale).expect("Failed to apply stale");
+
+    // The change stamped by replica 2 wins the tie of counters in all orders.
+    for replica in &replicas {
+        let item = replica.get(1.into()).and_then(|entry| entry.load());
+        assert_eq!(item.map(|item| item.price), Some(150));
+        assert!(!replica.contains(2.into()));
+        assert_eq!(replica.timestamp(1.into()), Some(Timestamp::new(2, 2)));
+    }
+
+    #[derive(Debug)]
+    struct MaxPrice;
+
+    impl Merge<Product> for MaxPrice {
+        fn merge(
+            &self,
+            current: &Arc<Product>,
+            _: Timestamp,
+            incoming: &Arc<Product>,
+            _: Timestamp,
+        ) -> Arc<Product> {
+            match incoming.price > current.price {
+                true => incoming.clone(),
+                false => current.clone(),
+            }
+        }
+    }
+
+    let reference = Reference::new(4).with_merge(MaxPrice);
+    reference.apply(&first).expect("Failed to apply first");
+    reference.apply(&stale).expect("Failed to apply stale");
+    reference
+        .apply(&concurrent)
+        .expect("Failed to apply concurrent");
+
+    let item = reference.get(1.into()).and_then(|entry| entry.load());
+    assert_eq!(item.map(|item| item.price), Some(150));
+
+    // Unstamped changes are applied as is.
+    reference
+        .apply(&ChangeSet::new(vec![], vec![product(1, 90)], vec![]))
+        .expect("Failed to apply");
+
+    let item = reference.get(1.into()).and_then(|entry| entry.load());
+    assert_eq!(item.map(|item| item.price), Some(90));
+}
+
+#[test]
+fn reconcile_replicas() {
+    use reference::HashContent;
+
+    let replica = |items: &[(i32, u64)]| {
+        let reference = Reference::new(64).with_content_hash(HashContent);
+
+        for (id, price) in items {
+            reference
+                .insert(Product {
+                    id: (*id).into(),
+                    price: *price,
+                })
+                .expect("Failed to insert product");
+        }
+
+        reference
+    };
+
+    let items = (1..40).map(|id| (id, id as u64 * 100)).collect::<Vec<_>>();
+    let remote = replica(&items);
+    let local = replica(&items);
+
+    let summary = remote.summary().expect("No summary");
+    assert_eq!(Some(summary.root()), remote.fingerprint());
+    assert!(local.reconcile(&summary).expect("No plan").is_empty());
+
+    // 12 is changed, 25 is missing and 27 is extra locally. Adjacent differing buckets
+    // are merged into one range.
+    local
+        .insert(Product {
+            id: 12.into(),
+            price: 1,
+        })
+        .expect("Failed to insert product");
+
+    local.remove(25.into()).expect("Nothing removed");
+    remote.remove(27.into()).expect("Nothing removed");
+
+    let summary = remote.summary_in(0..=99, 10).expect("No summary");
+    let plan = local.reconcile(&summary).expect("No plan");
+    assert_eq!(plan.ranges, [10..=29]);
+    assert_eq!(summary.buckets().count(), 4);
+
+    // Narrower buckets narrow down what to fetch.
+    assert_eq!(
+        local
+            .reconcile(&remote.summary_in(0..=99, 5).expect("No summary"))
+            .expect("No plan")
+            .ranges,
+        [10..=14, 25..=29]
+    );
+
+    for range in plan.ranges {
+        let fetched = remote
+            .iter()
+            .filter_map(|entry| entry.load())
+            .filter(|item| range.contains(&item.id.as_i32()))
+            .map(|item| (*item).clone())
+            .collect::<Vec<_>>();
+
+        local
+            .sync_range(range, fetched)
+            .expect("Failed to sync range");
+    }
+
+    assert!(local.contains(1.into()));
+    assert_eq!(local.fingerprint(), remote.fingerprint());
+    assert!(local.summary_in(0..=99, 0).is_none());
+
+    // Summaries come from other replicas so a bad one is refused rather than trusted.
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    {
+        let mut json = serde_json::to_value(&summary).expect("Failed to serialize");
+        json["width"] = 0.into();
+        let bad = serde_json::from_value(json).expect("Failed to deserialize");
+        assert!(local.reconcile(&bad).is_none());
+    }
+}
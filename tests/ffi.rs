@@ -0,0 +1,112 @@
+#![cfg(feature = "ffi")]
+
+use std::error::Error as StdError;
+use std::ffi::c_void;
+use std::sync::Arc;
+
+use reference::ffi::*;
+use reference::{Codec, Id, Identifiable, Reference};
+
+#[derive(Debug, PartialEq)]
+struct Price {
+    id: Id<Self>,
+    value: u64,
+}
+
+impl Identifiable for Price {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+struct PriceCodec;
+
+impl Codec<Price> for PriceCodec {
+    fn encode(
+        &self,
+        item: &Price,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        buf.extend_from_slice(&item.id.as_i32().to_le_bytes());
+        buf.extend_from_slice(&item.value.to_le_bytes());
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Price, Box<dyn StdError + Send + Sync>> {
+        let id = i32::from_le_bytes(bytes.get(..4).ok_or("Too short")?.try_into()?);
+        let value = u64::from_le_bytes(bytes.get(4..12).ok_or("Too short")?.try_into()?);
+        Ok(Price {
+            id: id.into(),
+            value,
+        })
+    }
+}
+
+extern "C" fn collect(id: i32, data: *const u8, len: usize, user_data: *mut c_void) -> bool {
+    let items = unsafe { &mut *(user_data as *mut Vec<(i32, Vec<u8>)>) };
+    let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+    items.push((id, bytes.to_vec()));
+    true
+}
+
+#[test]
+fn ffi() {
+    let reference = Arc::new(Reference::new(4));
+    let handle = FfiReference::new(reference.clone(), PriceCodec).into_raw();
+
+    let mut bytes = Vec::new();
+    let price = Price {
+        id: 1.into(),
+        value: 100,
+    };
+    PriceCodec
+        .encode(&price, &mut bytes)
+        .expect("Failed to encode");
+
+    unsafe {
+        assert_eq!(
+            reference_insert(handle, bytes.as_ptr(), bytes.len()),
+            REFERENCE_OK
+        );
+        assert_eq!(reference_insert(handle, bytes.as_ptr(), 3), REFERENCE_ERROR);
+        assert_eq!(
+            reference_insert(handle, std::ptr::null(), 0),
+            REFERENCE_ERROR
+        );
+        assert_eq!(
+            reference_insert(handle, std::ptr::null(), 3),
+            REFERENCE_ERROR
+        );
+        assert!(reference_get(handle, 2).is_null());
+
+        let mut items: Vec<(i32, Vec<u8>)> = Vec::new();
+        let user_data = &mut items as *mut _ as *mut c_void;
+        assert_eq!(reference_iter(handle, collect, user_data), REFERENCE_OK);
+        assert_eq!(items, [(1, bytes.clone())]);
+
+        let entry = reference_get(handle, 1);
+        assert!(!entry.is_null());
+
+        items.clear();
+        let user_data = &mut items as *mut _ as *mut c_void;
+        assert_eq!(
+            reference_entry_load(entry, collect, user_data),
+            REFERENCE_OK
+        );
+        assert_eq!(items, [(1, bytes.clone())]);
+
+        reference.remove(1.into()).expect("Failed to remove");
+        items.clear();
+        let user_data = &mut items as *mut _ as *mut c_void;
+        assert_eq!(
+            reference_entry_load(entry, collect, user_data),
+            REFERENCE_NOT_FOUND
+        );
+        assert!(items.is_empty());
+
+        reference_entry_free(entry);
+        reference_free(handle);
+    }
+
+    assert_eq!(Arc::strong_count(&reference), 1);
+}
@@ -0,0 +1,553 @@
+use std::sync::Arc;
+
+#[cfg(not(feature = "single-thread"))]
+use reference::graph::CheckpointError;
+use reference::graph::{find_cycles, walk, walk_in, Node, Order, Registry, Visit, Visitor};
+#[cfg(not(feature = "single-thread"))]
+use reference::Codec;
+use reference::{Entry, Id, Identifiable, Reference};
+
+#[derive(Debug)]
+struct Category {
+    id: Id<Self>,
+    parent: Option<Entry<Category>>,
+}
+
+impl Identifiable for Category {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+impl Visit for Category {
+    fn visit_references(&self, visitor: &mut dyn Visitor) {
+        if let Some(ref parent) = self.parent {
+            visitor.entry(parent);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Product {
+    id: Id<Self>,
+    category: Entry<Category>,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+impl Visit for Product {
+    fn visit_references(&self, visitor: &mut dyn Visitor) {
+        visitor.entry(&self.category);
+    }
+}
+
+#[test]
+fn find_cycles_across_references() {
+    let categories = Reference::<Category>::new(5);
+    let products = Reference::new(2);
+
+    // 1 -> 2 -> 3 -> 1 is a cycle while 4 is a root.
+    categories
+        .reserve_many([1.into(), 2.into(), 3.into(), 4.into()])
+        .expect("Failed to reserve categories");
+
+    for (id, parent) in [(1, Some(2)), (2, Some(3)), (3, Some(1)), (4, None)] {
+        let parent =
+            parent.map(|parent: i32| categories.get(parent.into()).expect("Category not found"));
+
+        categories
+            .insert(Category {
+                id: id.into(),
+                parent,
+            })
+            .expect("Failed to insert category");
+    }
+
+    products
+        .insert(Product {
+            id: 1.into(),
+            category: categories.get(1.into()).expect("Category not found"),
+        })
+        .expect("Failed to insert product");
+
+    let mut registry = Registry::new();
+
+    registry
+        .register(&categories, |category, links| {
+            if let Some(ref parent) = category.parent {
+                links.add(parent);
+            }
+        })
+        .register(&products, |product, links| links.add(&product.category));
+
+    let cycles = find_cycles(&registry);
+    assert_eq!(cycles.len(), 1);
+
+    let mut ids = cycles[0]
+        .iter()
+        .map(|node| node.id::<Category>().expect("Not a category").as_i32())
+        .collect::<Vec<_>>();
+
+    ids.sort();
+    assert_eq!(ids, [1, 2, 3]);
+    assert_eq!(
+        cycles[0][0].type_name(),
+        Node::new::<Category>(1.into()).type_name()
+    );
+}
+
+#[derive(Debug)]
+struct Bundle {
+    id: Id<Self>,
+    products: Vec<Entry<Product>>,
+}
+
+impl Identifiable for Bundle {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+impl Visit for Bundle {
+    fn visit_references(&self, visitor: &mut dyn Visitor) {
+        for product in &self.products {
+            visitor.entry(product);
+        }
+    }
+}
+
+#[test]
+fn walk_across_references() {
+    let categories = Reference::<Category>::new(3);
+    let products = Reference::<Product>::new(3);
+    let bundles = Reference::new(2);
+
+    categories
+        .reserve_many([1.into(), 2.into()])
+        .expect("Failed to reserve categories");
+
+    // Category 2 is the parent of 1 which is the parent of 2.
+    for (id, parent) in [(1, 2), (2, 1)] {
+        categories
+            .insert(Category {
+                id: id.into(),
+                parent: categories.get(parent.into()),
+            })
+            .expect("Failed to insert category");
+    }
+
+    for (id, category) in [(1, 1), (2, 2)] {
+        products
+            .insert(Product {
+                id: id.into(),
+                category: categories.get(category.into()).expect("Category not found"),
+            })
+            .expect("Failed to insert product");
+    }
+
+    let bundle = bundles
+        .insert(Bundle {
+            id: 1.into(),
+            products: vec![
+                products.get(1.into()).expect("Product not found"),
+                products.get(2.into()).expect("Product not found"),
+            ],
+        })
+        .expect("Failed to insert bundle");
+
+    let render = |node: Node| {
+        let name = node.type_name().rsplit("::").next().unwrap_or_default();
+        let id = node
+            .id::<Category>()
+            .map(|id| id.as_i32())
+            .or_else(|| node.id::<Product>().map(|id| id.as_i32()))
+            .or_else(|| node.id::<Bundle>().map(|id| id.as_i32()))
+            .expect("Unexpected type");
+
+        format!("{name}({id})")
+    };
+
+    let mut visited = Vec::new();
+    walk(&bundle, &mut |node, _: &_| visited.push(render(node)));
+
+    assert_eq!(
+        visited,
+        [
+            "Bundle(1)",
+            "Product(1)",
+            "Product(2)",
+            "Category(1)",
+            "Category(2)"
+        ]
+    );
+
+    let mut visited = Vec::new();
+    let mut category_ids = Vec::new();
+
+    walk_in(
+        &bundle,
+        Order::DepthFirst,
+        &mut |node, item: &Arc<dyn Visit>| {
+            visited.push(render(node));
+
+            if let Some(category) = item.downcast_ref::<Category>() {
+                category_ids.push(category.id.as_i32());
+            }
+        },
+    );
+
+    assert_eq!(
+        visited,
+        [
+            "Bundle(1)",
+            "Product(1)",
+            "Category(1)",
+            "Category(2)",
+            "Product(2)"
+        ]
+    );
+
+    assert_eq!(category_ids, [1, 2]);
+}
+
+#[test]
+fn collect_garbage() {
+    let categories = Reference::<Category>::new(5);
+    let products = Reference::<Product>::new(4);
+
+    categories
+        .reserve_many([1.into(), 2.into(), 3.into()])
+        .expect("Failed to reserve categories");
+
+    // 2 -> 1 is reachable from product 1 while 3 is only reachable from product 2
+    // and 4 is reserved.
+    for (id, parent) in [(1, None), (2, Some(1)), (3, None)] {
+        let parent =
+            parent.map(|parent: i32| categories.get(parent.into()).expect("Category not found"));
+
+        categories
+            .insert(Category {
+                id: id.into(),
+                parent,
+            })
+            .expect("Failed to insert category");
+    }
+
+    categories
+        .get_or_reserve(4.into())
+        .expect("Failed to reserve category");
+
+    for (id, category) in [(1, 2), (2, 3)] {
+        products
+            .insert(Product {
+                id: id.into(),
+                category: categories.get(category.into()).expect("Category not found"),
+            })
+            .expect("Failed to insert product");
+    }
+
+    let mut registry = Registry::new();
+
+    registry
+        .register(&categories, |category, links| {
+            if let Some(ref parent) = category.parent {
+                links.add(parent);
+            }
+        })
+        .register(&products, |product, links| links.add(&product.category));
+
+    // A pinned item is kept along with what it refers to.
+    let pinned = products
+        .insert(Product {
+            id: 3.into(),
+            category: categories.get(1.into()).expect("Category not found"),
+        })
+        .expect("Failed to insert product");
+
+    products.pin(3.into());
+
+    let summary = registry.collect_garbage([Node::new::<Product>(1.into())]);
+    assert!(pinned.load().is_some());
+    assert_eq!(summary.reachable, 4);
+    assert_eq!(summary.total_reclaimed(), 2);
+    assert_eq!(summary.reclaimed[0].1, 1);
+    assert_eq!(summary.reclaimed[1].1, 1);
+
+    assert!(categories.contains_resolved(1.into()));
+    assert!(categories.contains_resolved(2.into()));
+    assert!(!categories.contains(3.into()));
+    assert!(categories.contains(4.into()));
+    assert!(products.contains(1.into()));
+    assert!(!products.contains(2.into()));
+
+    // Nothing is left to collect.
+    let summary = registry.collect_garbage([Node::new::<Product>(1.into())]);
+    assert_eq!(summary.total_reclaimed(), 0);
+}
+
+#[test]
+fn registry_readiness() {
+    use std::time::Duration;
+
+    use reference::{ManualClock, Readiness};
+
+    let clock = ManualClock::new();
+
+    let categories = Reference::<Category>::new(3)
+        .with_clock(clock.clone())
+        .with_degradation(Duration::from_secs(60), 0.5);
+
+    let products = Reference::<Product>::new(2);
+
+    for id in 1..=2 {
+        categories
+            .insert(Category {
+                id: id.into(),
+                parent: None,
+            })
+            .expect("Failed to insert category");
+    }
+
+    let mut registry = Registry::new();
+    registry
+        .register(&categories, |_, _| ())
+        .register(&products, |product, links| links.add(&product.category));
+
+    assert_eq!(registry.readiness(), Readiness::Ready);
+
+    clock.advance(Duration::from_secs(61));
+
+    let category = categories
+        .insert(Category {
+            id: 1.into(),
+            parent: None,
+        })
+        .expect("Failed to refresh category");
+
+    // Half of the categories are stale which is still tolerable.
+    assert_eq!(registry.readiness(), Readiness::Ready);
+    clock.advance(Duration::from_secs(61));
+
+    assert_eq!(
+        registry.readiness_by_type(),
+        [
+            (
+                "graph::Category",
+                Readiness::Degraded {
+                    stale_fraction: 1.0
+                }
+            ),
+            ("graph::Product", Readiness::Ready),
+        ]
+    );
+
+    let mut loading = Vec::new();
+
+    products.insert_many_with_progress(
+        [Product {
+            id: 1.into(),
+            category,
+        }],
+        1,
+        |_| loading.push(registry.readiness()),
+    );
+
+    assert!(matches!(
+        loading[..],
+        [Readiness::Loading { progress }] if progress.processed == 1
+    ));
+
+    assert!(!registry.readiness().is_ready());
+}
+
+#[cfg(not(feature = "single-thread"))]
+/// Encodes an item as its id followed by the id of its link if any
+/// which is taken with `get` on decoding.
+struct LinkCodec(Arc<Reference<Category>>);
+
+#[cfg(not(feature = "single-thread"))]
+impl LinkCodec {
+    fn encode_ids(id: i32, target: Option<i32>, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&id.to_le_bytes());
+        buf.extend_from_slice(&target.unwrap_or(0).to_le_bytes());
+    }
+
+    fn decode_ids(&self, bytes: &[u8]) -> Result<(i32, Option<Entry<Category>>), CodecError> {
+        let id = i32::from_le_bytes(bytes[..4].try_into()?);
+        let target = i32::from_le_bytes(bytes[4..8].try_into()?);
+
+        let entry = match target {
+            0 => None,
+            target => Some(self.0.get(target.into()).ok_or("Category not reserved")?),
+        };
+
+        Ok((id, entry))
+    }
+}
+
+#[cfg(not(feature = "single-thread"))]
+type CodecError = Box<dyn std::error::Error + Send + Sync>;
+
+#[cfg(not(feature = "single-thread"))]
+impl Codec<Category> for LinkCodec {
+    fn encode(&self, item: &Category, buf: &mut Vec<u8>) -> Result<(), CodecError> {
+        let parent = item.parent.as_ref().and_then(|parent| parent.load());
+        let parent = parent.map(|parent| parent.id.as_i32());
+        Self::encode_ids(item.id.as_i32(), parent, buf);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Category, CodecError> {
+        let (id, parent) = self.decode_ids(bytes)?;
+
+        Ok(Category {
+            id: id.into(),
+            parent,
+        })
+    }
+}
+
+#[cfg(not(feature = "single-thread"))]
+impl Codec<Product> for LinkCodec {
+    fn encode(&self, item: &Product, buf: &mut Vec<u8>) -> Result<(), CodecError> {
+        let category = item.category.load().ok_or("Category not resolved")?;
+        Self::encode_ids(item.id.as_i32(), Some(category.id.as_i32()), buf);
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Product, CodecError> {
+        let (id, category) = self.decode_ids(bytes)?;
+
+        Ok(Product {
+            id: id.into(),
+            category: category.ok_or("Product without category")?,
+        })
+    }
+}
+
+#[cfg(not(feature = "single-thread"))]
+fn register_with_codecs<'a>(
+    registry: &mut Registry<'a>,
+    categories: &'a Arc<Reference<Category>>,
+    products: &'a Reference<Product>,
+) {
+    // Products go first so their categories are reserved rather than loaded by then.
+    registry
+        .register_with_codec(
+            products,
+            |product, links| links.add(&product.category),
+            LinkCodec(categories.clone()),
+        )
+        .register_with_codec(
+            categories,
+            |category, links| {
+                if let Some(ref parent) = category.parent {
+                    links.add(parent);
+                }
+            },
+            LinkCodec(categories.clone()),
+        );
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn checkpoint() {
+    let categories = Arc::new(Reference::<Category>::new(3));
+    let products = Reference::<Product>::new(3);
+
+    // Category 2 is the parent of 1 which is inserted before it.
+    let parent = categories
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve category");
+
+    for (id, parent) in [(1, Some(parent)), (2, None)] {
+        categories
+            .insert(Category {
+                id: id.into(),
+                parent,
+            })
+            .expect("Failed to insert category");
+    }
+
+    for (id, category) in [(1, 1), (2, 2)] {
+        products
+            .insert(Product {
+                id: id.into(),
+                category: categories.get(category.into()).expect("Category not found"),
+            })
+            .expect("Failed to insert product");
+    }
+
+    let mut registry = Registry::new();
+    register_with_codecs(&mut registry, &categories, &products);
+    let mut checkpoint = Vec::new();
+
+    registry
+        .save_to(&mut checkpoint)
+        .expect("Failed to save checkpoint");
+
+    let restored_categories = Arc::new(Reference::<Category>::new(3));
+    let restored_products = Reference::<Product>::new(3);
+    let mut restored = Registry::new();
+    register_with_codecs(&mut restored, &restored_categories, &restored_products);
+
+    restored
+        .restore_from(checkpoint.as_slice())
+        .expect("Failed to restore checkpoint");
+
+    let product = restored_products
+        .get(1.into())
+        .and_then(|entry| entry.load())
+        .expect("Product not restored");
+
+    let category = product.category.load().expect("Category not resolved");
+    let parent = category.parent.as_ref().expect("Parent not restored");
+    let parent = parent.load().expect("Parent not resolved");
+    assert_eq!(parent.id.as_i32(), 2);
+    assert!(restored_categories.contains_resolved(2.into()));
+    assert!(restored_products.contains_resolved(2.into()));
+
+    // Types must be registered the same way.
+    let mut mismatched = Registry::new();
+    mismatched.register(&restored_products, |_, _| ());
+
+    let err = mismatched
+        .restore_from(checkpoint.as_slice())
+        .expect_err("Restored into a mismatching registry");
+
+    assert!(matches!(err, CheckpointError::Mismatch(_)));
+
+    let err = mismatched
+        .save_to(Vec::new())
+        .expect_err("Saved without a codec");
+
+    assert!(matches!(err, CheckpointError::Reference { .. }));
+
+    // A lack of room or a corrupt part fails without changing anything.
+    let mut corrupt = checkpoint.clone();
+    // The last byte of the categories' snapshot precedes three links and their count.
+    let end = corrupt.len() - 8 - 3 * 16;
+    corrupt[end - 1] ^= 1;
+
+    for (checkpoint, capacity) in [(&checkpoint, 1), (&corrupt, 3)] {
+        let failed_categories = Arc::new(Reference::<Category>::new(3));
+        let failed_products = Reference::<Product>::new(capacity);
+        let mut failed = Registry::new();
+        register_with_codecs(&mut failed, &failed_categories, &failed_products);
+
+        let err = failed
+            .restore_from(checkpoint.as_slice())
+            .expect_err("Restored a checkpoint which doesn't fit or is corrupt");
+
+        assert!(matches!(err, CheckpointError::Reference { .. }));
+
+        for id in [1, 2] {
+            assert!(!failed_categories.contains(id.into()));
+            assert!(!failed_products.contains(id.into()));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+#![cfg(all(feature = "grpc", not(feature = "single-thread")))]
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use reference::grpc::{ReplicationClient, ReplicationServer};
+use reference::{Codec, Id, Identifiable, Reference};
+
+#[derive(Debug, PartialEq)]
+struct Price {
+    id: Id<Self>,
+    value: u64,
+}
+
+impl Identifiable for Price {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+struct PriceCodec;
+
+impl Codec<Price> for PriceCodec {
+    fn encode(
+        &self,
+        item: &Price,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        buf.extend_from_slice(&item.id.as_i32().to_le_bytes());
+        buf.extend_from_slice(&item.value.to_le_bytes());
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Price, Box<dyn StdError + Send + Sync>> {
+        let id = i32::from_le_bytes(bytes.get(..4).ok_or("Too short")?.try_into()?);
+        let value = u64::from_le_bytes(bytes.get(4..12).ok_or("Too short")?.try_into()?);
+        Ok(Price {
+            id: id.into(),
+            value,
+        })
+    }
+}
+
+#[test]
+fn replication() {
+    let source = Arc::new(Reference::new(8));
+
+    for id in 1..=3 {
+        let price = Price {
+            id: id.into(),
+            value: id as u64 * 10,
+        };
+
+        source.insert(price).expect("Failed to insert");
+    }
+
+    source.remove(2.into());
+
+    let replica = Reference::new(8);
+    let stale = Price {
+        id: 1.into(),
+        value: 0,
+    };
+
+    replica.insert(stale).expect("Failed to insert");
+
+    // The server is a tower service so the client can call it directly without a transport.
+    let mut client = ReplicationClient::new(ReplicationServer::new(source, PriceCodec));
+    let count = block_on(client.load_snapshot(&replica, &PriceCodec)).expect("Failed to load");
+    assert_eq!(count, 2);
+
+    let value = |id: i32| {
+        replica
+            .get(id.into())
+            .and_then(|e| e.load())
+            .map(|p| p.value)
+    };
+    assert_eq!(value(1), Some(10));
+    assert_eq!(value(2), None);
+    assert_eq!(value(3), Some(30));
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
@@ -0,0 +1,82 @@
+#![cfg(all(feature = "http", not(feature = "single-thread")))]
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use reference::http::Inspector;
+use reference::{Id, Identifiable, Reference};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+#[derive(Serialize)]
+struct Product {
+    id: i32,
+    name: &'static str,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id.into()
+    }
+}
+
+#[test]
+fn http() {
+    let products = Arc::new(Reference::new(4));
+    products
+        .insert(Product { id: 1, name: "a" })
+        .expect("Failed to insert");
+    products
+        .insert(Product { id: 2, name: "b" })
+        .expect("Failed to insert");
+
+    let mut inspector = Inspector::new();
+    inspector.register("products", products);
+    let router = inspector.routes(Router::new());
+
+    let (status, body) = request(&router, "/reference/products");
+    assert_eq!(status, StatusCode::OK);
+    let expected = json!([{ "id": 1, "name": "a" }, { "id": 2, "name": "b" }]);
+    assert_eq!(body, Some(expected));
+
+    let (status, body) = request(&router, "/reference/products/2");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, Some(json!({ "id": 2, "name": "b" })));
+
+    let (status, _) = request(&router, "/reference/products/3");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = request(&router, "/reference/categories");
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = request(&router, "/stats");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.expect("No stats")["products"]["len"], json!(3));
+}
+
+fn request(router: &Router, uri: &str) -> (StatusCode, Option<Value>) {
+    let request = Request::get(uri)
+        .body(Body::empty())
+        .expect("Failed to build request");
+    let response = block_on(router.clone().oneshot(request)).expect("Failed to handle request");
+    let status = response.status();
+    let bytes = block_on(to_bytes(response.into_body(), usize::MAX)).expect("Failed to read body");
+    (status, serde_json::from_slice(&bytes).ok())
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(loom)]
+
+use loom::sync::Arc;
+use loom::thread;
+use reference::{Id, Identifiable, Reference};
+
+#[derive(Debug, Default)]
+struct Foo {
+    id: Id<Self>,
+}
+
+impl Foo {
+    fn new(id: Id<Self>) -> Self {
+        Self { id }
+    }
+}
+
+impl Identifiable for Foo {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[test]
+fn concurrent_insert() {
+    loom::model(|| {
+        let reference = Arc::new(Reference::new(3));
+        let reference_clone = reference.clone();
+
+        let handle = thread::spawn(move || {
+            reference_clone
+                .insert(Foo::new(1.into()))
+                .expect("Failed to insert 1");
+        });
+
+        reference
+            .insert(Foo::new(2.into()))
+            .expect("Failed to insert 2");
+
+        handle.join().unwrap();
+
+        for id in [1, 2] {
+            let entry = reference.get(id.into()).expect("Entry not found");
+            let entity = entry.load().expect("Entry is empty");
+            assert_eq!(entity.id, id.into());
+        }
+
+        assert_eq!(reference.iter().count(), 3);
+    });
+}
+
+#[test]
+fn concurrent_insert_and_reserve_same_id() {
+    loom::model(|| {
+        let reference = Arc::new(Reference::new(2));
+        let reference_clone = reference.clone();
+
+        let handle = thread::spawn(move || {
+            reference_clone
+                .get_or_reserve(1.into())
+                .expect("Failed to reserve")
+        });
+
+        reference
+            .insert(Foo::new(1.into()))
+            .expect("Failed to insert");
+
+        let reserved = handle.join().unwrap();
+        let entity = reserved.load().expect("Reserved entry is empty");
+        assert_eq!(entity.id, 1.into());
+        assert_eq!(reference.iter().count(), 2);
+    });
+}
@@ -0,0 +1,73 @@
+#![cfg(all(feature = "pyo3", not(feature = "single-thread")))]
+
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use reference::python::PyReference;
+use reference::{Id, Identifiable, Reference};
+
+struct Product {
+    id: Id<Self>,
+    name: String,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[test]
+fn python() {
+    let products = Arc::new(Reference::new(4));
+
+    for (id, name) in [(1, "a"), (2, "b")] {
+        let name = name.to_string();
+        let product = Product {
+            id: id.into(),
+            name,
+        };
+        products.insert(product).expect("Failed to insert");
+    }
+
+    let view = PyReference::new(products.clone(), |py, product: &Product| {
+        let dict = PyDict::new(py);
+        dict.set_item("name", &product.name)?;
+        Ok(dict.into_any().unbind())
+    });
+
+    Python::initialize();
+
+    Python::attach(|py| {
+        let globals = PyDict::new(py);
+        let view = Py::new(py, view).expect("Failed to create view");
+        globals
+            .set_item("products", view)
+            .expect("Failed to set global");
+
+        let code = c"
+assert products[1]['name'] == 'a'
+assert 2 in products and 3 not in products
+assert products.get(3) is None
+assert sorted(products) == [1, 2]
+assert sorted(p['name'] for p in products.values()) == ['a', 'b']
+entry = products.entry(1)
+
+try:
+    products[3]
+    assert False
+except KeyError:
+    pass
+";
+
+        py.run(code, Some(&globals), None)
+            .expect("Failed to run python");
+
+        products.remove(1.into()).expect("Failed to remove");
+        let check = c"assert entry.load() is None and len(products) == 1";
+
+        py.run(check, Some(&globals), None)
+            .expect("Failed to run python");
+    });
+}
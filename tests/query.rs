@@ -0,0 +1,185 @@
+use std::cell::Cell;
+
+use reference::{Id, Identifiable, Reference};
+
+#[derive(Clone, Debug)]
+struct Product {
+    id: Id<Self>,
+    name: String,
+    category: u32,
+    active: bool,
+}
+
+impl Product {
+    fn new(id: i32, name: &str, category: u32, active: bool) -> Self {
+        Self {
+            id: id.into(),
+            name: name.to_string(),
+            category,
+            active,
+        }
+    }
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+fn names(products: Vec<std::sync::Arc<Product>>) -> Vec<String> {
+    products.iter().map(|p| p.name.clone()).collect()
+}
+
+fn setup() -> Reference<Product> {
+    let products = Reference::new(8);
+
+    for product in [
+        Product::new(1, "d", 1, true),
+        Product::new(2, "c", 2, true),
+        Product::new(3, "b", 1, false),
+        Product::new(4, "a", 1, true),
+    ] {
+        products.insert(product).expect("Failed to insert");
+    }
+
+    products
+}
+
+#[test]
+fn filter_sort_limit() {
+    let products = setup();
+
+    let found = products
+        .query()
+        .filter(|p| p.active)
+        .sort_by(|p| p.name.clone())
+        .limit(2)
+        .collect();
+
+    assert_eq!(names(found), ["a", "c"]);
+    assert_eq!(products.query().filter(|p| p.active).count(), 3);
+    assert_eq!(products.query().limit(1).collect().len(), 1);
+
+    // Keys are computed once per item.
+    let calls = Cell::new(0);
+
+    products
+        .query()
+        .sort_by(|p| {
+            calls.set(calls.get() + 1);
+            p.name.clone()
+        })
+        .collect();
+
+    assert_eq!(calls.get(), 4);
+}
+
+#[test]
+fn indexed_filter() {
+    let products = setup();
+    let by_category = products.add_index(|p: &Product| p.category);
+
+    let found = products
+        .query()
+        .filter_by(&by_category, 1)
+        .filter(|p| p.active)
+        .sort_by(|p| p.name.clone())
+        .collect();
+
+    assert_eq!(names(found), ["a", "d"]);
+
+    // Maintained on writes through the reference.
+    products
+        .insert(Product::new(2, "c", 1, true))
+        .expect("Failed to replace");
+
+    products
+        .insert(Product::new(5, "e", 2, true))
+        .expect("Failed to insert");
+
+    products.remove(4.into()).expect("Nothing removed");
+
+    let found = products
+        .query()
+        .filter_by(&by_category, 1)
+        .sort_by(|p| p.name.clone())
+        .collect();
+
+    assert_eq!(names(found), ["b", "c", "d"]);
+    assert_eq!(by_category.get(&2).len(), 1);
+
+    // Writes through entries need reindexing.
+    let entry = products.get(5.into()).expect("Entry not found");
+
+    entry.modify(|p| Product {
+        category: 1,
+        ..p.clone()
+    });
+
+    assert!(by_category.get(&2).is_empty());
+    assert_eq!(by_category.get(&1).len(), 3);
+    products.reindex();
+    assert_eq!(by_category.get(&1).len(), 4);
+}
+
+#[test]
+fn text_index() {
+    let products = setup();
+    let by_name = products.add_text_index(|p: &Product| &p.name);
+
+    products
+        .insert(Product::new(5, "Green Apple", 3, true))
+        .expect("Failed to insert");
+
+    products
+        .insert(Product::new(6, "apricot", 3, true))
+        .expect("Failed to insert");
+
+    let mut found = by_name
+        .search_prefix("AP")
+        .into_iter()
+        .filter_map(|entry| entry.load())
+        .map(|p| p.name.clone())
+        .collect::<Vec<_>>();
+
+    found.sort();
+    assert_eq!(found, ["Green Apple", "apricot"]);
+
+    products.remove(6.into()).expect("Nothing removed");
+    assert_eq!(by_name.search_prefix("apr").len(), 0);
+    assert_eq!(by_name.search_prefix("gr").len(), 1);
+    assert_eq!(by_name.search_prefix("x").len(), 0);
+}
+
+#[test]
+fn aggregates() {
+    let products = setup();
+    products.remove(2.into()).expect("Failed to remove");
+
+    assert_eq!(products.count_where(|p| p.active), 2);
+    assert_eq!(products.sum_by(|p| p.category), 3);
+
+    let mut groups = products.group_by(|p| p.category);
+
+    for ids in groups.values_mut() {
+        ids.sort_by_key(|id| id.as_i32());
+    }
+
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[&1], [1.into(), 3.into(), 4.into()]);
+
+    // Closures may call into the reference.
+    assert_eq!(products.count_where(|p| products.contains(p.id)), 3);
+
+    let removed = products.count_where(|p| p.id == 4.into() && products.remove(p.id).is_some());
+    assert_eq!(removed, 1);
+
+    let mut visited = Vec::new();
+    products.for_each(|id, p| {
+        assert!(products.get(id).is_some());
+        visited.push((id.as_i32(), p.category));
+    });
+    visited.sort();
+    assert_eq!(visited, [(1, 1), (3, 1)]);
+}
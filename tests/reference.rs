@@ -1,6 +1,17 @@
-use reference::{Id, Identifiable, Reference};
+use std::collections::HashMap;
+use std::sync::Arc;
+#[cfg(not(feature = "single-thread"))]
+use std::thread;
+use std::time::Duration;
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+use rand::prelude::*;
+use reference::{
+    heap_size, Backend, DuplicateMode, Entry, EntryKey, Error, FlatIdIndex, HotField, Id, IdIndex,
+    Identifiable, InPlaceBackend, InlineBackend, LazyEntity, ManualClock, PoisonPolicy, Reference,
+    RwLockBackend, SortedVecIndex,
+};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 struct Foo {
     id: Id<Self>,
     name: String,
@@ -21,6 +32,8 @@ impl Identifiable for Foo {
     }
 }
 
+heap_size!(Foo { name });
+
 #[test]
 fn insert_and_get() {
     let reference = Reference::new(3);
@@ -59,6 +72,31 @@ fn iterate() {
     assert_eq!(ids, [None, Some(1.into()), Some(4.into()), None]);
 }
 
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn iterate_while_inserting() {
+    let reference = Reference::new(2001);
+
+    // Every slot observed by iteration has its item fully initialized.
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for id in 1..=2000 {
+                reference
+                    .insert(Foo::new(id.into()))
+                    .expect("Failed to insert");
+            }
+        });
+
+        while reference.stats().len < 2001 {
+            for item in reference.iter().filter_map(|entry| entry.load()) {
+                assert!(item.id.as_i32() > 0);
+            }
+        }
+    });
+
+    assert_eq!(reference.iter().count(), 2001);
+}
+
 #[test]
 fn set_and_replace() {
     let reference = Reference::new(2);
@@ -91,3 +129,2212 @@ fn set_and_replace() {
         assert_eq!(entity.name, "other");
     }
 }
+
+#[test]
+fn rwlock_backend() {
+    let reference = Reference::with_backend(RwLockBackend::new(3));
+
+    let entry = reference
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve");
+
+    assert!(entry.load().is_none());
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to set entity");
+
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    let entity = entry.load().expect("Entry is empty");
+    assert_eq!(entity.id, 1.into());
+
+    let ids = reference
+        .iter()
+        .map(|entry| entry.load().map(|entity| entity.id))
+        .collect::<Vec<_>>();
+
+    assert_eq!(ids, [None, Some(1.into()), Some(2.into())]);
+    assert!(reference.insert(Foo::new(3.into())).is_err());
+
+    // The slot isn't locked while the new value is made.
+    entry.modify(|item| Foo {
+        name: format!("{}", entry.load().is_some()),
+        ..item.clone()
+    });
+
+    assert_eq!(entry.load().expect("Entry is empty").name, "true");
+}
+
+#[test]
+fn in_place_backend() {
+    let reference = Reference::with_backend(InPlaceBackend::new(3));
+
+    let entry = reference
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve");
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    entry.modify(|item| Foo {
+        name: "modified".into(),
+        ..item.clone()
+    });
+
+    let item = entry.load().expect("Entry is empty");
+    assert_eq!(item.name, "modified");
+
+    // Items are stored by value so each load makes a new `Arc`.
+    assert!(!Arc::ptr_eq(&item, &entry.load().expect("Entry is empty")));
+
+    let removed = reference.remove(1.into()).expect("Nothing removed");
+    assert_eq!(removed.name, "modified");
+    assert!(entry.load().is_none());
+}
+
+#[test]
+fn inline_backend() {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Point {
+        id: Id<Self>,
+        x: i64,
+    }
+
+    impl Identifiable for Point {
+        fn id(&self) -> Id<Self> {
+            self.id
+        }
+    }
+
+    let reference = Reference::with_backend(InlineBackend::new(3));
+
+    let entry = reference
+        .insert(Point { id: 1.into(), x: 1 })
+        .expect("Failed to insert 1");
+
+    entry.modify(|point| Point { x: 2, ..*point });
+    assert_eq!(entry.load().map(|point| point.x), Some(2));
+
+    let guard = entry.lock_for_update();
+    let prev = guard
+        .store(Point { id: 1.into(), x: 3 })
+        .expect("Failed to store");
+
+    drop(guard);
+    assert_eq!(prev.map(|point| point.x), Some(2));
+    assert_eq!(reference.sum_by(|point| point.x), 3);
+
+    reference
+        .insert(Point { id: 2.into(), x: 4 })
+        .expect("Failed to insert 2");
+
+    assert!(reference.insert(Point { id: 3.into(), x: 5 }).is_err());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mmap_backend() {
+    use reference::MmapBackend;
+
+    let reference = Reference::with_backend(MmapBackend::new(3));
+
+    for id in 1..=2 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    assert!(reference.insert(Foo::new(3.into())).is_err());
+    assert_eq!(
+        reference.remove(1.into()).map(|item| item.id),
+        Some(1.into())
+    );
+
+    reference
+        .insert(Foo::new(3.into()))
+        .expect("Failed to insert 3");
+
+    assert_eq!(reference.iter().filter_map(|entry| entry.load()).count(), 2);
+    assert!(MmapBackend::<Foo>::try_new(usize::MAX).is_err());
+}
+
+#[test]
+fn insert_pooled() {
+    let reference = Reference::new(2);
+
+    reference
+        .insert_pooled(Foo::new(1.into()))
+        .expect("Failed to insert");
+
+    let entry = reference.get(1.into()).expect("Entry not found");
+    let first = entry.load().expect("Entry is empty");
+    let first_ptr = Arc::as_ptr(&first);
+
+    let mut second = Foo::new(1.into());
+    second.name = "second".to_string();
+    reference.insert_pooled(second).expect("Failed to replace");
+
+    // The first allocation is still held here so it can't be reused.
+    let mut third = Foo::new(1.into());
+    third.name = "third".to_string();
+    reference.insert_pooled(third).expect("Failed to replace");
+    assert_ne!(Arc::as_ptr(&entry.load().unwrap()), first_ptr);
+    drop(first);
+
+    let mut fourth = Foo::new(1.into());
+    fourth.name = "fourth".to_string();
+    reference.insert_pooled(fourth).expect("Failed to replace");
+
+    let current = entry.load().expect("Entry is empty");
+    assert_eq!(current.name, "fourth");
+    assert_eq!(Arc::as_ptr(&current), first_ptr);
+}
+
+#[test]
+fn modify() {
+    let reference = Reference::<Foo>::new(3);
+    let empty = reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve");
+
+    assert!(empty.modify(|foo| foo.clone()).is_none());
+    assert!(empty.load().is_none());
+
+    let entry = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+
+    let prev = entry
+        .modify(|foo| Foo {
+            name: "modified".to_string(),
+            ..foo.clone()
+        })
+        .expect("Entry is empty");
+
+    assert_eq!(prev.name, "");
+    assert_eq!(entry.load().expect("Entry is empty").name, "modified");
+}
+
+#[test]
+fn hot_field() {
+    #[derive(Clone)]
+    struct Bar {
+        id: Id<Self>,
+        name: String,
+        counter: HotField<u32>,
+    }
+
+    impl Identifiable for Bar {
+        fn id(&self) -> Id<Self> {
+            self.id
+        }
+    }
+
+    let reference = Reference::new(2);
+
+    let entry = reference
+        .insert(Bar {
+            id: 1.into(),
+            name: "bar".to_string(),
+            counter: HotField::new(0),
+        })
+        .expect("Failed to insert");
+
+    let bar = entry.load().expect("Entry is empty");
+    bar.counter.set(1);
+    assert_eq!(*bar.counter.modify(|counter| counter + 1), 1);
+
+    entry.modify(|bar| Bar {
+        name: "renamed".to_string(),
+        ..bar.clone()
+    });
+
+    let renamed = entry.load().expect("Entry is empty");
+    assert_eq!(renamed.name, "renamed");
+    assert_eq!(*renamed.counter.load(), 2);
+
+    bar.counter.set(3);
+    assert_eq!(*renamed.counter.load(), 3);
+
+    let detached = renamed.counter.detach();
+    detached.set(4);
+    assert_eq!(*renamed.counter.load(), 3);
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn lock_for_update() {
+    let reference = Arc::new(Reference::new(2));
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+
+    let handles = (0..4)
+        .map(|_| {
+            let reference = reference.clone();
+
+            thread::spawn(move || {
+                let entry = reference.get(1.into()).expect("Entry not found");
+
+                for _ in 0..100 {
+                    let guard = entry.lock_for_update();
+                    let mut foo = Foo::clone(&guard.load().expect("Entry is empty"));
+                    foo.name.push('x');
+                    guard.store(foo).expect("Failed to store");
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().expect("Thread panicked");
+    }
+
+    let entry = reference.get(1.into()).expect("Entry not found");
+    assert_eq!(entry.load().expect("Entry is empty").name.len(), 400);
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn remove_while_holding_update_guard() {
+    let reference = Arc::new(Reference::new(2001));
+
+    for id in 1..2001 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    let entry = reference.get(1.into()).expect("Entry not found");
+    let guard = entry.lock_for_update();
+
+    let remover = {
+        let reference = reference.clone();
+
+        thread::spawn(move || {
+            for id in 2..2001 {
+                reference.remove(id.into());
+            }
+        })
+    };
+
+    // Removal never waits for the update lock while holding the index lock
+    // so lookups don't deadlock against it.
+    let started_at = std::time::Instant::now();
+
+    while started_at.elapsed() < Duration::from_millis(200) {
+        assert!(reference.get(1.into()).is_some());
+    }
+
+    drop(guard);
+    remover.join().expect("Thread panicked");
+    assert_eq!(reference.iter().filter_map(|entry| entry.load()).count(), 1);
+}
+
+#[test]
+fn remove() {
+    let reference = Reference::new(3);
+
+    let entry1 = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    let removed = reference.remove(1.into()).expect("Nothing removed");
+    assert_eq!(removed.id, 1.into());
+    assert!(reference.get(1.into()).is_none());
+    assert!(reference.remove(1.into()).is_none());
+    assert!(entry1.is_stale());
+    assert!(entry1.load().is_none());
+
+    // The freed slot gets reused without growing over capacity.
+    let entry2 = reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    let entry3 = reference
+        .insert(Foo::new(3.into()))
+        .expect("Failed to insert 3");
+
+    assert_ne!(entry1.generation(), entry2.generation());
+    assert!(entry1.load().is_none());
+    assert!(entry1.modify(|foo| foo.clone()).is_none());
+    assert_eq!(entry2.load().expect("Entry 2 is empty").id, 2.into());
+    assert_eq!(entry3.load().expect("Entry 3 is empty").id, 3.into());
+
+    let guard = entry1.lock_for_update();
+    assert!(guard.load().is_none());
+    assert!(guard.store(Foo::new(1.into())).is_err());
+    drop(guard);
+    assert_eq!(entry2.load().expect("Entry 2 is empty").id, 2.into());
+
+    reference.remove(3.into()).expect("Nothing removed");
+
+    let ids = reference
+        .iter()
+        .map(|entry| entry.load().map(|entity| entity.id))
+        .collect::<Vec<_>>();
+
+    assert_eq!(ids, [None, Some(2.into())]);
+}
+
+#[test]
+fn contains() {
+    let reference = Reference::new(3);
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve 2");
+
+    assert!(reference.contains(1.into()));
+    assert!(reference.contains_resolved(1.into()));
+    assert!(reference.contains(2.into()));
+    assert!(!reference.contains_resolved(2.into()));
+    assert!(!reference.contains(3.into()));
+    assert!(!reference.contains_resolved(3.into()));
+}
+
+#[test]
+fn reserve_many() {
+    let reference = Reference::new(4);
+
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    let entries = reference
+        .reserve_many([3.into(), 2.into(), 1.into(), 3.into()])
+        .expect("Failed to reserve");
+
+    let ids = entries
+        .iter()
+        .map(|entry| entry.load().map(|entity| entity.id))
+        .collect::<Vec<_>>();
+
+    assert_eq!(ids, [None, Some(2.into()), None, None]);
+    assert!(reference.contains(1.into()));
+    assert!(reference.contains(3.into()));
+
+    reference
+        .insert(Foo::new(3.into()))
+        .expect("Failed to insert 3");
+
+    assert_eq!(entries[0].load().expect("Entry 3 is empty").id, 3.into());
+    assert!(reference.reserve_many([4.into()]).is_err());
+}
+
+#[test]
+fn from_iter_exact() {
+    let items = vec![Foo::new(1.into()), Foo::new(2.into())];
+    let reference = Reference::try_from(items).expect("Failed to create reference");
+    assert_eq!(
+        reference.get(2.into()).and_then(|e| e.load()),
+        Some(Arc::new(Foo::new(2.into())))
+    );
+    assert!(reference.insert(Foo::new(3.into())).is_err());
+
+    let items = vec![Foo::new(1.into()), Foo::new(2.into())];
+    let reference = Reference::from_iter_with_headroom(items, 0.5).expect("Failed to create");
+    reference
+        .insert(Foo::new(3.into()))
+        .expect("Failed to insert 3");
+
+    let items = vec![Foo::new(1.into()), Foo::new(2.into()), Foo::new(1.into())];
+
+    match Reference::from_iter_exact(items) {
+        Err(Error::DuplicateIds(ids)) => assert_eq!(ids, [1.into()]),
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn utilization() {
+    let reference = Reference::with_headroom(3, 0.3).with_utilization_warning_threshold(0.5);
+    assert_eq!(reference.utilization(), 0.2);
+
+    for id in 1..=4 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    assert_eq!(reference.utilization(), 1.0);
+    assert!(reference.insert(Foo::new(5.into())).is_err());
+
+    reference.remove(1.into()).expect("Nothing removed");
+    assert_eq!(reference.utilization(), 0.8);
+}
+
+#[test]
+fn try_get_or_reserve() {
+    let reference = Reference::<Foo>::new(2);
+
+    assert!(reference
+        .try_get_or_reserve(1.into())
+        .expect("Failed to try reserving 1")
+        .is_none());
+
+    assert!(!reference.contains(1.into()));
+    reference
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve 1");
+
+    assert!(reference
+        .try_get_or_reserve(1.into())
+        .expect("Failed to try reserving 1")
+        .is_some());
+
+    match reference.try_get_or_reserve(2.into()) {
+        Err(Error::ReserveFailed { id, capacity }) => {
+            assert_eq!(id, 2.into());
+            assert_eq!(capacity, 2);
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    match reference.get_or_reserve(2.into()) {
+        Err(Error::ReserveFailed { id, .. }) => assert_eq!(id, 2.into()),
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn duplicate_mode() {
+    let reference = Reference::new(3).with_duplicate_mode(DuplicateMode::Reject);
+
+    reference
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve 1");
+
+    let entry = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    let mut foo = Foo::new(1.into());
+    foo.name = String::from("Updated");
+
+    match reference.insert(foo.clone()) {
+        Err(Error::DuplicateId(id)) => assert_eq!(id, 1.into()),
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    assert_eq!(entry.load().expect("Entry is empty").name, "");
+
+    reference.upsert(foo).expect("Failed to upsert 1");
+    assert_eq!(entry.load().expect("Entry is empty").name, "Updated");
+}
+
+#[test]
+fn stats() {
+    let reference = Reference::new(4).with_poison_policy(PoisonPolicy::Propagate);
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve 2");
+
+    reference.remove(1.into()).expect("Nothing removed");
+
+    let stats = reference.stats();
+    assert_eq!(stats.len, 2);
+    assert_eq!(stats.capacity, 4);
+    assert_eq!(stats.index_lock_contentions, 0);
+    assert_eq!(stats.free_list_lock_contentions, 0);
+    assert_eq!(stats.pool_lock_contentions, 0);
+    assert!(stats.id_index_memory > 0);
+}
+
+#[derive(Clone, Debug)]
+struct Tracked {
+    id: Id<Self>,
+    version: usize,
+    events: Arc<std::sync::Mutex<Vec<(&'static str, usize)>>>,
+}
+
+impl Identifiable for Tracked {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+
+    fn attached<B: Backend<Self>>(&self, _entry: &Entry<Self, B>) {
+        let mut events = self.events.lock().expect("Failed to lock events");
+        events.push(("attached", self.version));
+    }
+
+    fn detached<B: Backend<Self>>(&self, entry: &Entry<Self, B>) {
+        let mut events = self.events.lock().expect("Failed to lock events");
+        events.push(("detached", self.version));
+
+        if entry.is_stale() {
+            events.push(("removed", self.version));
+        }
+    }
+}
+
+#[test]
+fn lifecycle_hooks() {
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reference = Reference::new(2);
+
+    let tracked = Tracked {
+        id: 1.into(),
+        version: 1,
+        events: events.clone(),
+    };
+
+    let entry = reference.insert(tracked.clone()).expect("Failed to insert");
+
+    reference
+        .insert(Tracked {
+            version: 2,
+            ..tracked
+        })
+        .expect("Failed to replace");
+
+    entry.modify(|tracked| Tracked {
+        version: 3,
+        ..tracked.clone()
+    });
+
+    reference.remove(1.into()).expect("Nothing removed");
+
+    let events = events.lock().expect("Failed to lock events");
+
+    assert_eq!(
+        *events,
+        [
+            ("attached", 1),
+            ("detached", 1),
+            ("attached", 2),
+            ("detached", 2),
+            ("attached", 3),
+            ("detached", 3),
+            ("removed", 3),
+        ]
+    );
+}
+
+fn check_id_index<I: IdIndex<Foo>>(index: I) {
+    let reference = Reference::new(4);
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    let reference = reference.with_id_index(index);
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+    assert!(reference.contains_resolved(1.into()));
+    assert!(reference.contains_resolved(2.into()));
+
+    reference.remove(2.into()).expect("Failed to remove 2");
+    assert!(!reference.contains(2.into()));
+    assert!(!reference.contains(3.into()));
+}
+
+#[test]
+fn id_index() {
+    check_id_index(SortedVecIndex::new());
+    check_id_index(FlatIdIndex::new());
+    #[cfg(feature = "dashmap")]
+    check_id_index(dashmap::DashMap::<Id<Foo>, u32>::new());
+    #[cfg(feature = "papaya")]
+    check_id_index(papaya::HashMap::<Id<Foo>, u32>::new());
+}
+
+#[test]
+fn flat_id_index() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut index = FlatIdIndex::<Foo>::new();
+    let mut expected = HashMap::new();
+
+    for vid in 0..10_000 {
+        let id = Id::new(rng.gen_range(-500..500));
+
+        match rng.gen_bool(0.6) {
+            true => assert_eq!(index.insert(id, vid), expected.insert(id, vid)),
+            false => assert_eq!(index.remove(id), expected.remove(&id)),
+        }
+
+        assert_eq!(index.len(), expected.len());
+    }
+
+    for id in -500..500 {
+        assert_eq!(index.get(id.into()), expected.get(&id.into()).copied());
+    }
+
+    assert_eq!(index.iter().count(), expected.len());
+}
+
+#[test]
+fn bloom_filter() {
+    let reference = Reference::new(100);
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    let reference = reference.with_bloom_filter(0.01);
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    reference
+        .get_or_reserve(3.into())
+        .expect("Failed to reserve 3");
+
+    assert!(reference.contains_resolved(1.into()));
+    assert!(reference.contains_resolved(2.into()));
+    assert!(reference.contains(3.into()));
+    assert!(reference.get(0.into()).is_some());
+
+    for id in 4..1000 {
+        assert!(reference.get(id.into()).is_none());
+    }
+
+    reference.remove(1.into()).expect("Failed to remove 1");
+    assert!(!reference.contains(1.into()));
+}
+
+#[test]
+fn freeze() {
+    let reference = Reference::new(4);
+    let entry = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    reference
+        .get_or_reserve(3.into())
+        .expect("Failed to reserve 3");
+
+    let items = reference.to_vec();
+    assert_eq!(
+        items.iter().map(|item| item.id).collect::<Vec<_>>(),
+        [1.into(), 2.into()]
+    );
+
+    let map = reference.to_map();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&Id::new(2)].id, 2.into());
+
+    let frozen = reference.freeze();
+    assert_eq!(frozen.len(), 2);
+    assert_eq!(frozen.get(2.into()).map(|item| item.id), Some(2.into()));
+    assert!(frozen.get(3.into()).is_none());
+    assert!(!frozen.contains(0.into()));
+
+    let ids = frozen.iter().map(|item| item.id).collect::<Vec<_>>();
+    assert_eq!(ids, [1.into(), 2.into()]);
+    assert_eq!(entry.load().map(|item| item.id), Some(1.into()));
+}
+
+#[test]
+fn overlay() {
+    let reference = Reference::new(4);
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    let mut overlay = reference.overlay();
+
+    let replacement = Foo {
+        id: 1.into(),
+        name: "local".into(),
+    };
+
+    assert!(overlay.insert(replacement).is_some());
+    assert!(overlay.insert(Foo::new(3.into())).is_none());
+    assert!(overlay.remove(2.into()).is_some());
+
+    let name = overlay.get(1.into()).map(|item| item.name.clone());
+    assert_eq!(name.as_deref(), Some("local"));
+    assert!(overlay.contains(3.into()));
+    assert!(!overlay.contains(2.into()));
+
+    let mut ids = overlay
+        .iter()
+        .map(|item| item.id.as_i32())
+        .collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, [1, 3]);
+
+    let base_item = reference.get(1.into()).and_then(|entry| entry.load());
+    assert_eq!(base_item.map(|item| item.name.clone()), Some(String::new()));
+    assert!(reference.contains_resolved(2.into()));
+    assert!(!reference.contains(3.into()));
+
+    overlay.reset(2.into());
+    assert!(overlay.contains(2.into()));
+}
+
+#[test]
+fn stale_ids() {
+    let reference = Reference::new(5);
+    assert_eq!(reference.stats().oldest_update_age, None);
+
+    let entry = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+    reference
+        .insert(Foo::new(3.into()))
+        .expect("Failed to insert 3");
+
+    std::thread::sleep(Duration::from_millis(50));
+
+    entry.modify(|item| item.clone());
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to replace 2");
+    reference
+        .get_or_reserve(4.into())
+        .expect("Failed to reserve 4");
+
+    assert_eq!(reference.stale_ids(Duration::from_millis(30)), [3.into()]);
+    assert!(reference.stale_ids(Duration::from_secs(60)).is_empty());
+
+    let age = reference.stats().oldest_update_age.expect("No updates");
+    assert!(age >= Duration::from_millis(50));
+}
+
+#[test]
+fn manual_clock() {
+    let clock = ManualClock::new();
+    let reference = Reference::new(3).with_clock(clock.clone());
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    clock.advance(Duration::from_secs(60));
+
+    let entry = reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    clock.advance(Duration::from_secs(30));
+    assert!(entry.load().is_some());
+
+    assert_eq!(reference.stale_ids(Duration::from_secs(45)), [1.into()]);
+    assert_eq!(
+        reference.stats().oldest_update_age,
+        Some(Duration::from_secs(90))
+    );
+
+    entry.modify(|item| item.clone());
+    assert!(reference
+        .stale_ids(Duration::from_secs(45))
+        .contains(&1.into()));
+
+    // Changes through the reference are timed by its clock.
+    reference.modify(&entry, |item| item.clone());
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(reference.stale_ids(Duration::from_secs(45)), [1.into()]);
+
+    clock.advance(Duration::from_secs(30));
+    reference
+        .lock_for_update(&entry)
+        .store(Foo::new(2.into()))
+        .expect("Failed to store 2");
+
+    assert_eq!(reference.stale_ids(Duration::from_secs(45)), [1.into()]);
+}
+
+#[test]
+fn entry_set() {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Well {
+        First = 1,
+        Second = 2,
+    }
+
+    impl EntryKey<Foo> for Well {
+        const ALL: &'static [Self] = &[Self::First, Self::Second];
+
+        fn id(self) -> Id<Foo> {
+            Id::new(self as i32)
+        }
+    }
+
+    let reference = Reference::new(3);
+    reference
+        .insert(Foo {
+            id: 1.into(),
+            name: String::from("foo"),
+        })
+        .expect("Failed to insert 1");
+
+    let set = reference
+        .entry_set::<Well>()
+        .expect("Failed to get entry set");
+
+    assert_eq!(set.len(), 2);
+    assert_eq!(set[Well::First].load().expect("Not resolved").name, "foo");
+    assert!(set[Well::Second].load().is_none());
+
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert 2");
+
+    assert!(set.get(Well::Second).load().is_some());
+    assert!(reference.entry_set::<Well>().is_ok());
+}
+
+#[test]
+// Hashing doesn't look into the slot so its interior mutability doesn't matter.
+#[allow(clippy::mutable_key_type)]
+fn entry_identity() {
+    let reference = Reference::new(3);
+    let entry = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    let same = reference.get(1.into()).expect("Failed to get 1");
+    let other = reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve 2");
+    assert_eq!(entry, same);
+    assert_ne!(entry, other);
+
+    let set = [entry, same, other]
+        .into_iter()
+        .collect::<std::collections::HashSet<_>>();
+
+    assert_eq!(set.len(), 2);
+
+    // Readding the item makes a new referent.
+    reference.remove(1.into()).expect("Failed to remove 1");
+    let readded = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1 again");
+
+    assert!(!set.contains(&readded));
+}
+
+#[test]
+fn projection() {
+    let reference = Reference::new(2);
+    let entry = reference
+        .insert(Foo {
+            id: 1.into(),
+            name: String::from("foo"),
+        })
+        .expect("Failed to insert 1");
+
+    let name = entry.map(|foo: &Foo| &foo.name);
+    let first = name.load().expect("Failed to load name");
+    assert_eq!(*first, "foo");
+
+    // The same version gives the same cached value.
+    let second = name.load().expect("Failed to load name");
+    assert!(Arc::ptr_eq(&first, &second));
+
+    name.entry().modify(|foo| Foo {
+        name: String::from("bar"),
+        ..foo.clone()
+    });
+
+    assert_eq!(*name.load().expect("Failed to load name"), "bar");
+
+    reference.remove(1.into()).expect("Failed to remove 1");
+    assert!(name.load().is_none());
+}
+
+#[test]
+fn fallback() {
+    let unknown = Foo {
+        id: 0.into(),
+        name: String::from("unknown"),
+    };
+
+    let reference = Reference::new(3).with_fallback(unknown.clone());
+    let reserved = reference
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve 1");
+
+    let item = reference.load_or_fallback(&reserved).expect("No fallback");
+
+    assert_eq!(*item, unknown);
+    assert_eq!(*reserved.load_or_default(), Foo::default());
+    assert_eq!(*reserved.load_or(Arc::new(unknown.clone())), unknown);
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert 1");
+
+    let item = reference
+        .load_or_fallback(&reserved)
+        .expect("Failed to load 1");
+
+    assert_eq!(item.id, 1.into());
+}
+
+#[test]
+fn iter_ordered() {
+    let reference = Reference::new(5);
+
+    for id in [3, 1, 4] {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    reference.remove(1.into()).expect("Failed to remove 1");
+    reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve 2");
+
+    let ids = reference
+        .iter_ordered()
+        .filter_map(|entry| entry.load())
+        .map(|item| item.id.as_i32())
+        .collect::<Vec<_>>();
+
+    assert_eq!(ids, [3, 4]);
+    assert_eq!(reference.iter_ordered().count(), reference.iter().count());
+}
+
+#[test]
+fn approx_bytes() {
+    let reference = Reference::new(3);
+    let empty = reference.approx_bytes();
+    assert_eq!(empty.items, 0);
+    assert!(empty.slots > 0);
+
+    reference
+        .insert(Foo {
+            id: 1.into(),
+            name: String::with_capacity(100),
+        })
+        .expect("Failed to insert 1");
+
+    let usage = reference.approx_bytes();
+    assert!(usage.items >= std::mem::size_of::<Foo>() + 100);
+    assert_eq!(usage.slots, empty.slots);
+    assert_eq!(usage.total(), usage.slots + usage.index + usage.items);
+}
+
+#[cfg(all(debug_assertions, not(feature = "single-thread")))]
+#[test]
+fn read_lock_free_maintenance() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let reference = Reference::new(10_000);
+
+    for id in 1..=100 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    // The default index is shrunk through a shadow copy so lookups are never blocked.
+    thread::scope(|scope| {
+        let shrinking = scope.spawn(|| reference.shrink_index_to_fit());
+
+        while !shrinking.is_finished() {
+            reference.assert_read_lock_free();
+            reference.get(1.into()).expect("Failed to get");
+        }
+
+        assert!(shrinking.join().expect("Failed to shrink") > 0);
+    });
+
+    for id in 1..=100 {
+        assert!(reference.contains_resolved(id.into()));
+    }
+
+    /// An index without shadow copies which takes long to shrink.
+    #[derive(Debug)]
+    struct SlowIndex(FlatIdIndex<Foo>);
+
+    impl IdIndex<Foo> for SlowIndex {
+        fn get(&self, id: Id<Foo>) -> Option<u32> {
+            self.0.get(id)
+        }
+
+        fn insert(&mut self, id: Id<Foo>, vid: u32) -> Option<u32> {
+            self.0.insert(id, vid)
+        }
+
+        fn remove(&mut self, id: Id<Foo>) -> Option<u32> {
+            self.0.remove(id)
+        }
+
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn iter(&self) -> Box<dyn Iterator<Item = (Id<Foo>, u32)> + '_> {
+            self.0.iter()
+        }
+
+        fn memory_usage(&self) -> usize {
+            self.0.memory_usage()
+        }
+
+        fn shrink_to_fit(&mut self) {
+            thread::sleep(Duration::from_millis(300));
+            self.0.shrink_to_fit()
+        }
+    }
+
+    let reference = Reference::<Foo>::new(2).with_id_index(SlowIndex(FlatIdIndex::new()));
+
+    thread::scope(|scope| {
+        scope.spawn(|| reference.shrink_index_to_fit());
+        thread::sleep(Duration::from_millis(100));
+        assert!(catch_unwind(AssertUnwindSafe(|| reference.assert_read_lock_free())).is_err());
+    });
+
+    reference.assert_read_lock_free();
+}
+
+#[test]
+fn verify_invariants() {
+    use reference::Violation;
+
+    let reference = Reference::new(5);
+
+    for id in 1..=3 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    reference.remove(2.into()).expect("Failed to remove 2");
+    reference
+        .get_or_reserve(4.into())
+        .expect("Failed to reserve 4");
+
+    let report = reference.verify_invariants();
+    assert!(report.is_ok(), "{report}");
+    assert_eq!((report.ids, report.slots), (4, 4));
+
+    // An index coming with stray ids corrupts the reference.
+    let mut index = FlatIdIndex::new();
+    index.insert(7.into(), 1);
+    index.insert(8.into(), 99);
+
+    let report = reference.with_id_index(index).verify_invariants();
+
+    for violation in [
+        Violation::SharedVid {
+            vid: 1,
+            ids: vec![1, 7],
+        },
+        Violation::IdMismatch {
+            id: 7,
+            vid: 1,
+            item_id: 1,
+        },
+        Violation::VidOutOfBounds { id: 8, vid: 99 },
+        Violation::LenMismatch {
+            ids: 6,
+            used_slots: 4,
+        },
+    ] {
+        assert!(report.violations.contains(&violation), "{report}");
+    }
+
+    assert_eq!(report.violations.len(), 4, "{report}");
+}
+
+#[test]
+fn trim() {
+    let reference = Reference::new(1000).with_id_index(FlatIdIndex::with_capacity(1000));
+
+    for id in 1..=10 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    let before = reference.stats().id_index_memory;
+    let reclaimed = reference.trim();
+    let after = reference.stats().id_index_memory;
+
+    assert!(reclaimed >= before - after);
+    assert!(after < before);
+    assert_eq!(reference.trim(), 0);
+
+    for id in 1..=10 {
+        assert!(reference.contains_resolved(id.into()));
+    }
+
+    reference
+        .insert(Foo::new(11.into()))
+        .expect("Failed to insert after trimming");
+}
+
+#[test]
+fn lazy_entity() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static PARSED: AtomicUsize = AtomicUsize::new(0);
+
+    fn parse(raw: &[u8]) -> Result<Foo, String> {
+        PARSED.fetch_add(1, Ordering::SeqCst);
+
+        let raw = std::str::from_utf8(raw).map_err(|err| err.to_string())?;
+        let (id, name) = raw.split_once(':').ok_or("Missing colon")?;
+        let id = id.parse::<i32>().map_err(|err| err.to_string())?;
+
+        Ok(Foo {
+            id: id.into(),
+            name: name.to_string(),
+        })
+    }
+
+    let reference = Reference::new(3);
+
+    for (id, raw) in [(1, "1:foo"), (2, "broken")] {
+        reference
+            .insert(LazyEntity::new(id.into(), raw.to_string(), parse))
+            .expect("Failed to insert");
+    }
+
+    assert_eq!(PARSED.load(Ordering::SeqCst), 0);
+
+    let item = reference
+        .get(1.into())
+        .and_then(|entry| entry.load())
+        .expect("Failed to load 1");
+
+    assert_eq!(item.raw(), "1:foo");
+    assert_eq!(item.get().expect("Failed to parse").name, "foo");
+    assert_eq!(item.get().expect("Failed to parse").id, 1.into());
+    assert_eq!(PARSED.load(Ordering::SeqCst), 1);
+
+    let broken = reference
+        .get(2.into())
+        .and_then(|entry| entry.load())
+        .expect("Failed to load 2");
+
+    assert_eq!(broken.get(), Err(&String::from("Missing colon")));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_load() {
+    use reference::CapacityPolicy;
+
+    let reference = Reference::new(1002);
+    let sources = (1..=1000).map(|id| format!("{id}")).collect::<Vec<_>>();
+
+    let count = reference
+        .par_load(sources, |source| {
+            source.parse::<i32>().map(|id| Foo {
+                id: id.into(),
+                name: source,
+            })
+        })
+        .expect("Failed to load");
+
+    assert_eq!(count, 1000);
+
+    // Slots follow the order of sources.
+    let ids = reference
+        .iter()
+        .filter_map(|entry| entry.load())
+        .map(|item| item.id.as_i32())
+        .collect::<Vec<_>>();
+
+    assert_eq!(ids, (1..=1000).collect::<Vec<_>>());
+    assert_eq!(
+        reference
+            .get(42.into())
+            .and_then(|e| e.load())
+            .expect("Not found")
+            .name,
+        "42"
+    );
+
+    let result = reference.par_load(vec!["1001", "x"], |source| {
+        source.parse::<i32>().map(|id| Foo::new(id.into()))
+    });
+
+    assert!(result.is_err());
+    assert!(!reference.contains(1001.into()));
+
+    // A batch which doesn't fit is rejected as a whole.
+    let parse = |id: i32| Ok::<_, std::num::ParseIntError>(Foo::new(id.into()));
+    let reference = Reference::new(3);
+    assert!(reference.par_load(vec![1, 2, 3], parse).is_err());
+    assert!(reference.iter().all(|entry| entry.load().is_none()));
+
+    let reference = Reference::new(3).with_duplicate_mode(DuplicateMode::Reject);
+    assert!(reference.par_load(vec![1, 1], parse).is_err());
+    assert!(!reference.contains(1.into()));
+
+    let reference = Reference::new(3).with_capacity_policy(CapacityPolicy::EvictLeastRecentlyUsed);
+    assert_eq!(reference.par_load(vec![1, 2], parse).ok(), Some(2));
+    assert_eq!(reference.par_load(vec![3], parse).ok(), Some(1));
+    assert!(reference.contains(3.into()));
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn coalescer() {
+    use reference::Coalescer;
+
+    let reference = Arc::new(Reference::new(4));
+    let coalescer = Coalescer::start(reference.clone(), Duration::from_secs(3600));
+
+    for name in ["a", "b", "c"] {
+        coalescer.upsert(Foo {
+            id: 1.into(),
+            name: name.to_string(),
+        });
+    }
+
+    coalescer.upsert(Foo::new(2.into()));
+    assert_eq!(coalescer.pending(), 2);
+    assert_eq!(coalescer.coalesced(), 2);
+    assert!(!reference.contains(1.into()));
+
+    assert_eq!(coalescer.flush().expect("Failed to flush"), 2);
+    assert_eq!(coalescer.pending(), 0);
+
+    let item = reference.get(1.into()).and_then(|entry| entry.load());
+    assert_eq!(item.map(|item| item.name.clone()), Some("c".to_string()));
+    assert!(reference.contains_resolved(2.into()));
+
+    // Dropping flushes the rest.
+    coalescer.upsert(Foo::new(3.into()));
+    drop(coalescer);
+    assert!(reference.contains_resolved(3.into()));
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn throttled_writer() {
+    use std::time::Instant;
+
+    use reference::{Overflow, Throttle, WriterHandle};
+
+    let reference = Arc::new(Reference::new(62));
+    let started_at = Instant::now();
+
+    let writer =
+        WriterHandle::spawn_throttled(reference.clone(), Throttle::new(100).with_ops_per_sec(50));
+
+    // A second worth of operations goes at once and the rest 10 take 200 ms.
+    let pending = (1..=60)
+        .map(|id| writer.upsert(Foo::new(id.into())))
+        .collect::<Vec<_>>();
+
+    for pending in pending {
+        pending.wait().expect("Failed to upsert");
+    }
+
+    assert!(started_at.elapsed() >= Duration::from_millis(150));
+    assert_eq!(reference.count_where(|_| true), 60);
+
+    // At most one operation is being applied and another one is queued while the thread
+    // waits for a second after the first one so the rest get rejected.
+    let reference = Arc::new(Reference::new(6));
+
+    let writer = WriterHandle::spawn_throttled(
+        reference,
+        Throttle::new(1)
+            .with_ops_per_sec(1)
+            .with_overflow(Overflow::Reject),
+    );
+
+    let mut pending = (1..=4)
+        .map(|id| writer.upsert(Foo::new(id.into())))
+        .collect::<Vec<_>>();
+
+    match pending.pop().expect("No pending upsert").wait() {
+        Err(Error::InsertError(message)) => assert_eq!(message, "Writer queue is full"),
+        other => panic!("Unexpected result: {other:?}"),
+    }
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn writer_handle() {
+    use reference::WriterHandle;
+
+    let reference = Arc::new(Reference::new(102).with_duplicate_mode(DuplicateMode::Reject));
+    let writer = WriterHandle::spawn(reference.clone());
+
+    let pending = (1..=4)
+        .map(|thread_idx| {
+            let writer = writer.clone();
+
+            thread::spawn(move || {
+                (0..25)
+                    .map(|idx| writer.insert(Foo::new((thread_idx * 25 + idx - 24).into())))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .flat_map(|handle| handle.join().expect("Failed to join"))
+        .collect::<Vec<_>>();
+
+    for pending in pending {
+        pending.wait().expect("Failed to insert");
+    }
+
+    assert_eq!(reference.count_where(|_| true), 100);
+
+    let entry = writer
+        .upsert(Foo::new(1.into()))
+        .wait()
+        .expect("Failed to upsert");
+
+    assert_eq!(entry.load().map(|item| item.id), Some(1.into()));
+
+    match writer.insert(Foo::new(1.into())).wait() {
+        Err(Error::DuplicateId(id)) => assert_eq!(id, 1.into()),
+        other => panic!("Unexpected result: {other:?}"),
+    }
+
+    let removed = writer.remove(1.into()).wait().expect("Failed to remove");
+    assert_eq!(removed.map(|item| item.id), Some(1.into()));
+    assert!(!reference.contains_resolved(1.into()));
+}
+
+#[test]
+fn describe_entry() {
+    let reference = Reference::new(2);
+
+    let reserved = reference
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve");
+
+    let description = reserved.describe();
+    assert_eq!(description.id, None);
+    assert!(!description.resolved);
+    assert_eq!(description.to_string(), "reference::Foo#? v0");
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+
+    let description = reserved.describe();
+    assert_eq!(description.id, Some(1));
+    assert!(description.resolved);
+    assert_eq!(description.to_string(), "reference::Foo#1 v0");
+
+    reference.remove(1.into());
+    assert_eq!(reserved.describe().id, None);
+}
+
+#[test]
+fn bulk_report() {
+    let reference = Reference::new(4).with_duplicate_mode(DuplicateMode::Reject);
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+
+    let items = (1..=4).map(|id| Foo::new(id.into()));
+    let report = reference.insert_many(items);
+
+    assert_eq!((report.inserted, report.replaced), (2, 0));
+    assert_eq!(report.duplicates, [1.into()]);
+
+    // The zero element takes one slot so there's no room for the last item.
+    assert!(matches!(report.errors.as_slice(), [(3, Error::Other(_))]));
+
+    assert!(!report.is_clean());
+
+    let items = vec![Foo::new(1.into()), Foo::new(2.into()), Foo::new(1.into())];
+    let (reference, report) = Reference::from_iter_with_report(items, 0.0);
+
+    assert_eq!(report.inserted, 2);
+    assert_eq!(report.duplicates, [1.into()]);
+    assert!(report.errors.is_empty());
+    assert!(reference.contains_resolved(2.into()));
+}
+
+#[test]
+fn migrate() {
+    #[derive(Debug)]
+    struct FooV2 {
+        id: Id<Self>,
+        title: String,
+    }
+
+    impl Identifiable for FooV2 {
+        fn id(&self) -> Id<Self> {
+            self.id
+        }
+    }
+
+    let reference = Reference::new(4);
+
+    reference
+        .insert(Foo {
+            id: 1.into(),
+            name: String::from("one"),
+        })
+        .expect("Failed to insert");
+
+    reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve");
+
+    let migrated = reference
+        .migrate(|foo| FooV2 {
+            id: foo.id.as_i32().into(),
+            title: foo.name.to_uppercase(),
+        })
+        .expect("Failed to migrate");
+
+    let item = migrated
+        .get(1.into())
+        .and_then(|entry| entry.load())
+        .expect("Failed to load 1");
+
+    assert_eq!(item.title, "ONE");
+    assert!(migrated.contains(2.into()));
+    assert!(!migrated.contains_resolved(2.into()));
+
+    let result = reference.migrate(|foo| FooV2 {
+        id: (foo.id.as_i32() + 1).into(),
+        title: String::new(),
+    });
+
+    assert!(matches!(result, Err(Error::InsertError(_))));
+}
+
+#[test]
+fn import_with_remap() {
+    use reference::IdRemap;
+
+    let reference = Reference::new(5);
+
+    let reserved = reference
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve");
+
+    let resolved = reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert");
+
+    reference
+        .insert(Foo::new(3.into()))
+        .expect("Failed to insert");
+
+    // 1 and 2 swap places: 1 -> 2 -> 1 is a cycle. 3 conflicts with nothing since it stays.
+    let renumber = |id: Id<Foo>| match id.as_i32() {
+        1 => 2.into(),
+        2 => 1.into(),
+        _ => id,
+    };
+
+    let items = vec![Foo::new(2.into())];
+
+    let remap = reference
+        .import_with_remap(items, renumber)
+        .expect("Failed to import");
+
+    assert_eq!(remap.get(1.into()), Some(2.into()));
+    assert_eq!(remap.translate(2.into()), 1.into());
+    assert_eq!(remap.translate(3.into()), 3.into());
+
+    // Entries follow the slots to the new ids.
+    assert_eq!(reserved.load().map(|item| item.id), Some(2.into()));
+    assert_eq!(resolved.load(), None);
+    assert!(reference.contains(1.into()));
+    assert!(!reference.contains_resolved(1.into()));
+
+    // `remap` may call into the reference.
+    let result = reference.import_with_remap(Vec::new(), |id| match id.as_i32() {
+        1 if reference.contains(3.into()) => 3.into(),
+        _ => id,
+    });
+
+    match result {
+        Err(Error::DuplicateIds(ids)) => assert_eq!(ids, [3.into()]),
+        other => panic!("Unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    let mut buf = Vec::new();
+    remap.save_to(&mut buf).expect("Failed to save remap");
+    let loaded = IdRemap::load_from(buf.as_slice()).expect("Failed to load remap");
+    assert_eq!(loaded, remap);
+
+    let mut chained = IdRemap::new();
+    chained.insert(10.into(), 20.into());
+    chained.insert(20.into(), 30.into());
+    assert_eq!(chained.translate(10.into()), Id::<Foo>::new(30));
+}
+
+#[test]
+fn capacity_events() {
+    use std::sync::Mutex;
+
+    use reference::DEFAULT_CAPACITY_EVENT_THRESHOLDS;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_clone = events.clone();
+
+    let reference =
+        Reference::new(20).with_capacity_events(&DEFAULT_CAPACITY_EVENT_THRESHOLDS, move |event| {
+            events_clone
+                .lock()
+                .expect("Failed to lock events")
+                .push((event.threshold, event.used));
+        });
+
+    for id in 1..=19 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    let events = events.lock().expect("Failed to lock events").clone();
+    assert_eq!(events, [(0.8, 16), (0.95, 19), (1.0, 20)]);
+}
+
+#[test]
+fn capacity_policy() {
+    use reference::CapacityPolicy;
+
+    let reference = Reference::new(4).with_capacity_policy(CapacityPolicy::EvictLeastRecentlyUsed);
+
+    let entries = (1..=3)
+        .map(|id| {
+            reference
+                .insert(Foo::new(id.into()))
+                .expect("Failed to insert")
+        })
+        .collect::<Vec<_>>();
+
+    for id in [1, 3] {
+        reference.get(id.into()).expect("Failed to get");
+    }
+
+    reference
+        .insert(Foo::new(4.into()))
+        .expect("Failed to insert 4");
+
+    assert!(entries[1].is_stale());
+    assert!(!reference.contains(2.into()));
+    assert!(reference.contains_resolved(4.into()));
+    assert_eq!(reference.stats().evictions, 1);
+
+    let reference = Reference::new(2);
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+    assert!(reference.insert(Foo::new(2.into())).is_err());
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn evict_while_inserting() {
+    use reference::CapacityPolicy;
+
+    let reference = Reference::new(5).with_capacity_policy(CapacityPolicy::EvictLeastRecentlyUsed);
+
+    // Concurrent inserts into a full reference never take the room made for each other.
+    thread::scope(|scope| {
+        for thread in 0..4 {
+            let reference = &reference;
+
+            scope.spawn(move || {
+                for id in 1..=500 {
+                    reference
+                        .insert(Foo::new((thread * 1000 + id).into()))
+                        .expect("Failed to insert");
+                }
+            });
+        }
+    });
+
+    assert_eq!(reference.stats().len, 5);
+}
+
+#[test]
+fn access_tracking() {
+    use reference::CapacityPolicy;
+
+    let reference = Reference::new(5)
+        .with_capacity_policy(CapacityPolicy::EvictLeastRecentlyUsed)
+        .with_access_tracking(2);
+
+    for id in 1..=4 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    // Only every other lookup counts so 1 gets two and 2 gets one of them.
+    for id in [1, 1, 1, 1, 2, 2] {
+        reference.get(id.into()).expect("Failed to get");
+    }
+
+    // The hand passes 1 and 2 decrementing their counters and stops at 3.
+    let evicted = reference.evict().expect("Failed to evict");
+    assert_eq!(evicted.id, 3.into());
+    assert!(!reference.contains(3.into()));
+
+    // Then 4 which hasn't been looked up either.
+    reference.evict().expect("Failed to evict");
+    assert!(!reference.contains(4.into()));
+
+    // 2 has run out of lookups on the previous pass while 1 hasn't.
+    reference.evict().expect("Failed to evict");
+    assert!(!reference.contains(2.into()));
+    assert!(reference.contains_resolved(1.into()));
+    assert_eq!(reference.stats().evictions, 3);
+}
+
+#[test]
+fn pin() {
+    use reference::CapacityPolicy;
+
+    let clock = ManualClock::new();
+
+    let reference = Reference::new(4)
+        .with_capacity_policy(CapacityPolicy::EvictLeastRecentlyUsed)
+        .with_clock(clock.clone());
+
+    for id in 1..=3 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    assert!(reference.pin(1.into()));
+    assert!(reference.pin(2.into()));
+    assert!(!reference.pin(5.into()));
+    assert!(reference.is_pinned(1.into()));
+    assert_eq!(reference.stats().pinned, 2);
+
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(reference.stale_ids(Duration::from_secs(30)), [3.into()]);
+
+    // Only 3 is unpinned so it's evicted regardless of lookups.
+    reference.get(3.into()).expect("Failed to get");
+    reference.evict().expect("Failed to evict");
+    assert!(!reference.contains(3.into()));
+    assert!(reference.evict().is_none());
+
+    assert!(reference.unpin(2.into()));
+    assert!(!reference.unpin(2.into()));
+    reference.evict().expect("Failed to evict");
+    assert!(!reference.contains(2.into()));
+
+    // Explicit removal unpins.
+    reference.remove(1.into()).expect("Failed to remove");
+    assert!(!reference.is_pinned(1.into()));
+    assert_eq!(reference.stats().pinned, 0);
+}
+
+#[test]
+fn with_id() {
+    use reference::WithId;
+
+    #[derive(Debug, PartialEq)]
+    struct Foreign {
+        name: &'static str,
+    }
+
+    let reference = Reference::new(3);
+
+    reference
+        .insert(WithId::new(1, Foreign { name: "one" }))
+        .expect("Failed to insert");
+
+    let item = reference
+        .get(1.into())
+        .and_then(|entry| entry.load())
+        .expect("Failed to load 1");
+
+    assert_eq!(item.name, "one");
+    assert_eq!(item.id(), 1.into());
+
+    let pairs = Reference::from_iter_exact([(Id::<Foreign>::new(2), Foreign { name: "two" })])
+        .expect("Failed to collect pairs");
+
+    let pair = pairs
+        .get(2.into())
+        .and_then(|entry| entry.load())
+        .expect("Failed to load 2");
+
+    assert_eq!(pair.1, Foreign { name: "two" });
+}
+
+#[test]
+fn try_new() {
+    assert!(Reference::<Foo>::try_new(0).is_err());
+    assert!(Reference::<Foo>::try_new(usize::MAX).is_err());
+    assert!(Reference::<Foo, RwLockBackend<Foo>>::try_with_backend(RwLockBackend::new(0)).is_err());
+
+    let reference = Reference::<Foo>::try_new(2).expect("Failed to create reference");
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+    assert!(reference.contains(1.into()));
+}
+
+#[test]
+fn local_items() {
+    use std::rc::Rc;
+
+    struct Local {
+        id: Id<Self>,
+        name: Rc<str>,
+    }
+
+    impl Identifiable for Local {
+        fn id(&self) -> Id<Self> {
+            self.id
+        }
+    }
+
+    #[cfg(not(feature = "single-thread"))]
+    {
+        fn assert_shareable<S: Send + Sync>() {}
+        assert_shareable::<Reference<Foo>>();
+        assert_shareable::<Reference<Foo, RwLockBackend<Foo>>>();
+        assert_shareable::<Entry<Foo>>();
+    }
+
+    let name = Rc::<str>::from("one");
+    let reference = Reference::new(2);
+
+    let entry = reference
+        .insert(Local {
+            id: 1.into(),
+            name: name.clone(),
+        })
+        .expect("Failed to insert");
+
+    let item = entry.load().expect("Failed to load");
+    assert!(Rc::ptr_eq(&item.name, &name));
+    assert_eq!(Rc::strong_count(&name), 2);
+}
+
+#[test]
+fn branded() {
+    use reference::Branded;
+
+    enum Staging {}
+    enum Production {}
+
+    let staging = Reference::new(2);
+    let production = Reference::new(2);
+
+    staging
+        .insert(Branded::<_, Staging>::new(Foo::new(1.into())))
+        .expect("Failed to insert into staging");
+
+    let entry = production
+        .insert(Branded::<_, Production>::new(Foo::new(1.into())))
+        .expect("Failed to insert into production");
+
+    let id = Branded::<Foo, Production>::brand_id(1.into());
+    assert_eq!(entry.load().expect("Failed to load").id(), id);
+    assert!(production.contains(id));
+    assert!(staging.contains(Branded::<Foo, Staging>::brand_id(1.into())));
+
+    let item = production
+        .get(id)
+        .and_then(|entry| entry.load())
+        .expect("Failed to get");
+
+    assert_eq!(item.id, 1.into());
+}
+
+#[test]
+fn enterable() {
+    use reference::{Enterable, Referential};
+
+    fn link<R: Enterable<Foo>>(reference: &R, id: Id<Foo>) -> Entry<Foo, R::Backend> {
+        reference
+            .entry_or_reserve(id)
+            .expect("Failed to get or reserve")
+    }
+
+    let reference = Reference::with_backend(RwLockBackend::new(3));
+    Referential::upsert(&reference, Foo::new(1.into())).expect("Failed to upsert");
+
+    assert_eq!(
+        link(&reference, 1.into()).load().map(|item| item.id),
+        Some(1.into())
+    );
+    assert!(link(&reference, 2.into()).load().is_none());
+    assert!(reference.entry(2.into()).is_some());
+    assert!(reference.entry(3.into()).is_none());
+}
+
+#[test]
+fn ttl() {
+    let clock = ManualClock::new();
+
+    let reference = Reference::new(5)
+        .with_clock(clock.clone())
+        .with_ttl(Duration::from_secs(60))
+        .with_refresh_on_read();
+
+    for id in 1..=2 {
+        reference
+            .insert(Foo::new(id.into()))
+            .expect("Failed to insert");
+    }
+
+    reference
+        .insert_with_ttl(Foo::new(3.into()), Duration::from_secs(10))
+        .expect("Failed to insert with TTL");
+
+    reference
+        .get_or_reserve(4.into())
+        .expect("Failed to reserve");
+    assert_eq!(reference.ttl(3.into()), Some(Duration::from_secs(10)));
+    assert_eq!(reference.ttl(1.into()), Some(Duration::from_secs(60)));
+
+    clock.advance(Duration::from_secs(11));
+    assert_eq!(reference.expired_ids(), vec![3.into()]);
+
+    clock.advance(Duration::from_secs(40));
+    reference.get(1.into()).expect("Failed to get 1");
+    clock.advance(Duration::from_secs(10));
+
+    // Item 1 has been looked up 10 seconds ago and item 2 hasn't since inserted 61 seconds ago.
+    let mut expired = reference.expired_ids();
+    expired.sort_by_key(|id| id.as_i32());
+    assert_eq!(expired, vec![2.into(), 3.into()]);
+
+    assert_eq!(reference.remove_expired(), 2);
+    assert!(reference.contains_resolved(1.into()));
+    assert!(!reference.contains(2.into()));
+    assert!(reference.contains(4.into()));
+
+    // The override is dropped along with the item.
+    assert_eq!(reference.ttl(3.into()), Some(Duration::from_secs(60)));
+}
+
+#[test]
+fn negative_caching() {
+    use reference::Lookup;
+
+    let clock = ManualClock::new();
+    let reference = Reference::new(3).with_clock(clock.clone());
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+    reference.mark_missing(2.into(), Duration::from_secs(60));
+    reference.mark_missing(3.into(), Duration::from_secs(60));
+
+    assert!(matches!(reference.lookup(1.into()), Lookup::Resolved(_)));
+    assert!(reference.lookup(2.into()).is_known_missing());
+    assert!(matches!(reference.lookup(4.into()), Lookup::Unknown));
+
+    // The source has got the item meanwhile.
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to insert");
+    assert!(matches!(reference.lookup(2.into()), Lookup::Resolved(_)));
+    assert!(!reference.unmark_missing(2.into()));
+
+    clock.advance(Duration::from_secs(61));
+    assert!(matches!(reference.lookup(3.into()), Lookup::Unknown));
+}
+
+#[test]
+fn lookup() {
+    use reference::Lookup;
+
+    let reference = Reference::new(3);
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+    reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve");
+
+    assert!(reference.lookup(1.into()).is_resolved());
+    assert!(reference.lookup(2.into()).is_reserved());
+    assert!(matches!(reference.lookup(3.into()), Lookup::Unknown));
+
+    let entry = reference
+        .lookup(2.into())
+        .entry()
+        .expect("Failed to get reserved entry");
+
+    reference
+        .insert(Foo::new(2.into()))
+        .expect("Failed to fill");
+    assert!(entry.load().is_some());
+    assert!(reference.lookup(2.into()).is_resolved());
+}
+
+#[test]
+fn reconfigure() {
+    use std::sync::Mutex;
+
+    use reference::{ConfigChange, ConfigPatch};
+
+    let clock = ManualClock::new();
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let listened = changes.clone();
+
+    let reference = Reference::new(3)
+        .with_clock(clock.clone())
+        .with_ttl(Duration::from_secs(60))
+        .with_config_listener(move |change: &ConfigChange| {
+            listened
+                .lock()
+                .expect("Failed to lock")
+                .push(change.clone())
+        });
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+    clock.advance(Duration::from_secs(30));
+    assert!(reference.expired_ids().is_empty());
+
+    let patch = ConfigPatch::default()
+        .with_ttl(Some(Duration::from_secs(10)))
+        .with_utilization_warning_threshold(0.9);
+
+    let expected = vec![ConfigChange::Ttl {
+        from: Some(Duration::from_secs(60)),
+        to: Some(Duration::from_secs(10)),
+    }];
+
+    // The threshold is already at the default so it doesn't change.
+    assert_eq!(reference.reconfigure(&patch), expected);
+    assert_eq!(*changes.lock().expect("Failed to lock"), expected);
+    assert_eq!(reference.expired_ids(), vec![1.into()]);
+    assert!(reference.reconfigure(&patch).is_empty());
+}
+
+#[test]
+fn insert_many_with_progress() {
+    let clock = ManualClock::new();
+    let reference = Reference::new(11).with_clock(clock.clone());
+    let mut reports = Vec::new();
+
+    let report = reference.insert_many_with_progress(
+        (1..=10).map(|id| Foo::new(id.into())),
+        4,
+        |progress| {
+            reports.push((progress.processed, progress.fraction(), progress.eta()));
+            clock.advance(Duration::from_secs(1));
+        },
+    );
+
+    assert_eq!(report.inserted, 10);
+
+    // The second report comes 1 second after the start at 8 items per second.
+    assert_eq!(
+        reports,
+        [
+            (4, Some(0.4), None),
+            (8, Some(0.8), Some(Duration::from_millis(250))),
+            (10, Some(1.0), Some(Duration::ZERO)),
+        ]
+    );
+}
+
+#[test]
+fn overlapping_hydrations() {
+    use reference::Readiness;
+
+    let reference = Reference::new(11);
+    let mut readiness = Vec::new();
+
+    reference.insert_many_with_progress((1..=5).map(|id| Foo::new(id.into())), 5, |_| {
+        // The outer load is still going on once the inner one finishes.
+        reference.insert_many_with_progress((6..=10).map(|id| Foo::new(id.into())), 5, |_| ());
+        readiness.push(reference.readiness());
+    });
+
+    assert!(matches!(
+        readiness[..],
+        [Readiness::Loading { progress }] if progress.processed == 5
+    ));
+
+    assert!(reference.readiness().is_ready());
+}
+
+#[test]
+fn degraded_reads() {
+    use reference::DegradedReads;
+
+    let clock = ManualClock::new();
+
+    let build = |policy| {
+        let reference = Reference::new(4)
+            .with_clock(clock.clone())
+            .with_degradation(Duration::from_secs(60), 0.4)
+            .with_fallback(Foo {
+                id: 0.into(),
+                name: String::from("unknown"),
+            })
+            .with_degraded_reads(policy);
+
+        reference
+            .insert(Foo::new(1.into()))
+            .expect("Failed to insert");
+        reference
+    };
+
+    let stale = build(DegradedReads::ServeStale);
+    let fallback = build(DegradedReads::ServeFallback);
+    let failing = build(DegradedReads::Fail);
+
+    clock.advance(Duration::from_secs(61));
+
+    for reference in [&stale, &fallback, &failing] {
+        reference
+            .insert(Foo::new(2.into()))
+            .expect("Failed to insert");
+
+        // Not degraded until checked.
+        let item = reference.read(1.into()).expect("Failed to read");
+        assert_eq!(item.map(|item| item.id), Some(1.into()));
+        assert!(!reference.readiness().is_ready());
+    }
+
+    let item = stale.read(1.into()).expect("Failed to read stale");
+    assert_eq!(item.map(|item| item.id), Some(1.into()));
+    assert!(stale
+        .read(3.into())
+        .expect("Failed to read stale")
+        .is_none());
+
+    let read = |id: i32| {
+        fallback
+            .read(id.into())
+            .expect("Failed to read fallback")
+            .map(|item| item.id)
+    };
+
+    assert_eq!(read(1), Some(0.into()));
+    assert_eq!(read(2), Some(2.into()));
+    assert_eq!(read(3), Some(0.into()));
+
+    assert!(matches!(
+        failing.read(2.into()),
+        Err(Error::Unavailable { id }) if id == 2.into()
+    ));
+
+    failing
+        .insert(Foo::new(1.into()))
+        .expect("Failed to refresh");
+    assert!(failing.readiness().is_ready());
+    assert!(failing.read(2.into()).is_ok());
+}
+
+#[test]
+fn skip_unchanged() {
+    use reference::{ByHash, ByValue};
+
+    let reference = Reference::new(3).with_skip_unchanged(ByValue);
+    let entry = reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+
+    let current = entry.load().expect("Item is missing");
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert unchanged");
+
+    let item = entry.load().expect("Item is missing");
+    assert!(Arc::ptr_eq(&item, &current));
+
+    let changed = Foo {
+        id: 1.into(),
+        name: "changed".to_owned(),
+    };
+
+    reference
+        .insert(changed.clone())
+        .expect("Failed to replace");
+    assert_eq!(entry.load().as_deref(), Some(&changed));
+
+    // Only the length of the name matters to this hash.
+    let reference =
+        Reference::new(3).with_skip_unchanged(ByHash(|foo: &Foo| foo.name.len() as u64));
+    let entry = reference.insert(changed).expect("Failed to insert");
+    let current = entry.load().expect("Item is missing");
+
+    let same_len = Foo {
+        id: 1.into(),
+        name: "CHANGED".to_owned(),
+    };
+
+    reference
+        .insert(same_len)
+        .expect("Failed to insert unchanged");
+    let item = entry.load().expect("Item is missing");
+    assert!(Arc::ptr_eq(&item, &current));
+
+    // Duplicates are still rejected.
+    let reference = Reference::new(3)
+        .with_duplicate_mode(DuplicateMode::Reject)
+        .with_skip_unchanged(ByValue);
+
+    reference
+        .insert(Foo::new(1.into()))
+        .expect("Failed to insert");
+
+    assert!(reference.insert(Foo::new(1.into())).is_err());
+}
+
+#[test]
+fn fingerprint() {
+    use reference::{ByHash, HashContent};
+
+    let foo = |id: i32, name: &str| Foo {
+        id: id.into(),
+        name: name.to_owned(),
+    };
+
+    let first = Reference::new(4).with_content_hash(HashContent);
+    let second = Reference::new(4).with_content_hash(HashContent);
+    assert_eq!(first.stats().fingerprint, Some(0));
+    assert_eq!(Reference::<Foo>::new(4).stats().fingerprint, None);
+
+    for (id, name) in [(1, "one"), (2, "two")] {
+        first.insert(foo(id, name)).expect("Failed to insert");
+    }
+
+    // The order of insertion and reservations don't matter.
+    second.get_or_reserve(3.into()).expect("Failed to reserve");
+
+    for (id, name) in [(2, "two"), (1, "one")] {
+        second.insert(foo(id, name)).expect("Failed to insert");
+    }
+
+    assert!(first.fingerprint().is_some());
+    assert_eq!(first.stats().fingerprint, second.stats().fingerprint);
+    assert_eq!(first.content_hash(1.into()), second.content_hash(1.into()));
+    assert_ne!(first.content_hash(1.into()), first.content_hash(2.into()));
+    assert_eq!(second.content_hash(3.into()), None);
+
+    // Swapping contents of items changes the fingerprint.
+    second.insert(foo(1, "two")).expect("Failed to replace");
+    second.insert(foo(2, "one")).expect("Failed to replace");
+    assert_ne!(first.fingerprint(), second.fingerprint());
+
+    let by_len = Reference::new(4).with_content_hash(ByHash(|foo: &Foo| foo.name.len() as u64));
+    by_len.insert(foo(1, "three")).expect("Failed to insert");
+    assert_eq!(by_len.content_hash(1.into()), Some(5));
+}
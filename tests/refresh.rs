@@ -0,0 +1,171 @@
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use reference::{Id, Identifiable, Reference};
+
+#[derive(Debug, PartialEq)]
+struct Rate {
+    id: Id<Self>,
+    value: u32,
+}
+
+impl Identifiable for Rate {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+fn rate(id: i32, value: u32) -> Rate {
+    Rate {
+        id: id.into(),
+        value,
+    }
+}
+
+fn value(reference: &Reference<Rate>, id: i32) -> Option<u32> {
+    let item = reference.get(id.into()).and_then(|entry| entry.load());
+    item.map(|rate| rate.value)
+}
+
+#[test]
+fn replace_all() {
+    let reference = Reference::new(8);
+    let entry = reference.insert(rate(1, 10)).expect("Failed to insert");
+    reference.insert(rate(2, 20)).expect("Failed to insert");
+    reference
+        .get_or_reserve(3.into())
+        .expect("Failed to reserve");
+
+    let summary = reference
+        .replace_all([rate(1, 11), rate(4, 40)])
+        .expect("Failed to replace");
+
+    assert_eq!((summary.upserted, summary.removed), (2, 1));
+    assert_eq!(entry.load().map(|rate| rate.value), Some(11));
+    assert_eq!(value(&reference, 2), None);
+    assert!(reference.contains(3.into()));
+    assert_eq!(value(&reference, 4), Some(40));
+}
+
+#[test]
+fn partial_refresh() {
+    let reference = Reference::new(8);
+    let entry = reference.insert(rate(1, 10)).expect("Failed to insert");
+    reference.insert(rate(2, 20)).expect("Failed to insert");
+    reference.insert(rate(3, 30)).expect("Failed to insert");
+
+    let summary = reference
+        .refresh([1.into(), 2.into()], |ids: &[Id<Rate>]| {
+            assert_eq!(ids.len(), 2);
+            Ok::<_, String>(vec![rate(1, 11), rate(3, 0)])
+        })
+        .expect("Failed to refresh");
+
+    assert_eq!((summary.upserted, summary.removed), (1, 1));
+    assert_eq!(entry.load().map(|rate| rate.value), Some(11));
+    assert_eq!(value(&reference, 2), None);
+    assert_eq!(value(&reference, 3), Some(30));
+
+    let result = reference.refresh([3.into()], |_: &[Id<Rate>]| Err("Source is down"));
+    assert!(result.is_err());
+    assert_eq!(value(&reference, 3), Some(30));
+
+    let loader = |ids: Vec<Id<Rate>>| async move {
+        let items = ids.into_iter().map(|id| rate(id.as_i32(), 33)).collect();
+        Ok::<_, String>(items)
+    };
+
+    let summary = block_on(reference.refresh_async([3.into()], loader)).expect("Failed to refresh");
+    assert_eq!((summary.upserted, summary.removed), (1, 0));
+    assert_eq!(value(&reference, 3), Some(33));
+}
+
+#[test]
+fn provenance() {
+    let plain = Reference::new(2);
+    let entry = plain.insert(rate(1, 10)).expect("Failed to insert");
+    assert_eq!(plain.batch(), None);
+    assert_eq!(plain.begin_batch(), None);
+    assert_eq!(entry.provenance(), None);
+
+    let reference = Reference::new(8).with_provenance();
+    let initial = reference.batch().expect("No initial batch");
+    let first = reference.insert(rate(1, 10)).expect("Failed to insert");
+    let second = reference.insert(rate(2, 20)).expect("Failed to insert");
+    let reserved = reference
+        .get_or_reserve(3.into())
+        .expect("Failed to reserve");
+
+    let provenance = first.provenance().expect("No provenance");
+    assert_eq!(provenance.batch, initial);
+    assert_eq!(reserved.provenance(), None);
+
+    reference
+        .refresh([2.into()], |_| Ok::<_, String>(vec![rate(2, 22)]))
+        .expect("Failed to refresh");
+
+    let refreshed = reference.batch().expect("No batch");
+    assert!(refreshed > initial);
+    assert_eq!(first.provenance().map(|p| p.batch), Some(initial));
+    assert_eq!(second.provenance().map(|p| p.batch), Some(refreshed));
+
+    first.modify(|rate| Rate {
+        id: rate.id,
+        value: rate.value + 1,
+    });
+
+    assert_eq!(first.provenance(), None);
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn refresh_scheduler() {
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+
+    use reference::RefreshScheduler;
+
+    let reference = Arc::new(Reference::new(8));
+    reference.insert(rate(1, 0)).expect("Failed to insert");
+
+    let (success_tx, success_rx) = mpsc::channel();
+    let (failure_tx, failure_rx) = mpsc::channel();
+    let mut runs = 0;
+
+    let scheduler = RefreshScheduler::new(Duration::from_millis(5), move || {
+        runs += 1;
+
+        match runs {
+            2 | 3 => Err("Source is down".into()),
+            _ => Ok(vec![rate(1, runs)]),
+        }
+    })
+    .with_max_backoff(Duration::from_millis(15))
+    .with_success_hook(move |summary, _| success_tx.send(summary.upserted).unwrap_or(()))
+    .with_failure_hook(move |_, failures| failure_tx.send(failures).unwrap_or(()));
+
+    let handle = scheduler.start(reference.clone());
+    let timeout = Duration::from_secs(5);
+
+    assert_eq!(success_rx.recv_timeout(timeout), Ok(1));
+    assert_eq!(failure_rx.recv_timeout(timeout), Ok(1));
+    assert_eq!(failure_rx.recv_timeout(timeout), Ok(2));
+    assert_eq!(success_rx.recv_timeout(timeout), Ok(1));
+    handle.stop();
+
+    let value = value(&reference, 1).expect("No rate");
+    assert!(value >= 4);
+    assert_eq!(reference.iter().filter_map(|entry| entry.load()).count(), 1);
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use reference::{Cascade, Entry, Error, Id, Identifiable, Reference, Relation};
+
+#[derive(Debug)]
+struct Subject {
+    id: Id<Self>,
+}
+
+impl Identifiable for Subject {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[derive(Debug)]
+struct Product {
+    id: Id<Self>,
+    subject: Entry<Subject>,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+fn setup() -> (Reference<Subject>, Reference<Product>) {
+    let subjects = Reference::new(3);
+    let products = Reference::new(4);
+
+    for subject_id in [1, 2] {
+        subjects
+            .insert(Subject {
+                id: subject_id.into(),
+            })
+            .expect("Failed to insert subject");
+    }
+
+    for (product_id, subject_id) in [(1, 1), (2, 1), (3, 2)] {
+        let subject = subjects.get(subject_id.into()).expect("Subject not found");
+
+        products
+            .insert(Product {
+                id: product_id.into(),
+                subject,
+            })
+            .expect("Failed to insert product");
+    }
+
+    (subjects, products)
+}
+
+fn subject_of(product: &Product) -> &Entry<Subject> {
+    &product.subject
+}
+
+#[test]
+fn restrict() {
+    let (subjects, products) = setup();
+    let relation = Relation::new(subject_of, Cascade::Restrict);
+
+    match relation.remove(&subjects, &products, 1.into()) {
+        Err(Error::RemoveRestricted { id, dependents }) => {
+            assert_eq!(id, 1.into());
+            assert_eq!(dependents, 2);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    assert!(subjects.contains_resolved(1.into()));
+}
+
+#[test]
+fn set_none() {
+    let (subjects, products) = setup();
+    let relation = Relation::new(subject_of, Cascade::SetNone);
+
+    relation
+        .remove(&subjects, &products, 1.into())
+        .expect("Failed to remove subject")
+        .expect("Nothing removed");
+
+    let product = products
+        .get(1.into())
+        .and_then(|entry| entry.load())
+        .expect("Product not found");
+
+    assert!(product.subject.load().is_none());
+}
+
+#[test]
+fn cascade_remove() {
+    let (subjects, products) = setup();
+    let relation = Relation::new(subject_of, Cascade::CascadeRemove);
+
+    relation
+        .remove(&subjects, &products, 1.into())
+        .expect("Failed to remove subject")
+        .expect("Nothing removed");
+
+    assert!(!products.contains(1.into()));
+    assert!(!products.contains(2.into()));
+    assert!(products.contains(3.into()));
+}
+
+reference::relations! {
+    ProductSubject: Product.subject -> Subject [CascadeRemove];
+}
+
+#[test]
+fn declared_relation() {
+    let (subjects, products) = setup();
+    let relation = ProductSubject::relation();
+    assert_eq!(relation.cascade(), Cascade::CascadeRemove);
+
+    let back_references = relation.back_references(&products);
+    let mut dependents = back_references[&1.into()].clone();
+    dependents.sort_by_key(|id| id.as_i32());
+    assert_eq!(dependents, [1.into(), 2.into()]);
+    assert_eq!(back_references[&2.into()], [3.into()]);
+
+    let product = products
+        .get(3.into())
+        .and_then(|entry| entry.load())
+        .expect("Product not found");
+
+    assert_eq!(product.subject().expect("Subject not found").id, 2.into());
+
+    subjects.remove(2.into()).expect("Nothing removed");
+    assert_eq!(relation.validate(&products), [3.into()]);
+}
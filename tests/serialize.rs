@@ -0,0 +1,133 @@
+#![cfg(all(feature = "serde", feature = "serde_json"))]
+
+use reference::serialize::Expanded;
+use reference::{Entry, Id, Identifiable, Reference};
+use serde::Serialize;
+use serde_json::json;
+
+#[derive(Serialize)]
+struct Category {
+    id: Id<Self>,
+    #[serde(serialize_with = "reference::serialize::expanded")]
+    parent: Entry<Category>,
+}
+
+impl Identifiable for Category {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[derive(Serialize)]
+struct Product {
+    id: Id<Self>,
+    category: Entry<Category>,
+}
+
+impl Identifiable for Product {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[test]
+fn serialize_entries() {
+    let categories = Reference::<Category>::new(3);
+    let products = Reference::<Product>::new(3);
+
+    // 1 -> 2 -> 1 is a cycle.
+    categories
+        .reserve_many([1.into(), 2.into()])
+        .expect("Failed to reserve categories");
+
+    for (id, parent) in [(1, 2), (2, 1)] {
+        categories
+            .insert(Category {
+                id: id.into(),
+                parent: categories.get(parent.into()).expect("Category not found"),
+            })
+            .expect("Failed to insert category");
+    }
+
+    let product = products
+        .insert(Product {
+            id: 1.into(),
+            category: categories.get(1.into()).expect("Category not found"),
+        })
+        .expect("Failed to insert product");
+
+    let reserved = products
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve product");
+
+    let value = serde_json::to_value(&products).expect("Failed to serialize products");
+    assert_eq!(value, json!({"1": {"id": 1, "category": 1}}));
+
+    let value = serde_json::to_value(Expanded(&product)).expect("Failed to serialize product");
+    assert_eq!(value, json!({"id": 1, "category": 1}));
+
+    let item = product.load().expect("Product not found");
+    let value =
+        serde_json::to_value(Expanded(&item.category)).expect("Failed to serialize category");
+
+    assert_eq!(value, json!({"id": 1, "parent": {"id": 2, "parent": 1}}),);
+
+    let value = serde_json::to_value(&reserved).expect("Failed to serialize reservation");
+    assert_eq!(value, json!(null));
+}
+
+#[test]
+fn serialize_entry_description() {
+    let categories = Reference::<Category>::new(2);
+
+    let reserved = categories
+        .get_or_reserve(1.into())
+        .expect("Failed to reserve category");
+
+    let value = serde_json::to_value(reserved.describe()).expect("Failed to serialize");
+
+    assert_eq!(
+        value,
+        json!({"type_name": "serialize::Category", "id": null, "resolved": false, "version": 0})
+    );
+}
+
+#[test]
+fn deserialize_manifest() {
+    use reference::{CapacityPolicy, IdIndexKind, Manifest};
+
+    let manifest: Manifest = serde_json::from_value(json!({
+        "defaults": {"capacity": 4},
+        "references": {
+            "Category": {
+                "capacity": 10,
+                "headroom": 0.5,
+                "ttl_secs": 60,
+                "capacity_policy": "evict_least_recently_used",
+                "id_index": "flat"
+            }
+        }
+    }))
+    .expect("Failed to deserialize manifest");
+
+    let config = manifest.config::<Category>();
+    assert_eq!(
+        config.capacity_policy,
+        CapacityPolicy::EvictLeastRecentlyUsed
+    );
+    assert_eq!(config.id_index, IdIndexKind::Flat);
+    assert_eq!(manifest.config::<String>().capacity, 4);
+
+    let categories = manifest
+        .build::<Category>()
+        .expect("Failed to build reference");
+
+    assert_eq!(categories.stats().capacity, 16);
+    assert_eq!(
+        categories.ttl(1.into()),
+        Some(std::time::Duration::from_secs(60))
+    );
+
+    let unknown = serde_json::from_value::<Manifest>(json!({"defaults": {"capasity": 4}}));
+    assert!(unknown.is_err());
+}
@@ -0,0 +1,93 @@
+#![cfg(all(feature = "shm", not(feature = "single-thread")))]
+
+use std::path::PathBuf;
+
+use reference::{Error, Id, Identifiable, Reference, ShmBackend, ShmReader};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Price {
+    id: Id<Self>,
+    value: u64,
+}
+
+impl Price {
+    fn new(id: i32, value: u64) -> Self {
+        Self {
+            id: id.into(),
+            value,
+        }
+    }
+}
+
+impl Identifiable for Price {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+fn segment_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("reference-{}-{}", name, std::process::id()))
+}
+
+fn value(reference: &Reference<Price, ShmReader<Price>>, id: i32) -> Option<u64> {
+    let entry = reference.get(id.into())?;
+    entry.load().map(|price| price.value)
+}
+
+#[test]
+fn shared_memory() {
+    let path = segment_path("shared_memory");
+    let backend = unsafe { ShmBackend::create(&path, 8) }.expect("Failed to create segment");
+    let writer = Reference::with_backend(backend);
+    writer
+        .insert(Price::new(1, 100))
+        .expect("Failed to insert 1");
+    writer
+        .insert(Price::new(2, 200))
+        .expect("Failed to insert 2");
+
+    let reader = unsafe { ShmReader::<Price>::open(&path) }.expect("Failed to open segment");
+    let reader = Reference::from_shm_reader(reader);
+    assert_eq!(value(&reader, 1), Some(100));
+    assert_eq!(value(&reader, 2), Some(200));
+    assert!(!reader.sync_with_writer());
+
+    // Replacements are visible right away while additions and removals need syncing.
+    let entry = reader.get(1.into()).expect("Failed to get 1");
+    writer
+        .insert(Price::new(1, 101))
+        .expect("Failed to replace 1");
+    writer
+        .insert(Price::new(3, 300))
+        .expect("Failed to insert 3");
+    writer.remove(2.into()).expect("Failed to remove 2");
+    assert_eq!(entry.load().map(|price| price.value), Some(101));
+    assert_eq!(value(&reader, 2), None);
+    assert_eq!(value(&reader, 3), None);
+
+    writer
+        .get_or_reserve(5.into())
+        .expect("Failed to reserve 5");
+
+    assert!(reader.sync_with_writer());
+    assert_eq!(value(&reader, 3), Some(300));
+    assert!(!reader.contains(2.into()));
+
+    // Reservations are indexed too and get resolved without syncing.
+    assert!(reader.contains(5.into()));
+    assert_eq!(value(&reader, 5), None);
+    writer
+        .insert(Price::new(5, 500))
+        .expect("Failed to insert 5");
+    assert_eq!(value(&reader, 5), Some(500));
+
+    assert!(reader.insert(Price::new(4, 400)).is_err());
+    assert!(reader.remove(1.into()).is_none());
+    assert!(matches!(
+        reader.get_or_reserve(4.into()),
+        Err(Error::InsertError(_))
+    ));
+    assert_eq!(value(&reader, 1), Some(101));
+
+    std::fs::remove_file(path).expect("Failed to remove segment");
+}
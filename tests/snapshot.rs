@@ -0,0 +1,272 @@
+use std::error::Error as StdError;
+
+use reference::{Codec, Compression, Error, Id, Identifiable, Reference};
+
+#[derive(Debug, PartialEq)]
+struct Price {
+    id: Id<Self>,
+    value: u64,
+}
+
+impl Identifiable for Price {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+struct PriceCodec;
+
+impl Codec<Price> for PriceCodec {
+    fn encode(
+        &self,
+        item: &Price,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        buf.extend_from_slice(&item.id.as_i32().to_le_bytes());
+        buf.extend_from_slice(&item.value.to_le_bytes());
+        Ok(())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Price, Box<dyn StdError + Send + Sync>> {
+        let id = i32::from_le_bytes(bytes.get(..4).ok_or("Too short")?.try_into()?);
+        let value = u64::from_le_bytes(bytes.get(4..12).ok_or("Too short")?.try_into()?);
+        Ok(Price {
+            id: id.into(),
+            value,
+        })
+    }
+}
+
+fn check_snapshot(compression: Compression) {
+    // Enough items to span several chunks.
+    let count = 100_000;
+    let reference = Reference::new(count + 10);
+
+    for id in 1..=count as i32 {
+        let price = Price {
+            id: id.into(),
+            value: id as u64 % 7,
+        };
+
+        reference.insert(price).expect("Failed to insert");
+    }
+
+    reference.remove(5.into());
+
+    let mut buf = Vec::new();
+
+    reference
+        .save_compressed_to(&mut buf, &PriceCodec, compression)
+        .expect("Failed to save");
+
+    let loaded = Reference::load_from(buf.as_slice(), &PriceCodec).expect("Failed to load");
+    assert_eq!(loaded.stats().capacity, count + 10);
+    assert!(!loaded.contains(5.into()));
+
+    for id in [1, 6, count as i32] {
+        let price = loaded.get(id.into()).and_then(|entry| entry.load());
+        assert_eq!(price.map(|price| price.value), Some(id as u64 % 7));
+    }
+
+    assert_eq!(
+        loaded.iter().filter_map(|entry| entry.load()).count(),
+        count - 1
+    );
+}
+
+#[test]
+fn snapshot() {
+    check_snapshot(Compression::None);
+
+    let reference = Reference::<Price>::new(1);
+    let mut buf = Vec::new();
+    reference
+        .save_to(&mut buf, &PriceCodec)
+        .expect("Failed to save");
+    Reference::load_from(buf.as_slice(), &PriceCodec).expect("Failed to load empty");
+}
+
+#[test]
+fn corrupt_snapshot() {
+    let reference = Reference::new(4);
+
+    for id in 1..=3 {
+        let price = Price {
+            id: id.into(),
+            value: id as u64,
+        };
+
+        reference.insert(price).expect("Failed to insert");
+    }
+
+    let mut buf = Vec::new();
+    reference
+        .save_to(&mut buf, &PriceCodec)
+        .expect("Failed to save");
+    Reference::load_from(buf.as_slice(), &PriceCodec).expect("Failed to load");
+
+    let corrupt_at = |bytes: &[u8]| match Reference::load_from(bytes, &PriceCodec) {
+        Err(Error::CorruptSnapshot { offset }) => offset,
+        other => panic!("Unexpected result: {other:?}"),
+    };
+
+    // The first chunk goes right after the 26 bytes of the header.
+    let mut damaged = buf.clone();
+    damaged[44] ^= 1;
+    assert_eq!(corrupt_at(&damaged), 26);
+
+    assert_eq!(corrupt_at(&buf[..29]), 26);
+    assert_eq!(corrupt_at(&buf[..44]), 38);
+
+    // A damaged capacity is caught before allocating.
+    let mut damaged = buf.clone();
+    damaged[17] = 0xFF;
+    assert_eq!(corrupt_at(&damaged), 0);
+
+    let trailer_offset = buf.len() as u64 - 20;
+    assert_eq!(corrupt_at(&buf[..buf.len() - 1]), trailer_offset);
+
+    let mut damaged = buf.clone();
+    let len = damaged.len();
+    damaged[len - 20] += 1;
+    assert_eq!(corrupt_at(&damaged), trailer_offset);
+
+    assert_eq!(corrupt_at(b"garbage garbage garbage"), 0);
+}
+
+/// Encodes prices in cents since schema version 1.
+struct CentsCodec;
+
+impl Codec<Price> for CentsCodec {
+    fn encode(
+        &self,
+        item: &Price,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        PriceCodec.encode(item, buf)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Price, Box<dyn StdError + Send + Sync>> {
+        PriceCodec.decode(bytes)
+    }
+
+    fn schema_version(&self) -> u32 {
+        1
+    }
+
+    fn migrate(
+        &self,
+        version: u32,
+        bytes: &[u8],
+    ) -> Result<Price, Box<dyn StdError + Send + Sync>> {
+        match version {
+            0 => PriceCodec.decode(bytes).map(|price| Price {
+                id: price.id,
+                value: price.value * 100,
+            }),
+            _ => Err(format!("Unknown schema version {version}").into()),
+        }
+    }
+}
+
+#[test]
+fn snapshot_migration() {
+    let reference = Reference::new(2);
+    let price = Price {
+        id: 1.into(),
+        value: 5,
+    };
+
+    reference.insert(price).expect("Failed to insert");
+
+    let mut old = Vec::new();
+    reference
+        .save_to(&mut old, &PriceCodec)
+        .expect("Failed to save");
+    let loaded = Reference::load_from(old.as_slice(), &CentsCodec).expect("Failed to load");
+    let value = |reference: &Reference<Price>| reference.get(1.into()).and_then(|e| e.load());
+    assert_eq!(value(&loaded).map(|price| price.value), Some(500));
+
+    let mut new = Vec::new();
+    loaded
+        .save_to(&mut new, &CentsCodec)
+        .expect("Failed to save");
+    let reloaded = Reference::load_from(new.as_slice(), &CentsCodec).expect("Failed to load");
+    assert_eq!(value(&reloaded).map(|price| price.value), Some(500));
+
+    // A codec without migrations refuses snapshots of other versions.
+    let result = Reference::load_from(new.as_slice(), &PriceCodec);
+    assert!(matches!(result, Err(Error::CodecError(_))));
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_snapshot() {
+    check_snapshot(Compression::Zstd { level: 3 });
+}
+
+#[cfg(feature = "lz4_flex")]
+#[test]
+fn lz4_snapshot() {
+    check_snapshot(Compression::Lz4);
+}
+
+#[test]
+fn record_and_replay() {
+    use std::fs::File;
+    use std::io::BufWriter;
+
+    let path = std::env::temp_dir().join(format!("reference-recording-{}", std::process::id()));
+    let file = File::create(&path).expect("Failed to create recording");
+    let reference = Reference::new(8).with_recording(BufWriter::new(file), PriceCodec);
+
+    for id in 1..=3 {
+        let price = Price {
+            id: id.into(),
+            value: id as u64,
+        };
+
+        reference.insert(price).expect("Failed to insert");
+    }
+
+    reference
+        .get_or_reserve(4.into())
+        .expect("Failed to reserve");
+
+    reference.remove(2.into());
+
+    reference
+        .upsert(Price {
+            id: 1.into(),
+            value: 10,
+        })
+        .expect("Failed to upsert");
+
+    let expected = reference
+        .iter()
+        .map(|entry| entry.load().map(|price| (price.id, price.value)))
+        .collect::<Vec<_>>();
+
+    drop(reference);
+
+    let replayed = Reference::new(8);
+    let file = File::open(&path).expect("Failed to open recording");
+    let applied = replayed
+        .replay(file, &PriceCodec)
+        .expect("Failed to replay");
+
+    std::fs::remove_file(&path).expect("Failed to remove recording");
+    assert_eq!(applied, 6);
+
+    let actual = replayed
+        .iter()
+        .map(|entry| entry.load().map(|price| (price.id, price.value)))
+        .collect::<Vec<_>>();
+
+    assert_eq!(actual, expected);
+    assert!(replayed.contains(4.into()));
+    assert!(!replayed.contains(2.into()));
+
+    let err = replayed.replay(&b"garbage"[..], &PriceCodec);
+    assert!(err.is_err());
+}
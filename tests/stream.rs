@@ -0,0 +1,118 @@
+#![cfg(feature = "stream")]
+
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+use reference::{Id, Identifiable, Reference};
+
+#[derive(Debug, Default)]
+struct Foo {
+    id: Id<Self>,
+}
+
+impl Identifiable for Foo {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+#[test]
+fn stream_yields_cooperatively() {
+    let reference = Reference::new(4);
+
+    for id in 1..4 {
+        reference
+            .insert(Foo { id: id.into() })
+            .expect("Failed to insert");
+    }
+
+    let mut stream = reference.stream_with_yield_every(2);
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut polls = Vec::new();
+
+    loop {
+        match Pin::new(&mut stream).poll_next(&mut cx) {
+            Poll::Pending => polls.push("pending".to_string()),
+            Poll::Ready(None) => break,
+            Poll::Ready(Some(entry)) => {
+                let id = entry.load().map(|entity| entity.id.as_i32()).unwrap_or(0);
+                polls.push(id.to_string());
+            }
+        }
+    }
+
+    assert_eq!(polls, ["0", "1", "pending", "2", "3", "pending"]);
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+struct IterStream<I>(I);
+
+impl<I: Iterator + Unpin> Stream for IterStream<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.0.next())
+    }
+}
+
+#[test]
+fn load_stream() {
+    let reference = Reference::new(5);
+    reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve");
+    reference
+        .insert(Foo { id: 3.into() })
+        .expect("Failed to insert");
+
+    let items = [1, 2, -1, 3, 4]
+        .into_iter()
+        .map(|id| match id {
+            -1 => Err("broken item"),
+            id => Ok(Foo { id: id.into() }),
+        })
+        .collect::<Vec<_>>();
+
+    let summary = block_on(reference.load_stream_with_batch_size(IterStream(items.into_iter()), 2));
+    assert_eq!(summary.inserted, 3);
+    assert_eq!(summary.replaced, 1);
+    assert_eq!(summary.failed, 1);
+
+    for id in 1..5 {
+        let entry = reference.get(id.into()).expect("Entry not found");
+        assert!(entry.load().is_some());
+    }
+}
+
+#[test]
+fn load_stream_with_progress() {
+    let reference = Reference::new(4);
+    let mut reports = Vec::new();
+
+    let items = [1, -1, 2, 3].into_iter().map(|id| match id {
+        -1 => Err("broken item"),
+        id => Ok(Foo { id: id.into() }),
+    });
+
+    let summary = block_on(reference.load_stream_with_progress(
+        IterStream(items),
+        Some(4),
+        |progress| reports.push((progress.processed, progress.total)),
+    ));
+
+    assert_eq!(summary.inserted, 3);
+    assert_eq!(summary.failed, 1);
+    assert_eq!(reports, [(4, Some(4))]);
+}
@@ -0,0 +1,272 @@
+#![cfg(feature = "testing")]
+
+use proptest::prelude::*;
+use reference::testing::{ops, references};
+use reference::{Id, Identifiable, Reference};
+
+#[derive(Clone, Debug, PartialEq)]
+struct Foo {
+    id: Id<Self>,
+    value: u8,
+}
+
+impl Identifiable for Foo {
+    fn id(&self) -> Id<Self> {
+        self.id
+    }
+}
+
+fn foo(id: Id<Foo>) -> impl Strategy<Value = Foo> {
+    any::<u8>().prop_map(move |value| Foo { id, value })
+}
+
+proptest! {
+    #[test]
+    fn generated_references_are_consistent(reference in references(8, 64, foo)) {
+        let mut ids = reference.unresolved_ids();
+
+        for entry in reference.iter() {
+            if let Some(item) = entry.load() {
+                prop_assert_eq!(reference.get(item.id), Some(entry));
+                ids.push(item.id);
+            }
+        }
+
+        ids.sort_by_key(|id| id.as_i32());
+        ids.dedup();
+        prop_assert!(ids.iter().all(|id| (1..=8).contains(&id.as_i32())));
+        prop_assert!(reference.verify_invariants().is_ok());
+    }
+
+    #[test]
+    fn last_write_wins(sequence in proptest::collection::vec(ops(4, foo), 0..32)) {
+        use reference::testing::Op;
+
+        let reference = Reference::new(5);
+        let mut expected = [None; 5];
+
+        for op in sequence {
+            match &op {
+                Op::Insert(item) | Op::Upsert(item) => {
+                    expected[item.id.as_i32() as usize] = Some(item.value);
+                }
+                Op::Remove(id) => expected[id.as_i32() as usize] = None,
+                Op::Reserve(_) => (),
+            }
+
+            op.apply(&reference).expect("Failed to apply");
+        }
+
+        for (id, value) in expected.iter().enumerate().skip(1) {
+            let item = reference.get((id as i32).into()).and_then(|entry| entry.load());
+            prop_assert_eq!(item.map(|item| item.value), *value);
+        }
+    }
+}
+
+#[cfg(not(feature = "single-thread"))]
+proptest! {
+    #[test]
+    fn concurrent_ops(sequences in reference::testing::concurrent_ops(4, 8, 16, foo)) {
+        let reference = Reference::new(9);
+        reference::testing::apply_concurrently(&reference, sequences).expect("Failed to apply");
+
+        for entry in reference.iter() {
+            if let Some(item) = entry.load() {
+                prop_assert_eq!(reference.get(item.id), Some(entry));
+            }
+        }
+    }
+}
+
+#[test]
+fn fault_injection() {
+    use std::time::{Duration, Instant};
+
+    use reference::testing::{Call, FaultInjector};
+
+    let schedule = |seed| {
+        let faults = FaultInjector::new(seed).with_failures(Call::Get, 0.5);
+        let reference = Reference::new(2).with_fault_injector(faults.clone());
+
+        reference
+            .insert(Foo {
+                id: 1.into(),
+                value: 1,
+            })
+            .expect("Failed to insert");
+
+        let hits = (0..100)
+            .map(|_| reference.get(1.into()).is_some())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            faults.injected(Call::Get),
+            hits.iter().filter(|hit| !**hit).count()
+        );
+        hits
+    };
+
+    let hits = schedule(7);
+    assert_eq!(hits, schedule(7));
+    assert!(hits.contains(&true) && hits.contains(&false));
+
+    let faults = FaultInjector::new(1)
+        .with_failures(Call::Load, 1.0)
+        .with_delays(Call::Insert, Duration::from_millis(20), 1.0);
+
+    let reference = Reference::<Foo>::new(2).with_fault_injector(faults);
+
+    let result = reference.refresh([1.into()], |_| Ok::<_, String>(vec![]));
+    assert!(result.is_err());
+
+    let start = Instant::now();
+
+    reference
+        .insert(Foo {
+            id: 1.into(),
+            value: 1,
+        })
+        .expect("Failed to insert");
+
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
+
+#[test]
+fn mock_reference() {
+    use reference::testing::{MockCall, MockReference};
+    use reference::Referential;
+
+    fn bump<R: Referential<Foo>>(reference: &R, id: Id<Foo>) -> Result<u8, String> {
+        let item = reference.load(id).ok_or("Not found")?;
+        let value = item.value + 1;
+
+        reference
+            .upsert(Foo { id, value })
+            .map_err(|err| err.to_string())?;
+
+        Ok(value)
+    }
+
+    let real = Reference::new(2);
+
+    real.insert(Foo {
+        id: 1.into(),
+        value: 1,
+    })
+    .expect("Failed to insert");
+
+    assert_eq!(bump(&real, 1.into()), Ok(2));
+
+    let mock = MockReference::new()
+        .with_items([Foo {
+            id: 1.into(),
+            value: 1,
+        }])
+        .with_responder(|id| (id == Id::new(2)).then_some(Foo { id, value: 20 }));
+
+    assert_eq!(bump(&mock, 1.into()), Ok(2));
+    assert_eq!(bump(&mock, 2.into()), Ok(21));
+    assert!(bump(&mock, 3.into()).is_err());
+
+    assert_eq!(mock.loaded_ids(), [1.into(), 2.into(), 3.into()]);
+    assert_eq!(mock.count(MockCall::Upsert(1.into())), 1);
+    assert!(mock.contains(2.into()));
+
+    mock.clear_calls();
+
+    let failing = MockReference::new()
+        .with_items([Foo {
+            id: 1.into(),
+            value: 1,
+        }])
+        .with_failing_upserts("Read-only");
+
+    assert!(bump(&failing, 1.into()).is_err());
+    assert_eq!(
+        failing.calls(),
+        [MockCall::Load(1.into()), MockCall::Upsert(1.into())]
+    );
+}
+
+#[test]
+fn golden_snapshot() {
+    use reference::testing::assert_matches_snapshot;
+
+    let path = std::env::temp_dir().join(format!("reference-golden-{}.txt", std::process::id()));
+    let reference = Reference::new(4);
+
+    for id in [3, 1] {
+        reference
+            .insert(Foo {
+                id: id.into(),
+                value: id as u8,
+            })
+            .expect("Failed to insert");
+    }
+
+    reference
+        .get_or_reserve(2.into())
+        .expect("Failed to reserve");
+
+    assert_matches_snapshot(&reference, &path);
+
+    let golden = std::fs::read_to_string(&path).expect("Failed to read snapshot");
+    assert_eq!(
+        golden,
+        "1: Foo { id: Id<testing::Foo>(1), value: 1 }\n\
+         2: reserved\n\
+         3: Foo { id: Id<testing::Foo>(3), value: 3 }\n"
+    );
+
+    assert_matches_snapshot(&reference, &path);
+
+    reference
+        .upsert(Foo {
+            id: 3.into(),
+            value: 30,
+        })
+        .expect("Failed to upsert");
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        assert_matches_snapshot(&reference, &path)
+    }));
+    std::fs::remove_file(&path).expect("Failed to remove snapshot");
+
+    let message = result
+        .expect_err("Snapshot matched")
+        .downcast::<String>()
+        .expect("No panic message");
+
+    assert!(message.contains("- 3: Foo { id: Id<testing::Foo>(3), value: 3 }"));
+    assert!(message.contains("+ 3: Foo { id: Id<testing::Foo>(3), value: 30 }"));
+    assert!(!message.contains("1: Foo"));
+}
+
+#[cfg(not(feature = "single-thread"))]
+#[test]
+fn stress() {
+    use std::time::Duration;
+
+    use reference::testing::StressTest;
+    use reference::{InPlaceBackend, InlineBackend, RwLockBackend};
+
+    let test = StressTest::new()
+        .with_threads(4)
+        .with_ids(64)
+        .with_write_fraction(0.3)
+        .with_duration(Duration::from_millis(200));
+
+    let report = test.run().expect("Stress test failed");
+    assert!(report.reads > 0 && report.writes > 0 && report.scans > 0);
+    assert!(report.hits <= report.reads);
+
+    test.run_with_backend(RwLockBackend::new)
+        .expect("Stress test failed");
+
+    test.run_with_backend(InPlaceBackend::new)
+        .expect("Stress test failed");
+
+    test.run_with_backend(InlineBackend::new)
+        .expect("Stress test failed");
+}